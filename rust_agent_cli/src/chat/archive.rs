@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use rust_agent_core::api::ChatMessage;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// 会话归档
+///
+/// 内存中的对话消息（不含系统消息）超过阈值时，最早的若干轮对话按原顺序
+/// 以 NDJSON 格式追加到归档文件，每行一条消息，然后从内存中移除。
+pub struct SessionArchive {
+    file: File,
+    /// 内存中最多保留的对话消息数
+    threshold: usize,
+}
+
+impl SessionArchive {
+    /// 打开（或创建）归档文件，已有内容会被保留
+    pub fn open(path: &Path, threshold: usize) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("无法打开归档文件: {:?}", path))?;
+
+        Ok(Self {
+            file,
+            threshold: threshold.max(1),
+        })
+    }
+
+    /// 需要归档的消息范围的结束位置，未超过阈值时返回 None
+    ///
+    /// 只在用户消息处切分，保证归档的是完整的对话轮次，
+    /// 内存中剩下的对话仍以用户消息开头。
+    pub fn cut_point(&self, messages: &[ChatMessage]) -> Option<usize> {
+        let conversation: Vec<usize> = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.role != "system")
+            .map(|(index, _)| index)
+            .collect();
+        let excess = conversation.len().checked_sub(self.threshold)?;
+        if excess == 0 {
+            return None;
+        }
+
+        conversation[excess..]
+            .iter()
+            .copied()
+            .find(|&index| messages[index].role == "user")
+    }
+
+    /// 按顺序追加消息到归档文件
+    pub fn append(&mut self, messages: &[ChatMessage]) -> Result<()> {
+        for message in messages {
+            writeln!(self.file, "{}", serde_json::to_string(message)?)?;
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// 一组示例对话
+///
+/// 用于向模型演示正确的工具调用方式，`assistant` 中的工具调用块应使用会话配置的格式。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FewShotExample {
+    pub user: String,
+    pub assistant: String,
+}
+
+/// 从 JSON 文件读取示例对话，文件内容为示例对象的数组
+pub fn load_examples(path: &Path) -> Result<Vec<FewShotExample>> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("无法读取示例文件: {:?}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("示例文件格式无效: {:?}", path))
+}
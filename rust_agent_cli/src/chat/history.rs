@@ -0,0 +1,12 @@
+use anyhow::{Context, Result};
+use rust_agent_core::api::ChatMessage;
+use std::path::Path;
+
+/// 从 JSON 文件读取对话历史，文件内容为消息对象（包含 role 和 content）的数组
+///
+/// 只负责读取，角色和系统消息位置的检查由 [`ChatSession::set_messages`](super::ChatSession::set_messages) 完成。
+pub fn load_history(path: &Path) -> Result<Vec<ChatMessage>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("无法读取对话历史文件: {:?}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("对话历史文件格式无效: {:?}", path))
+}
@@ -1,2 +1,18 @@
+mod archive;
+mod examples;
+mod history;
+mod output_tee;
+mod results;
 mod session;
-pub use session::ChatSession;
+mod stream_filter;
+mod summary;
+mod tool_output;
+mod trace;
+mod trimmer;
+pub use examples::load_examples;
+pub use history::load_history;
+pub use session::{
+    ChatSession, StreamEvent, ToolCallRecord, TurnCancelled, TurnOutput,
+    DEFAULT_MAX_MODEL_CALLS_PER_TURN, DEFAULT_MAX_TOOL_CALLS_PER_TURN,
+};
+pub use tool_output::{ToolOutputFormat, DEFAULT_TOOL_NOTICE};
@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::warn;
+
+use super::StreamEvent;
+
+/// 把流式输出的回答同时写入文件
+///
+/// 每段内容写入后立即刷新，生成中途退出时文件中也保留已输出的部分。
+/// 推理过程不写入；工具执行的提示和结果按配置决定是否写入。
+pub struct OutputTee {
+    file: Mutex<TeeFile>,
+    include_tool_results: bool,
+}
+
+struct TeeFile {
+    file: File,
+    /// 已写入的内容是否以换行结束
+    at_line_start: bool,
+}
+
+impl OutputTee {
+    /// 打开（或创建）输出文件，已有内容会被保留
+    pub fn open(path: &Path, include_tool_results: bool) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("无法打开输出文件: {:?}", path))?;
+
+        Ok(Self {
+            file: Mutex::new(TeeFile {
+                file,
+                at_line_start: true,
+            }),
+            include_tool_results,
+        })
+    }
+
+    /// 写入一个流式事件中需要保存的内容
+    pub fn record(&self, event: &StreamEvent) {
+        match event {
+            StreamEvent::Content(text) => self.write(text),
+            StreamEvent::Notice(text) if self.include_tool_results => self.write(text),
+            _ => {}
+        }
+    }
+
+    /// 一轮回答结束，补上换行并空一行，与下一轮分开
+    pub fn end_turn(&self) {
+        let at_line_start = match self.file.lock() {
+            Ok(file) => file.at_line_start,
+            Err(_) => return,
+        };
+        self.write(if at_line_start { "\n" } else { "\n\n" });
+    }
+
+    /// 写入失败只记录警告，不影响对话
+    fn write(&self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let Ok(mut tee) = self.file.lock() else {
+            return;
+        };
+        if let Err(e) = tee
+            .file
+            .write_all(text.as_bytes())
+            .and_then(|_| tee.file.flush())
+        {
+            warn!("写入输出文件失败: {}", e);
+            return;
+        }
+        tee.at_line_start = text.ends_with('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_is_written_and_tool_notices_only_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = [
+            StreamEvent::Reasoning("先想一想"),
+            StreamEvent::Content("我来算一下"),
+            StreamEvent::Notice("\n\n执行工具 `add`...\n"),
+            StreamEvent::Content("等于 3"),
+        ];
+
+        for (include_tool_results, expected) in [
+            (false, "我来算一下等于 3\n\n第二轮\n\n"),
+            (
+                true,
+                "我来算一下\n\n执行工具 `add`...\n等于 3\n\n第二轮\n\n",
+            ),
+        ] {
+            let path = dir.path().join(format!("out/{}.md", include_tool_results));
+            let tee = OutputTee::open(&path, include_tool_results).unwrap();
+            for event in &events {
+                tee.record(event);
+            }
+            // 每次写入后立即刷新，一轮没有结束时文件中已经有内容
+            assert!(fs::read_to_string(&path).unwrap().ends_with("等于 3"));
+            tee.end_turn();
+            tee.record(&StreamEvent::Content("第二轮\n"));
+            tee.end_turn();
+            assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        }
+    }
+}
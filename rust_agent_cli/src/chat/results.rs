@@ -0,0 +1,79 @@
+use rust_agent_core::tools::ToolResult;
+use serde_json::Value;
+
+/// 保存的工具结果
+#[derive(Debug, Clone)]
+pub struct StoredToolResult {
+    /// 结果编号，从 1 开始
+    pub id: usize,
+    pub name: String,
+    pub args: Value,
+    pub result: ToolResult,
+}
+
+/// 会话内的工具结果存储
+///
+/// 每个结果按执行顺序分配编号，编号会写入格式化后的结果文本，
+/// 之后的对话中可以通过编号引用，而不必重新执行工具。
+#[derive(Debug, Clone, Default)]
+pub struct ToolResultStore {
+    results: Vec<StoredToolResult>,
+}
+
+impl ToolResultStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 保存结果并返回分配的编号
+    pub fn insert(&mut self, name: impl Into<String>, args: Value, result: ToolResult) -> usize {
+        let id = self.results.len() + 1;
+        self.results.push(StoredToolResult {
+            id,
+            name: name.into(),
+            args,
+            result,
+        });
+        id
+    }
+
+    /// 按编号获取结果
+    pub fn get(&self, id: usize) -> Option<&StoredToolResult> {
+        id.checked_sub(1).and_then(|index| self.results.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn stores_and_retrieves_results_by_id() {
+        let mut store = ToolResultStore::new();
+        let first = store.insert(
+            "file_analyzer",
+            json!({"path": "/tmp"}),
+            ToolResult::success(json!({"file_count": 3})),
+        );
+        let second = store.insert(
+            "file_hash",
+            json!({"path": "a.txt"}),
+            ToolResult::failure("文件不存在"),
+        );
+        assert_eq!((first, second), (1, 2));
+
+        let stored = store.get(1).unwrap();
+        assert_eq!(stored.id, 1);
+        assert_eq!(stored.name, "file_analyzer");
+        assert_eq!(stored.args, json!({"path": "/tmp"}));
+        assert_eq!(stored.result.data, json!({"file_count": 3}));
+        assert_eq!(
+            store.get(2).unwrap().result.error.as_deref(),
+            Some("文件不存在")
+        );
+
+        assert!(store.get(0).is_none());
+        assert!(store.get(3).is_none());
+    }
+}
@@ -1,121 +1,1503 @@
 use anyhow::{anyhow, Result};
 use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info_span, warn, Instrument};
 
-use rust_agent_core::api::{ChatMessage, DeepseekClient};
+use rust_agent_core::api::{
+    estimate_messages_tokens, Capabilities, ChatMessage, DeepseekClient, Feature, RequestOptions,
+    ToolCallAccumulator, ToolDefinition, Usage,
+};
+use rust_agent_core::correlation::new_request_id;
+use rust_agent_core::text::{last_chars, truncate_chars};
 use rust_agent_core::tools::{
-    format_tool_result, parse_tool_calls, ToolParameters, ToolResult, ToolsClient,
+    build_system_prompt_with_format, format_tool_result, ToolCallFormat, ToolInfo, ToolParameters,
+    ToolResult, ToolServiceError, ToolsClient,
 };
 
+use super::archive::SessionArchive;
+use super::output_tee::OutputTee;
+use super::results::{StoredToolResult, ToolResultStore};
+use super::stream_filter::ToolBlockFilter;
+use super::summary::RollingSummary;
+use super::tool_output::ToolOutputFormat;
+use super::trace::TraceWriter;
+use super::trimmer::ContextTrimmer;
+
+/// 单次回复中默认最多执行的工具调用数
+pub const DEFAULT_MAX_TOOL_CALLS_PER_TURN: usize = 10;
+/// 单轮对话中默认最多发起的模型调用次数
+pub const DEFAULT_MAX_MODEL_CALLS_PER_TURN: usize = 10;
+
+/// 检查工具服务能否重新连接的超时时间
+const TOOLS_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 工具服务不可用时返回给模型的结果
+const TOOLS_UNAVAILABLE: &str = "工具服务不可用，本次调用没有执行，请不使用工具继续回答";
+
+/// 通信中断后已重新连接时返回给模型的结果
+const TOOLS_RECONNECTED: &str =
+    "与工具服务的连接中断，已重新连接；本次调用可能没有执行完成，如有需要请重新调用";
+
+/// 注入的对话历史中最多允许的系统消息数
+const MAX_SYSTEM_MESSAGES: usize = 4;
+
+/// 对话历史中允许的消息角色
+const VALID_ROLES: &[&str] = &["system", "user", "assistant", "tool"];
+
+/// 最终回复的后处理函数
+type Postprocessor = dyn Fn(String) -> String + Send + Sync;
+
+/// 请求模型继续被截断的回复时附加的用户消息
+const CONTINUE_PROMPT: &str =
+    "你的上一条回复因长度限制被截断了，请从中断处直接继续，不要重复已经输出的内容。";
+
+/// 流式输出中的事件
+pub enum StreamEvent<'a> {
+    /// 推理过程（仅推理模型）
+    Reasoning(&'a str),
+    /// 模型的回答内容，工具调用块不会出现在这里
+    Content(&'a str),
+    /// 会话生成的提示，包括工具执行的提示和结果
+    Notice(&'a str),
+    /// 流式输出中一个工具调用块刚刚写完，回复结束后才会执行，可用于提前提示
+    ToolDetected(&'a ToolParameters),
+    /// 一次工具调用执行完毕，供需要结构化结果的调用方使用
+    ToolCall(&'a ToolCallRecord),
+}
+
+/// 工具调用及其执行结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCallRecord {
+    pub name: String,
+    pub args: Value,
+    /// 工具返回的结果；与工具服务通信失败时为反馈给模型的失败结果
+    pub result: Option<ToolResult>,
+    /// 与工具服务通信失败时的原始错误信息
+    pub error: Option<String>,
+}
+
+/// 一轮对话的结果
+#[derive(Debug, Clone)]
+pub struct TurnOutput {
+    /// 最终写入对话历史的回复内容，包括各次模型回复和穿插其中的工具执行结果
+    pub text: String,
+    /// 本轮最后一次模型回复去掉工具调用块后的内容，不包含工具执行结果
+    ///
+    /// 执行了工具时是工具结果反馈给模型之后那次调用的回答；没有执行工具时与 `text` 相同。
+    /// 供只需要最终回答的调用方使用。
+    pub final_answer: String,
+    /// 本轮按执行顺序执行过的工具调用
+    pub tool_calls: Vec<ToolCallRecord>,
+    /// 最后一次模型回复是否因达到 `max_tokens` 而被截断
+    pub truncated: bool,
+    /// 本轮的请求 ID，随模型请求和工具调用一起发送
+    pub request_id: String,
+    /// 本轮所有模型调用的 token 用量之和，服务端没有返回用量时为 None
+    pub usage: Option<Usage>,
+}
+
+/// 本轮对话被取消
+#[derive(Debug)]
+pub struct TurnCancelled;
+
+impl fmt::Display for TurnCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "本轮对话已取消")
+    }
+}
+
+impl std::error::Error for TurnCancelled {}
+
+/// 单轮对话的模型调用次数已达上限
+#[derive(Debug)]
+pub struct BudgetExhausted {
+    pub limit: usize,
+}
+
+impl fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "本轮对话的模型调用次数已达上限（{} 次）", self.limit)
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+/// 与工具服务通信失败（连接不上、连接断开或超时）
+///
+/// 工具返回的 `success: false` 以及工具不存在等调用本身的失败不属于这类错误，
+/// 它们会作为工具结果反馈给模型，由模型决定是否调整后重试。
+/// 通信失败的原始错误不会写入对话，会话会尝试重新连接，并反馈统一的失败结果。
+#[derive(Debug)]
+pub struct ToolTransportError {
+    pub tool: String,
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for ToolTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "调用工具 `{}` 时与工具服务通信失败: {}",
+            self.tool, self.source
+        )
+    }
+}
+
+impl std::error::Error for ToolTransportError {}
+
+/// 注入的对话历史不符合会话的约定
+#[derive(Debug)]
+pub struct InvalidMessages {
+    /// 出错的消息位置，从 0 开始
+    pub index: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidMessages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "第 {} 条消息无效: {}", self.index + 1, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidMessages {}
+
+/// 检查对话历史：角色必须有效，系统消息只能出现在开头且数量不超过上限
+fn validate_messages(messages: &[ChatMessage]) -> Result<(), InvalidMessages> {
+    let system_count = messages.iter().take_while(|m| m.role == "system").count();
+    if system_count > MAX_SYSTEM_MESSAGES {
+        return Err(InvalidMessages {
+            index: MAX_SYSTEM_MESSAGES,
+            reason: format!("系统消息最多 {} 条", MAX_SYSTEM_MESSAGES),
+        });
+    }
+
+    for (index, message) in messages.iter().enumerate() {
+        if !VALID_ROLES.contains(&message.role.as_str()) {
+            return Err(InvalidMessages {
+                index,
+                reason: format!("未知的角色: {:?}", message.role),
+            });
+        }
+        if message.role == "system" && index >= system_count {
+            return Err(InvalidMessages {
+                index,
+                reason: "系统消息只能出现在对话开头".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// 单轮对话的运行状态
+struct TurnContext<'a> {
+    cancel: &'a CancellationToken,
+    /// 当前模型调用使用的请求参数，其中包含本轮的请求 ID
+    options: &'a RequestOptions,
+    /// 本轮已发起的模型调用次数
+    model_calls: usize,
+    /// 本轮已执行的工具调用
+    tool_calls: Vec<ToolCallRecord>,
+    /// 最近一次模型回复是否被截断
+    truncated: bool,
+    /// 已输出的内容是否以换行结束
+    at_line_start: bool,
+    /// 本轮所有模型调用的 token 用量之和
+    usage: Option<Usage>,
+}
+
+impl TurnContext<'_> {
+    fn request_id(&self) -> String {
+        self.options.request_id.clone().unwrap_or_default()
+    }
+}
+
+/// 一次回复中的工具调用的执行结果
+#[derive(Default)]
+struct ToolRound {
+    /// 反馈给模型的工具结果和提示
+    feedback: Vec<String>,
+    /// 参数校验失败的描述
+    invalid_args: Vec<String>,
+}
+
 pub struct ChatSession {
     client: DeepseekClient,
     messages: Vec<ChatMessage>,
     verbose: bool,
-    tools_client: Option<Arc<Mutex<ToolsClient>>>,
+    tools_client: Option<ToolsClient>,
+    /// 最近一次从工具服务获取的工具信息
+    tools: Vec<ToolInfo>,
+    /// 根据工具信息生成、已写入对话历史的系统提示
+    tools_prompt: Option<String>,
+    /// 工具参数校验失败时最多请求模型修正的次数
+    tool_arg_autofix_attempts: usize,
+    /// 单次回复中最多执行的工具调用数
+    max_tool_calls_per_turn: usize,
+    /// 单轮对话中最多发起的模型调用次数，所有重试共用这一预算
+    max_model_calls_per_turn: usize,
+    /// 运行轨迹记录器
+    trace: Option<TraceWriter>,
+    /// 同时写入流式回答的文件
+    output_tee: Option<OutputTee>,
+    /// 上下文裁剪器，未设置时发送完整历史
+    trimmer: Option<ContextTrimmer>,
+    /// 本次会话中执行过的工具结果
+    tool_results: std::sync::Mutex<ToolResultStore>,
+    /// 写入对话历史的单个工具结果的最大字符数，None 表示不截断
+    max_tool_result_chars: Option<usize>,
+    /// 滚动摘要，未设置时不生成摘要
+    rolling_summary: Option<RollingSummary>,
+    /// 会话归档，未设置时对话历史全部保留在内存中
+    archive: Option<SessionArchive>,
+    /// 工具调用块的格式
+    tool_call_format: ToolCallFormat,
+    /// 上一轮回复是否被截断，决定能否继续生成
+    last_truncated: AtomicBool,
+    /// 固定在系统消息之后的示例对话
+    examples: Vec<ChatMessage>,
+    /// 对最终回复文本的后处理
+    postprocessor: Option<Arc<Postprocessor>>,
+    /// 工具执行提示和结果的输出格式
+    tool_output_format: ToolOutputFormat,
+    /// 当前（或最近一轮）对话的请求 ID
+    request_id: std::sync::Mutex<Option<String>>,
+    /// 工具服务是否不可用；不可用期间跳过工具调用，直到重新连接
+    tools_degraded: AtomicBool,
+    /// 每次调用工具前检查工具服务是否存活的超时时间，None 表示不检查
+    tools_liveness_timeout: Option<Duration>,
+    /// 只用于决定工具调用的中间模型调用使用的参数，未设置的参数沿用本轮的参数
+    intermediate_options: RequestOptions,
+    /// 本次会话累计的 token 用量，服务端没有返回过用量时为 None
+    total_usage: std::sync::Mutex<Option<Usage>>,
+    /// 是否随请求声明工具，让模型通过原生函数调用使用工具
+    native_tools: bool,
 }
 
 impl ChatSession {
-    pub fn new(api_key: String, verbose: bool) -> Self {
+    pub fn new(client: DeepseekClient, verbose: bool) -> Self {
         Self {
-            client: DeepseekClient::new(api_key),
+            client,
             messages: Vec::new(),
             verbose,
             tools_client: None,
+            tools: Vec::new(),
+            tools_prompt: None,
+            tool_arg_autofix_attempts: 0,
+            max_tool_calls_per_turn: DEFAULT_MAX_TOOL_CALLS_PER_TURN,
+            max_model_calls_per_turn: DEFAULT_MAX_MODEL_CALLS_PER_TURN,
+            trace: None,
+            output_tee: None,
+            trimmer: None,
+            tool_results: std::sync::Mutex::new(ToolResultStore::new()),
+            max_tool_result_chars: None,
+            rolling_summary: None,
+            archive: None,
+            tool_call_format: ToolCallFormat::default(),
+            last_truncated: AtomicBool::new(false),
+            examples: Vec::new(),
+            postprocessor: None,
+            tool_output_format: ToolOutputFormat::default(),
+            request_id: std::sync::Mutex::new(None),
+            tools_degraded: AtomicBool::new(false),
+            tools_liveness_timeout: None,
+            intermediate_options: RequestOptions::default(),
+            total_usage: std::sync::Mutex::new(None),
+            native_tools: false,
         }
     }
 
+    /// 把会话事件以 NDJSON 格式追加到指定文件
+    pub fn with_trace_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.trace = Some(TraceWriter::open(path.as_ref())?);
+        Ok(self)
+    }
+
+    /// 把流式输出的回答同时追加到指定文件，每段内容写入后立即刷新
+    ///
+    /// `include_tool_results` 为 true 时工具执行的提示和结果也写入文件；推理过程不写入。
+    pub fn with_output_file(
+        mut self,
+        path: impl AsRef<Path>,
+        include_tool_results: bool,
+    ) -> Result<Self> {
+        self.output_tee = Some(OutputTee::open(path.as_ref(), include_tool_results)?);
+        Ok(self)
+    }
+
+    /// 在回调之前把事件写入输出文件
+    fn tee_events<'s, F>(&'s self, mut on_event: F) -> impl FnMut(StreamEvent) + 's
+    where
+        F: FnMut(StreamEvent) + 's,
+    {
+        move |event| {
+            if let Some(tee) = &self.output_tee {
+                tee.record(&event);
+            }
+            on_event(event);
+        }
+    }
+
+    /// 记录运行轨迹事件
+    fn trace(&self, event_type: &str, payload: Value) {
+        if let Some(trace) = &self.trace {
+            trace.record(event_type, payload);
+        }
+    }
+
+    /// 设置工具参数自动修正的最大次数，0 表示不自动修正
+    pub fn with_tool_arg_autofix(mut self, max_attempts: usize) -> Self {
+        self.tool_arg_autofix_attempts = max_attempts;
+        self
+    }
+
+    /// 设置单次回复中最多执行的工具调用数，超出的调用会被忽略并提示模型
+    pub fn with_max_tool_calls_per_turn(mut self, max_calls: usize) -> Self {
+        self.max_tool_calls_per_turn = max_calls;
+        self
+    }
+
+    /// 设置写入对话历史的单个工具结果的最大字符数
+    ///
+    /// 超出时只保留开头和结尾，中间替换为截断说明；完整结果仍可通过结果编号获取。
+    pub fn with_max_tool_result_chars(mut self, max_chars: usize) -> Self {
+        self.max_tool_result_chars = Some(max_chars);
+        self
+    }
+
+    /// 设置上下文的 token 预算，系统消息计入预算，超出时丢弃最早的对话消息
+    pub fn with_context_budget(mut self, max_tokens: usize) -> Self {
+        self.trimmer = Some(ContextTrimmer::new(max_tokens));
+        self
+    }
+
+    /// 每完成 `every_k_turns` 轮对话更新一次滚动摘要
+    ///
+    /// 摘要作为单独的系统消息随请求发送，与上下文裁剪配合时可以在丢弃旧消息后保留长期上下文。
+    /// 需要在每轮对话结束后调用 [`update_rolling_summary`](Self::update_rolling_summary)。
+    pub fn with_rolling_summary(mut self, every_k_turns: usize) -> Self {
+        self.rolling_summary = Some(RollingSummary::new(every_k_turns));
+        self
+    }
+
+    /// 内存中的对话消息超过 `threshold` 条时，把最早的对话轮次归档到指定文件
+    ///
+    /// 归档的消息按原顺序以 NDJSON 格式追加到文件并从内存中移除，系统消息始终保留。
+    /// 启用了滚动摘要时，归档前会先把尚未纳入摘要的消息合并进摘要。
+    /// 需要在每轮对话结束后调用 [`archive_old_turns`](Self::archive_old_turns)。
+    pub fn with_auto_archive(mut self, path: impl AsRef<Path>, threshold: usize) -> Result<Self> {
+        self.archive = Some(SessionArchive::open(path.as_ref(), threshold)?);
+        Ok(self)
+    }
+
+    /// 设置识别工具调用块使用的格式，默认为 ```` ```tool ```` 代码块
+    ///
+    /// 系统提示中告知模型的调用格式应与此一致。
+    pub fn with_tool_call_format(mut self, format: ToolCallFormat) -> Self {
+        self.tool_call_format = format;
+        self
+    }
+
+    /// 设置最终回复的后处理函数，如统一空白或脱敏
+    ///
+    /// 在工具结果拼接完成后对整轮回复执行一次，[`TurnOutput::text`] 是处理后的文本，
+    /// 调用方写入对话历史的也是处理后的文本；流式回调收到的内容不受影响。
+    /// 继续生成被截断的回复时只处理新续写的部分。
+    pub fn with_postprocessor<F>(mut self, postprocessor: F) -> Self
+    where
+        F: Fn(String) -> String + Send + Sync + 'static,
+    {
+        self.postprocessor = Some(Arc::new(postprocessor));
+        self
+    }
+
+    /// 设置工具执行提示和结果的输出格式
+    pub fn with_tool_output_format(mut self, format: ToolOutputFormat) -> Self {
+        self.tool_output_format = format;
+        self
+    }
+
+    /// 当前模型支持的功能
+    ///
+    /// 工具调用有两种方式：默认使用回复文本中的工具调用块，不依赖原生函数调用，
+    /// 因此不支持原生函数调用的模型（如 deepseek-reasoner）同样可以使用工具；
+    /// 通过 [`with_native_tools`](Self::with_native_tools) 或附加字段（如 `tools`）
+    /// 启用的原生函数调用则要求模型支持 [`Feature::NativeTools`]。
+    pub fn capabilities(&self) -> Capabilities {
+        self.client.capabilities()
+    }
+
+    /// 检查当前模型是否支持指定功能，用于在发送请求前尽早发现不支持的功能（如图片输入）
+    pub fn require_feature(&self, feature: Feature) -> Result<()> {
+        Ok(self.client.require(feature)?)
+    }
+
+    /// 设置决定工具调用的中间模型调用使用的采样参数
+    ///
+    /// 选择工具和参数时通常适合较低的温度，而面向用户的回答可以使用较高的温度。
+    /// 连接了工具服务时，每轮的第一次模型调用和参数修正调用都使用这里的参数；
+    /// 执行工具并把结果反馈给模型后，生成回答的最后一次调用使用本轮的参数。
+    /// 第一次调用返回前无法知道模型是否会调用工具，为避免每轮多一次请求，
+    /// 模型不调用工具直接给出的回答同样使用这里的参数，不会用本轮的参数重新生成。
+    /// 这里未设置的参数沿用本轮的参数，默认与本轮完全相同。
+    pub fn with_intermediate_options(mut self, options: RequestOptions) -> Self {
+        self.intermediate_options = options;
+        self
+    }
+
+    /// 设置单轮对话中最多发起的模型调用次数
+    ///
+    /// 参数修正等所有重试都计入这一预算，用尽后返回 [`BudgetExhausted`] 错误。
+    pub fn with_max_model_calls_per_turn(mut self, max_calls: usize) -> Self {
+        self.max_model_calls_per_turn = max_calls;
+        self
+    }
+
+    /// 每次调用工具前先检查工具服务是否存活，超过 `timeout` 没有响应时直接按不可用处理
+    ///
+    /// 服务已经停止时可以很快跳过工具调用，而不是等待完整的调用超时；
+    /// 每次调用都会多一次往返，因此默认不开启。
+    pub fn with_tools_liveness_check(mut self, timeout: Duration) -> Self {
+        self.tools_liveness_timeout = Some(timeout);
+        self
+    }
+
+    /// 启用原生函数调用：每次请求都在 `tools` 字段中声明已连接的工具
+    ///
+    /// 需要模型支持原生函数调用，否则发送请求时返回 [`UnsupportedFeature`](rust_agent_core::api::UnsupportedFeature)。
+    /// 模型返回的函数调用与文本格式的工具调用块一样执行。
+    pub fn with_native_tools(mut self, enable: bool) -> Self {
+        self.native_tools = enable;
+        self
+    }
+
     /// 设置工具客户端
     pub fn set_tools_client(&mut self, client: ToolsClient) {
-        self.tools_client = Some(Arc::new(Mutex::new(client)));
+        self.tools_client = Some(client);
+        self.tools_degraded.store(false, Ordering::Relaxed);
+    }
+
+    /// 从当前位置分出一个独立的会话，用于对比同一段对话的不同后续
+    ///
+    /// 对话历史、示例、滚动摘要、已保存的工具结果和所有配置都会复制，
+    /// 之后两个会话互不影响；工具服务的连接是共享的。
+    /// 分出的会话不写入运行轨迹，也不自动归档，避免与原会话写入同一个文件。
+    pub fn fork(&self) -> ChatSession {
+        let tool_results = self
+            .tool_results
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+
+        Self {
+            client: self.client.clone(),
+            messages: self.messages.clone(),
+            verbose: self.verbose,
+            tools_client: self.tools_client.clone(),
+            tools: self.tools.clone(),
+            tools_prompt: self.tools_prompt.clone(),
+            tool_arg_autofix_attempts: self.tool_arg_autofix_attempts,
+            max_tool_calls_per_turn: self.max_tool_calls_per_turn,
+            max_model_calls_per_turn: self.max_model_calls_per_turn,
+            trace: None,
+            output_tee: None,
+            trimmer: self.trimmer.clone(),
+            tool_results: std::sync::Mutex::new(tool_results),
+            max_tool_result_chars: self.max_tool_result_chars,
+            rolling_summary: self.rolling_summary.clone(),
+            archive: None,
+            tool_call_format: self.tool_call_format.clone(),
+            last_truncated: AtomicBool::new(self.last_truncated.load(Ordering::Relaxed)),
+            examples: self.examples.clone(),
+            postprocessor: self.postprocessor.clone(),
+            tool_output_format: self.tool_output_format.clone(),
+            request_id: std::sync::Mutex::new(None),
+            tools_degraded: AtomicBool::new(self.tools_degraded.load(Ordering::Relaxed)),
+            tools_liveness_timeout: self.tools_liveness_timeout,
+            intermediate_options: self.intermediate_options.clone(),
+            total_usage: std::sync::Mutex::new(None),
+            native_tools: self.native_tools,
+        }
+    }
+
+    /// 当前正在进行（或最近一轮）对话的请求 ID，还没有发起过对话时为 None
+    pub fn request_id(&self) -> Option<String> {
+        self.request_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// 开始新一轮对话，返回带有请求 ID 的请求参数
+    ///
+    /// `options` 中已经指定请求 ID 时沿用调用方的 ID，否则生成新的 ID。
+    /// 启用原生函数调用时一并声明已连接的工具。
+    fn begin_request(&self, options: &RequestOptions) -> RequestOptions {
+        let request_id = options.request_id.clone().unwrap_or_else(new_request_id);
+        *self
+            .request_id
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(request_id.clone());
+        let mut options = options.clone().with_request_id(request_id);
+        if self.native_tools && options.tools.is_none() && !self.tools.is_empty() {
+            let tools = self
+                .tools
+                .iter()
+                .map(|tool| {
+                    ToolDefinition::function(&tool.name, &tool.description, tool.parameters.clone())
+                })
+                .collect();
+            options = options.with_tools(tools);
+        }
+        options
+    }
+
+    /// 重新获取工具服务上的工具，并更新对话历史中的工具系统提示
+    ///
+    /// 无法获取描述的工具会被跳过；之前写入的工具提示被替换为新生成的提示，
+    /// 服务上没有工具时移除该提示。工具服务运行期间新增的工具在刷新后即可使用。
+    pub async fn refresh_tools(&mut self) -> Result<&[ToolInfo]> {
+        let client = self
+            .tools_client
+            .as_ref()
+            .ok_or_else(|| anyhow!("未连接到工具服务"))?;
+
+        let mut tools = Vec::new();
+        for name in client.list_tools().await? {
+            match client.describe_tool(&name).await {
+                Ok(info) => tools.push(info),
+                Err(e) => warn!("无法获取工具 {} 的描述: {}", name, e),
+            }
+        }
+
+        self.tools_degraded.store(false, Ordering::Relaxed);
+        self.set_tools(tools);
+        Ok(&self.tools)
+    }
+
+    /// 设置可用的工具并生成对应的系统提示，替换之前的工具提示
+    fn set_tools(&mut self, tools: Vec<ToolInfo>) {
+        let prompt = (!tools.is_empty())
+            .then(|| build_system_prompt_with_format(&tools, &self.tool_call_format));
+        let previous = self.tools_prompt.take().and_then(|old| {
+            self.messages
+                .iter()
+                .position(|m| m.role == "system" && m.content == old)
+        });
+
+        match (previous, &prompt) {
+            (Some(index), Some(prompt)) => self.messages[index].content = prompt.clone(),
+            (Some(index), None) => {
+                self.messages.remove(index);
+            }
+            // 新的工具提示与其他系统消息放在一起，位于对话消息之前
+            (None, Some(prompt)) => {
+                let position = self
+                    .messages
+                    .iter()
+                    .position(|m| m.role != "system")
+                    .unwrap_or(self.messages.len());
+                self.messages
+                    .insert(position, ChatMessage::new("system", prompt.clone()));
+            }
+            (None, None) => {}
+        }
+
+        self.trace(
+            "tools_refresh",
+            json!({ "tools": tools.iter().map(|t| &t.name).collect::<Vec<_>>() }),
+        );
+        self.tools = tools;
+        self.tools_prompt = prompt;
+    }
+
+    /// 最近一次从工具服务获取的工具信息
+    pub fn tools(&self) -> &[ToolInfo] {
+        &self.tools
     }
 
     /// 检查是否已连接工具服务
+    #[allow(dead_code)]
     pub fn has_tools(&self) -> bool {
         self.tools_client.is_some()
     }
 
     pub fn add_user_message(&mut self, content: String) {
-        self.messages.push(ChatMessage {
-            role: "user".to_string(),
-            content,
-        });
+        self.trace("user_message", json!({ "content": content }));
+        self.messages.push(ChatMessage::new("user", content));
+    }
+
+    /// 添加附带图片的用户消息
+    ///
+    /// 当前模型不支持图片输入时返回错误，对话历史保持不变。
+    pub fn add_user_message_with_images(
+        &mut self,
+        content: String,
+        images: Vec<String>,
+    ) -> Result<()> {
+        if images.is_empty() {
+            self.add_user_message(content);
+            return Ok(());
+        }
+
+        self.require_feature(Feature::Vision)?;
+        self.trace(
+            "user_message",
+            json!({ "content": content, "images": images.len() }),
+        );
+        let mut message = ChatMessage::new("user", content);
+        message.images = images;
+        self.messages.push(message);
+        Ok(())
     }
 
     pub fn add_assistant_message(&mut self, content: String) {
-        self.messages.push(ChatMessage {
-            role: "assistant".to_string(),
-            content,
-        });
+        self.trace("assistant_message", json!({ "content": content }));
+        self.messages.push(ChatMessage::new("assistant", content));
     }
 
-    pub fn add_system_message(&mut self, content: String) {
-        self.messages.push(ChatMessage {
-            role: "system".to_string(),
-            content,
-        });
+    /// 用已有的对话历史（如从数据库读取的消息）创建会话，见 [`set_messages`](Self::set_messages)
+    pub fn with_messages(mut self, messages: Vec<ChatMessage>) -> Result<Self> {
+        self.set_messages(messages)?;
+        Ok(self)
+    }
+
+    /// 替换整个对话历史，之后的对话从这些消息继续
+    ///
+    /// 角色只能是 `system`、`user`、`assistant` 或 `tool`，系统消息只能出现在开头，
+    /// 最多 4 条；不符合时返回 [`InvalidMessages`]，原有历史保持不变。
+    /// 原有历史中被截断的回复不能再通过 [`continue_last`](Self::continue_last) 继续。
+    /// 已连接工具服务时，工具说明会重新加入新的历史。
+    pub fn set_messages(&mut self, messages: Vec<ChatMessage>) -> Result<()> {
+        validate_messages(&messages)?;
+        self.messages = messages;
+        self.last_truncated.store(false, Ordering::Relaxed);
+
+        let tools = std::mem::take(&mut self.tools);
+        if !tools.is_empty() {
+            self.tools_prompt = None;
+            self.set_tools(tools);
+        }
+        Ok(())
+    }
+
+    /// 添加一组示例对话，用于演示正确的工具调用方式
+    ///
+    /// 示例按添加顺序插入到系统消息之后，不写入对话历史，
+    /// 因此不会被归档或纳入摘要，上下文裁剪时也始终保留。
+    pub fn add_example(&mut self, user: impl Into<String>, assistant: impl Into<String>) {
+        self.examples.push(ChatMessage::new("user", user));
+        self.examples.push(ChatMessage::new("assistant", assistant));
     }
 
     /// 获取 AI 响应并处理工具调用（流式输出）
-    pub async fn get_response_stream<F>(&self, mut callback: F) -> Result<String>
+    ///
+    /// 返回的文本只包含回答内容，推理过程仅通过回调输出，不会写入对话历史；
+    /// 本轮执行过的工具调用及其结果一并返回。
+    /// 工具执行失败的结果会反馈给模型；与工具服务通信失败且无法重新连接时，
+    /// 向模型反馈工具服务不可用，之后的调用都被跳过，直到工具服务恢复。
+    /// 启用参数自动修正时，工具参数校验失败会把错误反馈给模型并让它重新调用。
+    pub async fn get_response_stream<F>(&self, on_event: F) -> Result<TurnOutput>
     where
-        F: FnMut(&str),
+        F: FnMut(StreamEvent),
     {
-        let mut stream = self.client.chat_stream(self.messages.clone()).await?;
-        let mut full_response = String::new();
+        self.get_response_stream_cancellable(&CancellationToken::new(), on_event)
+            .await
+    }
+
+    /// 与 [`get_response_stream`](Self::get_response_stream) 相同，但可以通过 `cancel` 中止本轮对话
+    ///
+    /// 取消后正在接收的回复和正在执行的工具调用都会被丢弃，返回 [`TurnCancelled`] 错误。
+    /// 工具调用的 gRPC 请求会随之中断，由工具服务负责终止对应的外部进程。
+    pub async fn get_response_stream_cancellable<F>(
+        &self,
+        cancel: &CancellationToken,
+        on_event: F,
+    ) -> Result<TurnOutput>
+    where
+        F: FnMut(StreamEvent),
+    {
+        self.get_response_stream_with(&RequestOptions::default(), cancel, on_event)
+            .await
+    }
 
-        while let Some(chunk) = stream.next().await {
-            let content = chunk?;
-            if !content.is_empty() {
-                callback(&content);
-                full_response.push_str(&content);
+    /// 与 [`get_response_stream_cancellable`](Self::get_response_stream_cancellable) 相同，
+    /// 但本轮的模型调用使用 `options` 中的参数（如临时调整温度），会话的默认参数不受影响
+    ///
+    /// 本轮的模型请求和工具调用携带同一个请求 ID（见 [`request_id`](Self::request_id)），
+    /// 期间的日志也都带有该 ID。
+    pub async fn get_response_stream_with<F>(
+        &self,
+        options: &RequestOptions,
+        cancel: &CancellationToken,
+        on_event: F,
+    ) -> Result<TurnOutput>
+    where
+        F: FnMut(StreamEvent),
+    {
+        let options = self.begin_request(options);
+        let span = info_span!("turn", request_id = options.request_id.as_deref());
+        let result = self
+            .run_turn(&options, cancel, self.tee_events(on_event))
+            .instrument(span)
+            .await
+            .map(|output| self.postprocess(output));
+        if let Some(tee) = &self.output_tee {
+            tee.end_turn();
+        }
+        match &result {
+            Ok(output) => self
+                .last_truncated
+                .store(output.truncated, Ordering::Relaxed),
+            Err(e) => self.trace("error", json!({ "message": e.to_string() })),
+        }
+        result
+    }
+
+    /// 继续生成上一条因达到 `max_tokens` 而被截断的回复
+    ///
+    /// 在上下文末尾附加一条请求继续的用户消息（不写入对话历史），
+    /// 模型续写的内容直接拼接到最后一条助手消息。续写中的工具调用不会执行。
+    /// 上一轮回复没有被截断时返回错误。
+    pub async fn continue_last<F>(
+        &mut self,
+        cancel: &CancellationToken,
+        on_event: F,
+    ) -> Result<TurnOutput>
+    where
+        F: FnMut(StreamEvent),
+    {
+        if self.messages.last().map(|m| m.role.as_str()) != Some("assistant") {
+            return Err(anyhow!("没有可以继续的助手回复"));
+        }
+        if !self.last_truncated.load(Ordering::Relaxed) {
+            return Err(anyhow!("上一条回复没有被截断，无需继续"));
+        }
+
+        let options = self.begin_request(&RequestOptions::default());
+        let mut turn = TurnContext {
+            cancel,
+            options: &options,
+            model_calls: 0,
+            tool_calls: Vec::new(),
+            truncated: false,
+            at_line_start: true,
+            usage: None,
+        };
+        let mut conversation = self.context_messages();
+        conversation.push(ChatMessage::new("user", CONTINUE_PROMPT));
+
+        let span = info_span!("turn", request_id = options.request_id.as_deref());
+        let mut on_event = self.tee_events(on_event);
+        let result = self
+            .stream_completion(conversation, &mut turn, &mut on_event)
+            .instrument(span)
+            .await;
+        drop(on_event);
+        if let Some(tee) = &self.output_tee {
+            tee.end_turn();
+        }
+        let continuation = match result {
+            Ok(text) => self.postprocess_text(self.tool_call_format.strip(&text)),
+            Err(e) => {
+                self.trace("error", json!({ "message": e.to_string() }));
+                return Err(e);
             }
+        };
+
+        self.trace("assistant_continuation", json!({ "content": continuation }));
+        if let Some(last) = self.messages.last_mut() {
+            last.content.push_str(&continuation);
         }
+        self.last_truncated.store(turn.truncated, Ordering::Relaxed);
+
+        Ok(TurnOutput {
+            final_answer: continuation.clone(),
+            text: continuation,
+            tool_calls: Vec::new(),
+            truncated: turn.truncated,
+            request_id: turn.request_id(),
+            usage: turn.usage,
+        })
+    }
+
+    /// 执行一轮对话：获取回复、执行工具调用并按需请求模型修正参数
+    async fn run_turn<F>(
+        &self,
+        options: &RequestOptions,
+        cancel: &CancellationToken,
+        mut on_event: F,
+    ) -> Result<TurnOutput>
+    where
+        F: FnMut(StreamEvent),
+    {
+        let intermediate_options = self.intermediate_options.or(options);
+        // 连接了工具服务时第一次调用可能决定调用工具，使用中间调用的参数
+        let tools_available = self.tools_client.is_some();
+        let mut turn = TurnContext {
+            cancel,
+            options: if tools_available {
+                &intermediate_options
+            } else {
+                options
+            },
+            model_calls: 0,
+            tool_calls: Vec::new(),
+            truncated: false,
+            at_line_start: true,
+            usage: None,
+        };
+
+        let full_response = self
+            .stream_completion(self.context_messages(), &mut turn, &mut on_event)
+            .await?;
 
         // 检查是否包含工具调用
-        let tool_calls = parse_tool_calls(&full_response);
-        if !tool_calls.is_empty() && self.tools_client.is_some() {
-            let mut result_content = full_response.clone();
-
-            // 逐个执行工具调用
-            for tool_params in tool_calls {
-                let tool_name = tool_params.name.clone();
-                callback(&format!("\n执行工具 `{}`...\n", tool_name));
-
-                match self.execute_tool(tool_params).await {
-                    Ok(result) => {
-                        let result_text = format_tool_result(&tool_name, &result);
-                        result_content.push_str("\n\n");
-                        result_content.push_str(&result_text);
-                        callback("\n\n");
-                        callback(&result_text);
-                    }
+        let mut tool_calls = self.tool_call_format.parse(&full_response);
+        if tool_calls.is_empty() || !tools_available {
+            return Ok(TurnOutput {
+                final_answer: full_response.clone(),
+                text: full_response,
+                tool_calls: Vec::new(),
+                truncated: turn.truncated,
+                request_id: turn.request_id(),
+                usage: turn.usage,
+            });
+        }
+
+        // 已执行的工具调用块不写入历史，避免模型在后续回复中重复这些内容
+        let mut result_content = self.tool_call_format.strip(&full_response);
+        let mut conversation = self.context_messages();
+        let mut last_response = full_response;
+        let mut attempts = 0;
+
+        loop {
+            let called_tools: Vec<String> = tool_calls.iter().map(|c| c.name.clone()).collect();
+            let round = self
+                .run_tool_calls(tool_calls, &mut turn, &mut on_event, &mut result_content)
+                .await?;
+            conversation.push(ChatMessage::new(
+                "assistant",
+                summarize_tool_round(&self.tool_call_format, &last_response, &called_tools),
+            ));
+
+            let retry = !round.invalid_args.is_empty() && attempts < self.tool_arg_autofix_attempts;
+            if !retry {
+                conversation.push(ChatMessage::new(
+                    "user",
+                    format!(
+                        "工具调用结果：\n{}\n请根据以上结果回答。",
+                        round.feedback.join("\n\n")
+                    ),
+                ));
+                break;
+            }
+            attempts += 1;
+
+            // 把校验错误反馈给模型，让它修正参数后重新调用
+            conversation.push(ChatMessage::new(
+                "user",
+                format!(
+                    "工具调用结果：\n{}\n以下工具调用的参数校验失败：\n{}\n请修正参数后重新调用这些工具。",
+                    round.feedback.join("\n\n"),
+                    round.invalid_args.join("\n")
+                ),
+            ));
+
+            let notice = format!(
+                "参数校验失败，正在请求模型修正参数（第 {} 次）...\n",
+                attempts
+            );
+            self.emit_notice(&mut turn, &mut on_event, &notice);
+            self.tool_output_format
+                .push_segment(&mut result_content, &notice);
+
+            // 修正参数的调用只用于重新选择工具，使用中间调用的参数
+            turn.options = &intermediate_options;
+            last_response = self
+                .stream_completion(conversation.clone(), &mut turn, &mut on_event)
+                .await?;
+            let stripped = self.tool_call_format.strip(&last_response);
+            if !stripped.is_empty() {
+                self.tool_output_format
+                    .push_segment(&mut result_content, &stripped);
+            }
+
+            tool_calls = self.tool_call_format.parse(&last_response);
+            if tool_calls.is_empty() {
+                // 模型没有重新调用工具，这次回复就是回答
+                return Ok(TurnOutput {
+                    text: result_content,
+                    final_answer: stripped,
+                    request_id: turn.request_id(),
+                    tool_calls: turn.tool_calls,
+                    truncated: turn.truncated,
+                    usage: turn.usage,
+                });
+            }
+        }
+
+        // 工具结果已经反馈给模型，使用本轮的参数生成面向用户的回答
+        turn.options = options;
+        let answer = self
+            .stream_completion(conversation, &mut turn, &mut on_event)
+            .await?;
+        if !self.tool_call_format.parse(&answer).is_empty() {
+            warn!("回答中的工具调用不会被执行");
+        }
+        let final_answer = self.tool_call_format.strip(&answer);
+        if !final_answer.is_empty() {
+            self.tool_output_format
+                .push_segment(&mut result_content, &final_answer);
+        }
+
+        Ok(TurnOutput {
+            text: result_content,
+            final_answer,
+            request_id: turn.request_id(),
+            tool_calls: turn.tool_calls,
+            truncated: turn.truncated,
+            usage: turn.usage,
+        })
+    }
+
+    /// 对最终回复执行后处理
+    fn postprocess(&self, mut output: TurnOutput) -> TurnOutput {
+        output.text = self.postprocess_text(output.text);
+        output.final_answer = self.postprocess_text(output.final_answer);
+        output
+    }
+
+    fn postprocess_text(&self, text: String) -> String {
+        match &self.postprocessor {
+            Some(postprocessor) => postprocessor(text),
+            None => text,
+        }
+    }
+
+    /// 本轮发送给模型的消息：插入滚动摘要和示例对话，设置了预算时再裁剪
+    fn context_messages(&self) -> Vec<ChatMessage> {
+        let mut messages = self.messages.clone();
+
+        // 示例对话紧跟在开头的系统消息之后，裁剪时始终保留。
+        // 系统提示和示例在各次请求之间保持不变，放在最前面以便命中服务端的上下文缓存；
+        // 定期更新的滚动摘要放在它们之后，更新时不会影响前面的缓存
+        let position = messages
+            .iter()
+            .position(|m| m.role != "system")
+            .unwrap_or(messages.len());
+        messages.splice(position..position, self.examples.iter().cloned());
+        let mut pinned = position + self.examples.len();
+        if let Some(summary) = self
+            .rolling_summary
+            .as_ref()
+            .and_then(RollingSummary::message)
+        {
+            messages.insert(pinned, summary);
+            pinned += 1;
+        }
+
+        match &self.trimmer {
+            Some(trimmer) => trimmer.trim(&messages, pinned),
+            None => messages,
+        }
+    }
+
+    /// 记录完成了一轮对话，达到间隔时更新滚动摘要
+    ///
+    /// 返回是否更新了摘要。未启用滚动摘要时什么也不做。
+    pub async fn update_rolling_summary(&mut self) -> Result<bool> {
+        let Some(summary) = &mut self.rolling_summary else {
+            return Ok(false);
+        };
+        if !summary.record_turn() {
+            return Ok(false);
+        }
+        self.refresh_summary().await
+    }
+
+    /// 把上次摘要之后的消息合并进滚动摘要，没有新消息时返回 false
+    async fn refresh_summary(&mut self) -> Result<bool> {
+        // 摘要请求跟随刚结束的一轮对话，沿用它的请求 ID
+        let mut options = RequestOptions::default();
+        if let Some(request_id) = self.request_id() {
+            options = options.with_request_id(request_id);
+        }
+        let Some(summary) = &mut self.rolling_summary else {
+            return Ok(false);
+        };
+        let Some(request) = summary.build_request(&self.messages) else {
+            return Ok(false);
+        };
+
+        let text = self.client.chat_with(request, &options).await?;
+        let summarized_until = self.messages.len();
+        summary.update(text, summarized_until);
+
+        let content = summary.summary().unwrap_or_default().to_string();
+        self.trace("summary", json!({ "content": content }));
+        Ok(true)
+    }
+
+    /// 对话消息超过归档阈值时，把最早的对话轮次写入归档文件并从内存中移除
+    ///
+    /// 返回归档的消息数量。未启用归档时什么也不做。
+    pub async fn archive_old_turns(&mut self) -> Result<usize> {
+        let Some(cut) = self
+            .archive
+            .as_ref()
+            .and_then(|archive| archive.cut_point(&self.messages))
+        else {
+            return Ok(0);
+        };
+
+        // 先把要移除的消息纳入摘要，避免丢失长期上下文
+        let needs_summary = self
+            .rolling_summary
+            .as_ref()
+            .is_some_and(|summary| summary.summarized_until() < cut);
+        if needs_summary {
+            self.refresh_summary().await?;
+        }
+
+        let archived: Vec<ChatMessage> = self.messages[..cut]
+            .iter()
+            .filter(|m| m.role != "system")
+            .cloned()
+            .collect();
+        if let Some(archive) = &mut self.archive {
+            archive.append(&archived)?;
+        }
+
+        // 系统消息不归档，保留在原来的位置
+        let mut index = 0;
+        self.messages.retain(|m| {
+            let keep = index >= cut || m.role == "system";
+            index += 1;
+            keep
+        });
+        if let Some(summary) = &mut self.rolling_summary {
+            summary.forget(archived.len());
+        }
+
+        self.trace("archive", json!({ "count": archived.len() }));
+        Ok(archived.len())
+    }
+
+    /// 流式获取一次模型回复
+    async fn stream_completion<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        turn: &mut TurnContext<'_>,
+        on_event: &mut F,
+    ) -> Result<String>
+    where
+        F: FnMut(StreamEvent),
+    {
+        if turn.model_calls >= self.max_model_calls_per_turn {
+            warn!(
+                "本轮模型调用次数已达上限 {}，停止继续请求",
+                self.max_model_calls_per_turn
+            );
+            return Err(BudgetExhausted {
+                limit: self.max_model_calls_per_turn,
+            }
+            .into());
+        }
+        turn.model_calls += 1;
+
+        let cancel = turn.cancel;
+        let mut stream = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Err(TurnCancelled.into()),
+            stream = self.client.chat_stream_with(messages, turn.options) => stream?,
+        };
+        let mut full_response = String::new();
+        let mut filter = ToolBlockFilter::new(self.tool_call_format.clone());
+        let mut chunk_count = 0;
+        let mut reasoning_chars = 0;
+        let mut truncated = false;
+        let mut usage = None;
+        let mut native_calls = ToolCallAccumulator::new();
+        let mut native_calls_finished = false;
+
+        loop {
+            let chunk = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => return Err(TurnCancelled.into()),
+                chunk = stream.next() => chunk,
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            let delta = chunk?;
+            chunk_count += 1;
+            truncated |= delta.is_truncated();
+            native_calls.push(&delta.tool_calls);
+            native_calls_finished |= delta.is_tool_calls();
+            if delta.usage.is_some() {
+                usage = delta.usage;
+            }
+            if !delta.reasoning_content.is_empty() {
+                reasoning_chars += delta.reasoning_content.chars().count();
+                on_event(StreamEvent::Reasoning(&delta.reasoning_content));
+            }
+            if !delta.content.is_empty() {
+                let visible = filter.push(&delta.content);
+                if !visible.is_empty() {
+                    on_event(StreamEvent::Content(&visible));
+                    turn.at_line_start = visible.ends_with('\n');
+                }
+                for call in filter.take_detected() {
+                    debug!("流式输出中检测到工具调用: {}", call.name);
+                    on_event(StreamEvent::ToolDetected(&call));
+                }
+                full_response.push_str(&delta.content);
+            }
+        }
+        let rest = filter.finish();
+        if !rest.is_empty() {
+            on_event(StreamEvent::Content(&rest));
+            turn.at_line_start = rest.ends_with('\n');
+        }
+        if !native_calls.is_empty() {
+            self.append_native_calls(
+                native_calls,
+                native_calls_finished,
+                &mut full_response,
+                on_event,
+            );
+        }
+
+        if truncated {
+            warn!("模型回复因达到 max_tokens 被截断");
+        }
+        turn.truncated = truncated;
+        if let Some(usage) = &usage {
+            turn.usage.get_or_insert_with(Usage::default).add(usage);
+            self.total_usage
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .get_or_insert_with(Usage::default)
+                .add(usage);
+        }
+
+        // 增量内容只记录汇总信息
+        self.trace(
+            "model_response",
+            json!({
+                "content": full_response,
+                "chunks": chunk_count,
+                "reasoning_chars": reasoning_chars,
+                "truncated": truncated,
+                "usage": usage,
+            }),
+        );
+
+        Ok(full_response)
+    }
+
+    /// 把拼接完成的原生函数调用转换为工具调用块追加到回复中，与文本格式的调用一起执行
+    ///
+    /// 参数片段只有在回复以 `finish_reason == "tool_calls"` 结束后才完整，
+    /// 中途结束（例如达到 max_tokens）时丢弃这些调用。
+    fn append_native_calls<F>(
+        &self,
+        native_calls: ToolCallAccumulator,
+        finished: bool,
+        full_response: &mut String,
+        on_event: &mut F,
+    ) where
+        F: FnMut(StreamEvent),
+    {
+        if !finished {
+            warn!("回复在函数调用完成前结束，丢弃不完整的函数调用");
+            return;
+        }
+
+        for call in native_calls.finish() {
+            let call = match call {
+                Ok(call) => call,
+                Err(e) => {
+                    warn!("{}", e);
+                    continue;
+                }
+            };
+            debug!("收到原生函数调用: {} ({:?})", call.name, call.id);
+            let params = ToolParameters {
+                name: call.name,
+                args: call.arguments,
+            };
+            on_event(StreamEvent::ToolDetected(&params));
+
+            if !full_response.is_empty() && !full_response.ends_with('\n') {
+                full_response.push('\n');
+            }
+            let content = json!({ "name": params.name, "args": params.args }).to_string();
+            full_response.push_str(&self.tool_call_format.wrap(&content));
+            full_response.push('\n');
+        }
+    }
+
+    /// 输出一段会话生成的提示，与之前的输出分段
+    fn emit_notice<F>(&self, turn: &mut TurnContext<'_>, on_event: &mut F, text: &str)
+    where
+        F: FnMut(StreamEvent),
+    {
+        let separator = self.tool_output_format.separator(turn.at_line_start);
+        on_event(StreamEvent::Notice(&separator));
+        on_event(StreamEvent::Notice(text));
+        turn.at_line_start = text.ends_with('\n');
+    }
+
+    /// 与工具服务通信失败后检查能否重新连接，返回反馈给模型的结果
+    ///
+    /// gRPC 连接会在下一次请求时自动重连，这里用一次列出工具的请求确认服务是否恢复。
+    /// 无法连接时标记工具服务不可用，之后的工具调用都被跳过。
+    async fn recover_tools<F>(&self, turn: &mut TurnContext<'_>, on_event: &mut F) -> ToolResult
+    where
+        F: FnMut(StreamEvent),
+    {
+        if self.probe_tools().await {
+            warn!("与工具服务的连接中断后已重新连接");
+            return ToolResult::failure(TOOLS_RECONNECTED);
+        }
+
+        self.mark_tools_unavailable(turn, on_event)
+    }
+
+    /// 标记工具服务不可用并提示用户，返回反馈给模型的结果
+    fn mark_tools_unavailable<F>(&self, turn: &mut TurnContext<'_>, on_event: &mut F) -> ToolResult
+    where
+        F: FnMut(StreamEvent),
+    {
+        warn!("工具服务不可用，暂停调用工具直到重新连接");
+        self.tools_degraded.store(true, Ordering::Relaxed);
+        self.emit_notice(
+            turn,
+            on_event,
+            "工具服务不可用，之后的工具调用将被跳过，直到重新连接。\n",
+        );
+        ToolResult::failure(TOOLS_UNAVAILABLE)
+    }
+
+    /// 工具服务能否在超时时间内响应
+    async fn probe_tools(&self) -> bool {
+        let Some(client) = &self.tools_client else {
+            return false;
+        };
+        matches!(
+            tokio::time::timeout(TOOLS_PROBE_TIMEOUT, client.list_tools()).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// 开启了存活检查时，工具服务能否在检查的超时时间内响应；未开启时总是返回 true
+    async fn tools_alive(&self) -> bool {
+        let (Some(timeout), Some(client)) = (self.tools_liveness_timeout, &self.tools_client)
+        else {
+            return true;
+        };
+        match tokio::time::timeout(timeout, client.health_check()).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                warn!("工具服务存活检查失败: {}", e);
+                false
+            }
+            Err(_) => {
+                warn!("工具服务存活检查在 {:?} 内没有响应", timeout);
+                false
+            }
+        }
+    }
+
+    /// 逐个执行工具调用，返回反馈给模型的结果和参数校验失败的描述
+    async fn run_tool_calls<F>(
+        &self,
+        mut tool_calls: Vec<ToolParameters>,
+        turn: &mut TurnContext<'_>,
+        on_event: &mut F,
+        result_content: &mut String,
+    ) -> Result<ToolRound>
+    where
+        F: FnMut(StreamEvent),
+    {
+        let mut round = ToolRound::default();
+
+        // 工具服务不可用期间，每次需要调用工具时检查一次是否已经恢复
+        if self.tools_degraded.load(Ordering::Relaxed) && self.probe_tools().await {
+            warn!("工具服务已恢复，继续调用工具");
+            self.tools_degraded.store(false, Ordering::Relaxed);
+        }
+
+        let skipped = tool_calls
+            .len()
+            .saturating_sub(self.max_tool_calls_per_turn);
+        if skipped > 0 {
+            warn!(
+                "单次回复包含 {} 个工具调用，超过上限 {}，忽略其余 {} 个",
+                tool_calls.len(),
+                self.max_tool_calls_per_turn,
+                skipped
+            );
+            tool_calls.truncate(self.max_tool_calls_per_turn);
+        }
+
+        for tool_params in tool_calls {
+            let tool_name = tool_params.name.clone();
+            self.trace(
+                "tool_call",
+                json!({ "name": tool_name, "args": tool_params.args }),
+            );
+            let notice = self.tool_output_format.notice(&tool_name);
+            self.emit_notice(turn, on_event, &notice);
+
+            let args = tool_params.args.clone();
+            let mut transport_error = None;
+            let result = if self.tools_degraded.load(Ordering::Relaxed) {
+                ToolResult::failure(TOOLS_UNAVAILABLE)
+            } else if !self.tools_alive().await {
+                self.trace(
+                    "error",
+                    json!({ "tool": tool_name, "message": "工具服务存活检查失败" }),
+                );
+                self.mark_tools_unavailable(turn, on_event)
+            } else {
+                // 取消时直接丢弃执行中的调用，gRPC 请求随之中断
+                let outcome = tokio::select! {
+                    biased;
+                    _ = turn.cancel.cancelled() => return Err(TurnCancelled.into()),
+                    outcome = self.execute_tool(tool_params, turn.options.request_id.as_deref()) => outcome,
+                };
+                match outcome {
+                    Ok(result) => result,
+                    // 原始的通信错误对模型没有帮助，尝试重新连接后反馈统一的结果
                     Err(e) => {
-                        let error_text = format!("工具 `{}` 执行失败: {}", tool_name, e);
-                        result_content.push_str("\n\n");
-                        result_content.push_str(&error_text);
-                        callback("\n\n");
-                        callback(&error_text);
+                        warn!("{}", e);
+                        self.trace(
+                            "error",
+                            json!({ "tool": tool_name, "message": e.to_string() }),
+                        );
+                        transport_error = Some(e.to_string());
+                        self.recover_tools(turn, on_event).await
                     }
                 }
+            };
+
+            self.trace(
+                "tool_result",
+                json!({
+                    "name": tool_name,
+                    "success": result.success,
+                    "data": result.data,
+                    "error": result.error,
+                }),
+            );
+            if result.is_invalid_args() {
+                round.invalid_args.push(format!(
+                    "- {}: {}",
+                    tool_name,
+                    result.error.as_deref().unwrap_or("未知错误")
+                ));
+            }
+
+            let id = self.store_tool_result(&tool_name, &args, &result);
+            let mut result_text = format_tool_result(&tool_name, &result);
+            if let Some(max_chars) = self.max_tool_result_chars {
+                result_text = truncate_tool_result(&result_text, max_chars, id);
             }
+            result_text.push_str(&format!("\n\n（结果编号：#{}）", id));
+            self.tool_output_format
+                .push_segment(result_content, &result_text);
+            self.emit_notice(turn, on_event, &result_text);
+            round.feedback.push(result_text);
+
+            let record = ToolCallRecord {
+                name: tool_name,
+                args,
+                result: Some(result),
+                error: transport_error,
+            };
+            on_event(StreamEvent::ToolCall(&record));
+            turn.tool_calls.push(record);
+        }
 
-            Ok(result_content)
-        } else {
-            Ok(full_response)
+        // 提示写入回复内容，模型在下一轮能看到哪些调用没有执行
+        if skipped > 0 {
+            let notice = format!(
+                "警告：单次回复最多执行 {} 个工具调用，其余 {} 个调用已被忽略。如有需要，请分批调用。",
+                self.max_tool_calls_per_turn, skipped
+            );
+            self.tool_output_format
+                .push_segment(result_content, &notice);
+            self.emit_notice(turn, on_event, &notice);
+            round.feedback.push(notice);
         }
+
+        Ok(round)
+    }
+
+    /// 保存工具结果并返回编号
+    fn store_tool_result(&self, name: &str, args: &Value, result: &ToolResult) -> usize {
+        let mut store = self
+            .tool_results
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        store.insert(name, args.clone(), result.clone())
+    }
+
+    /// 按编号获取本次会话中保存的工具结果
+    pub fn get_tool_result(&self, id: usize) -> Option<StoredToolResult> {
+        let store = self
+            .tool_results
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        store.get(id).cloned()
     }
 
     /// 执行工具调用
-    async fn execute_tool(&self, params: ToolParameters) -> Result<ToolResult> {
-        if let Some(tools_client) = &self.tools_client {
-            let mut client = tools_client.lock().await;
-            client.execute_tool(params).await
-        } else {
-            Err(anyhow!("工具客户端未初始化"))
+    ///
+    /// 工具返回的结果（包括 `success: false`）原样返回；工具不存在、工具执行出错等
+    /// 调用本身的失败转换为失败的工具结果，只有与工具服务通信失败时才返回错误。
+    async fn execute_tool(
+        &self,
+        params: ToolParameters,
+        request_id: Option<&str>,
+    ) -> Result<ToolResult, ToolTransportError> {
+        let Some(client) = &self.tools_client else {
+            return Ok(ToolResult::failure("工具客户端未初始化，无法执行工具"));
+        };
+
+        let tool = params.name.clone();
+        match client
+            .execute_tool_with_request_id(params, request_id)
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => match e.downcast_ref::<ToolServiceError>() {
+                Some(service_error) if !service_error.is_transport() => {
+                    Ok(ToolResult::failure(service_error.to_string()))
+                }
+                _ => Err(ToolTransportError { tool, source: e }),
+            },
         }
     }
 
+    /// 本次会话累计的 token 用量，包括命中上下文缓存的部分
+    pub fn total_usage(&self) -> Option<Usage> {
+        *self
+            .total_usage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
     pub fn remove_last_message(&mut self) {
         self.messages.pop();
     }
@@ -124,7 +1506,1346 @@ impl ChatSession {
         self.messages.len()
     }
 
+    /// 估算当前对话历史的 token 数
+    pub fn estimated_tokens(&self) -> usize {
+        estimate_messages_tokens(&self.messages)
+    }
+
     pub fn is_verbose(&self) -> bool {
         self.verbose
     }
 }
+
+/// 截断过长的工具结果，保留开头和结尾各一半，中间注明截断的字节数
+fn truncate_tool_result(text: &str, max_chars: usize, id: usize) -> String {
+    let total_chars = text.chars().count();
+    if total_chars <= max_chars {
+        return text.to_string();
+    }
+
+    let head_chars = max_chars / 2;
+    let head = truncate_chars(text, head_chars);
+    let tail = last_chars(text, max_chars - head_chars);
+
+    format!(
+        "{}\n\n[已截断 {} 字节，完整结果见结果编号 #{}]\n\n{}",
+        head,
+        text.len() - head.len() - tail.len(),
+        id,
+        tail
+    )
+}
+
+/// 去掉已执行的工具调用块，改为在末尾注明调用过的工具
+fn summarize_tool_round(
+    format: &ToolCallFormat,
+    response: &str,
+    called_tools: &[String],
+) -> String {
+    let text = format.strip(response);
+    let note = format!("（已调用工具：{}）", called_tools.join("、"));
+    if text.is_empty() {
+        note
+    } else {
+        format!("{}\n\n{}", text, note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_agent_core::api::testing::{MockChatServer, MockResponse};
+    use rust_agent_core::api::UnsupportedFeature;
+    use rust_agent_core::correlation::DEFAULT_REQUEST_ID_HEADER;
+    use rust_agent_core::tools::rpc::server::ToolsFlightService;
+    use rust_agent_core::tools::rpc::testing::{spawn_in_process, FnTool, InProcessServer};
+    use rust_agent_core::tools::Tool;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// 把两个整数相加，参数不是整数时返回参数校验失败
+    fn add_tool() -> Box<dyn Tool> {
+        FnTool::new("add", |args: Value| async move {
+            match (args["a"].as_i64(), args["b"].as_i64()) {
+                (Some(a), Some(b)) => Ok(ToolResult::success(json!(a + b))),
+                _ => Ok(ToolResult::invalid_args("a 和 b 必须是整数")),
+            }
+        })
+        .boxed()
+    }
+
+    /// 启动带有指定工具的进程内工具服务并连接到会话，服务在返回值被丢弃时停止
+    async fn connect_tools(
+        session: &mut ChatSession,
+        tools: Vec<Box<dyn Tool>>,
+    ) -> InProcessServer {
+        let (server, client) = spawn_in_process(ToolsFlightService::with_tools(tools))
+            .await
+            .unwrap();
+        session.set_tools_client(client);
+        session.refresh_tools().await.unwrap();
+        server
+    }
+
+    /// 调用工具的工具调用块
+    fn tool_block(name: &str, args: Value) -> String {
+        ToolCallFormat::default().wrap(&json!({ "name": name, "args": args }).to_string())
+    }
+
+    fn results(output: &TurnOutput) -> Vec<&ToolResult> {
+        output
+            .tool_calls
+            .iter()
+            .map(|call| call.result.as_ref().unwrap())
+            .collect()
+    }
+
+    /// 运行一轮对话，返回结果和流式输出的回答内容
+    async fn ask(session: &mut ChatSession, prompt: &str) -> (TurnOutput, String) {
+        session.add_user_message(prompt.to_string());
+        let mut streamed = String::new();
+        let output = session
+            .get_response_stream(|event| {
+                if let StreamEvent::Content(text) = event {
+                    streamed.push_str(text);
+                }
+            })
+            .await
+            .unwrap();
+        session.add_assistant_message(output.text.clone());
+        (output, streamed)
+    }
+
+    #[tokio::test]
+    async fn runs_a_turn_through_the_core_client() {
+        let server = MockChatServer::start(vec![MockResponse::text(&["你好", "，世界"])])
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+
+        let (output, streamed) = ask(&mut session, "打个招呼").await;
+        assert_eq!(output.text, "你好，世界");
+        assert_eq!(output.final_answer, "你好，世界");
+        assert_eq!(streamed, "你好，世界");
+        assert_eq!(session.message_count(), 2);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/v1/chat/completions");
+        assert_eq!(requests[0].body["stream"], true);
+        assert_eq!(requests[0].body["messages"][0]["content"], "打个招呼");
+    }
+
+    #[tokio::test]
+    async fn autofix_retries_tool_call_after_validation_error() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[
+                "我来算一下。\n",
+                &tool_block("add", json!({"a": "一", "b": 2})),
+            ]),
+            MockResponse::text(&[&tool_block("add", json!({"a": 1, "b": 2}))]),
+            MockResponse::text(&["等于 3。"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_tool_arg_autofix(1);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, _) = ask(&mut session, "1 加 2 等于几？").await;
+        assert_eq!(output.final_answer, "等于 3。");
+        let results = results(&output);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_invalid_args());
+        assert!(results[1].success);
+        assert_eq!(results[1].data, json!(3));
+
+        // 修正参数的请求带上了校验错误
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let messages = requests[1].body["messages"].as_array().unwrap();
+        let feedback = messages.last().unwrap();
+        assert_eq!(feedback["role"], "user");
+        assert!(feedback["content"]
+            .as_str()
+            .unwrap()
+            .contains("a 和 b 必须是整数"));
+    }
+
+    #[tokio::test]
+    async fn messages_carry_increasing_timestamps_not_sent_to_api() {
+        let server = MockChatServer::start(vec![MockResponse::text(&["好的"])])
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        session
+            .set_messages(vec![ChatMessage::new("system", "你是助手")])
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        ask(&mut session, "你好").await;
+
+        let timestamps: Vec<_> = session
+            .messages
+            .iter()
+            .map(|m| m.timestamp.unwrap())
+            .collect();
+        assert_eq!(timestamps.len(), 3);
+        assert!(timestamps[0] < timestamps[1]);
+        assert!(timestamps[1] <= timestamps[2]);
+
+        let body = &server.requests()[0].body;
+        for message in body["messages"].as_array().unwrap() {
+            let keys: Vec<_> = message.as_object().unwrap().keys().collect();
+            assert_eq!(keys, ["content", "role"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn trace_records_turn_with_tool_call_as_ndjson() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.ndjson");
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[&tool_block("add", json!({"a": 1, "b": 2}))]),
+            MockResponse::text(&["等于 3"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false)
+            .with_trace_file(&path)
+            .unwrap();
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+        ask(&mut session, "1 加 2").await;
+
+        let records: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let types: Vec<_> = records
+            .iter()
+            .map(|r| r["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "tools_refresh",
+                "user_message",
+                "model_response",
+                "tool_call",
+                "tool_result",
+                "model_response",
+                "assistant_message"
+            ]
+        );
+        assert_eq!(records[1]["payload"]["content"], "1 加 2");
+        assert_eq!(
+            records[3]["payload"],
+            json!({"name": "add", "args": {"a": 1, "b": 2}})
+        );
+        assert_eq!(records[4]["payload"]["data"], 3);
+        assert!(records.iter().all(|r| r["timestamp"].is_string()));
+    }
+
+    #[tokio::test]
+    async fn tool_calls_beyond_the_cap_are_skipped() {
+        let blocks: Vec<_> = (1..=3)
+            .map(|i| tool_block("add", json!({"a": i, "b": 0})) + "\n")
+            .collect();
+        let pieces: Vec<&str> = blocks.iter().map(String::as_str).collect();
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&pieces),
+            MockResponse::text(&["好了"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_max_tool_calls_per_turn(2);
+
+        let executed = Arc::new(AtomicUsize::new(0));
+        let counter = executed.clone();
+        let counting = FnTool::new("add", move |args: Value| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move { Ok(ToolResult::success(args["a"].clone())) }
+        });
+        let _tools = connect_tools(&mut session, vec![counting.boxed()]).await;
+
+        let (output, _) = ask(&mut session, "分别算三次").await;
+        let response = output.text;
+        assert_eq!(executed.load(Ordering::SeqCst), 2);
+        assert!(response.contains("执行成功：\n\n1"), "{}", response);
+        assert!(response.contains("执行成功：\n\n2"), "{}", response);
+        assert!(!response.contains("执行成功：\n\n3"), "{}", response);
+        assert!(response.contains("其余 1 个调用已被忽略"), "{}", response);
+    }
+
+    /// 工具执行完毕前被丢弃时记录工具被中止，执行完毕后用 `mem::forget` 跳过
+    struct AbortGuard(Arc<AtomicBool>);
+
+    impl Drop for AbortGuard {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_turn_aborts_the_running_tool() {
+        let server =
+            MockChatServer::start(vec![MockResponse::text(&[&tool_block("sleep", json!({}))])])
+                .await
+                .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+
+        let started = Arc::new(tokio::sync::Notify::new());
+        let aborted = Arc::new(AtomicBool::new(false));
+        let (tool_started, tool_aborted) = (started.clone(), aborted.clone());
+        let sleepy = FnTool::new("sleep", move |_| {
+            let started = tool_started.clone();
+            let guard = AbortGuard(tool_aborted.clone());
+            async move {
+                started.notify_one();
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                std::mem::forget(guard);
+                Ok(ToolResult::success(json!("睡醒了")))
+            }
+        });
+        let _tools = connect_tools(&mut session, vec![sleepy.boxed()]).await;
+
+        session.add_user_message("睡一会儿".to_string());
+        let cancel = CancellationToken::new();
+        let turn = session.get_response_stream_cancellable(&cancel, |_| {});
+        let canceller = async {
+            started.notified().await;
+            cancel.cancel();
+        };
+        let (result, _) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(turn, canceller)
+        })
+        .await
+        .expect("取消后本轮对话应当立即结束");
+
+        assert!(result.unwrap_err().is::<TurnCancelled>());
+        // 服务端在客户端中断请求后丢弃执行中的工具
+        for _ in 0..100 {
+            if aborted.load(Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(aborted.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn model_call_budget_stops_a_runaway_autofix_loop() {
+        let invalid = tool_block("add", json!({"a": "一", "b": 2}));
+        let server = MockChatServer::start(vec![MockResponse::text(&[&invalid]); 10])
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false)
+            .with_tool_arg_autofix(100)
+            .with_max_model_calls_per_turn(3);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        session.add_user_message("1 加 2 等于几？".to_string());
+        let err = session.get_response_stream(|_| {}).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<BudgetExhausted>().unwrap().limit, 3);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn executed_tool_blocks_are_not_sent_back_to_the_model() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[
+                "我来算一下。\n",
+                &tool_block("add", json!({"a": "一", "b": 2})),
+            ]),
+            MockResponse::text(&[&tool_block("add", json!({"a": 1, "b": 2}))]),
+            MockResponse::text(&["等于 3。"]),
+            MockResponse::text(&["还有别的问题吗？"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_tool_arg_autofix(1);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        ask(&mut session, "1 加 2 等于几？").await;
+        ask(&mut session, "谢谢").await;
+
+        let start = session.tool_call_format.start().to_string();
+        let requests = server.requests();
+        for request in &requests {
+            // 系统提示中有调用格式的说明，只检查对话消息
+            let messages = request.body["messages"].as_array().unwrap();
+            for message in messages.iter().filter(|m| m["role"] != "system") {
+                let content = message["content"].as_str().unwrap();
+                assert!(!content.contains(&start), "{}", content);
+            }
+        }
+
+        let history = requests.last().unwrap().body["messages"]
+            .as_array()
+            .unwrap();
+        let assistant = history.iter().find(|m| m["role"] == "assistant").unwrap();
+        let assistant = assistant["content"].as_str().unwrap();
+        assert_eq!(assistant.matches("我来算一下。").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_result_is_truncated_in_history_but_kept_in_store() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[&tool_block("dump", json!({}))]),
+            MockResponse::text(&["内容很长。"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_max_tool_result_chars(200);
+        let big = "数据".repeat(5000);
+        let data = big.clone();
+        let dump = FnTool::new("dump", move |_| {
+            let data = data.clone();
+            async move { Ok(ToolResult::success(json!(data))) }
+        });
+        let _tools = connect_tools(&mut session, vec![dump.boxed()]).await;
+
+        let (output, _) = ask(&mut session, "导出数据").await;
+        let response = output.text;
+        assert!(response.contains("[已截断"));
+        assert!(response.contains("完整结果见结果编号 #1"));
+        assert!(response.chars().count() < 400, "{}", response);
+
+        let stored = session.get_tool_result(1).unwrap();
+        assert_eq!(stored.name, "dump");
+        assert_eq!(stored.result.data, json!(big));
+    }
+
+    #[tokio::test]
+    async fn turn_output_lists_executed_tools_and_results() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[&tool_block("add", json!({"a": 2, "b": 5}))]),
+            MockResponse::text(&["结果是 7。"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, _) = ask(&mut session, "2 加 5").await;
+        assert_eq!(output.tool_calls.len(), 1);
+        let call = &output.tool_calls[0];
+        assert_eq!(call.name, "add");
+        assert_eq!(call.args, json!({"a": 2, "b": 5}));
+        assert!(call.error.is_none());
+        let result = call.result.as_ref().unwrap();
+        assert!(result.success);
+        assert_eq!(result.data, json!(7));
+    }
+
+    #[tokio::test]
+    async fn rolling_summary_is_injected_after_k_turns_and_updated() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["答一"]),
+            MockResponse::text(&["答二"]),
+            MockResponse::completion("摘要一"),
+            MockResponse::text(&["答三"]),
+            MockResponse::text(&["答四"]),
+            MockResponse::completion("摘要二"),
+            MockResponse::text(&["答五"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_rolling_summary(2);
+        session
+            .set_messages(vec![ChatMessage::new("system", "工具说明")])
+            .unwrap();
+
+        let mut updated = Vec::new();
+        for prompt in ["问一", "问二", "问三", "问四", "问五"] {
+            ask(&mut session, prompt).await;
+            updated.push(session.update_rolling_summary().await.unwrap());
+        }
+        assert_eq!(updated, [false, true, false, true, false]);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 7);
+        let system_messages = |index: usize| -> Vec<String> {
+            requests[index].body["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|m| m["role"] == "system")
+                .map(|m| m["content"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        // 达到间隔之前没有摘要
+        assert_eq!(system_messages(1), ["工具说明"]);
+        // 摘要作为单独的系统消息插入在工具说明之后
+        let third = system_messages(3);
+        assert_eq!(third.len(), 2);
+        assert_eq!(third[0], "工具说明");
+        assert!(third[1].ends_with("摘要一"), "{}", third[1]);
+
+        // 第二次更新在旧摘要的基础上合并，且只包含上次摘要之后的对话
+        let update = requests[5].body["messages"][1]["content"].as_str().unwrap();
+        assert!(update.contains("之前的摘要：\n摘要一"), "{}", update);
+        assert!(
+            update.contains("问三") && !update.contains("问二"),
+            "{}",
+            update
+        );
+
+        // 更新后仍只有一条摘要消息
+        let fifth = system_messages(6);
+        assert_eq!(fifth.len(), 2);
+        assert!(fifth[1].ends_with("摘要二"), "{}", fifth[1]);
+        assert_eq!(session.message_count(), 11);
+
+        // 摘要请求沿用刚结束的一轮对话的请求 ID
+        let request_id = |index: usize| requests[index].header(DEFAULT_REQUEST_ID_HEADER);
+        assert!(request_id(1).is_some());
+        assert_eq!(request_id(2), request_id(1));
+        assert_ne!(request_id(3), request_id(1));
+    }
+
+    #[tokio::test]
+    async fn tool_only_response_emits_no_assistant_content() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["\n", &tool_block("add", json!({"a": 1, "b": 2})), "\n"]),
+            MockResponse::text(&["结果是 3"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        session.add_user_message("1 加 2".to_string());
+        let mut events = Vec::new();
+        session
+            .get_response_stream(|event| match event {
+                StreamEvent::Content(text) => events.push(("content", text.to_string())),
+                StreamEvent::Notice(text) => events.push(("notice", text.to_string())),
+                _ => {}
+            })
+            .await
+            .unwrap();
+
+        // 工具调用之前没有正文，执行提示是第一个可见输出
+        let first_visible = events
+            .iter()
+            .find(|(_, text)| !text.trim().is_empty())
+            .unwrap();
+        assert_eq!(first_visible.0, "notice");
+        assert!(first_visible.1.contains("执行工具 `add`"), "{:?}", events);
+    }
+
+    #[tokio::test]
+    async fn exceeding_the_threshold_archives_the_oldest_turns_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive/session.ndjson");
+        let server = MockChatServer::start(vec![]).await.unwrap();
+        let mut session = ChatSession::new(server.client(), false)
+            .with_auto_archive(&path, 4)
+            .unwrap();
+        session
+            .set_messages(vec![ChatMessage::new("system", "工具说明")])
+            .unwrap();
+
+        let mut archived = Vec::new();
+        for turn in 1..=4 {
+            session.add_user_message(format!("问{}", turn));
+            session.add_assistant_message(format!("答{}", turn));
+            archived.push(session.archive_old_turns().await.unwrap());
+        }
+        assert_eq!(archived, [0, 0, 2, 2]);
+
+        let lines: Vec<ChatMessage> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let contents: Vec<&str> = lines.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["问1", "答1", "问2", "答2"]);
+
+        // 系统消息不归档，内存中保留最近的对话
+        let remaining: Vec<&str> = session
+            .messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(remaining, ["工具说明", "问3", "答3", "问4", "答4"]);
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn xml_format_calls_are_executed_and_hidden() {
+        let format = ToolCallFormat::xml();
+        let call = json!({"name": "add", "args": {"a": 1, "b": 2}}).to_string();
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[&format.wrap(&call)]),
+            MockResponse::text(&["结果是 3"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_tool_call_format(format);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, streamed) = ask(&mut session, "1 加 2").await;
+        assert_eq!(output.tool_calls.len(), 1);
+        assert_eq!(output.tool_calls[0].result.as_ref().unwrap().data, json!(3));
+        assert!(!streamed.contains("<tool>"), "{}", streamed);
+    }
+
+    #[tokio::test]
+    async fn tool_deciding_calls_use_intermediate_params_and_the_answer_uses_the_turn_params() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[&tool_block("add", json!({"a": "一", "b": 2}))]),
+            MockResponse::text(&[&tool_block("add", json!({"a": 1, "b": 2}))]),
+            MockResponse::text(&["1 加 2 等于 3。"]),
+            MockResponse::text(&["你好"]),
+        ])
+        .await
+        .unwrap();
+        let client = server.client().with_temperature(0.8);
+        let intermediate = RequestOptions::new().with_temperature(0.1).with_top_p(0.5);
+        let mut session = ChatSession::new(client.clone(), false)
+            .with_tool_arg_autofix(1)
+            .with_intermediate_options(intermediate);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, _) = ask(&mut session, "1 加 2 等于几？").await;
+        assert_eq!(output.final_answer, "1 加 2 等于 3。");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let params = |index: usize| {
+            let body = &requests[index].body;
+            (
+                body["temperature"].as_f64().unwrap(),
+                body["top_p"].as_f64(),
+            )
+        };
+        // 第一次调用和参数修正调用决定调用哪些工具
+        for index in [0, 1] {
+            let (temperature, top_p) = params(index);
+            assert!((temperature - 0.1).abs() < 1e-6);
+            assert!((top_p.unwrap() - 0.5).abs() < 1e-6);
+        }
+        // 工具结果反馈给模型后，生成回答的调用使用本轮的参数
+        let (temperature, top_p) = params(2);
+        assert!((temperature - 0.8).abs() < 1e-6);
+        assert_eq!(top_p, None);
+        let messages = requests[2].body["messages"].as_array().unwrap();
+        let feedback = messages.last().unwrap()["content"].as_str().unwrap();
+        assert!(feedback.starts_with("工具调用结果："), "{}", feedback);
+        assert!(feedback.contains('3'), "{}", feedback);
+
+        // 没有连接工具时不会调用工具，直接使用本轮的参数
+        let mut session = ChatSession::new(client, false)
+            .with_intermediate_options(RequestOptions::new().with_temperature(0.1));
+        ask(&mut session, "你好").await;
+        let temperature = server.requests()[3].body["temperature"].as_f64().unwrap();
+        assert!((temperature - 0.8).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn temperature_override_applies_to_a_single_turn() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["一"]),
+            MockResponse::text(&["二"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client().with_temperature(0.8), false);
+
+        session.add_user_message("准确回答".to_string());
+        let options = RequestOptions::new().with_temperature(0.1);
+        session
+            .get_response_stream_with(&options, &CancellationToken::new(), |_| {})
+            .await
+            .unwrap();
+        session.add_assistant_message("一".to_string());
+        ask(&mut session, "随便聊聊").await;
+
+        let temperature = |index: usize| {
+            server.requests()[index].body["temperature"]
+                .as_f64()
+                .unwrap()
+        };
+        assert!((temperature(0) - 0.1).abs() < 1e-6);
+        assert!((temperature(1) - 0.8).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn continue_last_extends_a_length_truncated_reply() {
+        let server = MockChatServer::start(vec![
+            MockResponse::frames(vec![
+                json!({"choices": [{"index": 0, "delta": {"content": "第一部分，"}}]}),
+                json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "length"}]}),
+            ]),
+            MockResponse::text(&["第二部分。"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+
+        let (output, _) = ask(&mut session, "写一篇长文").await;
+        assert!(output.truncated);
+
+        let cancel = CancellationToken::new();
+        let continued = session.continue_last(&cancel, |_| {}).await.unwrap();
+        assert_eq!(continued.text, "第二部分。");
+        assert!(!continued.truncated);
+        assert_eq!(
+            session.messages.last().unwrap().content,
+            "第一部分，第二部分。"
+        );
+        assert_eq!(session.message_count(), 2);
+
+        // 续写请求以续写提示结尾，提示不写入对话历史
+        let request = &server.requests()[1].body["messages"];
+        let messages = request.as_array().unwrap();
+        assert_eq!(messages.last().unwrap()["content"], CONTINUE_PROMPT);
+        assert_eq!(messages[messages.len() - 2]["content"], "第一部分，");
+
+        // 没有被截断的回复不能继续
+        assert!(session.continue_last(&cancel, |_| {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn examples_follow_the_system_prompt_in_order_and_survive_trimming() {
+        let server = MockChatServer::start(vec![MockResponse::text(&["好的"])])
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_context_budget(300);
+        session
+            .set_messages(vec![ChatMessage::new("system", "工具说明")])
+            .unwrap();
+        session.add_example("示例问一", "示例答一");
+        session.add_example("示例问二", "示例答二");
+        for turn in 0..5 {
+            session.add_user_message(format!("旧问题{}：{}", turn, "很长的内容".repeat(500)));
+            session.add_assistant_message(format!("旧回答{}：{}", turn, "很长的内容".repeat(500)));
+        }
+
+        ask(&mut session, "新问题").await;
+
+        let body = &server.requests()[0].body;
+        let contents: Vec<&str> = body["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "工具说明",
+                "示例问一",
+                "示例答一",
+                "示例问二",
+                "示例答二",
+                "新问题"
+            ]
+        );
+        // 示例只在请求中出现，不写入对话历史
+        assert!(!session
+            .messages
+            .iter()
+            .any(|m| m.content.starts_with("示例")));
+    }
+
+    #[tokio::test]
+    async fn postprocessor_changes_the_returned_text_but_not_the_stream() {
+        let echo = FnTool::new("echo", |args: Value| async move {
+            Ok(ToolResult::success(args["text"].clone()))
+        });
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[
+                "let me echo.\n",
+                &tool_block("echo", json!({"text": "abc"})),
+            ]),
+            MockResponse::text(&["done"]),
+        ])
+        .await
+        .unwrap();
+        let mut session =
+            ChatSession::new(server.client(), false).with_postprocessor(|text| text.to_uppercase());
+        let _tools = connect_tools(&mut session, vec![echo.boxed()]).await;
+
+        let (output, streamed) = ask(&mut session, "echo abc").await;
+
+        // 后处理在工具结果拼接之后执行，工具结果同样被处理
+        assert!(output.text.contains("LET ME ECHO."), "{}", output.text);
+        assert!(output.text.contains("ABC"), "{}", output.text);
+        assert!(!output.text.contains("abc"), "{}", output.text);
+        assert_eq!(output.final_answer, output.final_answer.to_uppercase());
+        // 流式回调收到的是模型的原始输出
+        assert!(streamed.contains("let me echo."), "{}", streamed);
+        // 调用方写入对话历史的是处理后的文本
+        assert_eq!(session.messages.last().unwrap().content, output.text);
+    }
+
+    #[tokio::test]
+    async fn tool_notice_starts_on_a_new_line_after_model_text() {
+        let reply =
+            || MockResponse::text(&["我来算一下", &tool_block("add", json!({"a": 1, "b": 2}))]);
+        let answer = || MockResponse::text(&["等于 3"]);
+        let server = MockChatServer::start(vec![reply(), answer(), reply(), answer()])
+            .await
+            .unwrap();
+
+        let formats = [
+            (
+                ToolOutputFormat::default(),
+                "我来算一下\n\n执行工具 `add`...\n",
+            ),
+            (
+                ToolOutputFormat::new("运行 {name}", 0),
+                "我来算一下\n运行 add\n",
+            ),
+        ];
+        for (format, expected) in formats {
+            let mut session =
+                ChatSession::new(server.client(), false).with_tool_output_format(format);
+            let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+            session.add_user_message("1 加 2".to_string());
+            let mut visible = String::new();
+            session
+                .get_response_stream(|event| match event {
+                    StreamEvent::Content(text) | StreamEvent::Notice(text) => {
+                        visible.push_str(text)
+                    }
+                    _ => {}
+                })
+                .await
+                .unwrap();
+            assert!(visible.starts_with(expected), "{:?}", visible);
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_calls_are_detected_as_soon_as_their_block_completes() {
+        let block = tool_block("add", json!({"a": 1, "b": 2}));
+        let (head, tail) = block.split_at(block.len() / 2);
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["我来算一下", head, tail, "\n还在继续输出"]),
+            MockResponse::text(&["等于 3"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        session.add_user_message("1 加 2".to_string());
+        let mut events = Vec::new();
+        session
+            .get_response_stream(|event| match event {
+                StreamEvent::Content(text) => events.push(format!("content:{}", text)),
+                StreamEvent::ToolDetected(call) => events.push(format!("detected:{}", call.name)),
+                StreamEvent::ToolCall(call) => events.push(format!("executed:{}", call.name)),
+                _ => {}
+            })
+            .await
+            .unwrap();
+
+        // 块写完时立即报告，后续内容仍在输出，工具在回复结束后才执行
+        assert_eq!(
+            events,
+            vec![
+                "content:我来算一下",
+                "detected:add",
+                "content:\n还在继续输出",
+                "executed:add",
+                "content:等于 3",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn forked_branches_keep_separate_histories() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["你好"]),
+            MockResponse::text(&["答 A"]),
+            MockResponse::text(&["答 B"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+        ask(&mut session, "打个招呼").await;
+
+        let mut branch = session.fork();
+        let (output, _) = ask(&mut session, "问题 A").await;
+        assert_eq!(output.text, "答 A");
+        let (output, _) = ask(&mut branch, "问题 B").await;
+        assert_eq!(output.text, "答 B");
+
+        let contents = |session: &ChatSession| {
+            session
+                .messages
+                .iter()
+                .map(|m| m.content.clone())
+                .collect::<Vec<_>>()
+        };
+        let session_history = contents(&session);
+        let branch_history = contents(&branch);
+        assert_eq!(session_history[..3], branch_history[..3]);
+        assert_eq!(session_history[3..], ["问题 A", "答 A"]);
+        assert_eq!(branch_history[3..], ["问题 B", "答 B"]);
+
+        // 分支共享工具连接，请求中只包含各自的历史
+        assert_eq!(branch.tools().len(), 1);
+        let requests = server.requests();
+        let sent = requests[2].body["messages"].as_array().unwrap();
+        assert!(!sent.iter().any(|m| m["content"] == "问题 A"));
+        assert!(sent.iter().any(|m| m["content"] == "问题 B"));
+    }
+
+    #[tokio::test]
+    async fn tool_failures_are_fed_back_and_transport_failures_degrade_the_tools() {
+        let call = || MockResponse::text(&[&tool_block("lookup", json!({"id": 7}))]);
+        let answer = || MockResponse::text(&["没有找到"]);
+        let server = MockChatServer::start(vec![call(), answer(), call(), answer()])
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let lookup = FnTool::new("lookup", |_| async {
+            Ok(ToolResult::failure("记录 7 不存在"))
+        });
+        let tools = connect_tools(&mut session, vec![lookup.boxed()]).await;
+
+        // 工具返回的失败原样反馈给模型，不是通信错误
+        let (output, _) = ask(&mut session, "查一下 7 号").await;
+        let record = &output.tool_calls[0];
+        assert_eq!(record.error, None);
+        assert_eq!(
+            record.result.as_ref().unwrap().error.as_deref(),
+            Some("记录 7 不存在")
+        );
+        assert!(output.text.contains("记录 7 不存在"), "{}", output.text);
+        assert!(!session.tools_degraded.load(Ordering::Relaxed));
+
+        // 工具服务停止后的调用是通信错误，之后暂停调用工具
+        tools.stop().await;
+        let (output, _) = ask(&mut session, "再查一次").await;
+        let record = &output.tool_calls[0];
+        assert!(record.error.is_some());
+        assert_eq!(
+            record.result.as_ref().unwrap().error.as_deref(),
+            Some(TOOLS_UNAVAILABLE)
+        );
+        assert!(session.tools_degraded.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn turn_degrades_gracefully_when_the_tools_server_disappears() {
+        let call = || MockResponse::text(&[&tool_block("add", json!({"a": 1, "b": 2}))]);
+        let answer = || MockResponse::text(&["好的"]);
+        let server = MockChatServer::start(vec![
+            call(),
+            answer(),
+            call(),
+            answer(),
+            call(),
+            answer(),
+            call(),
+            answer(),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, _) = ask(&mut session, "1 加 2").await;
+        assert_eq!(results(&output)[0].data, json!(3));
+
+        // 服务在会话中途消失：本轮照常结束，模型只看到统一的不可用结果
+        tools.stop().await;
+        session.add_user_message("再算一次".to_string());
+        let mut notices = String::new();
+        let output = session
+            .get_response_stream(|event| {
+                if let StreamEvent::Notice(text) = event {
+                    notices.push_str(text);
+                }
+            })
+            .await
+            .unwrap();
+        session.add_assistant_message(output.text.clone());
+        assert_eq!(
+            results(&output)[0].error.as_deref(),
+            Some(TOOLS_UNAVAILABLE)
+        );
+        assert!(output.text.contains(TOOLS_UNAVAILABLE), "{}", output.text);
+        let raw_error = output.tool_calls[0].error.clone().unwrap();
+        assert!(!output.text.contains(&raw_error), "{}", output.text);
+        assert!(notices.contains("之后的工具调用将被跳过"), "{}", notices);
+        assert!(session.tools_degraded.load(Ordering::Relaxed));
+
+        // 不可用期间直接跳过调用，不再尝试执行
+        let (output, _) = ask(&mut session, "还能算吗").await;
+        assert_eq!(output.tool_calls[0].error, None);
+        assert_eq!(
+            results(&output)[0].error.as_deref(),
+            Some(TOOLS_UNAVAILABLE)
+        );
+
+        // 重新连接后恢复调用工具
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+        assert!(!session.tools_degraded.load(Ordering::Relaxed));
+        let (output, _) = ask(&mut session, "1 加 2").await;
+        assert_eq!(results(&output)[0].data, json!(3));
+    }
+
+    #[tokio::test]
+    async fn native_mode_declares_tools_and_reassembles_split_arguments() {
+        let call = |index: usize, function: Value| {
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+                {"index": index, "function": function}
+            ]}}]})
+        };
+        let native_call = MockResponse::frames(vec![
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "id": "call_1", "function": {"name": "add", "arguments": ""}}
+            ]}}]}),
+            call(0, json!({"arguments": "{\"a\": "})),
+            call(0, json!({"arguments": "1, \"b"})),
+            call(0, json!({"arguments": "\": 2}"})),
+            json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}]}),
+        ]);
+        let server = MockChatServer::start(vec![
+            native_call,
+            MockResponse::text(&["等于 3"]),
+            MockResponse::text(&["你好"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_native_tools(true);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, _) = ask(&mut session, "1 加 2").await;
+        assert_eq!(output.tool_calls.len(), 1);
+        assert_eq!(output.tool_calls[0].args, json!({"a": 1, "b": 2}));
+        assert_eq!(results(&output)[0].data, json!(3));
+        let tools = &server.requests()[0].body["tools"];
+        assert_eq!(tools[0]["type"], "function");
+        assert_eq!(tools[0]["function"]["name"], "add");
+
+        // 未启用原生模式时不声明工具
+        let mut session = ChatSession::new(server.client(), false);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+        ask(&mut session, "你好").await;
+        assert!(server.requests()[2].body.get("tools").is_none());
+    }
+
+    /// 在会话和工具服务之间转发数据的代理；`frozen` 被设置后不再转发任何数据但保持连接，
+    /// 模拟服务进程挂起：请求既不会失败，也永远等不到响应
+    async fn spawn_freezing_proxy(upstream: &str, frozen: Arc<AtomicBool>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        async fn forward(
+            mut from: tokio::net::tcp::OwnedReadHalf,
+            mut to: tokio::net::tcp::OwnedWriteHalf,
+            frozen: Arc<AtomicBool>,
+        ) {
+            let mut buf = vec![0u8; 16 * 1024];
+            while let Ok(n) = from.read(&mut buf).await {
+                if n == 0 {
+                    return;
+                }
+                if frozen.load(Ordering::SeqCst) {
+                    std::future::pending::<()>().await;
+                }
+                if to.write_all(&buf[..n]).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        let upstream = upstream.trim_start_matches("http://").to_string();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let server = TcpStream::connect(&upstream).await.unwrap();
+                let (client_read, client_write) = client.into_split();
+                let (server_read, server_write) = server.into_split();
+                tokio::spawn(forward(client_read, server_write, frozen.clone()));
+                tokio::spawn(forward(server_read, client_write, frozen.clone()));
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn liveness_check_fails_fast_when_the_tools_server_hangs() {
+        let call = || MockResponse::text(&[&tool_block("add", json!({"a": 1, "b": 2}))]);
+        let answer = || MockResponse::text(&["等于 3"]);
+        let server = MockChatServer::start(vec![call(), answer(), call(), answer()])
+            .await
+            .unwrap();
+        let tools = InProcessServer::start(ToolsFlightService::with_tools(vec![add_tool()]))
+            .await
+            .unwrap();
+        let frozen = Arc::new(AtomicBool::new(false));
+        let proxy = spawn_freezing_proxy(tools.addr(), frozen.clone()).await;
+        let client = ToolsClient::connect(&proxy).await.unwrap();
+        let mut session = ChatSession::new(server.client(), false)
+            .with_tools_liveness_check(Duration::from_millis(100));
+        session.set_tools_client(client.clone());
+
+        let (output, _) = ask(&mut session, "1 加 2").await;
+        assert_eq!(results(&output)[0].data, json!(3));
+
+        // 服务挂起后直接调用工具会一直等待
+        frozen.store(true, Ordering::SeqCst);
+        let params = ToolParameters {
+            name: "add".to_string(),
+            args: json!({"a": 1, "b": 2}),
+        };
+        let hung = tokio::time::timeout(Duration::from_millis(500), client.execute_tool(params));
+        assert!(hung.await.is_err());
+
+        // 存活检查在超时后放弃，本轮很快进入工具不可用状态
+        let started = std::time::Instant::now();
+        let (output, _) =
+            tokio::time::timeout(Duration::from_secs(5), ask(&mut session, "再算一次"))
+                .await
+                .expect("存活检查没有让调用快速失败");
+        assert!(
+            started.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            started.elapsed()
+        );
+        assert_eq!(
+            results(&output)[0].error.as_deref(),
+            Some(TOOLS_UNAVAILABLE)
+        );
+        assert!(session.tools_degraded.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn output_file_matches_the_rendered_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["我来算一下", &tool_block("add", json!({"a": 1, "b": 2}))]),
+            MockResponse::text(&["1 加 2 ", "等于 3。"]),
+            MockResponse::text(&["我来算一下", &tool_block("add", json!({"a": 1, "b": 2}))]),
+            MockResponse::text(&["1 加 2 ", "等于 3。"]),
+        ])
+        .await
+        .unwrap();
+
+        for include_tool_results in [true, false] {
+            let path = dir.path().join(format!("{}.md", include_tool_results));
+            let mut session = ChatSession::new(server.client(), false)
+                .with_output_file(&path, include_tool_results)
+                .unwrap();
+            let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+            session.add_user_message("1 加 2".to_string());
+            let mut rendered = String::new();
+            session
+                .get_response_stream(|event| match event {
+                    StreamEvent::Content(text) => rendered.push_str(text),
+                    StreamEvent::Notice(text) if include_tool_results => rendered.push_str(text),
+                    _ => {}
+                })
+                .await
+                .unwrap();
+
+            // 一轮结束时补上换行并空一行
+            let written = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(written, format!("{}\n\n", rendered));
+            assert!(written.contains("等于 3。"));
+            assert_eq!(written.contains("执行工具 `add`"), include_tool_results);
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_picks_up_tools_added_to_the_server_and_replaces_the_prompt() {
+        let server = MockChatServer::start(Vec::new()).await.unwrap();
+        let service = Arc::new(ToolsFlightService::with_tools(vec![add_tool()]));
+        let tools = InProcessServer::start_shared(service.clone())
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false)
+            .with_messages(vec![
+                ChatMessage::new("system", "你是一个助手"),
+                ChatMessage::new("user", "你好"),
+            ])
+            .unwrap();
+        session.set_tools_client(tools.connect().await.unwrap());
+
+        let names = |session: &ChatSession| -> Vec<String> {
+            session.tools().iter().map(|t| t.name.clone()).collect()
+        };
+        session.refresh_tools().await.unwrap();
+        assert_eq!(names(&session), ["add"]);
+
+        // 服务运行期间新增工具，刷新后出现在工具列表和工具提示中
+        let echo = FnTool::new("echo", |args: Value| async move {
+            Ok(ToolResult::success(args))
+        })
+        .with_description("原样返回参数");
+        service.register_tool(echo.boxed()).await;
+        assert_eq!(names(&session), ["add"]);
+        session.refresh_tools().await.unwrap();
+        assert_eq!(names(&session), ["add", "echo"]);
+
+        // 旧的工具提示被替换，仍位于对话消息之前
+        let roles: Vec<_> = session.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "system", "user"]);
+        assert_eq!(session.messages[0].content, "你是一个助手");
+        assert!(session.messages[1].content.contains("原样返回参数"));
+    }
+
+    #[tokio::test]
+    async fn final_answer_is_only_the_reply_after_tool_results() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&[
+                "我先算第一步。\n",
+                &tool_block("add", json!({"a": "一", "b": 2})),
+            ]),
+            MockResponse::text(&[
+                "参数写错了，重新算。\n",
+                &tool_block("add", json!({"a": 1, "b": 2})),
+            ]),
+            MockResponse::text(&["1 加 2 等于 3。"]),
+        ])
+        .await
+        .unwrap();
+        let mut session = ChatSession::new(server.client(), false).with_tool_arg_autofix(1);
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, streamed) = ask(&mut session, "1 加 2 等于几？").await;
+        assert_eq!(output.tool_calls.len(), 2);
+        assert_eq!(output.final_answer, "1 加 2 等于 3。");
+        // 写入历史的回复仍包含各次模型回复和工具结果
+        for part in ["我先算第一步。", "参数写错了，重新算。", "（结果编号：#2）"]
+        {
+            assert!(output.text.contains(part), "{}", output.text);
+        }
+        assert!(output.text.ends_with(&output.final_answer));
+        assert!(streamed.ends_with("1 加 2 等于 3。"));
+
+        // 回答来自工具结果反馈给模型之后的那次调用
+        let requests = server.requests();
+        let messages = requests[2].body["messages"].as_array().unwrap();
+        let feedback = messages.last().unwrap()["content"].as_str().unwrap();
+        assert!(feedback.contains("（结果编号：#2）"), "{}", feedback);
+    }
+
+    #[tokio::test]
+    async fn images_are_rejected_early_on_models_without_vision() {
+        let server = MockChatServer::start(vec![MockResponse::text(&["一只猫"])])
+            .await
+            .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        let image = "data:image/png;base64,iVBORw0KGgo=".to_string();
+
+        assert!(session.require_feature(Feature::NativeTools).is_ok());
+        let error = session
+            .add_user_message_with_images("图里是什么".to_string(), vec![image.clone()])
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UnsupportedFeature>().unwrap().feature,
+            Feature::Vision
+        );
+        assert_eq!(session.message_count(), 0);
+
+        // 已在历史中的图片同样在发送请求之前被拒绝
+        session
+            .messages
+            .push(ChatMessage::new("user", "图里是什么").with_image(image));
+        let error = session.get_response_stream(|_| {}).await.unwrap_err();
+        assert!(error.is::<UnsupportedFeature>(), "{}", error);
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn asking_after_set_messages_continues_from_that_context() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["你叫小明"]),
+            MockResponse::text(&["你 18 岁"]),
+        ])
+        .await
+        .unwrap();
+        let seeded = vec![
+            ChatMessage::new("system", "你是一个记性很好的助手"),
+            ChatMessage::new("user", "我叫小明"),
+            ChatMessage::new("assistant", "记住了"),
+        ];
+        let mut session = ChatSession::new(server.client(), false)
+            .with_messages(seeded)
+            .unwrap();
+        let _tools = connect_tools(&mut session, vec![add_tool()]).await;
+
+        let (output, _) = ask(&mut session, "我叫什么").await;
+        assert_eq!(output.text, "你叫小明");
+
+        // 替换历史后工具说明仍然保留，之前的对话不再发送
+        session
+            .set_messages(vec![
+                ChatMessage::new("user", "我今年 18 岁"),
+                ChatMessage::new("assistant", "好的"),
+            ])
+            .unwrap();
+        let (output, _) = ask(&mut session, "我几岁").await;
+        assert_eq!(output.text, "你 18 岁");
+
+        let sent = |index: usize| -> Vec<(String, String)> {
+            server.requests()[index].body["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| {
+                    let content = m["content"].as_str().unwrap();
+                    let content = if m["role"] == "system" && content.contains("add") {
+                        "<工具说明>"
+                    } else {
+                        content
+                    };
+                    (m["role"].as_str().unwrap().to_string(), content.to_string())
+                })
+                .collect()
+        };
+        let pairs = |items: &[(&str, &str)]| -> Vec<(String, String)> {
+            items
+                .iter()
+                .map(|(role, content)| (role.to_string(), content.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            sent(0),
+            pairs(&[
+                ("system", "你是一个记性很好的助手"),
+                ("system", "<工具说明>"),
+                ("user", "我叫小明"),
+                ("assistant", "记住了"),
+                ("user", "我叫什么"),
+            ])
+        );
+        assert_eq!(
+            sent(1),
+            pairs(&[
+                ("system", "<工具说明>"),
+                ("user", "我今年 18 岁"),
+                ("assistant", "好的"),
+                ("user", "我几岁"),
+            ])
+        );
+
+        // 不合法的历史被拒绝，原有历史保持不变
+        let count = session.message_count();
+        let error = session
+            .set_messages(vec![
+                ChatMessage::new("user", "你好"),
+                ChatMessage::new("system", "晚到的系统消息"),
+            ])
+            .unwrap_err();
+        assert_eq!(error.downcast_ref::<InvalidMessages>().unwrap().index, 1);
+        assert_eq!(session.message_count(), count);
+    }
+}
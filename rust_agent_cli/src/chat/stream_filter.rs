@@ -0,0 +1,140 @@
+use rust_agent_core::tools::{ToolCallFormat, ToolParameters};
+
+/// 从流式输出中隐藏工具调用块
+///
+/// 工具调用块会被执行并单独显示执行提示，原始 JSON 不需要展示给用户。
+/// 增量可能在标记中间断开，可能属于标记开头的内容会先暂存，确定后再输出。
+/// 无法解析的块原样输出，方便用户看到模型写错的调用。
+/// 块的结束标记一到达就会被解析，调用方可以通过 [`take_detected`](Self::take_detected)
+/// 在回复结束前得知已经写完的工具调用；仍在输出中的块不会被解析。
+pub struct ToolBlockFilter {
+    format: ToolCallFormat,
+    /// 尚未确定是否输出的内容；处于块内时为块的内容（不含开始标记）
+    buffer: String,
+    in_block: bool,
+    /// 已经写完、尚未被取走的工具调用
+    detected: Vec<ToolParameters>,
+}
+
+impl ToolBlockFilter {
+    pub fn new(format: ToolCallFormat) -> Self {
+        Self {
+            format,
+            buffer: String::new(),
+            in_block: false,
+            detected: Vec::new(),
+        }
+    }
+
+    /// 追加一段增量，返回可以显示的内容
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.buffer.push_str(chunk);
+        let mut output = String::new();
+
+        loop {
+            if self.in_block {
+                let Some(end) = self.buffer.find(self.format.end()) else {
+                    break;
+                };
+                let block_end = end + self.format.end().len();
+                let block = format!("{}{}", self.format.start(), &self.buffer[..block_end]);
+                let calls = self.format.parse(&block);
+                if calls.is_empty() {
+                    output.push_str(&block);
+                }
+                self.detected.extend(calls);
+                self.buffer.drain(..block_end);
+                self.in_block = false;
+            } else if let Some(start) = self.buffer.find(self.format.start()) {
+                output.push_str(&self.buffer[..start]);
+                self.buffer.drain(..start + self.format.start().len());
+                self.in_block = true;
+            } else {
+                // 末尾可能是开始标记的一部分，暂不输出
+                let keep = partial_marker_len(&self.buffer, self.format.start());
+                let split = self.buffer.len() - keep;
+                output.push_str(&self.buffer[..split]);
+                self.buffer.drain(..split);
+                break;
+            }
+        }
+
+        output
+    }
+
+    /// 取走上次调用以来写完的工具调用
+    pub fn take_detected(&mut self) -> Vec<ToolParameters> {
+        std::mem::take(&mut self.detected)
+    }
+
+    /// 输出结束，返回暂存的剩余内容；未结束的块原样返回
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.buffer);
+        if std::mem::take(&mut self.in_block) {
+            format!("{}{}", self.format.start(), rest)
+        } else {
+            rest
+        }
+    }
+}
+
+/// 文本末尾与标记开头重合的最大长度
+fn partial_marker_len(text: &str, marker: &str) -> usize {
+    (1..marker.len())
+        .rev()
+        .filter(|&len| marker.is_char_boundary(len))
+        .find(|&len| text.ends_with(&marker[..len]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn tool_only_response_leaves_nothing_to_display() {
+        for format in [ToolCallFormat::default(), ToolCallFormat::xml()] {
+            let call = json!({"name": "add", "args": {"a": 1, "b": 2}}).to_string();
+            let response = format!("\n{}\n", format.wrap(&call));
+
+            // 在每个字符边界处切开，包括标记的中间
+            for (split, _) in response.char_indices().skip(1) {
+                let mut filter = ToolBlockFilter::new(format.clone());
+                let mut visible = filter.push(&response[..split]);
+                visible.push_str(&filter.push(&response[split..]));
+                visible.push_str(&filter.finish());
+
+                assert!(
+                    visible.trim().is_empty(),
+                    "在第 {} 字节切开: {:?}",
+                    split,
+                    visible
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prose_around_a_tool_block_is_kept() {
+        let format = ToolCallFormat::default();
+        let call = json!({"name": "add", "args": {}}).to_string();
+        let mut filter = ToolBlockFilter::new(format.clone());
+
+        let mut visible = filter.push(&format!("先算一下。{}", format.wrap(&call)));
+        visible.push_str(&filter.push("算好了"));
+        visible.push_str(&filter.finish());
+        assert_eq!(visible, "先算一下。算好了");
+    }
+
+    #[test]
+    fn unparsable_block_is_shown_as_is() {
+        let format = ToolCallFormat::default();
+        let mut filter = ToolBlockFilter::new(format.clone());
+        let block = format.wrap("不是 JSON");
+
+        let mut visible = filter.push(&block);
+        visible.push_str(&filter.finish());
+        assert_eq!(visible, block);
+    }
+}
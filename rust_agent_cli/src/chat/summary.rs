@@ -0,0 +1,95 @@
+use rust_agent_core::api::ChatMessage;
+
+/// 摘要系统消息的前缀
+const SUMMARY_PREFIX: &str = "以下是之前对话的摘要，供参考：\n";
+
+/// 生成摘要时使用的指令
+const SUMMARY_INSTRUCTION: &str = "请把下面的对话整理成简洁的要点摘要，保留用户的目标、已确认的事实、做出的决定和尚未解决的问题。\
+如果提供了之前的摘要，请在其基础上合并更新。只输出摘要内容。";
+
+/// 滚动摘要
+///
+/// 每完成 `every_k_turns` 轮对话，把上次摘要之后的新消息与旧摘要合并为新的摘要。
+/// 摘要不写入对话历史，而是在发送请求时作为单独的系统消息插入到已有系统消息之后，
+/// 因此多次更新也只会存在一条摘要消息，不会与工具说明等系统提示混在一起。
+#[derive(Clone)]
+pub struct RollingSummary {
+    every_k_turns: usize,
+    /// 上次更新后完成的对话轮数
+    turns_since_update: usize,
+    /// 已纳入摘要的消息数量（对话历史中的下标）
+    summarized_until: usize,
+    summary: Option<String>,
+}
+
+impl RollingSummary {
+    pub fn new(every_k_turns: usize) -> Self {
+        Self {
+            every_k_turns: every_k_turns.max(1),
+            turns_since_update: 0,
+            summarized_until: 0,
+            summary: None,
+        }
+    }
+
+    /// 记录完成了一轮对话，返回是否需要更新摘要
+    pub fn record_turn(&mut self) -> bool {
+        self.turns_since_update += 1;
+        self.turns_since_update >= self.every_k_turns
+    }
+
+    /// 构建生成摘要的请求，没有新的对话消息时返回 None
+    pub fn build_request(&self, messages: &[ChatMessage]) -> Option<Vec<ChatMessage>> {
+        let new_messages: Vec<String> = messages
+            .iter()
+            .skip(self.summarized_until)
+            .filter(|m| m.role != "system")
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect();
+        if new_messages.is_empty() {
+            return None;
+        }
+
+        let mut content = String::new();
+        if let Some(summary) = &self.summary {
+            content.push_str(&format!("之前的摘要：\n{}\n\n", summary));
+        }
+        content.push_str(&format!("新的对话：\n{}", new_messages.join("\n\n")));
+
+        Some(vec![
+            ChatMessage::new("system", SUMMARY_INSTRUCTION),
+            ChatMessage::new("user", content),
+        ])
+    }
+
+    /// 保存新的摘要，`summarized_until` 为已纳入摘要的消息数量
+    pub fn update(&mut self, summary: String, summarized_until: usize) {
+        self.summary = Some(summary.trim().to_string()).filter(|s| !s.is_empty());
+        self.summarized_until = summarized_until;
+        self.turns_since_update = 0;
+    }
+
+    /// 已纳入摘要的消息数量
+    pub fn summarized_until(&self) -> usize {
+        self.summarized_until
+    }
+
+    /// 对话历史开头的 `count` 条消息已被移除，调整已纳入摘要的位置
+    pub fn forget(&mut self, count: usize) {
+        self.summarized_until = self.summarized_until.saturating_sub(count);
+    }
+
+    /// 当前摘要
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    /// 以系统消息的形式返回摘要，还没有摘要时返回 None
+    pub fn message(&self) -> Option<ChatMessage> {
+        let summary = self.summary.as_ref()?;
+        Some(ChatMessage::new(
+            "system",
+            format!("{}{}", SUMMARY_PREFIX, summary),
+        ))
+    }
+}
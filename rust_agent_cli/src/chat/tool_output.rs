@@ -0,0 +1,54 @@
+/// 默认的工具执行提示
+pub const DEFAULT_TOOL_NOTICE: &str = "执行工具 `{name}`...";
+
+/// 工具执行提示和结果的输出格式
+///
+/// 提示、结果和警告各自成段；模型的回答没有以换行结束时会先补齐当前行，
+/// 再按设置空出若干行，避免工具输出和回答内容挤在同一行。
+#[derive(Debug, Clone)]
+pub struct ToolOutputFormat {
+    /// 执行工具的提示，`{name}` 会被替换为工具名称
+    notice_template: String,
+    /// 段落之间的空行数
+    blank_lines: usize,
+}
+
+impl Default for ToolOutputFormat {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOOL_NOTICE, 1)
+    }
+}
+
+impl ToolOutputFormat {
+    pub fn new(notice_template: impl Into<String>, blank_lines: usize) -> Self {
+        Self {
+            notice_template: notice_template.into(),
+            blank_lines,
+        }
+    }
+
+    /// 执行指定工具时的提示，以换行结束
+    pub fn notice(&self, tool_name: &str) -> String {
+        format!("{}\n", self.notice_template.replace("{name}", tool_name))
+    }
+
+    /// 开始新段落前需要输出的换行
+    ///
+    /// `at_line_start` 表示之前的输出是否已经以换行结束。
+    pub fn separator(&self, at_line_start: bool) -> String {
+        let newlines = if at_line_start {
+            self.blank_lines
+        } else {
+            self.blank_lines + 1
+        };
+        "\n".repeat(newlines)
+    }
+
+    /// 把一段内容追加到写入历史的回复中，与之前的内容分段
+    pub fn push_segment(&self, content: &mut String, text: &str) {
+        if !content.is_empty() {
+            content.push_str(&self.separator(content.ends_with('\n')));
+        }
+        content.push_str(text);
+    }
+}
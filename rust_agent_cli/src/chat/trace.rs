@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// 运行轨迹记录器
+///
+/// 把会话中的每个事件以 NDJSON 格式追加到文件，每行一个
+/// `{"type", "timestamp", "payload"}` 对象，用于回放和分析；与 tracing 日志相互独立。
+pub struct TraceWriter {
+    file: Mutex<File>,
+}
+
+impl TraceWriter {
+    /// 打开（或创建）轨迹文件，已有内容会被保留
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("无法打开轨迹文件: {:?}", path))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// 记录一个事件，写入失败只记录警告，不影响对话
+    pub fn record(&self, event_type: &str, payload: Value) {
+        let timestamp = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default();
+        let line = json!({
+            "type": event_type,
+            "timestamp": timestamp,
+            "payload": payload,
+        });
+
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            warn!("写入轨迹文件失败: {}", e);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use rust_agent_core::api::ChatMessage;
+use tracing::{debug, warn};
+
+/// 上下文裁剪器
+///
+/// 系统消息和开头固定的消息（如示例对话）始终保留，并且先从预算中扣除；
+/// 剩余预算从最新的消息开始向前填充，放不下的旧消息会被丢弃。
+/// 最新一条消息（通常是当前问题）无论如何都会保留。
+#[derive(Clone)]
+pub struct ContextTrimmer {
+    max_tokens: usize,
+}
+
+impl ContextTrimmer {
+    pub fn new(max_tokens: usize) -> Self {
+        Self { max_tokens }
+    }
+
+    /// 裁剪消息列表，使估算的 token 数不超过预算
+    ///
+    /// 开头的 `pinned` 条消息与系统消息一样不会被丢弃。
+    pub fn trim(&self, messages: &[ChatMessage], pinned: usize) -> Vec<ChatMessage> {
+        let is_pinned = |i: usize, m: &ChatMessage| i < pinned || m.role == "system";
+        let system_tokens: usize = messages
+            .iter()
+            .enumerate()
+            .filter(|(i, m)| is_pinned(*i, m))
+            .map(|(_, m)| m.estimated_tokens())
+            .sum();
+
+        let mut remaining = match self.max_tokens.checked_sub(system_tokens) {
+            Some(remaining) if remaining > 0 => remaining,
+            _ => {
+                warn!(
+                    "系统消息和示例对话约 {} tokens，已超过上下文预算 {} tokens，只保留它们和最新一条消息",
+                    system_tokens, self.max_tokens
+                );
+                0
+            }
+        };
+
+        // 从最新的消息开始决定保留哪些
+        let mut keep = vec![false; messages.len()];
+        let mut is_latest = true;
+        for (i, message) in messages.iter().enumerate().rev() {
+            if is_pinned(i, message) {
+                keep[i] = true;
+                continue;
+            }
+
+            let tokens = message.estimated_tokens();
+            if is_latest || tokens <= remaining {
+                keep[i] = true;
+                remaining = remaining.saturating_sub(tokens);
+                is_latest = false;
+            } else {
+                // 保持对话连续，不跳过中间的消息去保留更早的消息
+                remaining = 0;
+            }
+        }
+
+        let dropped = keep.iter().filter(|k| !**k).count();
+        if dropped > 0 {
+            debug!(
+                "上下文超过预算 {} tokens（系统消息约 {} tokens），丢弃 {} 条旧消息",
+                self.max_tokens, system_tokens, dropped
+            );
+        }
+
+        messages
+            .iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(message, _)| message.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    /// 收集日志输出的写入器
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn oversized_system_prompt_warns_and_keeps_latest_message() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+
+        let messages = vec![
+            ChatMessage::new("system", "工具说明".repeat(200)),
+            ChatMessage::new("user", "旧问题"),
+            ChatMessage::new("assistant", "旧回答"),
+            ChatMessage::new("user", "新问题"),
+        ];
+        let trimmed = tracing::subscriber::with_default(subscriber, || {
+            ContextTrimmer::new(50).trim(&messages, 0)
+        });
+
+        let roles: Vec<_> = trimmed.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user"]);
+        assert_eq!(trimmed[1].content, "新问题");
+
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("WARN"), "{}", logs);
+        assert!(logs.contains("已超过上下文预算 50 tokens"), "{}", logs);
+    }
+}
@@ -1,23 +1,266 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use rust_agent_core::correlation::DEFAULT_REQUEST_ID_HEADER;
+use rust_agent_core::tools::ToolCallFormat;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+use crate::chat::{
+    DEFAULT_MAX_MODEL_CALLS_PER_TURN, DEFAULT_MAX_TOOL_CALLS_PER_TURN, DEFAULT_TOOL_NOTICE,
+};
+use crate::ui::SpinnerStyle;
+
+/// 默认的工具服务地址
+pub const DEFAULT_TOOLS_ADDR: &str = "http://[::1]:50051";
+
+/// 命令行参数
+///
+/// 也可以通过 `--config` 从 TOML 文件读取，文件中的键与字段名相同。
+#[derive(Parser, Debug, Serialize, Deserialize)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// Deepseek API Key
-    #[arg(short, long)]
+    /// 子命令，未指定时进入对话模式
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+
+    /// 从 TOML 文件读取配置，命令行参数和环境变量优先于文件中的值
+    #[arg(long)]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// Deepseek API Key，未设置时依次尝试钥匙串和交互输入
+    #[arg(short, long, env = "DEEPSEEK_API_KEY", hide_env_values = true)]
     pub api_key: Option<String>,
 
+    /// 把本次使用的 API Key 保存到系统钥匙串，之后启动时自动读取（需要启用 keychain 功能）
+    #[arg(long, default_value_t = false)]
+    pub store_key: bool,
+
+    /// 使用的模型，例如 deepseek-chat 或 deepseek-reasoner
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// 启动时检查模型名称是否在已知模型或服务端的模型列表中，不在时只给出警告
+    #[arg(long)]
+    pub validate_model: bool,
+
+    /// 检查模型名称时额外认可的模型（逗号分隔），用于 Deepseek 兼容接口上的其他模型
+    #[arg(long, value_delimiter = ',')]
+    pub known_models: Vec<String>,
+
+    /// 采样温度，取值范围 [0, 2]
+    #[arg(long)]
+    pub temperature: Option<f32>,
+
+    /// 核采样概率，取值范围 [0, 1]；通常只建议调整 temperature 和 top_p 中的一个
+    #[arg(long)]
+    pub top_p: Option<f32>,
+
+    /// 决定工具调用的模型调用使用的采样温度，未设置时与 --temperature 相同
+    ///
+    /// 连接了工具服务时，模型不调用工具直接给出的回答同样使用该温度，/temp 对这类回答不生效
+    #[arg(long)]
+    pub intermediate_temperature: Option<f32>,
+
+    /// 决定工具调用的模型调用使用的核采样概率，未设置时与 --top-p 相同
+    ///
+    /// 连接了工具服务时，模型不调用工具直接给出的回答同样使用该参数
+    #[arg(long)]
+    pub intermediate_top_p: Option<f32>,
+
+    /// 丢弃与上一帧完全相同的流式帧，用于修复某些代理重放 SSE 帧导致的重复输出
+    #[arg(long)]
+    pub dedup_stream: bool,
+
+    /// 输出中显示的助手名称，默认根据模型推断
+    #[arg(long)]
+    pub assistant_name: Option<String>,
+
+    /// 单次提问模式：发送该消息，输出回复后退出
+    #[arg(short, long)]
+    pub prompt: Option<String>,
+
+    /// 以单个 JSON 对象输出结果（需配合 --prompt 使用）
+    #[arg(long, requires = "prompt", default_value_t = false)]
+    pub output_json: bool,
+
+    /// 安静模式：不显示加载动画、欢迎语和调试信息，只输出回答
+    #[arg(short, long, default_value_t = false)]
+    pub quiet: bool,
+
+    /// 加载动画样式：braille（默认）、ascii 或 none（不显示动画），终端显示乱码时可改用 ascii
+    #[arg(long, value_enum, env = "RUST_AGENT_SPINNER", default_value_t = SpinnerStyle::Braille)]
+    pub spinner_style: SpinnerStyle,
+
+    /// 不按终端宽度自动换行
+    #[arg(long, default_value_t = false)]
+    pub no_wrap: bool,
+
     /// 是否显示详细信息
     #[arg(short, long, default_value_t = false)]
     pub verbose: bool,
 
+    /// 模型 API 请求中携带请求 ID 的请求头，同一轮对话的工具调用也会带上相同的 ID
+    #[arg(long, default_value = DEFAULT_REQUEST_ID_HEADER)]
+    pub request_id_header: String,
+
     /// 工具服务地址
     #[arg(short, long)]
     pub tools_addr: Option<String>,
+
+    /// 与工具服务建立的连接数，并发的工具调用会分散到各个连接上
+    #[arg(long, default_value_t = 1)]
+    pub tools_pool_size: usize,
+
+    /// 工具服务繁忙时自动重试的次数（0 表示不重试），每次重试的等待时间加倍
+    #[arg(long, default_value_t = 2)]
+    pub tools_busy_retries: usize,
+
+    /// 每次调用工具前先检查工具服务是否存活，超过该时间（毫秒）没有响应时跳过调用；未设置时不检查
+    #[arg(long)]
+    pub tools_liveness_timeout_ms: Option<u64>,
+
+    /// 使用原生函数调用：随请求声明工具，模型通过 tool_calls 调用（需要模型支持）
+    #[arg(long, default_value_t = false)]
+    pub native_tools: bool,
+
+    /// 工具参数校验失败时请求模型修正参数的最大次数（0 表示不修正）
+    #[arg(long, default_value_t = 1)]
+    pub tool_arg_retries: usize,
+
+    /// 单次回复中最多执行的工具调用数，超出的调用会被忽略
+    #[arg(long, default_value_t = DEFAULT_MAX_TOOL_CALLS_PER_TURN)]
+    pub max_tool_calls: usize,
+
+    /// 单轮对话中最多发起的模型调用次数，所有重试共用这一预算
+    #[arg(long, default_value_t = DEFAULT_MAX_MODEL_CALLS_PER_TURN)]
+    pub max_model_calls: usize,
+
+    /// 写入对话历史的单个工具结果的最大字符数，超出时截断中间部分；不设置则不截断
+    #[arg(long)]
+    pub max_tool_result_chars: Option<usize>,
+
+    /// 每完成指定轮数的对话更新一次滚动摘要，作为系统消息保留长期上下文；不设置则不生成摘要
+    #[arg(long)]
+    pub summary_every: Option<usize>,
+
+    /// 上下文的 token 预算（含系统消息），超出时丢弃最早的对话消息；不设置则发送完整历史
+    #[arg(long)]
+    pub max_context_tokens: Option<usize>,
+
+    /// 内存中的对话消息超过 --archive-threshold 条时，把最早的对话轮次追加到该文件并从内存中移除
+    #[arg(long)]
+    pub archive_file: Option<PathBuf>,
+
+    /// 启用归档时内存中最多保留的对话消息数（不含系统消息）
+    #[arg(long, default_value_t = 200)]
+    pub archive_threshold: usize,
+
+    /// 示例对话文件（JSON 数组，每项包含 user 和 assistant），用于向模型演示工具调用方式
+    #[arg(long)]
+    pub examples_file: Option<PathBuf>,
+
+    /// 对话历史文件（JSON 数组，每项包含 role 和 content），启动后从这段对话继续
+    #[arg(long)]
+    pub history_file: Option<PathBuf>,
+
+    /// 执行工具时显示的提示，{name} 会被替换为工具名称
+    #[arg(long, default_value = DEFAULT_TOOL_NOTICE)]
+    pub tool_notice_format: String,
+
+    /// 工具提示、工具结果与回答内容之间的空行数
+    #[arg(long, default_value_t = 1)]
+    pub tool_output_spacing: usize,
+
+    /// 整理每轮回复中的空白：去掉行尾空白并合并连续的空行
+    #[arg(long)]
+    pub normalize_whitespace: bool,
+
+    /// 工具调用块的格式：backtick 为 ```tool 代码块，xml 为 <tool>...</tool> 标签
+    #[arg(long, value_enum, default_value_t = ToolCallStyle::Backtick)]
+    pub tool_call_format: ToolCallStyle,
+
+    /// 把会话事件以 NDJSON 格式追加到指定文件，用于回放和分析
+    #[arg(long)]
+    pub trace_file: Option<PathBuf>,
+
+    /// 把流式输出的回答同时追加到指定文件，生成中途退出时也保留已输出的部分
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// 配合 --out 使用，把工具执行的提示和结果也写入文件
+    #[arg(long, requires = "out")]
+    pub out_tool_results: bool,
+}
+
+/// 子命令
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// 列出工具服务提供的工具及其描述后退出
+    ListTools,
+}
+
+/// 工具调用块的格式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolCallStyle {
+    /// ```tool 代码块
+    Backtick,
+    /// <tool>...</tool> 标签，适合会转义或改写反引号的模型
+    Xml,
+}
+
+impl ToolCallStyle {
+    pub fn format(self) -> ToolCallFormat {
+        match self {
+            Self::Backtick => ToolCallFormat::backtick(),
+            Self::Xml => ToolCallFormat::xml(),
+        }
+    }
 }
 
 impl Args {
-    pub fn new() -> Self {
-        Self::parse()
+    /// 工具服务地址，未指定时使用默认地址
+    pub fn tools_addr(&self) -> String {
+        self.tools_addr
+            .clone()
+            .unwrap_or_else(|| DEFAULT_TOOLS_ADDR.to_string())
+    }
+
+    /// 输出中显示的助手名称
+    ///
+    /// 未指定时，Deepseek 的模型显示为 "Deepseek"，其他模型直接显示模型名称。
+    pub fn assistant_name(&self) -> String {
+        if let Some(name) = &self.assistant_name {
+            return name.clone();
+        }
+
+        match self.model.as_deref() {
+            Some(model) if !model.starts_with("deepseek") => model.to_string(),
+            _ => "Deepseek".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(std::iter::once("rust_agent_cli").chain(flags.iter().copied()))
+    }
+
+    #[test]
+    fn assistant_name_prefers_the_flag_then_the_model() {
+        assert_eq!(
+            args(&["--assistant-name", "小助手"]).assistant_name(),
+            "小助手"
+        );
+        assert_eq!(args(&["--model", "llama3"]).assistant_name(), "llama3");
+        assert_eq!(
+            args(&["--model", "deepseek-reasoner"]).assistant_name(),
+            "Deepseek"
+        );
+        assert_eq!(args(&[]).assistant_name(), "Deepseek");
     }
 }
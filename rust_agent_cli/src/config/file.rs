@@ -0,0 +1,123 @@
+use anyhow::{anyhow, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use super::args::Args;
+
+/// 解析命令行参数，指定 `--config` 时合并配置文件中的值
+///
+/// 优先级从高到低依次为：命令行参数、环境变量、配置文件、默认值。
+/// 配置文件的键与命令行参数的长名称对应，使用下划线分隔（如 `tools_addr`）。
+pub fn parse_args() -> Result<Args> {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match args.config.clone() {
+        Some(path) => merge_config_file(args, &matches, &path),
+        None => Ok(args),
+    }
+}
+
+/// 用配置文件中的值替换没有在命令行或环境变量中指定的参数
+fn merge_config_file(args: Args, matches: &ArgMatches, path: &Path) -> Result<Args> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("无法读取配置文件: {:?}", path))?;
+    let file: toml::Table =
+        toml::from_str(&content).with_context(|| format!("配置文件格式错误: {:?}", path))?;
+
+    let Value::Object(mut merged) = serde_json::to_value(&args)? else {
+        return Err(anyhow!("无法序列化命令行参数"));
+    };
+    for (key, value) in file {
+        if !merged.contains_key(&key) {
+            return Err(anyhow!("配置文件 {:?} 中有未知的配置项: {}", path, key));
+        }
+
+        let explicit = matches!(
+            matches.value_source(&key),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        );
+        if !explicit {
+            merged.insert(key, serde_json::to_value(value)?);
+        }
+    }
+
+    let mut merged: Args = serde_json::from_value(Value::Object(merged))
+        .with_context(|| format!("配置文件中的值无效: {:?}", path))?;
+    // 子命令和配置文件路径不参与序列化，沿用命令行中的值
+    merged.command = args.command;
+    merged.config = args.config;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 解析给定的命令行并合并其中 `--config` 指定的配置文件
+    fn parse(argv: &[&str]) -> Result<Args> {
+        let matches = Args::command().try_get_matches_from(argv)?;
+        let args = Args::from_arg_matches(&matches)?;
+        let path = args.config.clone().unwrap();
+        merge_config_file(args, &matches, &path)
+    }
+
+    #[test]
+    fn command_line_flags_override_config_file_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.toml");
+        fs::write(
+            &path,
+            "model = \"deepseek-reasoner\"\ntemperature = 0.2\ntools_pool_size = 4\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        // 命令行没有指定的值来自配置文件
+        let args = parse(&["rust_agent_cli", "--config", path]).unwrap();
+        assert_eq!(args.model.as_deref(), Some("deepseek-reasoner"));
+        assert_eq!(args.temperature, Some(0.2));
+        assert_eq!(args.tools_pool_size, 4);
+        assert_eq!(args.config.as_deref(), Some(Path::new(path)));
+
+        // 同一配置项在命令行中指定时以命令行为准
+        let args = parse(&[
+            "rust_agent_cli",
+            "--config",
+            path,
+            "--model",
+            "deepseek-chat",
+            "--tools-pool-size",
+            "2",
+        ])
+        .unwrap();
+        assert_eq!(args.model.as_deref(), Some("deepseek-chat"));
+        assert_eq!(args.tools_pool_size, 2);
+        assert_eq!(args.temperature, Some(0.2));
+    }
+
+    #[test]
+    fn unknown_keys_and_invalid_values_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.toml");
+
+        fs::write(&path, "modle = \"deepseek-chat\"\n").unwrap();
+        let error = parse(&["rust_agent_cli", "--config", path.to_str().unwrap()]).unwrap_err();
+        assert!(
+            error.to_string().contains("未知的配置项: modle"),
+            "{}",
+            error
+        );
+
+        fs::write(&path, "temperature = \"hot\"\n").unwrap();
+        let error = parse(&["rust_agent_cli", "--config", path.to_str().unwrap()]).unwrap_err();
+        assert!(
+            error.to_string().contains("配置文件中的值无效"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+
+/// 钥匙串中的服务名称
+#[cfg(feature = "keychain")]
+const SERVICE: &str = "rust_agent_cli";
+/// 钥匙串中的账户名称
+#[cfg(feature = "keychain")]
+const ACCOUNT: &str = "deepseek_api_key";
+
+/// 读取钥匙串中保存的 API Key
+///
+/// 需要启用 `keychain` 功能；未启用、没有保存或钥匙串不可用时返回 None，
+/// 调用方继续使用交互输入。
+#[cfg(feature = "keychain")]
+pub fn load_api_key() -> Option<String> {
+    match keyring::Entry::new(SERVICE, ACCOUNT) {
+        Ok(entry) => read_key(&entry),
+        Err(e) => {
+            tracing::warn!("Keychain unavailable: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn load_api_key() -> Option<String> {
+    None
+}
+
+/// 把 API Key 保存到钥匙串，已有的值会被覆盖
+#[cfg(feature = "keychain")]
+pub fn store_api_key(key: &str) -> Result<()> {
+    keyring::Entry::new(SERVICE, ACCOUNT)
+        .map_err(|e| anyhow::anyhow!("无法把 API Key 保存到钥匙串: {}", e))
+        .and_then(|entry| write_key(&entry, key))
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn store_api_key(_key: &str) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "当前版本未启用钥匙串支持，请使用 --features keychain 重新编译"
+    ))
+}
+
+/// 从钥匙串条目读取 API Key，没有保存或读取失败时返回 None
+#[cfg(feature = "keychain")]
+fn read_key(entry: &keyring::Entry) -> Option<String> {
+    match entry.get_password() {
+        Ok(key) => Some(key),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            tracing::warn!("Failed to read API key from keychain: {}", e);
+            None
+        }
+    }
+}
+
+/// 把 API Key 写入钥匙串条目
+#[cfg(feature = "keychain")]
+fn write_key(entry: &keyring::Entry, key: &str) -> Result<()> {
+    entry
+        .set_password(key)
+        .map_err(|e| anyhow::anyhow!("无法把 API Key 保存到钥匙串: {}", e))
+}
+
+#[cfg(all(test, feature = "keychain"))]
+mod tests {
+    use super::*;
+    use keyring::mock::{self, MockCredential};
+
+    /// 使用模拟的钥匙串条目；模拟条目之间不共享数据，读写需要使用同一个条目
+    fn mock_entry() -> keyring::Entry {
+        keyring::set_default_credential_builder(mock::default_credential_builder());
+        keyring::Entry::new(SERVICE, ACCOUNT).unwrap()
+    }
+
+    #[test]
+    fn stored_key_is_read_back() {
+        let entry = mock_entry();
+        assert_eq!(read_key(&entry), None);
+
+        write_key(&entry, "sk-first").unwrap();
+        assert_eq!(read_key(&entry).as_deref(), Some("sk-first"));
+        // 再次保存时覆盖原来的值
+        write_key(&entry, "sk-second").unwrap();
+        assert_eq!(read_key(&entry).as_deref(), Some("sk-second"));
+    }
+
+    #[test]
+    fn keychain_errors_fall_back_to_none() {
+        let entry = mock_entry();
+        write_key(&entry, "sk-key").unwrap();
+
+        let credential: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        credential.set_error(keyring::Error::NoStorageAccess("locked".into()));
+        assert_eq!(read_key(&entry), None);
+
+        credential.set_error(keyring::Error::NoStorageAccess("locked".into()));
+        let error = write_key(&entry, "sk-other").unwrap_err();
+        assert!(error.to_string().contains("无法把 API Key 保存到钥匙串"));
+    }
+}
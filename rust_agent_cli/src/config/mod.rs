@@ -1,7 +1,11 @@
 mod args;
+mod file;
+mod keychain;
 
-pub use args::Args;
+pub use args::{Args, Command};
+pub use keychain::{load_api_key, store_api_key};
 
-pub fn get_config() -> Args {
-    Args::new()
+/// 解析命令行参数，指定 `--config` 时合并配置文件中的值
+pub fn get_config() -> anyhow::Result<Args> {
+    file::parse_args()
 }
@@ -3,134 +3,165 @@ mod config;
 mod ui;
 
 use anyhow::Result;
-use chat::ChatSession;
+use base64::prelude::*;
+use chat::{
+    load_examples, load_history, ChatSession, StreamEvent, ToolOutputFormat, TurnCancelled,
+    TurnOutput,
+};
 use colored::Colorize;
 use rust_agent_core::{
-    logging::{init_logger, LoggerConfig},
-    tools::ToolsClient,
+    api::{count_tokens, DeepseekClient, RequestOptions, KNOWN_MODELS},
+    logging::{flush_logs, init_logger, LoggerConfig},
+    paths::AgentDirs,
+    tools::{format_tool_result, ConnectOptions, ToolInfo, ToolsClient},
 };
 use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
 use tracing::{error, info, warn, Level};
 
+/// 检查模型名称时获取服务端模型列表的超时时间
+const MODEL_LIST_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // 初始化日志系统
-    let log_config =
-        LoggerConfig::new("logs", "rust_agent_cli", Level::DEBUG).with_console_output(false); // CLI 程序不需要在控制台显示日志
+    let dirs = AgentDirs::resolve();
+    let log_config = LoggerConfig::new(
+        dirs.log_dir().to_string_lossy(),
+        "rust_agent_cli",
+        Level::DEBUG,
+    )
+    .with_console_output(false); // CLI 程序不需要在控制台显示日志
 
-    if let Err(e) = init_logger(log_config) {
-        eprintln!("日志系统初始化失败: {}", e);
-        return Err(anyhow::anyhow!("日志系统初始化失败: {}", e));
-    }
+    // 持有守卫直到程序退出，退出时写出缓冲中的日志
+    let _log_guard = match init_logger(log_config) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("日志系统初始化失败: {}", e);
+            return Err(anyhow::anyhow!("日志系统初始化失败: {}", e));
+        }
+    };
 
     info!("Starting Rust Agent CLI...");
-    let config = config::get_config();
+    let config = config::get_config()?;
+    ui::init_output();
 
-    let api_key = match config.api_key {
-        Some(key) => key,
-        None => {
-            let key = ui::get_user_input("请输入你的 Deepseek API Key")?;
-            info!("API key provided by user");
-            key
+    if config.command == Some(config::Command::ListTools) {
+        return list_tools(&config.tools_addr(), &mut io::stdout()).await;
+    }
+    // JSON 输出时只能输出结果对象
+    ui::set_quiet(config.quiet || config.output_json);
+    ui::set_spinner_style(config.spinner_style);
+    ui::init_word_wrap(!config.no_wrap);
+    let assistant_name = config.assistant_name();
+
+    // 单次提问或没有终端时无法交互输入
+    let api_key = resolve_api_key(
+        config.api_key.clone().or_else(config::load_api_key),
+        config.prompt.is_none() && ui::can_prompt(),
+        || ui::get_user_input("请输入你的 Deepseek API Key"),
+    )?;
+    if config.store_key {
+        config::store_api_key(&api_key)?;
+        info!("API key stored in keychain");
+        ui::print_debug("API Key 已保存到系统钥匙串");
+    }
+
+    let mut client = DeepseekClient::new(api_key);
+    if let Some(model) = &config.model {
+        info!("Using model: {}", model);
+        client = client.with_model(model);
+    }
+    if let Some(temperature) = config.temperature {
+        client = client.with_temperature(temperature);
+    }
+    if let Some(top_p) = config.top_p {
+        client = client.with_top_p(top_p);
+    }
+    if config.dedup_stream {
+        client = client.with_stream_dedup(true);
+    }
+    client = client.with_request_id_header(&config.request_id_header);
+    if config.validate_model {
+        if let Some(warning) = validate_model(&client, &config.known_models).await {
+            ui::print_warning(&warning);
         }
-    };
+    }
 
-    let mut session = ChatSession::new(api_key, config.verbose);
+    let mut session = ChatSession::new(client, config.verbose)
+        .with_tool_arg_autofix(config.tool_arg_retries)
+        .with_max_tool_calls_per_turn(config.max_tool_calls)
+        .with_max_model_calls_per_turn(config.max_model_calls)
+        .with_tool_call_format(config.tool_call_format.format())
+        .with_native_tools(config.native_tools)
+        .with_tool_output_format(ToolOutputFormat::new(
+            &config.tool_notice_format,
+            config.tool_output_spacing,
+        ));
+    info!("Model capabilities: {:?}", session.capabilities());
+    if config.normalize_whitespace {
+        session = session.with_postprocessor(normalize_whitespace);
+    }
+    if let Some(max_chars) = config.max_tool_result_chars {
+        session = session.with_max_tool_result_chars(max_chars);
+    }
+    if let Some(every_k_turns) = config.summary_every {
+        session = session.with_rolling_summary(every_k_turns);
+    }
+    if let Some(max_tokens) = config.max_context_tokens {
+        session = session.with_context_budget(max_tokens);
+    }
+    if let Some(path) = &config.archive_file {
+        session = session.with_auto_archive(path, config.archive_threshold)?;
+        info!("Archiving old turns to {:?}", path);
+    }
+    if let Some(path) = &config.examples_file {
+        let examples = load_examples(path)?;
+        info!(
+            "Loaded {} few-shot examples from {:?}",
+            examples.len(),
+            path
+        );
+        for example in examples {
+            session.add_example(example.user, example.assistant);
+        }
+    }
+    let mut intermediate_options = RequestOptions::default();
+    if let Some(temperature) = config.intermediate_temperature {
+        intermediate_options = intermediate_options.with_temperature(temperature);
+    }
+    if let Some(top_p) = config.intermediate_top_p {
+        intermediate_options = intermediate_options.with_top_p(top_p);
+    }
+    session = session.with_intermediate_options(intermediate_options);
+    if let Some(timeout_ms) = config.tools_liveness_timeout_ms {
+        session = session.with_tools_liveness_check(Duration::from_millis(timeout_ms));
+    }
+    if let Some(path) = &config.trace_file {
+        session = session.with_trace_file(path)?;
+        info!("Writing run trace to {:?}", path);
+    }
+    if let Some(path) = &config.out {
+        session = session.with_output_file(path, config.out_tool_results)?;
+        info!("Writing assistant output to {:?}", path);
+    }
 
     // 尝试连接工具服务
-    let tools_addr = config
-        .tools_addr
-        .unwrap_or_else(|| "http://[::1]:50051".to_string());
-    match ToolsClient::connect(&tools_addr).await {
+    let tools_addr = config.tools_addr();
+    let connect_options = ConnectOptions::default()
+        .with_pool_size(config.tools_pool_size)
+        .with_busy_retries(config.tools_busy_retries);
+    match ToolsClient::connect_with_options(&tools_addr, connect_options).await {
         Ok(client) => {
-            session.set_tools_client(client);
             info!("Connected to tools service at {}", tools_addr);
             ui::print_debug("已连接到工具服务");
 
             // 添加系统提示，告知 AI 可以使用工具
-            session.add_system_message(
-                "你可以使用以下工具来辅助完成任务：
-
-1. 文件分析工具 (file_analyzer)：
-   - 功能：分析指定目录下的文件信息，包括大小、类型统计等
-   - 参数：
-     - path: 要分析的目录路径（字符串）
-     - recursive: 是否递归分析子目录（布尔值）
-   - 示例：
-   ```tool
-   {\"name\": \"file_analyzer\", \"args\": {\"path\": \"/tmp\", \"recursive\": true}}
-   ```
-   - 返回信息：
-     - total_size: 总文件大小
-     - file_count: 文件数量
-     - extension_stats: 文件扩展名统计
-     - largest_files: 最大的5个文件
-
-2. 文件处理工具 (file_tool)：
-   - 功能：处理各种文件操作，包括格式转换、压缩解压等
-   - 参数：
-     - operation: 操作类型
-       - convert: 格式转换
-       - compress: 压缩文件（未实现）
-       - decompress: 解压文件（未实现）
-       - rename: 重命名（未实现）
-       - organize: 文件整理（未实现）
-     - input: 输入文件路径
-     - output: 输出路径（可选）
-     - options: 选项（可选）
-       - format: 目标格式
-       - quality: 质量设置（high/medium/low）
-       - page_range: 页面范围
-       - extra_args: 额外参数
-   - 示例：
-   ```tool
-   {\"name\": \"file_tool\", \"args\": {
-     \"operation\": \"convert\",
-     \"input\": \"document.docx\",
-     \"output\": \"document.pdf\",
-     \"options\": {
-       \"format\": \"pdf\",
-       \"quality\": \"high\"
-     }
-   }}
-   ```
-   - 返回信息：
-     - success: 是否成功
-     - message: 处理结果消息
-     - output_path: 输出文件路径
-     - details: 处理详情
-       - original_size: 原始大小
-       - processed_size: 处理后大小
-       - processing_time: 处理时间
-
-3. 网络搜索工具 (web_search)：
-   - 功能：在互联网上搜索信息，返回相关结果
-   - 参数：
-     - query: 搜索查询词（字符串）
-     - max_results: 最大结果数量（可选，默认5）
-   - 示例：
-   ```tool
-   {\"name\": \"web_search\", \"args\": {\"query\": \"Rust 编程语言\", \"max_results\": 5}}
-   ```
-   - 返回信息：
-     - query: 搜索查询词
-     - results: 搜索结果列表，每个结果包含：
-       - title: 标题
-       - link: 链接
-       - snippet: 摘要
-
-注意事项：
-1. 工具调用必须使用上述 JSON 格式
-2. 参数名称和类型必须严格匹配
-3. 每个工具都有特定的用途，请根据实际需求选择合适的工具
-4. 如果工具执行失败，会返回错误信息
-5. 文件处理工具需要系统安装相应的命令行工具：
-   - 文档转换：LibreOffice (soffice)
-   - 图片处理：ImageMagick (convert)
-   - 音视频处理：FFmpeg (ffmpeg)
-   - PDF处理：Ghostscript (gs)"
-                    .to_string(),
-            );
+            session.set_tools_client(client);
+            if let Err(e) = session.refresh_tools().await {
+                warn!("Failed to list tools: {}", e);
+            }
         }
         Err(e) => {
             warn!("Failed to connect to tools service: {}", e);
@@ -139,46 +170,289 @@ async fn main() -> Result<()> {
         }
     }
 
+    if let Some(path) = &config.history_file {
+        let messages = load_history(path)?;
+        info!("Loaded {} history messages from {:?}", messages.len(), path);
+        session = session.with_messages(messages)?;
+    }
+
+    if let Some(prompt) = config.prompt {
+        return run_prompt(&mut session, prompt, config.output_json, &mut io::stdout()).await;
+    }
+
     ui::print_welcome();
+    let interrupts = ui::InterruptHandler::install();
+    // 通过 /fork 保存的对话分支
+    let mut branches: Vec<ChatSession> = Vec::new();
 
     loop {
-        let user_input = ui::get_user_input("你")?;
+        let user_input = match ui::get_user_input("你") {
+            Ok(input) => input,
+            // 在输入提示处按下 Ctrl-C 时退出
+            Err(e) if ui::is_interrupted(&e) => break,
+            Err(e) => return Err(e),
+        };
 
         if user_input.to_lowercase() == "quit" || user_input.to_lowercase() == "exit" {
             break;
         }
 
-        info!("User input: {}", user_input);
-        session.add_user_message(user_input);
+        // 查看之前保存的工具结果：/result <编号>
+        if let Some(id) = user_input.strip_prefix("/result") {
+            print_stored_result(&session, id.trim());
+            continue;
+        }
+
+        // 用文件中的对话历史替换当前历史：/load <路径>
+        if let Some(path) = user_input.strip_prefix("/load") {
+            let path = path.trim();
+            if path.is_empty() {
+                ui::print_error("用法：/load <对话历史文件>");
+                continue;
+            }
+            match load_history(Path::new(path)).and_then(|messages| session.set_messages(messages))
+            {
+                Ok(()) => {
+                    info!("Loaded history from {:?}", path);
+                    ui::print_debug(&format!("已载入 {} 条对话历史", session.message_count()));
+                }
+                Err(e) => ui::print_error(&format!("载入对话历史失败: {:#}", e)),
+            }
+            continue;
+        }
+
+        // 在当前位置分出一个分支：/fork；与保存的分支互换：/branch <编号>
+        if user_input == "/fork" {
+            branches.push(session.fork());
+            let id = branches.len();
+            ui::print_debug(&format!("已保存分支 #{}，输入 /branch {} 切换过去", id, id));
+            continue;
+        }
+        if let Some(arg) = user_input.strip_prefix("/branch") {
+            match parse_branch_id(arg, branches.len()) {
+                Some(index) => {
+                    std::mem::swap(&mut session, &mut branches[index]);
+                    info!("Switched to branch #{}", index + 1);
+                    ui::print_debug(&format!(
+                        "已切换到分支 #{0}，之前的对话保存为分支 #{0}",
+                        index + 1
+                    ));
+                }
+                None => ui::print_error(&format!(
+                    "用法：/branch <编号>，当前有 {} 个分支",
+                    branches.len()
+                )),
+            }
+            continue;
+        }
+
+        // 查看可用的工具：/tools；重新获取工具服务上的工具：/tools refresh
+        if let Some(args) = user_input.strip_prefix("/tools") {
+            match args.trim() {
+                "" => print_tools(session.tools()),
+                "refresh" => match session.refresh_tools().await {
+                    Ok(tools) => {
+                        info!("Refreshed tools: {} available", tools.len());
+                        print_tools(tools);
+                    }
+                    Err(e) => {
+                        warn!("Failed to refresh tools: {}", e);
+                        ui::print_error(&format!("刷新工具失败: {}", e));
+                    }
+                },
+                _ => ui::print_error("用法：/tools [refresh]"),
+            }
+            continue;
+        }
+
+        // 查看对话历史的估算大小和本次会话累计的 token 用量：/tokens
+        if user_input == "/tokens" {
+            print_token_stats(&session);
+            continue;
+        }
+
+        // 继续生成上一条被截断的回复：/continue
+        let continuing = user_input == "/continue";
+
+        // 只对本轮使用指定的温度：/temp <温度> <消息>
+        let mut options = RequestOptions::default();
+        let user_input = match user_input.strip_prefix("/temp") {
+            Some(rest) => match parse_temp_command(rest) {
+                Some((temperature, message)) => {
+                    info!("Using temperature {} for this turn", temperature);
+                    options = options.with_temperature(temperature);
+                    message
+                }
+                None => {
+                    ui::print_error("用法：/temp <温度> <消息>");
+                    continue;
+                }
+            },
+            None => user_input,
+        };
+
+        // 附带图片提问：/image <图片路径> <消息>
+        let mut images = Vec::new();
+        let user_input = match user_input.strip_prefix("/image") {
+            Some(rest) => match parse_image_command(rest) {
+                Some((path, message)) => match image_data_uri(Path::new(&path)) {
+                    Ok(image) => {
+                        images.push(image);
+                        message
+                    }
+                    Err(e) => {
+                        ui::print_error(&e.to_string());
+                        continue;
+                    }
+                },
+                None => {
+                    ui::print_error("用法：/image <图片路径> <消息>");
+                    continue;
+                }
+            },
+            None => user_input,
+        };
+
+        if !continuing {
+            info!("User input: {}", user_input);
+            if let Err(e) = session.add_user_message_with_images(user_input, images) {
+                ui::print_error(&e.to_string());
+                continue;
+            }
+        }
 
         // 创建加载动画
-        let spinner = ui::create_spinner("Deepseek: 思考中...", true);
+        let spinner = ui::create_spinner(&format!("{}: 思考中...", assistant_name), true);
+        let quiet = ui::is_quiet();
+        let verbose = session.is_verbose();
         let mut is_first_chunk = true;
+        let mut in_reasoning = false;
+        let mut content_started = false;
+        let mut wrapper = ui::StreamWrapper::new();
 
-        match session
-            .get_response_stream(|chunk| {
-                if is_first_chunk {
-                    spinner.finish_and_clear(); // 在第一个响应到达时清除加载动画
-                    print!("{}: {}", "Deepseek".blue(), chunk);
-                    is_first_chunk = false;
-                } else {
-                    print!("{}", chunk);
+        let cancel = interrupts.begin_turn();
+        let on_event = |event: StreamEvent| {
+            if is_first_chunk {
+                spinner.finish_and_clear(); // 在第一个响应到达时清除加载动画
+                is_first_chunk = false;
+            }
+
+            match event {
+                // 安静模式只输出回答
+                StreamEvent::Reasoning(_) if quiet => {}
+                StreamEvent::Reasoning(text) => {
+                    if !in_reasoning {
+                        print!(
+                            "{}",
+                            wrapper.write_raw(&format!("{}: ", "思考过程".dimmed()))
+                        );
+                        in_reasoning = true;
+                    }
+                    print!("{}", wrapper.push(text).dimmed());
                 }
-                io::stdout().flush().unwrap();
-            })
-            .await
+                // 只有工具调用的回复没有可显示的内容，等到出现正文时才输出助手名称
+                StreamEvent::Content(text) if !content_started && text.trim().is_empty() => {}
+                StreamEvent::Content(text) => {
+                    let mut text = text;
+                    if !content_started {
+                        if !quiet {
+                            if in_reasoning {
+                                print!("{}", wrapper.finish().dimmed());
+                                print!("{}", wrapper.write_raw("\n\n"));
+                            }
+                            print!(
+                                "{}",
+                                wrapper.write_raw(&ui::assistant_prefix(&assistant_name))
+                            );
+                        }
+                        content_started = true;
+                        text = text.trim_start();
+                    }
+                    print!("{}", wrapper.push(text));
+                }
+                StreamEvent::Notice(text) => print!("{}", wrapper.write_raw(text)),
+                StreamEvent::ToolDetected(call) => {
+                    info!("Tool call detected while streaming: {}", call.name);
+                    if verbose && !quiet {
+                        let hint = format!("（检测到工具调用 `{}`，回复结束后执行）", call.name);
+                        print!("{}", wrapper.write_raw(&format!("\n{}\n", hint.dimmed())));
+                    }
+                }
+                // 工具结果已经以文本形式输出
+                StreamEvent::ToolCall(_) => {}
+            }
+            io::stdout().flush().unwrap();
+        };
+        let result = if continuing {
+            session.continue_last(&cancel, on_event).await
+        } else {
+            session
+                .get_response_stream_with(&options, &cancel, on_event)
+                .await
+        };
+        interrupts.end_turn();
+        print!("{}", wrapper.finish());
+
+        if let Ok(TurnOutput {
+            usage: Some(usage), ..
+        }) = &result
         {
-            Ok(response) => {
+            if session.is_verbose() {
+                println!();
+                ui::print_debug(&format!("本轮 token 用量：{}", ui::describe_usage(usage)));
+            }
+        }
+
+        match result {
+            Ok(output) if continuing => {
                 println!();
-                info!("Assistant response received");
-                session.add_assistant_message(response);
+                info!(
+                    "Assistant response continued (request {})",
+                    output.request_id
+                );
+                if output.truncated {
+                    ui::print_debug("回复仍被截断，可以再次输入 /continue 继续");
+                }
+            }
+            Ok(output) => {
+                println!();
+                info!(
+                    "Assistant response received (request {})",
+                    output.request_id
+                );
+                if output.truncated {
+                    ui::print_debug("回复因长度限制被截断，输入 /continue 继续");
+                }
+                session.add_assistant_message(output.text);
+                match session.update_rolling_summary().await {
+                    Ok(true) => info!("Rolling summary updated"),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to update rolling summary: {}", e),
+                }
+                match session.archive_old_turns().await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Archived {} messages", count),
+                    Err(e) => warn!("Failed to archive old turns: {}", e),
+                }
             }
             Err(e) => {
                 spinner.finish_and_clear(); // 确保在出错时也清除加载动画
                 println!();
-                error!("Failed to get assistant response: {}", e);
-                ui::print_error(&e.to_string());
-                session.remove_last_message();
+                if e.is::<TurnCancelled>() {
+                    info!("Turn cancelled by user");
+                    ui::print_debug("已取消本轮对话");
+                } else {
+                    error!(
+                        "Failed to get assistant response (request {}): {}",
+                        session.request_id().unwrap_or_default(),
+                        e
+                    );
+                    ui::print_error(&e.to_string());
+                }
+                // 继续生成失败时上一条回复保持不变
+                if !continuing {
+                    session.remove_last_message();
+                }
             }
         }
 
@@ -192,3 +466,442 @@ async fn main() -> Result<()> {
     ui::print_goodbye();
     Ok(())
 }
+
+/// 确定使用的 API Key：优先使用已配置的值，没有时交互输入
+///
+/// 不能交互输入时直接报错并提示如何配置，而不是等待输入。
+fn resolve_api_key(
+    configured: Option<String>,
+    interactive: bool,
+    ask: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    match configured {
+        Some(key) => Ok(key),
+        None if !interactive => {
+            error!("No API key configured in non-interactive mode");
+            Err(anyhow::anyhow!(
+                "未找到 Deepseek API Key：请设置环境变量 DEEPSEEK_API_KEY 或使用 --api-key 传入"
+            ))
+        }
+        None => {
+            let key = ask()?;
+            info!("API key provided by user");
+            Ok(key)
+        }
+    }
+}
+
+/// 列出工具服务提供的工具：名称、描述和参数的 JSON Schema
+async fn list_tools(addr: &str, out: &mut impl Write) -> Result<()> {
+    let client = ToolsClient::connect(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("无法连接到工具服务 {}: {}", addr, e))?;
+
+    let names = client.list_tools().await?;
+    if names.is_empty() {
+        writeln!(out, "工具服务 {} 没有注册任何工具", addr)?;
+        return Ok(());
+    }
+
+    for name in names {
+        match client.describe_tool(&name).await {
+            Ok(info) => {
+                writeln!(out, "{}\n  {}", info.name.green(), info.description)?;
+                if let Some(parameters) = &info.parameters {
+                    let schema = serde_json::to_string_pretty(parameters)?;
+                    writeln!(out, "  参数: {}", schema.replace('\n', "\n  "))?;
+                }
+            }
+            Err(e) => {
+                warn!("Failed to describe tool {}: {}", name, e);
+                writeln!(out, "{}\n  （无法获取描述: {}）", name.green(), e)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 去掉行尾空白并合并连续的空行
+fn normalize_whitespace(text: String) -> String {
+    let mut output = String::new();
+    for line in text.trim().lines().map(str::trim_end) {
+        if line.is_empty() && output.ends_with("\n\n") {
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    output.trim_end().to_string()
+}
+
+/// 解析 `/temp` 命令的参数，返回温度和消息内容
+fn parse_temp_command(args: &str) -> Option<(f32, String)> {
+    let (temperature, message) = args.trim().split_once(char::is_whitespace)?;
+    let temperature = temperature.parse().ok()?;
+    let message = message.trim();
+    (!message.is_empty()).then(|| (temperature, message.to_string()))
+}
+
+/// 解析 `/image` 命令的参数：图片路径和消息
+fn parse_image_command(args: &str) -> Option<(String, String)> {
+    let (path, message) = args.trim().split_once(char::is_whitespace)?;
+    let message = message.trim();
+    (!message.is_empty()).then(|| (path.to_string(), message.to_string()))
+}
+
+/// 读取图片文件并编码为 data URI，按扩展名确定图片类型
+fn image_data_uri(path: &Path) -> Result<String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let mime = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => return Err(anyhow::anyhow!("不支持的图片格式: {:?}", path)),
+    };
+    let data =
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("无法读取图片 {:?}: {}", path, e))?;
+    Ok(format!(
+        "data:{};base64,{}",
+        mime,
+        BASE64_STANDARD.encode(data)
+    ))
+}
+
+/// 解析 `/branch` 命令的分支编号（从 1 开始），返回分支列表中的下标
+fn parse_branch_id(arg: &str, count: usize) -> Option<usize> {
+    let id: usize = arg.trim().parse().ok()?;
+    (1..=count).contains(&id).then(|| id - 1)
+}
+
+/// 检查模型名称是否在已知模型、用户补充的模型或服务端的模型列表中
+///
+/// 列表可能过时，找不到时只返回给用户的警告，仍然使用该模型继续运行。
+async fn validate_model(client: &DeepseekClient, extra_models: &[String]) -> Option<String> {
+    let model = client.model();
+    if KNOWN_MODELS.contains(&model) || extra_models.iter().any(|m| m == model) {
+        return None;
+    }
+
+    match tokio::time::timeout(MODEL_LIST_TIMEOUT, client.list_models()).await {
+        Ok(Ok(models)) if models.iter().any(|m| m == model) => {
+            info!("Model {} found in the provider's model list", model);
+            return None;
+        }
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => warn!("Failed to fetch model list: {}", e),
+        Err(_) => warn!("Timed out fetching model list"),
+    }
+
+    warn!("Unknown model: {}", model);
+    Some(format!(
+        "未知的模型 {}，请确认名称是否正确；已知模型: {}",
+        model,
+        KNOWN_MODELS
+            .iter()
+            .copied()
+            .chain(extra_models.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// 输出当前可用的工具
+fn print_tools(tools: &[ToolInfo]) {
+    if tools.is_empty() {
+        println!("当前没有可用的工具");
+        return;
+    }
+    for tool in tools {
+        println!("{}\n  {}", tool.name.green(), tool.description);
+    }
+}
+
+/// 输出对话历史的估算 token 数和本次会话累计的用量
+fn print_token_stats(session: &ChatSession) {
+    println!(
+        "对话历史：{} 条消息，约 {} tokens",
+        session.message_count(),
+        session.estimated_tokens()
+    );
+    match session.total_usage() {
+        Some(usage) => println!("本次会话累计：{}", ui::describe_usage(&usage)),
+        None => println!("本次会话累计：服务端尚未返回 token 用量"),
+    }
+}
+
+/// 输出指定编号的工具结果
+fn print_stored_result(session: &ChatSession, id: &str) {
+    let Ok(id) = id.trim_start_matches('#').parse::<usize>() else {
+        ui::print_error("用法：/result <结果编号>");
+        return;
+    };
+
+    match session.get_tool_result(id) {
+        Some(stored) => println!(
+            "#{} {} {}\n{}",
+            stored.id,
+            stored.name,
+            stored.args,
+            format_tool_result(&stored.name, &stored.result)
+        ),
+        None => ui::print_error(&format!("没有编号为 #{} 的工具结果", id)),
+    }
+}
+
+/// 单次提问模式：发送一条消息，输出回复后退出
+///
+/// `output_json` 为 true 时不输出流式文本，只在结束时向 `out` 输出一个 JSON 对象。
+async fn run_prompt(
+    session: &mut ChatSession,
+    prompt: String,
+    output_json: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    info!("Running single prompt");
+    session.add_user_message(prompt);
+    let prompt_tokens = session.estimated_tokens();
+
+    let mut report = ui::TurnReport::default();
+    let result = session
+        .get_response_stream(|event| match event {
+            StreamEvent::Content(text) | StreamEvent::Notice(text) if !output_json => {
+                let _ = write!(out, "{}", text);
+                let _ = out.flush();
+            }
+            // 出错时返回值中没有工具调用，通过事件收集已执行的调用
+            StreamEvent::ToolCall(record) if output_json => {
+                report.tool_calls.push(record.clone());
+            }
+            _ => {}
+        })
+        .await;
+    report.request_id = session.request_id();
+
+    match result {
+        Ok(output) => {
+            info!(
+                "Assistant response received (request {})",
+                output.request_id
+            );
+            if output_json {
+                report.usage = match &output.usage {
+                    Some(usage) => usage.into(),
+                    None => ui::Usage::estimated(prompt_tokens, count_tokens(&output.text)),
+                };
+                report.response = Some(output.text.clone());
+                report.final_answer = Some(output.final_answer);
+                report.tool_calls = output.tool_calls;
+                ui::write_json_report(out, &report);
+            } else {
+                let _ = writeln!(out);
+            }
+            session.add_assistant_message(output.text);
+            Ok(())
+        }
+        Err(e) => {
+            error!("Failed to get assistant response: {}", e);
+            if output_json {
+                report.usage = ui::Usage::estimated(prompt_tokens, 0);
+                report.error = Some(e.to_string());
+                ui::write_json_report(out, &report);
+                flush_logs();
+                std::process::exit(1);
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_agent_core::api::testing::{MockChatServer, MockResponse};
+    use rust_agent_core::api::ChatMessage;
+    use rust_agent_core::tools::rpc::server::ToolsFlightService;
+    use rust_agent_core::tools::rpc::testing::{spawn_in_process, FnTool};
+    use rust_agent_core::tools::ToolResult;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn missing_api_key_fails_with_guidance_instead_of_prompting() {
+        let prompted = std::cell::Cell::new(false);
+        let ask = || {
+            prompted.set(true);
+            Ok("sk-typed".to_string())
+        };
+
+        let error = resolve_api_key(None, false, ask).unwrap_err();
+        assert!(!prompted.get());
+        let message = error.to_string();
+        assert!(message.contains("DEEPSEEK_API_KEY"), "{}", message);
+        assert!(message.contains("--api-key"), "{}", message);
+
+        // 已配置时直接使用，可以交互时才提示输入
+        assert_eq!(
+            resolve_api_key(Some("sk-env".into()), false, ask).unwrap(),
+            "sk-env"
+        );
+        assert!(!prompted.get());
+        assert_eq!(resolve_api_key(None, true, ask).unwrap(), "sk-typed");
+        assert!(prompted.get());
+    }
+
+    #[tokio::test]
+    async fn output_json_reports_response_and_tool_calls() {
+        let call = json!({"name": "echo", "args": {"text": "hi"}});
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["我来调用工具。\n", &format!("```tool\n{}\n```", call)]),
+            MockResponse::text(&["工具返回了 hi"]),
+        ])
+        .await
+        .unwrap();
+
+        let echo = FnTool::new("echo", |args: Value| async move {
+            Ok(ToolResult::success(args["text"].clone()))
+        });
+        let (_tools, tools_client) =
+            spawn_in_process(ToolsFlightService::with_tools(vec![echo.boxed()]))
+                .await
+                .unwrap();
+        let mut session = ChatSession::new(server.client(), false);
+        session.set_tools_client(tools_client);
+
+        let mut out = Vec::new();
+        run_prompt(&mut session, "回显 hi".to_string(), true, &mut out)
+            .await
+            .unwrap();
+
+        let report: Value = serde_json::from_slice(&out).unwrap();
+        assert!(report["response"]
+            .as_str()
+            .unwrap()
+            .contains("我来调用工具。"));
+        assert_eq!(report["final_answer"], "工具返回了 hi");
+        assert_eq!(report["tool_calls"][0]["name"], "echo");
+        assert_eq!(report["tool_calls"][0]["args"], json!({"text": "hi"}));
+        assert_eq!(report["tool_calls"][0]["result"]["data"], "hi");
+        assert!(report["error"].is_null());
+    }
+
+    #[tokio::test]
+    async fn list_tools_prints_each_tool_with_its_schema() {
+        let weather = FnTool::new("weather", |_| async {
+            Ok(ToolResult::success(json!("晴")))
+        })
+        .with_description("查询城市天气")
+        .with_parameters(json!({"type": "object", "properties": {"city": {"type": "string"}}}));
+        let echo = FnTool::new("echo", |args: Value| async move {
+            Ok(ToolResult::success(args))
+        });
+        let service = ToolsFlightService::with_tools(vec![weather.boxed(), echo.boxed()]);
+        let (server, _client) = spawn_in_process(service).await.unwrap();
+
+        let mut out = Vec::new();
+        list_tools(server.addr(), &mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("weather"), "{}", out);
+        assert!(out.contains("查询城市天气"), "{}", out);
+        assert!(out.contains("\"city\""), "{}", out);
+        assert!(out.contains("echo"), "{}", out);
+        assert!(out.contains("测试工具"), "{}", out);
+    }
+
+    #[tokio::test]
+    async fn list_tools_reports_an_unavailable_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mut out = Vec::new();
+        let error = list_tools(&addr, &mut out).await.unwrap_err();
+        assert!(
+            error.to_string().contains("无法连接到工具服务"),
+            "{}",
+            error
+        );
+        assert!(out.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_model_warns_but_still_proceeds() {
+        let models =
+            json!({"object": "list", "data": [{"id": "deepseek-chat"}, {"id": "deepseek-v4"}]});
+        let server = MockChatServer::start(vec![
+            MockResponse::Json(models.clone()),
+            MockResponse::completion("你好"),
+            MockResponse::Json(models),
+        ])
+        .await
+        .unwrap();
+
+        let client = server.client().with_model("deepseek-chatt");
+        let warning = validate_model(&client, &["my-model".to_string()])
+            .await
+            .unwrap();
+        assert!(warning.contains("未知的模型 deepseek-chatt"), "{}", warning);
+        assert!(
+            warning.contains("deepseek-reasoner, my-model"),
+            "{}",
+            warning
+        );
+        assert_eq!(server.requests()[0].path, "/models");
+
+        // 只是警告，仍然使用该模型发送请求
+        let messages = vec![ChatMessage::new("user", "你好")];
+        assert_eq!(client.chat(messages).await.unwrap(), "你好");
+        assert_eq!(server.requests()[1].body["model"], "deepseek-chatt");
+
+        // 内置列表、用户补充的模型和服务端列表中的模型都不警告
+        let extra = ["my-model".to_string()];
+        assert_eq!(validate_model(&server.client(), &extra).await, None);
+        let custom = server.client().with_model("my-model");
+        assert_eq!(validate_model(&custom, &extra).await, None);
+        let listed = server.client().with_model("deepseek-v4");
+        assert_eq!(validate_model(&listed, &extra).await, None);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn normalize_whitespace_trims_lines_and_merges_blank_lines() {
+        let text = "\n第一段  \n\n\n\n第二段\t\n  \n".to_string();
+        assert_eq!(normalize_whitespace(text), "第一段\n\n第二段");
+    }
+
+    #[test]
+    fn branch_ids_start_at_one_and_must_exist() {
+        assert_eq!(parse_branch_id(" 1", 2), Some(0));
+        assert_eq!(parse_branch_id("2", 2), Some(1));
+        assert_eq!(parse_branch_id("0", 2), None);
+        assert_eq!(parse_branch_id("3", 2), None);
+        assert_eq!(parse_branch_id("", 2), None);
+    }
+
+    #[test]
+    fn image_files_are_encoded_as_data_uris() {
+        assert_eq!(
+            parse_image_command(" cat.png  图里是什么 "),
+            Some(("cat.png".to_string(), "图里是什么".to_string()))
+        );
+        assert_eq!(parse_image_command("cat.png"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("cat.PNG");
+        std::fs::write(&png, [0x89, b'P', b'N', b'G']).unwrap();
+        assert_eq!(
+            image_data_uri(&png).unwrap(),
+            "data:image/png;base64,iVBORw=="
+        );
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "不是图片").unwrap();
+        assert!(image_data_uri(&text).is_err());
+        assert!(image_data_uri(&dir.path().join("missing.jpg")).is_err());
+    }
+}
@@ -1,5 +1,11 @@
 use anyhow::Result;
 use dialoguer::{theme::ColorfulTheme, Input};
+use std::io::{self, IsTerminal};
+
+/// 能否交互输入：标准输入和标准输出都连接到终端
+pub fn can_prompt() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
 
 pub fn get_user_input(prompt: &str) -> Result<String> {
     let input = Input::<String>::with_theme(&ColorfulTheme::default())
@@ -7,3 +13,13 @@ pub fn get_user_input(prompt: &str) -> Result<String> {
         .interact()?;
     Ok(input)
 }
+
+/// 输入是否因按下 Ctrl-C 被中断
+///
+/// 输入提示处于原始模式，Ctrl-C 不会产生信号，而是让读取返回 `Interrupted` 错误。
+pub fn is_interrupted(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<dialoguer::Error>() {
+        Some(dialoguer::Error::IO(e)) => e.kind() == io::ErrorKind::Interrupted,
+        None => false,
+    }
+}
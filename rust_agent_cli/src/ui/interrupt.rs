@@ -0,0 +1,163 @@
+use rust_agent_core::logging::flush_logs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// 两次 Ctrl-C 间隔小于该值时强制退出
+const FORCE_QUIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// 强制退出时的退出码（128 + SIGINT）
+const INTERRUPT_EXIT_CODE: i32 = 130;
+
+/// 收到 Ctrl-C 后应执行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptAction {
+    /// 取消正在进行的一轮对话，回到输入提示
+    CancelTurn,
+    /// 空闲时按下，退出程序
+    Exit,
+    /// 短时间内连续按下，立即强制退出
+    ForceQuit,
+}
+
+/// Ctrl-C 的处理状态
+#[derive(Debug, Default)]
+struct InterruptState {
+    /// 正在进行的一轮对话的取消令牌，空闲时为 None
+    turn: Option<CancellationToken>,
+    /// 上一次按下 Ctrl-C 的时间
+    last_interrupt: Option<Instant>,
+}
+
+impl InterruptState {
+    /// 根据当前状态决定 Ctrl-C 的处理方式
+    fn on_interrupt(&mut self, now: Instant) -> InterruptAction {
+        let double_press = self
+            .last_interrupt
+            .is_some_and(|last| now.duration_since(last) < FORCE_QUIT_WINDOW);
+        self.last_interrupt = Some(now);
+
+        if double_press {
+            return InterruptAction::ForceQuit;
+        }
+
+        match self.turn.take() {
+            Some(token) => {
+                token.cancel();
+                InterruptAction::CancelTurn
+            }
+            None => InterruptAction::Exit,
+        }
+    }
+}
+
+/// Ctrl-C 处理器
+///
+/// 对话进行中按下 Ctrl-C 只取消本轮对话；空闲时按下退出程序；
+/// 短时间内连续按两次立即强制退出。
+///
+/// 输入提示处于原始模式，此时按下 Ctrl-C 不会产生信号，而是由
+/// [`is_interrupted`](super::is_interrupted) 识别输入被中断。
+#[derive(Clone, Default)]
+pub struct InterruptHandler {
+    state: Arc<Mutex<InterruptState>>,
+}
+
+impl InterruptHandler {
+    /// 创建处理器并开始监听 Ctrl-C
+    pub fn install() -> Self {
+        let handler = Self::default();
+        let listener = handler.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                match listener.handle_interrupt() {
+                    InterruptAction::CancelTurn => info!("收到 Ctrl-C，取消本轮对话"),
+                    InterruptAction::Exit => {
+                        info!("收到 Ctrl-C，退出程序");
+                        println!();
+                        super::print_goodbye();
+                        flush_logs();
+                        std::process::exit(0);
+                    }
+                    InterruptAction::ForceQuit => {
+                        warn!("连续收到 Ctrl-C，强制退出");
+                        flush_logs();
+                        std::process::exit(INTERRUPT_EXIT_CODE);
+                    }
+                }
+            }
+        });
+        handler
+    }
+
+    /// 开始一轮对话，返回本轮的取消令牌
+    pub fn begin_turn(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.lock().turn = Some(token.clone());
+        token
+    }
+
+    /// 结束一轮对话，之后按下 Ctrl-C 将退出程序
+    pub fn end_turn(&self) {
+        self.lock().turn = None;
+    }
+
+    fn handle_interrupt(&self) -> InterruptAction {
+        self.lock().on_interrupt(Instant::now())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, InterruptState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupt_during_a_turn_cancels_only_the_turn() {
+        let handler = InterruptHandler::default();
+        let token = handler.begin_turn();
+        let start = Instant::now();
+
+        assert_eq!(
+            handler.lock().on_interrupt(start),
+            InterruptAction::CancelTurn
+        );
+        assert!(token.is_cancelled());
+
+        // 本轮已取消，稍后再按时处于空闲状态，退出程序
+        let later = start + FORCE_QUIT_WINDOW * 2;
+        assert_eq!(handler.lock().on_interrupt(later), InterruptAction::Exit);
+    }
+
+    #[test]
+    fn interrupt_at_an_idle_prompt_exits() {
+        let handler = InterruptHandler::default();
+        let token = handler.begin_turn();
+        handler.end_turn();
+
+        assert_eq!(
+            handler.lock().on_interrupt(Instant::now()),
+            InterruptAction::Exit
+        );
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn double_press_forces_quit() {
+        let mut state = InterruptState {
+            turn: Some(CancellationToken::new()),
+            last_interrupt: None,
+        };
+        let start = Instant::now();
+
+        assert_eq!(state.on_interrupt(start), InterruptAction::CancelTurn);
+        let again = start + FORCE_QUIT_WINDOW / 2;
+        assert_eq!(state.on_interrupt(again), InterruptAction::ForceQuit);
+    }
+}
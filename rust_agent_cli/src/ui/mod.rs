@@ -1,7 +1,16 @@
 mod input;
+mod interrupt;
 mod output;
+mod report;
 mod spinner;
+mod wrap;
 
-pub use input::get_user_input;
-pub use output::{print_assistant_message, print_debug, print_error, print_goodbye, print_welcome};
-pub use spinner::{create_progress_bar, create_spinner};
+pub use input::{can_prompt, get_user_input, is_interrupted};
+pub use interrupt::InterruptHandler;
+pub use output::{
+    assistant_prefix, init_output, is_quiet, print_debug, print_error, print_goodbye,
+    print_warning, print_welcome, set_quiet,
+};
+pub use report::{describe_usage, write_json_report, TurnReport, Usage};
+pub use spinner::{create_spinner, set_spinner_style, SpinnerStyle};
+pub use wrap::{init_word_wrap, StreamWrapper};
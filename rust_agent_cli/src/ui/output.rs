@@ -1,21 +1,97 @@
 use colored::*;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 安静模式：不显示加载动画、欢迎语和调试信息
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// 标准输出是否连接到终端
+pub fn is_stdout_terminal() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// 初始化终端输出
+///
+/// 标准输出被重定向或通过管道传递时关闭 ANSI 颜色，避免控制字符混入输出。
+pub fn init_output() {
+    if !is_stdout_terminal() {
+        colored::control::set_override(false);
+    }
+}
+
+/// 设置安静模式
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// 是否处于安静模式
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
 
 pub fn print_welcome() {
-    println!("欢迎使用 Rust Agent CLI！输入 quit 或 exit 退出程序。");
+    write_welcome(&mut io::stdout(), is_quiet());
+}
+
+/// 安静模式下不输出欢迎语
+fn write_welcome(out: &mut impl Write, quiet: bool) {
+    if quiet {
+        return;
+    }
+    let _ = writeln!(out, "欢迎使用 Rust Agent CLI！输入 quit 或 exit 退出程序。");
 }
 
 pub fn print_goodbye() {
+    if is_quiet() {
+        return;
+    }
     println!("感谢使用 Rust Agent CLI，再见！");
 }
 
-pub fn print_assistant_message(message: &str) {
-    println!("{}: {}", "Deepseek".blue(), message);
+/// 助手名称的显示样式
+pub fn assistant_label(name: &str) -> ColoredString {
+    name.blue()
+}
+
+/// 助手回答前的名称前缀
+pub fn assistant_prefix(name: &str) -> String {
+    format!("{}: ", assistant_label(name))
 }
 
 pub fn print_error(message: &str) {
     eprintln!("{} {}", "错误:".red().bold(), message);
 }
 
+/// 输出警告，不影响程序继续运行
+pub fn print_warning(message: &str) {
+    eprintln!("{} {}", "警告:".yellow().bold(), message);
+}
+
 pub fn print_debug(message: &str) {
+    if is_quiet() {
+        return;
+    }
     println!("{} {}", "调试:".yellow().bold(), message);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_mode_skips_the_welcome_banner() {
+        let mut out = Vec::new();
+        write_welcome(&mut out, true);
+        assert!(out.is_empty());
+
+        write_welcome(&mut out, false);
+        assert!(String::from_utf8(out).unwrap().contains("欢迎使用"));
+    }
+
+    #[test]
+    fn assistant_prefix_shows_the_configured_name() {
+        let prefix = assistant_prefix("小助手");
+        assert!(prefix.contains("小助手"));
+        assert!(prefix.ends_with(": "));
+    }
+}
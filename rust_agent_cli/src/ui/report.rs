@@ -0,0 +1,106 @@
+use rust_agent_core::api;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::chat::ToolCallRecord;
+
+/// token 用量，服务端没有返回用量时按本地估算
+#[derive(Debug, Default, Serialize)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+    /// 命中上下文缓存的提示 token 数，只有服务端返回时才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_cache_hit_tokens: Option<usize>,
+    pub estimated: bool,
+}
+
+impl Usage {
+    pub fn estimated(prompt_tokens: usize, completion_tokens: usize) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            prompt_cache_hit_tokens: None,
+            estimated: true,
+        }
+    }
+}
+
+impl From<&api::Usage> for Usage {
+    fn from(usage: &api::Usage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+            prompt_cache_hit_tokens: Some(usage.prompt_cache_hit_tokens),
+            estimated: false,
+        }
+    }
+}
+
+/// 用一行文字描述 token 用量及其中命中上下文缓存的部分
+pub fn describe_usage(usage: &api::Usage) -> String {
+    let mut text = format!(
+        "提示 {} tokens，回复 {} tokens，共 {} tokens",
+        usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+    );
+    if let Some(ratio) = usage.cache_hit_ratio() {
+        text.push_str(&format!(
+            "；缓存命中 {} tokens（{:.0}%）",
+            usage.prompt_cache_hit_tokens,
+            ratio * 100.0
+        ));
+    }
+    text
+}
+
+/// 单轮对话的结构化结果，供 `--output-json` 使用
+#[derive(Debug, Default, Serialize)]
+pub struct TurnReport {
+    /// 本轮的请求 ID，可用于在模型 API 和工具服务的日志中查找对应的请求
+    pub request_id: Option<String>,
+    pub response: Option<String>,
+    /// 最后一次模型回复的内容，不包含穿插其中的工具执行结果
+    pub final_answer: Option<String>,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub usage: Usage,
+    pub error: Option<String>,
+}
+
+/// 以单个 JSON 对象的形式输出结果
+pub fn write_json_report(out: &mut impl Write, report: &TurnReport) {
+    match serde_json::to_string(report) {
+        Ok(json) => {
+            let _ = writeln!(out, "{}", json);
+        }
+        Err(e) => super::print_error(&format!("结果序列化失败: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn estimated_usage_sums_tokens() {
+        let usage = Usage::estimated(12, 30);
+        assert_eq!(usage.total_tokens, 42);
+        assert!(usage.estimated);
+    }
+
+    #[test]
+    fn report_serializes_missing_fields_as_null() {
+        let report = TurnReport {
+            error: Some("连接失败".to_string()),
+            ..Default::default()
+        };
+        let value: Value = serde_json::to_value(&report).unwrap();
+        assert!(value["response"].is_null());
+        assert_eq!(value["tool_calls"], json!([]));
+        assert_eq!(value["error"], "连接失败");
+        assert_eq!(value["usage"]["total_tokens"], 0);
+    }
+}
@@ -1,35 +1,169 @@
+use clap::ValueEnum;
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::time::Duration;
 
+use super::output::{is_quiet, is_stdout_terminal};
+
+/// 加载动画的样式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum SpinnerStyle {
+    /// 盲文点阵字符
+    #[default]
+    Braille,
+    /// 只使用 ASCII 字符，适合无法显示盲文字符的终端
+    Ascii,
+    /// 不显示动画，只显示一行静态提示
+    None,
+}
+
+impl SpinnerStyle {
+    /// 动画使用的字符，最后一个字符在动画结束时显示
+    pub fn tick_chars(self) -> &'static str {
+        match self {
+            Self::Braille => "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏",
+            Self::Ascii => "|/-\\ ",
+            Self::None => "  ",
+        }
+    }
+
+    /// 对应的进度条样式
+    pub fn progress_style(self) -> ProgressStyle {
+        let template = match self {
+            Self::None => "{msg}",
+            _ => "{spinner:.blue} {msg}",
+        };
+        ProgressStyle::default_spinner()
+            .tick_chars(self.tick_chars())
+            .template(template)
+            .unwrap()
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Ascii,
+            2 => Self::None,
+            _ => Self::Braille,
+        }
+    }
+}
+
+/// 当前使用的加载动画样式
+static SPINNER_STYLE: AtomicU8 = AtomicU8::new(SpinnerStyle::Braille as u8);
+
+/// 设置加载动画的样式
+pub fn set_spinner_style(style: SpinnerStyle) {
+    SPINNER_STYLE.store(style as u8, Ordering::Relaxed);
+}
+
+/// 当前的加载动画样式
+pub fn spinner_style() -> SpinnerStyle {
+    SpinnerStyle::from_u8(SPINNER_STYLE.load(Ordering::Relaxed))
+}
+
 /// 创建一个加载动画
 ///
+/// 标准输出不是终端时不显示动画，只在 stderr 打印一行提示；安静模式下完全不显示。
+///
 /// # Arguments
 /// * `message` - 显示的消息
 /// * `auto_tick` - 是否自动更新动画（默认为 true）
 pub fn create_spinner(message: &str, auto_tick: bool) -> ProgressBar {
+    if is_quiet() {
+        return ProgressBar::hidden();
+    }
+    build_spinner(message, auto_tick, is_stdout_terminal(), spinner_style())
+}
+
+/// 根据是否为终端创建加载动画
+///
+/// 非终端时返回隐藏的进度条，对它的所有操作都是空操作。
+fn build_spinner(
+    message: &str,
+    auto_tick: bool,
+    is_terminal: bool,
+    style: SpinnerStyle,
+) -> ProgressBar {
+    if !is_terminal {
+        eprintln!("{}", message);
+        return ProgressBar::hidden();
+    }
+
     let pb = ProgressBar::new_spinner();
 
     // 设置样式
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
-            .template("{spinner:.blue} {msg}")
-            .unwrap(),
-    );
+    pb.set_style(style.progress_style());
 
     // 设置消息
     pb.set_message(message.to_string());
 
-    // 如果启用自动更新，设置更新间隔
-    if auto_tick {
+    // 如果启用自动更新，设置更新间隔；不显示动画时只需绘制一次
+    if style == SpinnerStyle::None {
+        pb.tick();
+    } else if auto_tick {
         pb.enable_steady_tick(Duration::from_millis(100));
     }
 
     pb
 }
 
-/// 创建一个简单的进度条（为了向后兼容，实际上也是加载动画）
-#[deprecated(since = "0.1.0", note = "请使用 create_spinner 替代")]
-pub fn create_progress_bar(message: &str) -> ProgressBar {
-    create_spinner(message, true)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spinner_is_hidden_when_not_a_terminal() {
+        let pb = build_spinner("思考中...", true, false, SpinnerStyle::Braille);
+        assert!(pb.is_hidden());
+        assert!(pb.message().is_empty());
+
+        // 隐藏的进度条上的操作都是空操作
+        pb.set_message("仍然隐藏");
+        pb.finish_and_clear();
+    }
+
+    #[test]
+    fn chosen_style_is_applied_to_the_progress_style() {
+        // 动画在除最后一个字符以外的字符间循环，最后一个字符在结束时显示
+        let ticks = |style: &ProgressStyle, count: usize| -> Vec<String> {
+            (0..count as u64)
+                .map(|i| style.get_tick_str(i).to_string())
+                .collect()
+        };
+
+        let pb = build_spinner("思考中...", false, true, SpinnerStyle::Ascii);
+        let style = pb.style();
+        assert_eq!(ticks(&style, 5), ["|", "/", "-", "\\", "|"]);
+        assert_eq!(style.get_final_tick_str(), " ");
+        assert_eq!(pb.message(), "思考中...");
+        pb.finish_and_clear();
+
+        let style = SpinnerStyle::Braille.progress_style();
+        assert_eq!(ticks(&style, 2), ["⠋", "⠙"]);
+        assert_eq!(style.get_final_tick_str(), "⠏");
+        assert_eq!(SpinnerStyle::default(), SpinnerStyle::Braille);
+    }
+
+    #[test]
+    fn factory_returns_a_configured_spinner_for_terminals() {
+        for style in [
+            SpinnerStyle::Braille,
+            SpinnerStyle::Ascii,
+            SpinnerStyle::None,
+        ] {
+            let pb = build_spinner("加载中...", true, true, style);
+            // 加载动画没有总长度，只显示动画和消息
+            assert_eq!(pb.length(), None);
+            assert_eq!(pb.message(), "加载中...");
+            let final_tick = style.tick_chars().chars().last().unwrap().to_string();
+            assert_eq!(pb.style().get_final_tick_str(), final_tick);
+            assert!(!pb.is_finished());
+
+            pb.finish_and_clear();
+            assert!(pb.is_finished());
+        }
+    }
 }
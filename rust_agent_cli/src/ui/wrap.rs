@@ -0,0 +1,254 @@
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unicode_width::UnicodeWidthChar;
+
+use super::output::is_stdout_terminal;
+
+/// 当前终端宽度，0 表示不换行
+static TERMINAL_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// 代码块的围栏标记
+const FENCE: &str = "```";
+
+/// 初始化自动换行：记录终端宽度，并在终端窗口大小变化（SIGWINCH）时重新获取
+///
+/// 标准输出不是终端或 `enabled` 为 false 时不换行。需要在 tokio 运行时中调用。
+pub fn init_word_wrap(enabled: bool) {
+    if !enabled || !is_stdout_terminal() {
+        TERMINAL_WIDTH.store(0, Ordering::Relaxed);
+        return;
+    }
+
+    refresh_terminal_width();
+    #[cfg(unix)]
+    tokio::spawn(async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let Ok(mut resized) = signal(SignalKind::window_change()) else {
+            return;
+        };
+        while resized.recv().await.is_some() {
+            refresh_terminal_width();
+        }
+    });
+}
+
+fn refresh_terminal_width() {
+    let (_, columns) = console::Term::stdout().size();
+    TERMINAL_WIDTH.store(columns as usize, Ordering::Relaxed);
+}
+
+/// 当前用于换行的终端宽度，不换行时返回 None
+pub fn terminal_width() -> Option<usize> {
+    Some(TERMINAL_WIDTH.load(Ordering::Relaxed)).filter(|&width| width > 0)
+}
+
+/// 字符的显示宽度，控制字符按 1 列计算
+fn char_width(ch: char) -> usize {
+    ch.width().unwrap_or(1)
+}
+
+/// 流式输出的自动换行
+///
+/// 回复按片段陆续到达，已输出的内容无法重新排版，因此在片段到达时决定换行位置：
+/// 跨片段记录当前列位置，尚未结束的单词暂存到下一个空白字符出现时再输出，
+/// 放不下时在单词前换行；中文等宽字符之间可以直接换行，比整行还长的单词按宽度硬换行。
+/// 代码块（```` ``` ```` 之间）内的内容原样输出，不会换行。
+#[derive(Debug, Default)]
+pub struct StreamWrapper {
+    /// 当前列位置
+    column: usize,
+    /// 单词前待输出的空白
+    spaces: String,
+    /// 尚未结束的单词
+    word: String,
+    word_width: usize,
+    /// 当前行已收到的内容，用于识别代码块围栏
+    line: String,
+    in_code_block: bool,
+    /// 固定的换行宽度，None 时使用当前终端宽度
+    fixed_width: Option<usize>,
+}
+
+impl StreamWrapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用固定宽度换行，不跟随终端宽度
+    #[cfg(test)]
+    fn with_width(width: usize) -> Self {
+        Self {
+            fixed_width: Some(width),
+            ..Self::default()
+        }
+    }
+
+    /// 当前用于换行的宽度，不换行时返回 None
+    fn width(&self) -> Option<usize> {
+        self.fixed_width.or_else(terminal_width)
+    }
+
+    /// 处理一段流式文本，返回可以立即输出的内容
+    pub fn push(&mut self, text: &str) -> String {
+        let width = self.width();
+        let mut out = String::new();
+        for ch in text.chars() {
+            self.push_char(ch, width, &mut out);
+        }
+        out
+    }
+
+    /// 返回暂存的单词，在回复结束或插入其他输出前调用；行尾的空白会被丢弃
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.flush_word(self.width(), &mut out);
+        self.spaces.clear();
+        out
+    }
+
+    /// 输出不参与换行的文本（如助手名称、工具提示），先返回暂存的单词，并按文本更新列位置
+    pub fn write_raw(&mut self, text: &str) -> String {
+        let mut out = self.finish();
+        match text.rsplit_once('\n') {
+            Some((_, last)) => {
+                self.column = console::measure_text_width(last);
+                self.line.clear();
+            }
+            None => self.column += console::measure_text_width(text),
+        }
+        out.push_str(text);
+        out
+    }
+
+    fn push_char(&mut self, ch: char, width: Option<usize>, out: &mut String) {
+        if ch == '\n' {
+            self.flush_word(width, out);
+            self.spaces.clear();
+            out.push('\n');
+            self.column = 0;
+            if self.line.trim_start().starts_with(FENCE) {
+                self.in_code_block = !self.in_code_block;
+            }
+            self.line.clear();
+            return;
+        }
+        self.line.push(ch);
+
+        // 不换行或在代码块中时原样输出
+        let Some(width) = width.filter(|_| !self.in_code_block) else {
+            self.flush_word(None, out);
+            out.push_str(&mem::take(&mut self.spaces));
+            out.push(ch);
+            self.column += char_width(ch);
+            return;
+        };
+
+        if ch.is_whitespace() {
+            self.flush_word(Some(width), out);
+            self.spaces.push(ch);
+            return;
+        }
+
+        let ch_width = char_width(ch);
+        if ch_width > 1 {
+            // 宽字符前后都可以换行
+            self.flush_word(Some(width), out);
+            self.word.push(ch);
+            self.word_width = ch_width;
+            self.flush_word(Some(width), out);
+        } else {
+            self.word.push(ch);
+            self.word_width += ch_width;
+        }
+    }
+
+    /// 输出暂存的单词，当前行放不下时先换行
+    fn flush_word(&mut self, width: Option<usize>, out: &mut String) {
+        if self.word.is_empty() {
+            return;
+        }
+
+        let spaces_width: usize = self.spaces.chars().map(char_width).sum();
+        match width {
+            Some(width)
+                if self.column > 0 && self.column + spaces_width + self.word_width > width =>
+            {
+                out.push('\n');
+                self.column = 0;
+                self.spaces.clear();
+            }
+            _ => {
+                out.push_str(&mem::take(&mut self.spaces));
+                self.column += spaces_width;
+            }
+        }
+
+        for ch in mem::take(&mut self.word).chars() {
+            let ch_width = char_width(ch);
+            if width.is_some_and(|width| self.column > 0 && self.column + ch_width > width) {
+                out.push('\n');
+                self.column = 0;
+            }
+            out.push(ch);
+            self.column += ch_width;
+        }
+        self.word_width = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 逐个片段送入换行器，返回完整输出
+    fn wrap(width: usize, chunks: &[&str]) -> String {
+        let mut wrapper = StreamWrapper::with_width(width);
+        let mut out: String = chunks.iter().map(|chunk| wrapper.push(chunk)).collect();
+        out.push_str(&wrapper.finish());
+        out
+    }
+
+    #[test]
+    fn words_split_across_chunks_wrap_at_word_boundaries() {
+        let chunks = [
+            "the qu",
+            "ick bro",
+            "wn fox ",
+            "jumps over",
+            " the lazy dog",
+        ];
+        let out = wrap(10, &chunks);
+        assert_eq!(out, "the quick\nbrown fox\njumps over\nthe lazy\ndog");
+        assert!(out.lines().all(|line| line.len() <= 10));
+        // 按整段送入时结果相同
+        assert_eq!(wrap(10, &[&chunks.concat()]), out);
+    }
+
+    #[test]
+    fn wide_characters_wrap_by_display_width() {
+        // 每个汉字占 2 列
+        assert_eq!(wrap(6, &["你好", "世界和平"]), "你好世\n界和平");
+        // 比整行还长的单词按宽度硬换行
+        assert_eq!(wrap(4, &["abcdefghij"]), "abcd\nefgh\nij");
+    }
+
+    #[test]
+    fn code_blocks_are_not_wrapped() {
+        let text =
+            "some words here\n```\nlet value = compute(first, second);\n```\nmore words here";
+        assert_eq!(
+            wrap(10, &[text]),
+            "some words\nhere\n```\nlet value = compute(first, second);\n```\nmore words\nhere"
+        );
+    }
+
+    #[test]
+    fn raw_text_moves_the_column() {
+        let mut wrapper = StreamWrapper::with_width(10);
+        let mut out = wrapper.write_raw("助手: ");
+        out.push_str(&wrapper.push("hi there"));
+        out.push_str(&wrapper.finish());
+        assert_eq!(out, "助手: hi\nthere");
+    }
+}
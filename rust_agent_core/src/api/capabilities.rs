@@ -0,0 +1,96 @@
+use std::fmt;
+
+/// 需要模型支持才能使用的功能
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// 原生函数调用（请求中的 `tools` 字段）
+    NativeTools,
+    /// 图片等多模态输入
+    Vision,
+    /// JSON 模式（`response_format: {"type": "json_object"}`）
+    JsonMode,
+    /// 单独返回推理过程
+    Reasoning,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::NativeTools => "原生函数调用",
+            Self::Vision => "图片输入",
+            Self::JsonMode => "JSON 模式",
+            Self::Reasoning => "推理过程输出",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 模型支持的功能
+///
+/// 会话据此决定使用哪些功能，请求不支持的功能时尽早返回 [`UnsupportedFeature`]，
+/// 而不是等到服务端报错。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    pub native_tools: bool,
+    pub vision: bool,
+    pub json_mode: bool,
+    pub reasoning: bool,
+}
+
+impl Capabilities {
+    /// Deepseek 各模型的能力
+    ///
+    /// `deepseek-reasoner` 输出推理过程，但不支持函数调用和 JSON 模式；
+    /// 其他模型按 `deepseek-chat` 处理。目前的 Deepseek 模型都不支持图片输入。
+    pub fn deepseek(model: &str) -> Self {
+        if model.starts_with("deepseek-reasoner") {
+            Self {
+                reasoning: true,
+                ..Self::default()
+            }
+        } else {
+            Self {
+                native_tools: true,
+                json_mode: true,
+                ..Self::default()
+            }
+        }
+    }
+
+    /// 是否支持指定功能
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::NativeTools => self.native_tools,
+            Feature::Vision => self.vision,
+            Feature::JsonMode => self.json_mode,
+            Feature::Reasoning => self.reasoning,
+        }
+    }
+
+    /// 检查模型是否支持指定功能，不支持时返回错误
+    pub fn require(&self, model: &str, feature: Feature) -> Result<(), UnsupportedFeature> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(UnsupportedFeature {
+                model: model.to_string(),
+                feature,
+            })
+        }
+    }
+}
+
+/// 模型不支持请求的功能
+#[derive(Debug, Clone)]
+pub struct UnsupportedFeature {
+    pub model: String,
+    pub feature: Feature,
+}
+
+impl fmt::Display for UnsupportedFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "模型 {} 不支持{}", self.model, self.feature)
+    }
+}
+
+impl std::error::Error for UnsupportedFeature {}
@@ -1,100 +1,983 @@
 use anyhow::Result;
+use futures::future::Either;
 use futures::Stream;
+use reqwest::RequestBuilder;
 use tokio_stream::StreamExt;
+use tracing::warn;
 
-use super::types::{ChatMessage, ChatRequest, ChatResponse, ChatStreamResponse};
+use super::capabilities::{Capabilities, Feature, UnsupportedFeature};
+use super::roles::RoleMapping;
+use super::sse::SseDecoder;
+use super::tool_calls::{ToolCallDelta, ToolDefinition};
+use super::types::{
+    parse_json_content, ApiError, ChatMessage, ChatRequest, ChatResponse, ErrorEnvelope, ModelList,
+    ResponseFormat, StreamDelta, StreamOptions,
+};
+use crate::correlation::DEFAULT_REQUEST_ID_HEADER;
+use crate::text::truncate_chars;
 
+/// Deepseek 接口的默认地址
+pub const DEFAULT_BASE_URL: &str = "https://api.deepseek.com";
+
+/// 默认使用的模型
+pub const DEFAULT_MODEL: &str = "deepseek-chat";
+
+/// 已知的 Deepseek 模型，用于在启动时提示可能拼写错误的模型名称
+///
+/// 服务端随时可能增加新模型，不在列表中的名称不一定无效。
+pub const KNOWN_MODELS: &[&str] = &["deepseek-chat", "deepseek-reasoner"];
+
+/// Deepseek 允许的最大停止序列数量
+pub const MAX_STOP_SEQUENCES: usize = 16;
+
+/// 由客户端设置的请求字段，通过附加字段覆盖时给出警告
+const RESERVED_FIELDS: &[&str] = &[
+    "model",
+    "messages",
+    "temperature",
+    "top_p",
+    "n",
+    "stream",
+    "stop",
+    "seed",
+    "response_format",
+    "stream_options",
+    "tools",
+];
+
+/// 请求体中启用原生函数调用的字段
+const NATIVE_TOOL_FIELDS: &[&str] = &["tools", "tool_choice", "functions", "function_call"];
+
+/// 默认采样温度
+pub const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+/// Deepseek 允许的最大采样温度
+pub const MAX_TEMPERATURE: f32 = 2.0;
+
+/// 只作用于单次请求的参数，未设置的字段使用客户端的默认值
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// 覆盖采样温度，超出 [0, 2] 时会被截断
+    pub temperature: Option<f32>,
+    /// 覆盖核采样概率，超出 [0, 1] 时会被截断
+    pub top_p: Option<f32>,
+    /// 随请求头发送的请求 ID，用于关联模型请求、工具调用和日志
+    pub request_id: Option<String>,
+    /// 原生函数调用模式下随请求声明的函数，需要模型支持原生函数调用
+    pub tools: Option<Vec<ToolDefinition>>,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置本次请求的采样温度
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// 设置本次请求的请求 ID
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// 设置本次请求的核采样概率
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// 在请求中声明可调用的函数，模型通过原生函数调用（`tool_calls`）调用它们
+    pub fn with_tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// 以 `defaults` 补全未设置的选项，已设置的选项优先
+    pub fn or(&self, defaults: &RequestOptions) -> RequestOptions {
+        RequestOptions {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            request_id: self
+                .request_id
+                .clone()
+                .or_else(|| defaults.request_id.clone()),
+            tools: self.tools.clone().or_else(|| defaults.tools.clone()),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct DeepseekClient {
     client: reqwest::Client,
+    /// 接口地址，不含末尾的 `/`
+    base_url: String,
     api_key: String,
+    model: String,
+    temperature: f32,
+    top_p: Option<f32>,
+    stop: Option<Vec<String>>,
+    seed: Option<u64>,
+    response_format: Option<ResponseFormat>,
+    /// 是否丢弃与上一帧完全相同的流式帧
+    dedup_stream: bool,
+    /// 携带请求 ID 的请求头名称
+    request_id_header: String,
+    /// 覆盖按模型推断的能力，用于 Deepseek 兼容接口上的其他模型
+    capabilities: Option<Capabilities>,
+    /// 原样附加到请求体中的其他字段
+    extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl DeepseekClient {
+    /// 发送请求时使用的角色映射方式
+    pub const ROLE_MAPPING: RoleMapping = RoleMapping::Passthrough;
+
     pub fn new(api_key: String) -> Self {
         Self {
             client: reqwest::Client::new(),
+            base_url: DEFAULT_BASE_URL.to_string(),
             api_key,
+            model: DEFAULT_MODEL.to_string(),
+            temperature: DEFAULT_TEMPERATURE,
+            top_p: None,
+            stop: None,
+            seed: None,
+            response_format: None,
+            dedup_stream: false,
+            request_id_header: DEFAULT_REQUEST_ID_HEADER.to_string(),
+            capabilities: None,
+            extra: serde_json::Map::new(),
         }
     }
 
-    pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        let request = ChatRequest {
-            model: "deepseek-chat".to_string(),
-            messages,
-            temperature: 0.7,
-            stream: false,
+    /// 设置接口地址，用于 Deepseek 兼容的其他服务或代理，例如 `http://localhost:8000`
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// 设置模型，例如 `deepseek-reasoner`
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// 使用的模型名称
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// 设置采样温度
+    ///
+    /// 取值范围为 [0, 2]，超出范围时会被截断到边界。
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = clamp_param("temperature", temperature, 0.0, MAX_TEMPERATURE);
+        self.warn_if_both_sampling_params();
+        self
+    }
+
+    /// 设置核采样概率
+    ///
+    /// 取值范围为 [0, 1]，超出范围时会被截断到边界。
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(clamp_param("top_p", top_p, 0.0, 1.0));
+        self.warn_if_both_sampling_params();
+        self
+    }
+
+    /// 通常只建议调整 temperature 和 top_p 中的一个，同时调整时结果较难预期
+    fn warn_if_both_sampling_params(&self) {
+        if let Some(top_p) = self.top_p {
+            if self.temperature != DEFAULT_TEMPERATURE && top_p < 1.0 {
+                warn!(
+                    "同时设置了 temperature = {} 和 top_p = {}，建议只调整其中一个",
+                    self.temperature, top_p
+                );
+            }
+        }
+    }
+
+    /// 设置停止序列
+    ///
+    /// Deepseek 最多接受 [`MAX_STOP_SEQUENCES`] 个停止序列，超出部分会被丢弃。
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        let mut stop = stop;
+        if stop.len() > MAX_STOP_SEQUENCES {
+            warn!(
+                "停止序列数量 {} 超过上限 {}，多余部分将被忽略",
+                stop.len(),
+                MAX_STOP_SEQUENCES
+            );
+            stop.truncate(MAX_STOP_SEQUENCES);
+        }
+        self.stop = if stop.is_empty() { None } else { Some(stop) };
+        self
+    }
+
+    /// 设置随机种子
+    ///
+    /// 相同的种子和参数通常能得到相同的输出，便于调试和快照测试；
+    /// 但可复现性取决于服务端实现，并不保证。
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 丢弃与上一帧完全相同的流式帧
+    ///
+    /// 某些代理会重放 SSE 帧，导致输出中出现重复的片段。模型本身也可能连续输出
+    /// 相同的内容，开启后这类正常的重复同样会被丢弃，因此默认关闭。
+    pub fn with_stream_dedup(mut self, enabled: bool) -> Self {
+        self.dedup_stream = enabled;
+        self
+    }
+
+    /// 设置携带请求 ID 的请求头名称（默认 `X-Request-Id`），
+    /// 用于匹配网关或代理约定的关联 ID 请求头
+    pub fn with_request_id_header(mut self, header: impl Into<String>) -> Self {
+        self.request_id_header = header.into();
+        self
+    }
+
+    /// 在请求体顶层附加一个字段，例如本地模型后端支持的 `top_k`、`repetition_penalty`、`min_p`
+    ///
+    /// 字段不会被校验，原样发送给服务端；与客户端设置的字段（如 `model`、`temperature`）同名时
+    /// 覆盖客户端的值，并记录一条警告。
+    pub fn with_extra_field(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        let key = key.into();
+        warn_if_reserved(&key);
+        self.extra.insert(key, value);
+        self
+    }
+
+    /// 一次附加多个字段，与已附加的同名字段会被覆盖
+    pub fn with_extra_fields(mut self, fields: serde_json::Map<String, serde_json::Value>) -> Self {
+        for key in fields.keys() {
+            warn_if_reserved(key);
+        }
+        self.extra.extend(fields);
+        self
+    }
+
+    /// 启用 JSON 模式
+    ///
+    /// 请求体会携带 `response_format: {"type": "json_object"}`，`chat` 返回前会校验内容，
+    /// 不是合法 JSON 时返回 [`InvalidJsonError`](super::InvalidJsonError)。
+    /// 流式输出无法逐块校验，调用方应对拼接后的完整内容调用 [`parse_json_content`]。
+    /// 注意 Deepseek 要求提示词中包含 "json" 字样，否则可能返回错误。
+    /// 模型不支持 JSON 模式时，发送请求前就会返回 [`UnsupportedFeature`]。
+    pub fn with_json_mode(mut self) -> Self {
+        self.response_format = Some(ResponseFormat::json_object());
+        self
+    }
+
+    /// 是否启用了 JSON 模式
+    pub fn is_json_mode(&self) -> bool {
+        self.response_format.is_some()
+    }
+
+    /// 指定模型的能力，不再按模型名称推断
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// 当前模型支持的功能
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+            .unwrap_or_else(|| Capabilities::deepseek(&self.model))
+    }
+
+    /// 检查当前模型是否支持指定功能
+    pub fn require(&self, feature: Feature) -> Result<(), UnsupportedFeature> {
+        self.capabilities().require(&self.model, feature)
+    }
+
+    /// 构建聊天请求，启用了模型不支持的功能时返回错误
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        stream: bool,
+        options: &RequestOptions,
+    ) -> Result<ChatRequest> {
+        if self.is_json_mode() {
+            self.require(Feature::JsonMode)?;
+        }
+        if messages.iter().any(|message| !message.images.is_empty()) {
+            self.require(Feature::Vision)?;
+        }
+        // 空的函数列表不发送，也不要求模型支持原生函数调用
+        let tools = options.tools.clone().filter(|tools| !tools.is_empty());
+        if tools.is_some()
+            || NATIVE_TOOL_FIELDS
+                .iter()
+                .any(|field| self.extra.contains_key(*field))
+        {
+            self.require(Feature::NativeTools)?;
+        }
+
+        let temperature = match options.temperature {
+            Some(temperature) => clamp_param("temperature", temperature, 0.0, MAX_TEMPERATURE),
+            None => self.temperature,
         };
+        let top_p = match options.top_p {
+            Some(top_p) => Some(clamp_param("top_p", top_p, 0.0, 1.0)),
+            None => self.top_p,
+        };
+        // Deepseek 兼容 OpenAI 的角色约定，系统消息保留在消息列表中
+        let mapped = Self::ROLE_MAPPING.map(messages);
+        Ok(ChatRequest {
+            model: self.model.clone(),
+            messages: mapped.messages,
+            temperature,
+            top_p,
+            n: None,
+            stream,
+            stop: self.stop.clone(),
+            seed: self.seed,
+            response_format: self.response_format.clone(),
+            // 流式输出默认不返回用量，需要显式请求
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            tools,
+            extra: self.extra.clone(),
+        })
+    }
 
-        let response = self
+    /// 创建聊天接口的 HTTP 请求，流式和非流式请求共用，附带认证和请求 ID 请求头
+    fn chat_request(&self, request: &ChatRequest, options: &RequestOptions) -> RequestBuilder {
+        let mut builder = self
             .client
-            .post("https://api.deepseek.com/v1/chat/completions")
+            .post(format!("{}/v1/chat/completions", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
+            .header("Content-Type", "application/json");
+        if let Some(request_id) = &options.request_id {
+            builder = builder.header(self.request_id_header.as_str(), request_id);
+        }
+        builder.json(&request.body())
+    }
+
+    /// 发送非流式请求
+    async fn send_chat(
+        &self,
+        request: &ChatRequest,
+        options: &RequestOptions,
+    ) -> Result<ChatResponse> {
+        let response = self
+            .chat_request(request, options)
             .send()
             .await?
             .json::<ChatResponse>()
             .await?;
 
-        Ok(response.choices[0].message.content.clone())
+        Ok(response)
     }
 
-    pub async fn chat_stream(
-        &self,
-        messages: Vec<ChatMessage>,
-    ) -> Result<impl Stream<Item = Result<String>>> {
-        let request = ChatRequest {
-            model: "deepseek-chat".to_string(),
-            messages,
-            temperature: 0.7,
-            stream: true,
-        };
-
+    /// 列出服务端提供的模型（`GET /models`）
+    pub async fn list_models(&self) -> Result<Vec<String>> {
         let response = self
             .client
-            .post("https://api.deepseek.com/v1/chat/completions")
+            .get(format!("{}/models", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
             .send()
+            .await?
+            .error_for_status()?
+            .json::<ModelList>()
             .await?;
 
-        let stream = response.bytes_stream().map(|chunk| {
-            chunk.map_err(|e| anyhow::anyhow!(e)).and_then(|bytes| {
-                if bytes.is_empty() {
-                    return Ok(String::new());
-                }
-
-                let text = String::from_utf8(bytes.to_vec())?;
-                let mut responses = Vec::new();
-
-                for line in text.lines() {
-                    let line = line.trim();
-                    if line.starts_with("data: ") {
-                        let json_str = line.trim_start_matches("data: ");
-                        if json_str == "[DONE]" {
-                            continue;
-                        }
-                        if let Ok(stream_response) =
-                            serde_json::from_str::<ChatStreamResponse>(json_str)
-                        {
-                            if let Some(choice) = stream_response.choices.first() {
-                                if !choice.delta.content.is_empty() {
-                                    responses.push(choice.delta.content.clone());
-                                }
-                            }
-                        }
-                    }
-                }
-
-                if responses.is_empty() {
-                    Ok(String::new())
-                } else {
-                    Ok(responses.join(""))
-                }
+        Ok(response.data.into_iter().map(|model| model.id).collect())
+    }
+
+    pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<String> {
+        self.chat_with(messages, &RequestOptions::default()).await
+    }
+
+    /// 使用单次请求的参数进行非流式对话，客户端的默认参数不受影响
+    pub async fn chat_with(
+        &self,
+        messages: Vec<ChatMessage>,
+        options: &RequestOptions,
+    ) -> Result<String> {
+        let request = self.build_request(messages, false, options)?;
+        let response = self.send_chat(&request, options).await?;
+
+        let content = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or(ApiError::NoChoices)?
+            .message
+            .content;
+        if self.is_json_mode() {
+            parse_json_content(&content)?;
+        }
+
+        Ok(content)
+    }
+
+    /// 请求多个候选回复，按服务端返回的顺序给出所有候选
+    ///
+    /// 是否支持取决于服务端：OpenAI 兼容的服务通常支持 `n`，
+    /// Deepseek 目前可能忽略该参数而只返回一个候选，调用方不应假设结果数量等于 `n`。
+    pub async fn chat_n(&self, messages: Vec<ChatMessage>, n: u32) -> Result<Vec<String>> {
+        let options = RequestOptions::default();
+        let mut request = self.build_request(messages, false, &options)?;
+        request.n = Some(n.max(1));
+        let response = self.send_chat(&request, &options).await?;
+
+        let contents = response.into_contents()?;
+        if self.is_json_mode() {
+            for content in &contents {
+                parse_json_content(content)?;
+            }
+        }
+
+        Ok(contents)
+    }
+
+    /// 流式对话
+    ///
+    /// 每个元素是一段增量，`reasoning_content` 与最终回答分开返回。
+    /// 服务端不支持流式输出、返回的不是 `text/event-stream` 时，按完整响应解析，
+    /// 整个回答作为唯一的一段增量返回。
+    pub async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+    ) -> Result<impl Stream<Item = Result<StreamDelta>>> {
+        self.chat_stream_with(messages, &RequestOptions::default())
+            .await
+    }
+
+    /// 使用单次请求的参数进行流式对话，客户端的默认参数不受影响
+    pub async fn chat_stream_with(
+        &self,
+        messages: Vec<ChatMessage>,
+        options: &RequestOptions,
+    ) -> Result<impl Stream<Item = Result<StreamDelta>>> {
+        let request = self.build_request(messages, true, options)?;
+
+        let response = self.chat_request(&request, options).send().await?;
+
+        let is_event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_event_stream {
+            warn!("响应不是流式格式，按完整响应解析");
+            let body = response.bytes().await?;
+            let delta = parse_complete_response(&body);
+            return Ok(Either::Left(futures::stream::iter([delta])));
+        }
+
+        // 流结束时 `decoder` 被取出，解析最后一帧后不再产生增量
+        let decoder = SseDecoder::new(self.dedup_stream);
+        let stream = futures::stream::unfold(
+            (Box::pin(response.bytes_stream()), Some(decoder)),
+            |(mut bytes, mut decoder)| async move {
+                let item = match bytes.next().await {
+                    Some(Ok(chunk)) => decoder.as_mut()?.push(&chunk),
+                    Some(Err(e)) => Err(e.into()),
+                    None => decoder.take()?.finish(),
+                };
+                Some((item, (bytes, decoder)))
+            },
+        );
+
+        Ok(Either::Right(Box::pin(stream)))
+    }
+}
+
+/// 把非流式的完整响应转换为一段增量
+fn parse_complete_response(body: &[u8]) -> Result<StreamDelta> {
+    if let Ok(response) = serde_json::from_slice::<ChatResponse>(body) {
+        let usage = response.usage;
+        let choice = response
+            .choices
+            .into_iter()
+            .min_by_key(|choice| choice.index)
+            .ok_or(ApiError::NoChoices)?;
+        // 完整响应中的函数调用不带序号，按出现顺序编号
+        let tool_calls = choice
+            .message
+            .tool_calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, call)| ToolCallDelta { index, ..call })
+            .collect();
+        return Ok(StreamDelta {
+            content: choice.message.content,
+            reasoning_content: choice.message.reasoning_content.unwrap_or_default(),
+            finish_reason: choice.finish_reason,
+            usage,
+            tool_calls,
+        });
+    }
+
+    if let Ok(envelope) = serde_json::from_slice::<ErrorEnvelope>(body) {
+        let error = ApiError::from(envelope.error);
+        warn!("{}", error);
+        return Err(error.into());
+    }
+
+    Err(anyhow::anyhow!(
+        "无法解析非流式响应: {}",
+        truncate_chars(&String::from_utf8_lossy(body), 200)
+    ))
+}
+
+/// 把采样参数限制在允许的范围内，非法值（NaN）使用下限
+fn clamp_param(name: &str, value: f32, min: f32, max: f32) -> f32 {
+    if value.is_nan() {
+        warn!("{} 不是有效数字，使用 {}", name, min);
+        return min;
+    }
+
+    let clamped = value.clamp(min, max);
+    if clamped != value {
+        warn!(
+            "{} = {} 超出范围 [{}, {}]，已调整为 {}",
+            name, value, min, max, clamped
+        );
+    }
+    clamped
+}
+
+/// 附加字段与客户端设置的字段同名时给出警告
+fn warn_if_reserved(key: &str) {
+    if RESERVED_FIELDS.contains(&key) {
+        warn!("附加字段 {} 会覆盖客户端设置的同名字段", key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing::{sse_frame, MockChatServer, MockResponse};
+    use crate::api::InvalidJsonError;
+    use serde_json::json;
+
+    /// 按客户端配置构建一个请求并序列化为请求体
+    fn request_body(client: &DeepseekClient) -> serde_json::Value {
+        let messages = vec![ChatMessage::new("user", "你好")];
+        let request = client
+            .build_request(messages, false, &RequestOptions::new())
+            .unwrap();
+        request.body()
+    }
+
+    #[test]
+    fn top_p_serializes_only_when_set_and_is_clamped() {
+        let client = DeepseekClient::new("key".to_string());
+        assert!(request_body(&client).get("top_p").is_none());
+
+        let body = request_body(&client.with_top_p(0.9));
+        assert!((body["top_p"].as_f64().unwrap() - 0.9).abs() < 1e-6);
+        let client = DeepseekClient::new("key".to_string());
+        assert_eq!(request_body(&client.with_top_p(1.5))["top_p"], 1.0);
+        let client = DeepseekClient::new("key".to_string());
+        assert_eq!(request_body(&client.with_top_p(f32::NAN))["top_p"], 0.0);
+
+        // 单次请求的参数同样会被截断
+        let options = RequestOptions::new().with_top_p(-0.5);
+        let client = DeepseekClient::new("key".to_string());
+        let request = client
+            .build_request(vec![ChatMessage::new("user", "你好")], false, &options)
+            .unwrap();
+        assert_eq!(request.top_p, Some(0.0));
+    }
+
+    #[test]
+    fn stop_sequences_serialize_into_request_body() {
+        let client = DeepseekClient::new("key".into()).with_stop(vec!["END".into(), "\n\n".into()]);
+        assert_eq!(request_body(&client)["stop"], json!(["END", "\n\n"]));
+
+        let client = DeepseekClient::new("key".into()).with_stop(Vec::new());
+        assert!(request_body(&client).get("stop").is_none());
+    }
+
+    #[test]
+    fn stop_sequences_are_capped() {
+        let stop = (0..20).map(|i| format!("stop-{}", i)).collect();
+        let client = DeepseekClient::new("key".into()).with_stop(stop);
+        let body = request_body(&client);
+        assert_eq!(body["stop"].as_array().unwrap().len(), MAX_STOP_SEQUENCES);
+    }
+
+    #[test]
+    fn request_temperature_overrides_client_default_and_is_clamped() {
+        let client = DeepseekClient::new("key".into()).with_temperature(0.8);
+        let messages = vec![ChatMessage::new("user", "你好")];
+        let options = RequestOptions::new().with_temperature(0.1);
+        let request = client.build_request(messages.clone(), false, &options);
+        let body = serde_json::to_value(request.unwrap()).unwrap();
+        assert!((body["temperature"].as_f64().unwrap() - 0.1).abs() < 1e-6);
+
+        let options = RequestOptions::new().with_temperature(5.0);
+        let request = client.build_request(messages, false, &options);
+        let body = serde_json::to_value(request.unwrap()).unwrap();
+        assert_eq!(body["temperature"], MAX_TEMPERATURE as f64);
+        assert!((request_body(&client)["temperature"].as_f64().unwrap() - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn seed_serializes_only_when_set() {
+        let client = DeepseekClient::new("key".into()).with_seed(42);
+        assert_eq!(request_body(&client)["seed"], json!(42));
+
+        let client = DeepseekClient::new("key".into());
+        assert!(request_body(&client).get("seed").is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_go_to_the_configured_base_url() {
+        let server = MockChatServer::start(vec![MockResponse::completion("你好")])
+            .await
+            .unwrap();
+        let messages = vec![ChatMessage::new("user", "你好")];
+
+        assert_eq!(server.client().chat(messages).await.unwrap(), "你好");
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/chat/completions");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer test-key"));
+    }
+
+    #[test]
+    fn json_mode_serializes_response_format() {
+        let client = DeepseekClient::new("key".into()).with_json_mode();
+        assert_eq!(
+            request_body(&client)["response_format"],
+            json!({"type": "json_object"})
+        );
+
+        let client = DeepseekClient::new("key".into());
+        assert!(request_body(&client).get("response_format").is_none());
+    }
+
+    #[test]
+    fn extra_fields_are_passed_through_in_the_request_body() {
+        let client = DeepseekClient::new("key".into())
+            .with_extra_field("top_k", json!(40))
+            .with_extra_fields(
+                json!({"min_p": 0.05, "repetition_penalty": 1.1})
+                    .as_object()
+                    .unwrap()
+                    .clone(),
+            );
+        let body = request_body(&client);
+        assert_eq!(body["top_k"], json!(40));
+        assert_eq!(body["min_p"], json!(0.05));
+        assert_eq!(body["repetition_penalty"], json!(1.1));
+        assert_eq!(body["model"], json!(DEFAULT_MODEL));
+
+        let client = DeepseekClient::new("key".into());
+        assert!(request_body(&client).get("top_k").is_none());
+    }
+
+    #[test]
+    fn extra_fields_override_colliding_fields_with_a_warning() {
+        use crate::logging::{LogCapture, LogCaptureLayer};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let capture = LogCapture::start(10);
+        let client = capture.span().in_scope(|| {
+            DeepseekClient::new("key".into())
+                .with_temperature(0.7)
+                .with_extra_field("temperature", json!(0.1))
+                .with_extra_field("top_k", json!(40))
+        });
+        let logs = capture.finish();
+        assert_eq!(logs.len(), 1, "{:?}", logs);
+        assert!(logs[0].contains("temperature"), "{:?}", logs);
+
+        // 附加字段的值生效，请求体中只有一个同名的键
+        let body = request_body(&client);
+        assert_eq!(body["temperature"], json!(0.1));
+        let text = serde_json::to_string(&body).unwrap();
+        assert_eq!(text.matches("\"temperature\"").count(), 1, "{}", text);
+    }
+
+    #[test]
+    fn declared_tools_are_sent_only_to_models_with_native_tools() {
+        let messages = || vec![ChatMessage::new("user", "1 加 2")];
+        let schema = json!({"type": "object", "properties": {"a": {"type": "integer"}}});
+        let options = RequestOptions::new().with_tools(vec![ToolDefinition::function(
+            "add",
+            "两数相加",
+            Some(schema.clone()),
+        )]);
+
+        let client = DeepseekClient::new("key".into());
+        let body = client
+            .build_request(messages(), true, &options)
+            .unwrap()
+            .body();
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "type": "function",
+                "function": {"name": "add", "description": "两数相加", "parameters": schema}
+            }])
+        );
+        // 没有声明函数时不发送该字段
+        assert!(request_body(&client).get("tools").is_none());
+        let empty = RequestOptions::new().with_tools(Vec::new());
+        let request = client.build_request(messages(), true, &empty).unwrap();
+        assert!(request.body().get("tools").is_none());
+
+        // 不支持原生函数调用的模型在构建请求时就被拒绝
+        let reasoner = client.with_model("deepseek-reasoner");
+        let error = reasoner
+            .build_request(messages(), true, &options)
+            .unwrap_err();
+        let error = error.downcast_ref::<UnsupportedFeature>().unwrap();
+        assert_eq!(error.feature, Feature::NativeTools);
+        assert!(reasoner.build_request(messages(), true, &empty).is_ok());
+    }
+
+    #[tokio::test]
+    async fn json_mode_rejects_invalid_json_content() {
+        let server = MockChatServer::start(vec![
+            MockResponse::completion("这不是 JSON"),
+            MockResponse::completion(r#"{"answer": 42}"#),
+        ])
+        .await
+        .unwrap();
+        let client = server.client().with_json_mode();
+        let messages = vec![ChatMessage::new("user", "用 json 回答")];
+
+        let error = client.chat(messages.clone()).await.unwrap_err();
+        let error = error.downcast_ref::<InvalidJsonError>().unwrap();
+        assert_eq!(error.content, "这不是 JSON");
+
+        assert_eq!(client.chat(messages).await.unwrap(), r#"{"answer": 42}"#);
+    }
+
+    #[tokio::test]
+    async fn stream_error_frame_surfaces_as_api_error() {
+        let content = sse_frame(
+            &json!({"choices": [{"index": 0, "delta": {"content": "部分回答"}}]}).to_string(),
+        );
+        let error = sse_frame(r#"{"error": {"message": "服务繁忙", "type": "server_error"}}"#);
+        // 错误帧被拆成两段发送，在多字节字符中间切开
+        let split = error.find('忙').unwrap() + 1;
+        let server = MockChatServer::start(vec![MockResponse::Stream(vec![
+            content.into_bytes(),
+            error.as_bytes()[..split].to_vec(),
+            error.as_bytes()[split..].to_vec(),
+        ])])
+        .await
+        .unwrap();
+
+        let mut stream = server
+            .client()
+            .chat_stream(vec![ChatMessage::new("user", "你好")])
+            .await
+            .unwrap();
+        let mut received = String::new();
+        let error = loop {
+            match stream.next().await.expect("流在错误帧之前结束") {
+                Ok(delta) => received.push_str(&delta.content),
+                Err(e) => break e,
+            }
+        };
+
+        assert_eq!(received, "部分回答");
+        assert!(matches!(
+            error.downcast_ref::<ApiError>(),
+            Some(ApiError::Stream { message, .. }) if message == "服务繁忙"
+        ));
+    }
+
+    #[tokio::test]
+    async fn empty_choices_is_an_error_not_a_panic() {
+        let server = MockChatServer::start(vec![
+            MockResponse::Json(json!({"choices": []})),
+            MockResponse::Json(json!({"choices": []})),
+        ])
+        .await
+        .unwrap();
+        let client = server.client();
+        let messages = vec![ChatMessage::new("user", "你好")];
+
+        let error = client.chat(messages.clone()).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ApiError>(),
+            Some(ApiError::NoChoices)
+        ));
+        let error = client.chat_n(messages, 2).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ApiError>(),
+            Some(ApiError::NoChoices)
+        ));
+        assert!(parse_complete_response(br#"{"choices": []}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn chat_n_returns_all_choices_in_index_order() {
+        let choice = |index: u32, content: &str| {
+            json!({
+                "index": index,
+                "message": {"role": "assistant", "content": content},
+                "finish_reason": "stop"
             })
+        };
+        let server = MockChatServer::start(vec![MockResponse::Json(json!({
+            "choices": [choice(2, "丙"), choice(0, "甲"), choice(1, "乙")]
+        }))])
+        .await
+        .unwrap();
+
+        let answers = server
+            .client()
+            .chat_n(vec![ChatMessage::new("user", "给三个名字")], 3)
+            .await
+            .unwrap();
+        assert_eq!(answers, ["甲", "乙", "丙"]);
+        assert_eq!(server.requests()[0].body["n"], 3);
+    }
+
+    #[tokio::test]
+    async fn duplicated_frames_are_dropped_only_when_dedup_is_enabled() {
+        let frame =
+            |content: &str| json!({"choices": [{"index": 0, "delta": {"content": content}}]});
+        // 代理重放了第一帧
+        let response = || MockResponse::frames(vec![frame("你"), frame("你"), frame("好")]);
+        let server = MockChatServer::start(vec![response(), response()])
+            .await
+            .unwrap();
+
+        let collect = |client: DeepseekClient| async move {
+            let mut stream = client
+                .chat_stream(vec![ChatMessage::new("user", "打招呼")])
+                .await
+                .unwrap();
+            let mut content = String::new();
+            while let Some(delta) = stream.next().await {
+                content.push_str(&delta.unwrap().content);
+            }
+            content
+        };
+
+        assert_eq!(
+            collect(server.client().with_stream_dedup(true)).await,
+            "你好"
+        );
+        // 默认不去重，合法的重复内容原样保留
+        assert_eq!(collect(server.client()).await, "你你好");
+    }
+
+    #[tokio::test]
+    async fn non_streaming_response_is_yielded_as_one_delta() {
+        let server = MockChatServer::start(vec![MockResponse::Json(json!({
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "完整的回答"},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 3, "total_tokens": 8}
+        }))])
+        .await
+        .unwrap();
+
+        let stream = server
+            .client()
+            .chat_stream(vec![ChatMessage::new("user", "你好")])
+            .await
+            .unwrap();
+        let deltas: Vec<StreamDelta> = stream.map(Result::unwrap).collect().await;
+
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].content, "完整的回答");
+        assert_eq!(deltas[0].finish_reason.as_deref(), Some("stop"));
+        assert_eq!(deltas[0].usage.as_ref().unwrap().total_tokens, 8);
+        assert_eq!(server.requests()[0].body["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn unsupported_features_are_rejected_before_sending() {
+        let server = MockChatServer::start(vec![MockResponse::text(&["一只猫"])])
+            .await
+            .unwrap();
+        let with_image =
+            vec![ChatMessage::new("user", "图里是什么").with_image("https://example.com/cat.png")];
+
+        // deepseek-chat 不支持图片输入
+        let error = server
+            .client()
+            .chat_stream(with_image.clone())
+            .await
+            .err()
+            .unwrap();
+        let error = error.downcast_ref::<UnsupportedFeature>().unwrap();
+        assert_eq!(error.feature, Feature::Vision);
+
+        // deepseek-reasoner 不支持原生函数调用
+        let reasoner = server
+            .client()
+            .with_model("deepseek-reasoner")
+            .with_extra_field("tools", json!([]));
+        let error = reasoner
+            .chat(vec![ChatMessage::new("user", "你好")])
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<UnsupportedFeature>().unwrap();
+        assert_eq!(error.feature, Feature::NativeTools);
+
+        assert!(server.requests().is_empty());
+
+        // 声明支持图片输入后按多模态格式发送
+        let vision = server.client().with_capabilities(Capabilities {
+            vision: true,
+            ..Capabilities::default()
         });
+        let stream = vision.chat_stream(with_image).await.unwrap();
+        let _: Vec<_> = stream.collect().await;
+        assert_eq!(
+            server.requests()[0].body["messages"][0]["content"],
+            json!([
+                {"type": "text", "text": "图里是什么"},
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn request_id_header_is_sent_on_streaming_and_non_streaming_requests() {
+        let server = MockChatServer::start(vec![
+            MockResponse::text(&["流式"]),
+            MockResponse::completion("非流式"),
+            MockResponse::completion("没有请求 ID"),
+        ])
+        .await
+        .unwrap();
+        let client = server.client().with_request_id_header("x-trace-id");
+        let options = RequestOptions::default().with_request_id("req-42");
+        let messages = vec![ChatMessage::new("user", "你好")];
+
+        let stream = client
+            .chat_stream_with(messages.clone(), &options)
+            .await
+            .unwrap();
+        let _: Vec<_> = stream.collect().await;
+        assert_eq!(
+            client.chat_with(messages.clone(), &options).await.unwrap(),
+            "非流式"
+        );
+        client.chat(messages).await.unwrap();
 
-        Ok(stream)
+        let requests = server.requests();
+        assert_eq!(requests[0].body["stream"], true);
+        assert_eq!(requests[0].header("x-trace-id"), Some("req-42"));
+        assert_eq!(requests[1].body["stream"], false);
+        assert_eq!(requests[1].header("x-trace-id"), Some("req-42"));
+        assert_eq!(requests[2].header("x-trace-id"), None);
     }
 }
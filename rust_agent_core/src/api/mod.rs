@@ -1,5 +1,25 @@
+mod capabilities;
 mod client;
+mod roles;
+mod sse;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+mod tokens;
+mod tool_calls;
 mod types;
 
-pub use client::DeepseekClient;
-pub use types::{ChatMessage, ChatRequest, ChatResponse};
+pub use capabilities::{Capabilities, Feature, UnsupportedFeature};
+pub use client::{
+    DeepseekClient, RequestOptions, DEFAULT_BASE_URL, DEFAULT_MODEL, DEFAULT_TEMPERATURE,
+    KNOWN_MODELS,
+};
+pub use roles::{MappedMessages, RoleMapping};
+pub use tokens::{count_tokens, estimate_messages_tokens};
+pub use tool_calls::{
+    FunctionDefinition, FunctionDelta, InvalidToolArguments, NativeToolCall, ToolCallAccumulator,
+    ToolCallDelta, ToolDefinition,
+};
+pub use types::{
+    parse_json_content, ApiError, ChatMessage, ChatRequest, ChatResponse, InvalidJsonError,
+    ResponseFormat, StreamDelta, StreamOptions, Usage,
+};
@@ -0,0 +1,123 @@
+use super::types::ChatMessage;
+
+/// 消息角色的映射方式
+///
+/// 会话内部统一使用 `system`、`user`、`assistant`、`tool` 四种角色，
+/// 发送请求前按提供方的约定转换。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleMapping {
+    /// 原样发送，适用于 Deepseek 等 OpenAI 兼容接口
+    Passthrough,
+    /// 系统消息提升到请求顶层的 `system` 字段，适用于 Anthropic
+    ///
+    /// 对话中只保留 `user` 和 `assistant`：`tool` 角色的消息作为用户消息发送，
+    /// 相邻的同角色消息合并为一条，满足角色必须交替出现的要求。
+    SystemHoist,
+}
+
+/// 映射后的消息
+#[derive(Debug, Clone, Default)]
+pub struct MappedMessages {
+    /// 提升到顶层的系统提示，原样发送时为 None
+    pub system: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl RoleMapping {
+    /// 把会话中的消息转换为提供方接受的形式
+    pub fn map(&self, messages: Vec<ChatMessage>) -> MappedMessages {
+        match self {
+            Self::Passthrough => MappedMessages {
+                system: None,
+                messages,
+            },
+            Self::SystemHoist => hoist_system(messages),
+        }
+    }
+}
+
+fn hoist_system(messages: Vec<ChatMessage>) -> MappedMessages {
+    let mut system = Vec::new();
+    let mut mapped: Vec<ChatMessage> = Vec::new();
+
+    for mut message in messages {
+        match message.role.as_str() {
+            "system" => {
+                system.push(message.content);
+                continue;
+            }
+            "tool" => message.role = "user".to_string(),
+            _ => {}
+        }
+
+        match mapped.last_mut() {
+            Some(last) if last.role == message.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+                last.images.extend(message.images);
+            }
+            _ => mapped.push(message),
+        }
+    }
+
+    MappedMessages {
+        system: Some(system.join("\n\n")).filter(|s| !s.is_empty()),
+        messages: mapped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::new("system", "工具说明"),
+            ChatMessage::new("system", "摘要"),
+            ChatMessage::new("user", "现在几点"),
+            ChatMessage::new("assistant", "我查一下"),
+            ChatMessage::new("tool", "12:00"),
+            ChatMessage::new("user", "谢谢"),
+        ]
+    }
+
+    fn roles_and_contents(messages: &[ChatMessage]) -> Vec<(&str, &str)> {
+        messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn deepseek_passes_messages_through() {
+        let mapped = RoleMapping::Passthrough.map(conversation());
+
+        assert_eq!(mapped.system, None);
+        assert_eq!(
+            roles_and_contents(&mapped.messages),
+            roles_and_contents(&conversation())
+        );
+    }
+
+    #[test]
+    fn anthropic_hoists_system_messages_and_merges_roles() {
+        let mapped = RoleMapping::SystemHoist.map(conversation());
+
+        assert_eq!(mapped.system.as_deref(), Some("工具说明\n\n摘要"));
+        assert_eq!(
+            roles_and_contents(&mapped.messages),
+            [
+                ("user", "现在几点"),
+                ("assistant", "我查一下"),
+                ("user", "12:00\n\n谢谢"),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_system_messages_means_no_system_field() {
+        let mapped = RoleMapping::SystemHoist.map(vec![ChatMessage::new("user", "你好")]);
+        assert_eq!(mapped.system, None);
+        assert_eq!(mapped.messages.len(), 1);
+    }
+}
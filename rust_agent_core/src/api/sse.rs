@@ -0,0 +1,214 @@
+use anyhow::Result;
+use tracing::{debug, warn};
+
+use super::types::{ApiError, ChatStreamResponse, ErrorEnvelope, StreamDelta};
+
+/// 按帧解析流式响应（SSE）的字节流
+///
+/// 网络数据块的边界与帧的边界无关：一帧可能分散在多个数据块中，
+/// 多字节字符也可能被切开。因此先缓冲收到的字节，只解析以空行结束的完整帧。
+pub(crate) struct SseDecoder {
+    buffer: Vec<u8>,
+    /// 上一帧的数据，只在开启去重时记录
+    last_frame: Option<String>,
+}
+
+impl SseDecoder {
+    /// `dedup` 为 true 时丢弃与上一帧完全相同的帧
+    pub fn new(dedup: bool) -> Self {
+        Self {
+            buffer: Vec::new(),
+            last_frame: dedup.then(String::new),
+        }
+    }
+
+    /// 追加一个数据块，合并其中已经完整的帧的增量，不完整的部分留到下次
+    ///
+    /// 遇到错误帧时返回 [`ApiError::Stream`]，避免把中途出错的回复当成完整回答。
+    pub fn push(&mut self, chunk: &[u8]) -> Result<StreamDelta> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut delta = StreamDelta::default();
+        while let Some((end, separator)) = frame_end(&self.buffer) {
+            let frame: Vec<u8> = self.buffer.drain(..end + separator).take(end).collect();
+            self.decode_frame(&frame, &mut delta)?;
+        }
+        Ok(delta)
+    }
+
+    /// 流结束时解析缓冲中剩余的、没有以空行结束的最后一帧
+    pub fn finish(mut self) -> Result<StreamDelta> {
+        let frame = std::mem::take(&mut self.buffer);
+        let mut delta = StreamDelta::default();
+        if !frame.iter().all(u8::is_ascii_whitespace) {
+            self.decode_frame(&frame, &mut delta)?;
+        }
+        Ok(delta)
+    }
+
+    /// 解析一帧，把增量合并到 `delta`
+    ///
+    /// 一帧中的多行 `data:` 按 SSE 规范以换行拼接；没有数据的帧（如注释）被忽略。
+    fn decode_frame(&mut self, frame: &[u8], delta: &mut StreamDelta) -> Result<()> {
+        let text = std::str::from_utf8(frame)?;
+        let lines: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        let data = lines.join("\n");
+        let data = data.trim();
+        if data == "[DONE]" {
+            return Ok(());
+        }
+
+        if let Some(last) = self.last_frame.as_mut() {
+            if last == data {
+                debug!("丢弃重复的流式帧: {}", data);
+                return Ok(());
+            }
+            data.clone_into(last);
+        }
+
+        // 正常帧的字段都有默认值，错误帧也能按正常帧解析，因此先检查是否为错误帧
+        if let Ok(envelope) = serde_json::from_str::<ErrorEnvelope>(data) {
+            let error = ApiError::from(envelope.error);
+            warn!("{}", error);
+            return Err(error.into());
+        }
+
+        if let Ok(stream_response) = serde_json::from_str::<ChatStreamResponse>(data) {
+            if let Some(choice) = stream_response.choices.first() {
+                delta.content.push_str(&choice.delta.content);
+                if let Some(reasoning) = &choice.delta.reasoning_content {
+                    delta.reasoning_content.push_str(reasoning);
+                }
+                // 函数调用的参数片段原样保留，由调用方在回复结束后拼接
+                delta
+                    .tool_calls
+                    .extend(choice.delta.tool_calls.iter().cloned());
+                // 命中停止序列时 finish_reason 为 "stop"，达到 max_tokens 时为 "length"，流随后结束
+                if let Some(reason) = &choice.finish_reason {
+                    debug!("流式响应结束，finish_reason = {}", reason);
+                    delta.finish_reason = Some(reason.clone());
+                }
+            }
+            if let Some(usage) = stream_response.usage {
+                debug!("本次请求的 token 用量: {:?}", usage);
+                delta.usage = Some(usage);
+            }
+        } else {
+            debug!("忽略无法解析的流式帧: {}", data);
+        }
+
+        Ok(())
+    }
+}
+
+/// 查找第一个帧结束的空行，返回帧内容的长度和空行分隔符的长度
+fn frame_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    (0..buffer.len()).find_map(|i| {
+        if buffer[i..].starts_with(b"\n\n") {
+            Some((i, 2))
+        } else if buffer[i..].starts_with(b"\r\n\r\n") {
+            Some((i, 4))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn content_frame(content: &str) -> String {
+        format!(
+            "data: {}\n\n",
+            json!({"choices": [{"index": 0, "delta": {"content": content}}]})
+        )
+    }
+
+    #[test]
+    fn frames_split_across_chunks_are_buffered() {
+        let stream = format!("{}{}", content_frame("你好"), content_frame("世界"));
+        let bytes = stream.as_bytes();
+
+        // 在每个字节处切开，包括多字节字符的中间
+        for split in 1..bytes.len() {
+            let mut decoder = SseDecoder::new(false);
+            let mut content = decoder.push(&bytes[..split]).unwrap().content;
+            content.push_str(&decoder.push(&bytes[split..]).unwrap().content);
+            content.push_str(&decoder.finish().unwrap().content);
+            assert_eq!(content, "你好世界", "在第 {} 字节切开", split);
+        }
+    }
+
+    #[test]
+    fn final_frame_without_blank_line_is_parsed_on_finish() {
+        let mut decoder = SseDecoder::new(false);
+        let frame = content_frame("结尾");
+        let delta = decoder.push(frame.trim_end().as_bytes()).unwrap();
+        assert!(delta.content.is_empty());
+        assert_eq!(decoder.finish().unwrap().content, "结尾");
+    }
+
+    #[test]
+    fn error_frame_at_the_end_is_a_typed_error() {
+        let mut decoder = SseDecoder::new(false);
+        let frame = "data: {\"error\": {\"message\": \"服务繁忙\", \"type\": \"server_error\", \"code\": 503}}\r\n\r\n";
+        assert_eq!(
+            decoder
+                .push(content_frame("部分").as_bytes())
+                .unwrap()
+                .content,
+            "部分"
+        );
+
+        let (head, tail) = frame.as_bytes().split_at(20);
+        assert!(decoder.push(head).is_ok());
+        let error = decoder.push(tail).unwrap_err();
+        match error.downcast_ref::<ApiError>() {
+            Some(ApiError::Stream {
+                message,
+                error_type,
+                code,
+            }) => {
+                assert_eq!(message, "服务繁忙");
+                assert_eq!(error_type.as_deref(), Some("server_error"));
+                assert_eq!(code.as_deref(), Some("503"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn usage_frame_without_choices_is_reported() {
+        let mut decoder = SseDecoder::new(false);
+        let usage = format!(
+            "data: {}\n\n",
+            json!({
+                "choices": [],
+                "usage": {
+                    "prompt_tokens": 12,
+                    "completion_tokens": 4,
+                    "total_tokens": 16,
+                    "prompt_cache_hit_tokens": 8,
+                    "prompt_cache_miss_tokens": 4
+                }
+            })
+        );
+        let stream = format!("{}{}data: [DONE]\n\n", content_frame("好"), usage);
+
+        let delta = decoder.push(stream.as_bytes()).unwrap();
+        assert_eq!(delta.content, "好");
+        let usage = delta.usage.unwrap();
+        assert_eq!(usage.total_tokens, 16);
+        assert_eq!(usage.prompt_cache_hit_tokens, 8);
+    }
+}
@@ -0,0 +1,248 @@
+//! 进程内模拟的 Deepseek 接口，便于测试模型请求的完整 HTTP 路径
+//!
+//! 需要启用 `test-util` 特性。
+
+use anyhow::Result;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use super::client::DeepseekClient;
+
+/// 分段写出流式响应时，两段之间的间隔，让每段尽量作为单独的数据块到达
+const SEGMENT_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 服务收到的一个请求
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// 请求头，名称统一为小写
+    pub headers: HashMap<String, String>,
+    /// 请求体，不是 JSON 时为 `Value::Null`
+    pub body: serde_json::Value,
+}
+
+impl RecordedRequest {
+    /// 按名称（不区分大小写）取请求头
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// 预设的响应，按请求到达的顺序依次返回
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// `text/event-stream` 响应，每个元素单独写出，可以在任意位置切分帧
+    Stream(Vec<Vec<u8>>),
+    /// `application/json` 响应
+    Json(serde_json::Value),
+    /// 指定状态码和响应体的响应
+    Status(u16, String),
+}
+
+impl MockResponse {
+    /// 逐段流式返回文本回答，最后一帧带 `finish_reason: "stop"`
+    pub fn text(pieces: &[&str]) -> Self {
+        let mut frames: Vec<_> = pieces
+            .iter()
+            .map(|piece| json!({"choices": [{"index": 0, "delta": {"content": piece}}]}))
+            .collect();
+        frames.push(json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}));
+        Self::frames(frames)
+    }
+
+    /// 把每个 JSON 作为一帧流式返回，最后发送 `[DONE]`
+    pub fn frames(frames: Vec<serde_json::Value>) -> Self {
+        let mut segments: Vec<_> = frames
+            .iter()
+            .map(|frame| sse_frame(&frame.to_string()).into_bytes())
+            .collect();
+        segments.push(sse_frame("[DONE]").into_bytes());
+        Self::Stream(segments)
+    }
+
+    /// 非流式的完整回答
+    pub fn completion(content: &str) -> Self {
+        Self::Json(json!({
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content},
+                "finish_reason": "stop"
+            }]
+        }))
+    }
+}
+
+/// 一个 SSE 帧：`data: ...` 加上空行
+pub fn sse_frame(data: &str) -> String {
+    format!("data: {}\n\n", data)
+}
+
+/// 运行在当前进程中的模拟接口
+///
+/// 预设的响应用完后返回 500。丢弃时服务随之停止。
+pub struct MockChatServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockChatServer {
+    /// 在本机的随机端口上启动服务
+    pub async fn start(responses: Vec<MockResponse>) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responses = Arc::new(Mutex::new(VecDeque::from(responses)));
+
+        let recorded = requests.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let responses = responses.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(socket, &recorded, &responses).await {
+                        tracing::warn!("模拟接口处理请求失败: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            requests,
+            handle,
+        })
+    }
+
+    /// 服务地址，形如 `http://127.0.0.1:port`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// 创建使用该服务的客户端
+    pub fn client(&self) -> DeepseekClient {
+        DeepseekClient::new("test-key".to_string()).with_base_url(self.url())
+    }
+
+    /// 已收到的请求
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockChatServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// 读取一个请求并写出下一个预设响应，之后关闭连接
+async fn serve(
+    mut socket: TcpStream,
+    recorded: &Mutex<Vec<RecordedRequest>>,
+    responses: &Mutex<VecDeque<MockResponse>>,
+) -> std::io::Result<()> {
+    let Some(request) = read_request(&mut socket).await? else {
+        return Ok(());
+    };
+    recorded.lock().unwrap().push(request);
+
+    let response = responses.lock().unwrap().pop_front();
+    match response {
+        Some(MockResponse::Stream(segments)) => {
+            write_head(&mut socket, 200, "text/event-stream", None).await?;
+            for segment in segments {
+                socket.write_all(&segment).await?;
+                socket.flush().await?;
+                tokio::time::sleep(SEGMENT_INTERVAL).await;
+            }
+        }
+        Some(MockResponse::Json(body)) => {
+            let body = body.to_string();
+            write_head(&mut socket, 200, "application/json", Some(body.len())).await?;
+            socket.write_all(body.as_bytes()).await?;
+        }
+        Some(MockResponse::Status(status, body)) => {
+            write_head(&mut socket, status, "application/json", Some(body.len())).await?;
+            socket.write_all(body.as_bytes()).await?;
+        }
+        None => {
+            let body = json!({"error": {"message": "没有预设的响应", "type": "mock"}}).to_string();
+            write_head(&mut socket, 500, "application/json", Some(body.len())).await?;
+            socket.write_all(body.as_bytes()).await?;
+        }
+    }
+    socket.shutdown().await
+}
+
+async fn read_request(socket: &mut TcpStream) -> std::io::Result<Option<RecordedRequest>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .get("content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    let body_start = head_end + 4;
+    while buf.len() < body_start + content_length {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = serde_json::from_slice(&buf[body_start..]).unwrap_or(serde_json::Value::Null);
+
+    Ok(Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+async fn write_head(
+    socket: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    content_length: Option<usize>,
+) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nConnection: close\r\n",
+        status, content_type
+    );
+    if let Some(length) = content_length {
+        head.push_str(&format!("Content-Length: {}\r\n", length));
+    }
+    head.push_str("\r\n");
+    socket.write_all(head.as_bytes()).await
+}
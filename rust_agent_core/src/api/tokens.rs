@@ -0,0 +1,86 @@
+//! token 数量估算
+//!
+//! 启用 `tokenizer` 特性时使用 tiktoken 的 cl100k_base 分词器计数，
+//! 否则按每 4 个字符约 1 个 token 粗略估算。
+
+use super::types::ChatMessage;
+
+/// 每条消息除内容外的固定开销（角色标记等）
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+#[cfg(feature = "tokenizer")]
+fn tokenizer() -> Option<&'static tiktoken_rs::CoreBPE> {
+    use std::sync::OnceLock;
+
+    // 分词器初始化开销较大，只加载一次
+    static TOKENIZER: OnceLock<Option<tiktoken_rs::CoreBPE>> = OnceLock::new();
+    TOKENIZER
+        .get_or_init(|| match tiktoken_rs::cl100k_base() {
+            Ok(bpe) => Some(bpe),
+            Err(e) => {
+                tracing::warn!("分词器加载失败，回退到字符估算: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// 按字符数估算 token 数量
+fn heuristic_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// 计算文本的 token 数量
+pub fn count_tokens(text: &str) -> usize {
+    #[cfg(feature = "tokenizer")]
+    if let Some(bpe) = tokenizer() {
+        return bpe.encode_with_special_tokens(text).len();
+    }
+
+    heuristic_tokens(text)
+}
+
+/// 估算一组消息的 token 总数
+pub fn estimate_messages_tokens(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(ChatMessage::estimated_tokens).sum()
+}
+
+impl ChatMessage {
+    /// 估算这条消息占用的 token 数量
+    pub fn estimated_tokens(&self) -> usize {
+        count_tokens(&self.content) + MESSAGE_OVERHEAD_TOKENS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "tokenizer")]
+    #[test]
+    fn tokenizer_counts_known_strings() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("hello world"), 2);
+        assert_eq!(count_tokens("tiktoken is great!"), 6);
+    }
+
+    #[cfg(not(feature = "tokenizer"))]
+    #[test]
+    fn heuristic_counts_four_chars_per_token() {
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("hello world"), 3);
+        assert_eq!(count_tokens("你好"), 1);
+    }
+
+    #[test]
+    fn messages_include_fixed_overhead() {
+        let messages = vec![
+            ChatMessage::new("user", "hello world"),
+            ChatMessage::new("assistant", ""),
+        ];
+        assert_eq!(
+            estimate_messages_tokens(&messages),
+            count_tokens("hello world") + 2 * MESSAGE_OVERHEAD_TOKENS
+        );
+    }
+}
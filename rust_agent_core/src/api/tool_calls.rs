@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 请求中声明的可调用函数，按 OpenAI 的 `tools` 格式序列化
+///
+/// 只有支持原生函数调用的模型（[`Feature::NativeTools`](super::Feature::NativeTools)）才接受这个字段。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolDefinition {
+    /// 固定为 `"function"`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+/// 函数的名称、说明和参数的 JSON Schema
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+}
+
+impl ToolDefinition {
+    /// 声明一个函数，`parameters` 为参数的 JSON Schema
+    pub fn function(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: Option<serde_json::Value>,
+    ) -> Self {
+        Self {
+            kind: "function".to_string(),
+            function: FunctionDefinition {
+                name: name.into(),
+                description: description.into(),
+                parameters,
+            },
+        }
+    }
+}
+
+/// 原生函数调用的一段增量
+///
+/// 流式输出时，一次调用的名称和参数会拆成多段，按 `index` 区分属于哪一次调用；
+/// 非流式响应中每次调用只有一段，参数是完整的 JSON 字符串。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolCallDelta {
+    /// 调用在本次回复中的序号
+    #[serde(default)]
+    pub index: usize,
+    /// 调用 ID，只在第一段中出现
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub function: Option<FunctionDelta>,
+}
+
+/// 函数名称和参数的片段
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FunctionDelta {
+    /// 函数名称，只在第一段中出现
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 参数 JSON 的一个片段，拼接后才是完整的 JSON
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+/// 拼接完成的原生函数调用
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeToolCall {
+    pub id: Option<String>,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// 拼接后的参数不是合法 JSON
+#[derive(Debug)]
+pub struct InvalidToolArguments {
+    /// 函数名称
+    pub name: String,
+    /// 拼接后的原始参数
+    pub arguments: String,
+    /// 解析错误
+    pub source: serde_json::Error,
+}
+
+impl fmt::Display for InvalidToolArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "函数调用 `{}` 的参数不是合法 JSON: {}",
+            self.name, self.source
+        )
+    }
+}
+
+impl std::error::Error for InvalidToolArguments {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// 还没拼接完成的调用
+#[derive(Debug, Default)]
+struct PartialCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// 在流式输出的各段增量之间拼接原生函数调用
+///
+/// 参数片段本身不是完整的 JSON，只有在回复结束（`finish_reason` 为 `"tool_calls"`）
+/// 后调用 [`finish`](Self::finish) 时才解析。
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: BTreeMap<usize, PartialCall>,
+}
+
+impl ToolCallAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一段增量中的函数调用片段
+    pub fn push(&mut self, deltas: &[ToolCallDelta]) {
+        for delta in deltas {
+            let call = self.calls.entry(delta.index).or_default();
+            if let Some(id) = &delta.id {
+                call.id = Some(id.clone());
+            }
+            if let Some(function) = &delta.function {
+                if let Some(name) = &function.name {
+                    call.name.push_str(name);
+                }
+                if let Some(arguments) = &function.arguments {
+                    call.arguments.push_str(arguments);
+                }
+            }
+        }
+    }
+
+    /// 是否收到过函数调用片段
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// 按序号解析所有拼接完成的调用，参数为空时视为没有参数
+    pub fn finish(self) -> Vec<Result<NativeToolCall, InvalidToolArguments>> {
+        self.calls
+            .into_values()
+            .map(|call| {
+                let arguments = if call.arguments.trim().is_empty() {
+                    Ok(serde_json::Value::Object(serde_json::Map::new()))
+                } else {
+                    serde_json::from_str(&call.arguments)
+                };
+                match arguments {
+                    Ok(arguments) => Ok(NativeToolCall {
+                        id: call.id,
+                        name: call.name,
+                        arguments,
+                    }),
+                    Err(source) => Err(InvalidToolArguments {
+                        name: call.name,
+                        arguments: call.arguments,
+                        source,
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 一段只包含参数片段的增量
+    fn fragment(index: usize, arguments: &str) -> ToolCallDelta {
+        ToolCallDelta {
+            index,
+            id: None,
+            function: Some(FunctionDelta {
+                name: None,
+                arguments: Some(arguments.to_string()),
+            }),
+        }
+    }
+
+    /// 一次调用的第一段，带 ID 和名称
+    fn start(index: usize, id: &str, name: &str) -> ToolCallDelta {
+        ToolCallDelta {
+            index,
+            id: Some(id.to_string()),
+            function: Some(FunctionDelta {
+                name: Some(name.to_string()),
+                arguments: Some(String::new()),
+            }),
+        }
+    }
+
+    #[test]
+    fn arguments_split_across_deltas_are_reassembled() {
+        let mut accumulator = ToolCallAccumulator::new();
+        assert!(accumulator.is_empty());
+
+        // 两次调用的片段交错到达，参数在字符串、键名和数字中间被切开
+        accumulator.push(&[start(0, "call_1", "read_file")]);
+        accumulator.push(&[fragment(0, r#"{"pa"#)]);
+        accumulator.push(&[fragment(0, r#"th": "/tmp/a "#), start(1, "call_2", "add")]);
+        accumulator.push(&[fragment(1, r#"{"a": 1"#), fragment(0, r#"b.txt"}"#)]);
+        accumulator.push(&[fragment(1, r#"2, "b": 3}"#)]);
+        assert!(!accumulator.is_empty());
+
+        let calls: Vec<_> = accumulator
+            .finish()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                NativeToolCall {
+                    id: Some("call_1".to_string()),
+                    name: "read_file".to_string(),
+                    arguments: json!({"path": "/tmp/a b.txt"}),
+                },
+                NativeToolCall {
+                    id: Some("call_2".to_string()),
+                    name: "add".to_string(),
+                    arguments: json!({"a": 12, "b": 3}),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_arguments_are_an_empty_object_and_broken_json_is_an_error() {
+        let mut accumulator = ToolCallAccumulator::new();
+        accumulator.push(&[start(0, "call_1", "list_tools")]);
+        accumulator.push(&[start(1, "call_2", "add"), fragment(1, r#"{"a": 1"#)]);
+
+        let mut calls = accumulator.finish().into_iter();
+        assert_eq!(calls.next().unwrap().unwrap().arguments, json!({}));
+        let error = calls.next().unwrap().unwrap_err();
+        assert_eq!(error.name, "add");
+        assert_eq!(error.arguments, r#"{"a": 1"#);
+        assert!(error.to_string().contains("add"), "{}", error);
+    }
+}
@@ -1,32 +1,346 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use time::OffsetDateTime;
 
-#[derive(Debug, Serialize, Clone)]
+use super::tool_calls::{ToolCallDelta, ToolDefinition};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
+    /// 消息创建时间（UTC），只用于保存和导出，不会发送给 API
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timestamp: Option<OffsetDateTime>,
+    /// 随消息发送的图片（URL 或 data URI），需要模型支持图片输入
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+}
+
+impl ChatMessage {
+    /// 创建消息并记录当前时间
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+            timestamp: Some(OffsetDateTime::now_utc()),
+            images: Vec::new(),
+        }
+    }
+
+    /// 附加一张图片
+    pub fn with_image(mut self, url: impl Into<String>) -> Self {
+        self.images.push(url.into());
+        self
+    }
+}
+
+/// 发送给 API 的消息，只包含 API 支持的字段
+#[derive(Serialize)]
+struct ApiMessage<'a> {
+    role: &'a str,
+    content: ApiContent<'a>,
+}
+
+/// 消息内容：纯文本，或带图片时按 OpenAI 的多模态格式拆成多个部分
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ApiContent<'a> {
+    Text(&'a str),
+    Parts(Vec<ContentPart<'a>>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: ImageUrl<'a> },
+}
+
+#[derive(Serialize)]
+struct ImageUrl<'a> {
+    url: &'a str,
+}
+
+fn serialize_api_messages<S>(messages: &[ChatMessage], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(messages.iter().map(|m| {
+        let content = if m.images.is_empty() {
+            ApiContent::Text(&m.content)
+        } else {
+            let text = ContentPart::Text { text: &m.content };
+            let images = m.images.iter().map(|url| ContentPart::ImageUrl {
+                image_url: ImageUrl { url },
+            });
+            ApiContent::Parts(std::iter::once(text).chain(images).collect())
+        };
+        ApiMessage {
+            role: &m.role,
+            content,
+        }
+    }))
 }
 
 #[derive(Debug, Serialize)]
 pub struct ChatRequest {
     pub model: String,
+    #[serde(serialize_with = "serialize_api_messages")]
     pub messages: Vec<ChatMessage>,
     pub temperature: f32,
+    /// 核采样概率，取值范围 [0, 1]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// 生成的候选回复数量，未设置时为 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
     pub stream: bool,
+    /// 停止序列，生成内容遇到其中任意一个时结束（Deepseek 最多支持 16 个）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// 随机种子，用于尽量复现相同的生成结果（依赖服务端支持，不保证完全一致）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// 输出格式，设置为 json_object 时要求模型只返回合法 JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// 流式输出的选项，用于在最后一帧中返回 token 用量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    /// 原生函数调用模式下声明的可调用函数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolDefinition>>,
+    /// 附加到请求体顶层的其他字段，用于特定后端支持的参数
+    ///
+    /// 不参与结构体本身的序列化，由 [`body`](Self::body) 合并到请求体中。
+    #[serde(skip)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ChatRequest {
+    /// 发送给 API 的请求体
+    ///
+    /// `extra` 中的字段合并到顶层；与已有字段同名时覆盖原来的值，请求体中不会出现重复的键。
+    pub fn body(&self) -> serde_json::Value {
+        let mut body = serde_json::to_value(self).unwrap_or_default();
+        if let serde_json::Value::Object(fields) = &mut body {
+            fields.extend(self.extra.clone());
+        }
+        body
+    }
+}
+
+/// 响应格式
+#[derive(Debug, Serialize, Clone)]
+pub struct ResponseFormat {
+    #[serde(rename = "type")]
+    pub format_type: String,
+}
+
+impl ResponseFormat {
+    /// JSON 模式：`{"type": "json_object"}`
+    pub fn json_object() -> Self {
+        Self {
+            format_type: "json_object".to_string(),
+        }
+    }
+}
+
+/// `GET /models` 返回的模型列表
+#[derive(Debug, Deserialize)]
+pub struct ModelList {
+    pub data: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+}
+
+/// 流式输出的选项
+#[derive(Debug, Serialize, Clone)]
+pub struct StreamOptions {
+    /// 在流结束前额外发送一帧 token 用量
+    pub include_usage: bool,
+}
+
+/// 服务端返回的 token 用量
+///
+/// Deepseek 会缓存请求之间相同的消息前缀，命中缓存的提示 token 单独计数且价格更低。
+/// 不支持缓存的服务不返回缓存字段，此时两者都为 0。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: usize,
+    #[serde(default)]
+    pub completion_tokens: usize,
+    #[serde(default)]
+    pub total_tokens: usize,
+    /// 命中上下文缓存的提示 token 数
+    #[serde(default)]
+    pub prompt_cache_hit_tokens: usize,
+    /// 未命中上下文缓存的提示 token 数
+    #[serde(default)]
+    pub prompt_cache_miss_tokens: usize,
+}
+
+impl Usage {
+    /// 累加另一次请求的用量
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.prompt_cache_hit_tokens += other.prompt_cache_hit_tokens;
+        self.prompt_cache_miss_tokens += other.prompt_cache_miss_tokens;
+    }
+
+    /// 提示 token 中命中缓存的比例，服务端没有返回缓存信息时为 None
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let cached = self.prompt_cache_hit_tokens + self.prompt_cache_miss_tokens;
+        (cached > 0).then(|| self.prompt_cache_hit_tokens as f64 / cached as f64)
+    }
+}
+
+/// JSON 模式下模型返回的内容无法解析为 JSON
+#[derive(Debug)]
+pub struct InvalidJsonError {
+    /// 模型返回的原始内容
+    pub content: String,
+    /// 解析错误
+    pub source: serde_json::Error,
+}
+
+impl fmt::Display for InvalidJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JSON 模式下返回的内容不是合法 JSON: {}", self.source)
+    }
+}
+
+impl std::error::Error for InvalidJsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// 校验内容是否为合法 JSON
+pub fn parse_json_content(content: &str) -> Result<serde_json::Value, InvalidJsonError> {
+    serde_json::from_str(content).map_err(|source| InvalidJsonError {
+        content: content.to_string(),
+        source,
+    })
+}
+
+/// API 调用过程中返回的错误
+#[derive(Debug)]
+pub enum ApiError {
+    /// 流式响应中途返回的错误帧（`data: {"error": ...}`）
+    Stream {
+        message: String,
+        error_type: Option<String>,
+        code: Option<String>,
+    },
+    /// 响应中没有任何结果（例如内容被过滤时 `choices` 为空）
+    NoChoices,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stream {
+                message,
+                error_type,
+                code,
+            } => {
+                write!(f, "流式响应中返回错误: {}", message)?;
+                if let Some(error_type) = error_type {
+                    write!(f, "（类型: {}）", error_type)?;
+                }
+                if let Some(code) = code {
+                    write!(f, "（错误码: {}）", code)?;
+                }
+                Ok(())
+            }
+            Self::NoChoices => write!(f, "模型没有返回任何结果（choices 为空）"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// 错误帧：`{"error": {"message": ..., "type": ..., "code": ...}}`
+#[derive(Debug, Deserialize)]
+pub struct ErrorEnvelope {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ErrorBody {
+    #[serde(default)]
+    pub message: String,
+    #[serde(default, rename = "type")]
+    pub error_type: Option<String>,
+    /// 不同服务返回的错误码可能是字符串或数字
+    #[serde(default)]
+    pub code: Option<serde_json::Value>,
+}
+
+impl From<ErrorBody> for ApiError {
+    fn from(body: ErrorBody) -> Self {
+        Self::Stream {
+            message: body.message,
+            error_type: body.error_type,
+            code: body.code.map(|code| match code {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ChatResponse {
     pub choices: Vec<ResponseChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+impl ChatResponse {
+    /// 按 `index` 排序后取出所有候选回复的内容，没有候选时返回 [`ApiError::NoChoices`]
+    pub fn into_contents(self) -> Result<Vec<String>, ApiError> {
+        if self.choices.is_empty() {
+            return Err(ApiError::NoChoices);
+        }
+
+        let mut choices = self.choices;
+        choices.sort_by_key(|choice| choice.index);
+        Ok(choices
+            .into_iter()
+            .map(|choice| choice.message.content)
+            .collect())
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ResponseChoice {
+    /// 候选在请求的 `n` 个结果中的序号
+    #[serde(default)]
+    pub index: u32,
     pub message: Message,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ChatStreamResponse {
+    #[serde(default)]
     pub choices: Vec<StreamChoice>,
+    /// 只在携带用量的最后一帧中出现
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +353,116 @@ pub struct StreamChoice {
 pub struct Message {
     #[serde(default)]
     pub role: Option<String>,
+    /// 推理模型在输出推理过程时、或只有函数调用时内容为 null
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub content: String,
+    /// 推理过程（仅 deepseek-reasoner 返回）
     #[serde(default)]
+    pub reasoning_content: Option<String>,
+    /// 原生函数调用，流式输出时是需要拼接的片段
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+/// 把 null 当作空值处理
+fn null_as_empty<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// 流式响应中的一段增量
+#[derive(Debug, Default, Clone)]
+pub struct StreamDelta {
+    /// 最终回答内容
     pub content: String,
+    /// 推理过程，普通对话模型始终为空
+    pub reasoning_content: String,
+    /// 回复结束的原因，只在最后一段增量中出现
+    pub finish_reason: Option<String>,
+    /// 本次请求的 token 用量，只在流结束前的一段增量中出现
+    pub usage: Option<Usage>,
+    /// 原生函数调用的片段，需要用 [`ToolCallAccumulator`](super::ToolCallAccumulator) 拼接
+    pub tool_calls: Vec<ToolCallDelta>,
+}
+
+impl StreamDelta {
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty() && self.reasoning_content.is_empty()
+    }
+
+    /// 回复是否因达到 `max_tokens` 而被截断
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason.as_deref() == Some("length")
+    }
+
+    /// 回复是否以原生函数调用结束，此时函数调用的参数已经全部发送
+    pub fn is_tool_calls(&self) -> bool {
+        self.finish_reason.as_deref() == Some("tool_calls")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasoner_stream_chunk_carries_reasoning_content() {
+        let chunk = r#"{
+            "id": "1",
+            "object": "chat.completion.chunk",
+            "model": "deepseek-reasoner",
+            "choices": [{
+                "index": 0,
+                "delta": {"role": "assistant", "content": null, "reasoning_content": "先想一想"},
+                "finish_reason": null
+            }]
+        }"#;
+
+        let response: ChatStreamResponse = serde_json::from_str(chunk).unwrap();
+        let delta = &response.choices[0].delta;
+        assert_eq!(delta.reasoning_content.as_deref(), Some("先想一想"));
+        assert_eq!(delta.content, "");
+        assert!(response.choices[0].finish_reason.is_none());
+    }
+
+    #[test]
+    fn usage_block_carries_cache_hit_tokens() {
+        // 流式输出的最后一帧没有 choices，只有用量
+        let chunk = r#"{
+            "id": "1",
+            "object": "chat.completion.chunk",
+            "model": "deepseek-chat",
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 1200,
+                "completion_tokens": 80,
+                "total_tokens": 1280,
+                "prompt_cache_hit_tokens": 900,
+                "prompt_cache_miss_tokens": 300
+            }
+        }"#;
+
+        let response: ChatStreamResponse = serde_json::from_str(chunk).unwrap();
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 1200);
+        assert_eq!(usage.prompt_cache_hit_tokens, 900);
+        assert_eq!(usage.prompt_cache_miss_tokens, 300);
+        assert_eq!(usage.cache_hit_ratio(), Some(0.75));
+
+        let mut total = usage;
+        total.add(&usage);
+        assert_eq!(total.total_tokens, 2560);
+        assert_eq!(total.prompt_cache_hit_tokens, 1800);
+
+        // 不支持缓存的服务不返回缓存字段
+        let usage: Usage = serde_json::from_str(
+            r#"{"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}"#,
+        )
+        .unwrap();
+        assert_eq!(usage.prompt_cache_hit_tokens, 0);
+        assert_eq!(usage.cache_hit_ratio(), None);
+    }
 }
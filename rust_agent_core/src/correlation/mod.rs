@@ -0,0 +1,3 @@
+mod request_id;
+
+pub use request_id::{new_request_id, DEFAULT_REQUEST_ID_HEADER, REQUEST_ID_METADATA};
@@ -0,0 +1,24 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 模型 API 请求中携带请求 ID 的默认请求头
+pub const DEFAULT_REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// 工具调用的 Flight 元数据中携带请求 ID 的键（gRPC 元数据的键必须是小写）
+pub const REQUEST_ID_METADATA: &str = "x-request-id";
+
+/// 同一毫秒内生成的 ID 依靠计数器区分
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// 生成一个新的请求 ID，用于关联同一轮对话中的模型请求、工具调用和日志
+///
+/// 由当前时间、进程 ID 和进程内计数器组成，在同一进程内不会重复，
+/// 不同进程间重复的概率也很低，但不具备加密意义上的随机性。
+pub fn new_request_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}-{:04x}", millis, std::process::id(), count)
+}
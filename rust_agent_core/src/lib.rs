@@ -1,9 +1,13 @@
 pub mod api;
+pub mod correlation;
 pub mod logging;
+pub mod paths;
+pub mod text;
 pub mod tools;
 
 pub use api::{ChatMessage, ChatRequest, ChatResponse};
 pub use logging::{init_logger, LoggerConfig};
+pub use paths::AgentDirs;
 pub use tools::rpc::client::ToolsClient;
 pub use tools::rpc::server::ToolsFlightService;
 pub use tools::{Tool, ToolParameters, ToolResult};
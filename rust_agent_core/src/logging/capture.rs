@@ -0,0 +1,191 @@
+//! 按请求捕获日志
+//!
+//! 在 `LogCapture` 的 span 内产生的日志事件会被 `LogCaptureLayer` 额外记录到
+//! 该次捕获的缓冲区中，用于把工具执行时的日志随结果返回给客户端。
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// 捕获用 span 的名称
+const CAPTURE_SPAN: &str = "log_capture";
+/// 捕获用 span 中记录捕获编号的字段
+const CAPTURE_FIELD: &str = "capture_id";
+
+/// 一次捕获的缓冲区，只保留最新的若干行
+struct Buffer {
+    lines: VecDeque<String>,
+    max_lines: usize,
+}
+
+type Buffers = Mutex<HashMap<u64, Buffer>>;
+
+/// 进行中的捕获，按编号索引
+fn buffers() -> &'static Buffers {
+    static BUFFERS: OnceLock<Buffers> = OnceLock::new();
+    BUFFERS.get_or_init(Default::default)
+}
+
+/// 一次日志捕获
+///
+/// 在 `span()` 内执行的代码产生的日志会被记录下来，调用 `finish` 取出。
+/// 需要在日志系统中注册 `LogCaptureLayer`（`init_logger` 已默认注册）。
+/// 放到阻塞线程中执行的代码不在该 span 内，其日志不会被捕获。
+pub struct LogCapture {
+    id: u64,
+    span: Span,
+}
+
+impl LogCapture {
+    /// 开始捕获，最多保留最新的 `max_lines` 行
+    pub fn start(max_lines: usize) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        if let Ok(mut buffers) = buffers().lock() {
+            buffers.insert(
+                id,
+                Buffer {
+                    lines: VecDeque::new(),
+                    max_lines,
+                },
+            );
+        }
+
+        let span = tracing::info_span!(CAPTURE_SPAN, capture_id = id);
+        Self { id, span }
+    }
+
+    /// 捕获日志的 span
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+
+    /// 结束捕获并返回记录的日志行
+    pub fn finish(self) -> Vec<String> {
+        buffers()
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.remove(&self.id))
+            .map(|buffer| buffer.lines.into())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        // 未调用 finish 时也要释放缓冲区
+        if let Ok(mut buffers) = buffers().lock() {
+            buffers.remove(&self.id);
+        }
+    }
+}
+
+/// 记录在捕获 span 扩展中的捕获编号
+struct CaptureId(u64);
+
+/// 把捕获 span 内的日志事件写入对应缓冲区的日志层
+#[derive(Debug, Default)]
+pub struct LogCaptureLayer;
+
+impl LogCaptureLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for LogCaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != CAPTURE_SPAN {
+            return;
+        }
+
+        let mut visitor = CaptureIdVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(capture_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(CaptureId(capture_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        let Some(capture_id) = scope
+            .into_iter()
+            .find_map(|span| span.extensions().get::<CaptureId>().map(|id| id.0))
+        else {
+            return;
+        };
+
+        let Ok(mut buffers) = buffers().lock() else {
+            return;
+        };
+        let Some(buffer) = buffers.get_mut(&capture_id) else {
+            return;
+        };
+
+        let metadata = event.metadata();
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let line = format!(
+            "{} {}: {}",
+            metadata.level(),
+            metadata.target(),
+            visitor.line
+        );
+
+        if buffer.lines.len() >= buffer.max_lines {
+            buffer.lines.pop_front();
+        }
+        if buffer.max_lines > 0 {
+            buffer.lines.push_back(line);
+        }
+    }
+}
+
+/// 读取捕获编号字段
+struct CaptureIdVisitor(Option<u64>);
+
+impl Visit for CaptureIdVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == CAPTURE_FIELD {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// 把事件格式化为一行文本：消息在前，其余字段以 `key=value` 附在后面
+#[derive(Default)]
+struct LineVisitor {
+    line: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.line.insert_str(0, value);
+        } else {
+            let _ = write!(self.line, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.line.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.line, " {}={:?}", field.name(), value);
+        }
+    }
+}
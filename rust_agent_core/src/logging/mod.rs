@@ -1,3 +1,5 @@
+mod capture;
 mod setup;
 
-pub use setup::{init_logger, LoggerConfig};
+pub use capture::{LogCapture, LogCaptureLayer};
+pub use setup::{flush_logs, init_logger, LogGuard, LoggerConfig};
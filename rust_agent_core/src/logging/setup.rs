@@ -5,10 +5,16 @@
 //! - 日志初始化
 //! - 日志文件滚动
 //! - 本地时间支持
+//! - 按请求捕获日志
+//! - 退出前刷新缓冲的日志
 
 use std::path::Path;
-use tracing::{error, info, warn, Level};
+use std::sync::Mutex;
+use tracing::{info, Level};
+use tracing_appender::non_blocking::WorkerGuard;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+use super::LogCaptureLayer;
 use tracing_subscriber::{
     fmt, fmt::time::LocalTime, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
 };
@@ -73,6 +79,35 @@ impl LoggerConfig {
     }
 }
 
+/// 文件日志后台写入线程的守卫，释放时写出缓冲中剩余的日志
+static FILE_WRITER_GUARD: Mutex<Option<WorkerGuard>> = Mutex::new(None);
+
+/// 日志系统的守卫
+///
+/// 文件日志由后台线程异步写入，需要在程序整个生命周期内持有这个守卫，
+/// 释放时会等待缓冲中的日志全部写入文件。
+#[must_use = "释放守卫会立即刷新并停止写入文件日志"]
+pub struct LogGuard {
+    _private: (),
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        flush_logs();
+    }
+}
+
+/// 写出缓冲中剩余的文件日志，之后的日志不再写入文件
+///
+/// `std::process::exit` 不会运行析构函数，直接退出前需要先调用这个函数。
+pub fn flush_logs() {
+    let guard = FILE_WRITER_GUARD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+    drop(guard);
+}
+
 /// 初始化日志系统
 ///
 /// # 参数
@@ -81,19 +116,21 @@ impl LoggerConfig {
 ///
 /// # 返回值
 ///
-/// 返回 Result，成功时返回 ()，失败时返回错误
+/// 返回 Result，成功时返回日志守卫，失败时返回错误
 ///
 /// # 示例
 ///
-/// ```rust
-/// use document_search_engine::utils::logger::{init_logger, LoggerConfig};
+/// ```rust,no_run
+/// use rust_agent_core::logging::{init_logger, LoggerConfig};
 /// use tracing::Level;
 ///
 /// let config = LoggerConfig::default()
 ///     .with_level(Level::DEBUG);
-/// init_logger(config).expect("初始化日志失败");
+/// let _log_guard = init_logger(config).expect("初始化日志失败");
 /// ```
-pub fn init_logger(config: LoggerConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub fn init_logger(
+    config: LoggerConfig,
+) -> Result<LogGuard, Box<dyn std::error::Error + Send + Sync>> {
     // 确保日志目录存在
     if !Path::new(&config.log_dir).exists() {
         std::fs::create_dir_all(&config.log_dir)?;
@@ -105,6 +142,8 @@ pub fn init_logger(config: LoggerConfig) -> Result<(), Box<dyn std::error::Error
         &config.log_dir,
         format!("{}.log", config.file_prefix),
     );
+    // 在后台线程写入文件，避免阻塞调用方
+    let (file_writer, writer_guard) = tracing_appender::non_blocking(file_appender);
 
     // 创建环境过滤器
     let env_filter = EnvFilter::from_default_env()
@@ -121,12 +160,13 @@ pub fn init_logger(config: LoggerConfig) -> Result<(), Box<dyn std::error::Error
         .with_ansi(false)
         .with_timer(LocalTime::rfc_3339())
         .with_level(true)
-        .with_writer(file_appender);
+        .with_writer(file_writer);
 
-    // 创建基础订阅者
+    // 创建基础订阅者，捕获层只在存在进行中的捕获时记录日志
     let subscriber = tracing_subscriber::registry()
         .with(env_filter)
-        .with(file_layer);
+        .with(file_layer)
+        .with(LogCaptureLayer::new());
 
     // 如果启用终端输出，添加终端输出层
     if config.console_output {
@@ -146,6 +186,39 @@ pub fn init_logger(config: LoggerConfig) -> Result<(), Box<dyn std::error::Error
         subscriber.try_init()?;
     }
 
+    *FILE_WRITER_GUARD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(writer_guard);
+
     info!("日志系统初始化完成");
-    Ok(())
+    Ok(LogGuard { _private: () })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 读取目录下所有日志文件的内容（文件名带有日期后缀）
+    fn read_logs(dir: &Path) -> String {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn logs_written_just_before_shutdown_reach_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LoggerConfig::new(dir.path().to_str().unwrap(), "shutdown", Level::INFO)
+            .with_console_output(false);
+        let guard = init_logger(config).unwrap();
+
+        info!("退出前的最后一行日志");
+        // 释放守卫时等待后台线程写完缓冲中的日志
+        drop(guard);
+
+        let logs = read_logs(dir.path());
+        assert!(logs.contains("日志系统初始化完成"), "{}", logs);
+        assert!(logs.contains("退出前的最后一行日志"), "{}", logs);
+    }
 }
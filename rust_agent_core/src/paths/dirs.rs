@@ -0,0 +1,121 @@
+//! 目录解析模块
+//!
+//! 统一计算配置、数据、缓存和日志目录，避免文件散落在当前工作目录：
+//! - 设置了 `RUST_AGENT_HOME` 环境变量时，所有目录都位于该目录下
+//! - 否则使用各平台的标准目录（Linux 下为 XDG 目录，macOS 下为 Library 目录等）
+//! - 无法确定用户主目录时，回退到当前目录下的 `.rust_agent`
+
+use directories::ProjectDirs;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// 覆盖所有目录位置的环境变量
+pub const HOME_ENV: &str = "RUST_AGENT_HOME";
+
+/// 应用名称，用于平台标准目录
+const APP_NAME: &str = "rust_agent";
+
+/// 无法确定主目录时使用的回退目录
+const FALLBACK_HOME: &str = ".rust_agent";
+
+/// 应用使用的各类目录
+#[derive(Debug, Clone)]
+pub struct AgentDirs {
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+    cache_dir: PathBuf,
+}
+
+impl AgentDirs {
+    /// 解析目录，优先使用 `RUST_AGENT_HOME` 环境变量
+    pub fn resolve() -> Self {
+        match env::var_os(HOME_ENV).filter(|home| !home.is_empty()) {
+            Some(home) => Self::from_home(home),
+            None => Self::platform_default(),
+        }
+    }
+
+    /// 所有目录都位于指定的根目录下
+    pub fn from_home(home: impl Into<PathBuf>) -> Self {
+        let home = home.into();
+        Self {
+            config_dir: home.join("config"),
+            data_dir: home.join("data"),
+            cache_dir: home.join("cache"),
+        }
+    }
+
+    /// 使用平台标准目录
+    pub fn platform_default() -> Self {
+        match ProjectDirs::from("", "", APP_NAME) {
+            Some(dirs) => Self {
+                config_dir: dirs.config_dir().to_path_buf(),
+                data_dir: dirs.data_dir().to_path_buf(),
+                cache_dir: dirs.cache_dir().to_path_buf(),
+            },
+            None => Self::from_home(FALLBACK_HOME),
+        }
+    }
+
+    /// 配置目录
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// 数据目录
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// 缓存目录
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// 日志目录
+    pub fn log_dir(&self) -> PathBuf {
+        self.data_dir.join("logs")
+    }
+
+    /// 会话保存目录
+    pub fn sessions_dir(&self) -> PathBuf {
+        self.data_dir.join("sessions")
+    }
+
+    /// 输入历史文件
+    pub fn history_file(&self) -> PathBuf {
+        self.data_dir.join("history")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 环境变量是进程级的，覆盖和回退放在同一个测试中，避免与其他测试并发修改
+    #[test]
+    fn resolve_honors_env_override_and_falls_back() {
+        let home = env::temp_dir().join("rust_agent_dirs_test");
+        env::set_var(HOME_ENV, &home);
+        let dirs = AgentDirs::resolve();
+        assert_eq!(dirs.config_dir(), home.join("config"));
+        assert_eq!(dirs.data_dir(), home.join("data"));
+        assert_eq!(dirs.cache_dir(), home.join("cache"));
+        assert_eq!(dirs.log_dir(), home.join("data").join("logs"));
+        assert_eq!(dirs.history_file(), home.join("data").join("history"));
+
+        // 空值等同于未设置
+        env::set_var(HOME_ENV, "");
+        let empty = AgentDirs::resolve();
+        env::remove_var(HOME_ENV);
+        let unset = AgentDirs::resolve();
+
+        let platform = AgentDirs::platform_default();
+        for dirs in [empty, unset] {
+            assert_eq!(dirs.config_dir(), platform.config_dir());
+            assert_eq!(dirs.data_dir(), platform.data_dir());
+            assert_eq!(dirs.cache_dir(), platform.cache_dir());
+        }
+        assert!(!platform.data_dir().starts_with(&home));
+    }
+}
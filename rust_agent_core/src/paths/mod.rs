@@ -0,0 +1,3 @@
+mod dirs;
+
+pub use dirs::{AgentDirs, HOME_ENV};
@@ -0,0 +1,3 @@
+mod truncate;
+
+pub use truncate::{last_chars, truncate_bytes_safe, truncate_chars};
@@ -0,0 +1,79 @@
+/// 保留开头的至多 `max_chars` 个字符
+///
+/// 按字符而不是字节计数，不会切开多字节的 UTF-8 字符。
+pub fn truncate_chars(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// 保留结尾的至多 `max_chars` 个字符
+pub fn last_chars(text: &str, max_chars: usize) -> &str {
+    if max_chars == 0 {
+        return "";
+    }
+
+    match text.char_indices().rev().nth(max_chars - 1) {
+        Some((start, _)) => &text[start..],
+        None => text,
+    }
+}
+
+/// 保留开头的至多 `max_bytes` 个字节
+///
+/// 上限落在多字节字符中间时向前退到字符边界，结果可能略短于 `max_bytes`。
+pub fn truncate_bytes_safe(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&index| text.is_char_boundary(index))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 混合了 ASCII、两字节、三字节和四字节字符
+    const TEXT: &str = "ab中文é🙂结尾";
+
+    #[test]
+    fn byte_limit_inside_a_multibyte_char_backs_off_to_a_boundary() {
+        // "中" 占第 2..5 字节，上限落在它中间时只保留 "ab"
+        assert_eq!(truncate_bytes_safe(TEXT, 3), "ab");
+        assert_eq!(truncate_bytes_safe(TEXT, 4), "ab");
+        assert_eq!(truncate_bytes_safe(TEXT, 5), "ab中");
+        // 四字节的 emoji 同样不会被切开
+        let emoji_start = TEXT.find('🙂').unwrap();
+        assert_eq!(truncate_bytes_safe(TEXT, emoji_start + 2), "ab中文é");
+
+        for limit in 0..=TEXT.len() + 1 {
+            let truncated = truncate_bytes_safe(TEXT, limit);
+            assert!(truncated.len() <= limit);
+            assert!(TEXT.starts_with(truncated));
+            // 只有离下一个字符边界不到一个字符时才会变短
+            assert!(limit.min(TEXT.len()) - truncated.len() < 4);
+        }
+    }
+
+    #[test]
+    fn char_limits_count_characters_not_bytes() {
+        assert_eq!(truncate_chars(TEXT, 3), "ab中");
+        assert_eq!(truncate_chars(TEXT, 0), "");
+        assert_eq!(truncate_chars(TEXT, 100), TEXT);
+        assert_eq!(last_chars(TEXT, 3), "🙂结尾");
+        assert_eq!(last_chars(TEXT, 0), "");
+        assert_eq!(last_chars(TEXT, 100), TEXT);
+
+        let count = TEXT.chars().count();
+        for limit in 0..=count {
+            assert_eq!(truncate_chars(TEXT, limit).chars().count(), limit);
+            assert_eq!(last_chars(TEXT, limit).chars().count(), limit);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use serde_json::Value;
+
+use crate::tools::interface::DisplayHint;
+
+/// 表格列之间的间隔
+const COLUMN_GAP: &str = "  ";
+
+/// 待输出的表格
+struct Table {
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+}
+
+/// 按展示方式把结果数据格式化为文本
+pub(crate) fn render_data(data: &Value, hint: DisplayHint) -> String {
+    match hint {
+        DisplayHint::Json => render_json(data),
+        DisplayHint::Text => render_scalar(data),
+        DisplayHint::List => render_list(data),
+        DisplayHint::Table => render_table(data),
+    }
+}
+
+fn render_json(data: &Value) -> String {
+    match data {
+        Value::String(s) => s.clone(),
+        _ => serde_json::to_string_pretty(data).unwrap_or_default(),
+    }
+}
+
+/// 标量直接输出，字符串不带引号；复合值输出紧凑的 JSON
+fn render_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        _ => value.to_string(),
+    }
+}
+
+fn render_list(data: &Value) -> String {
+    match data {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| format!("- {}", render_scalar(item)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| format!("- {}: {}", key, render_scalar(value)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => render_scalar(data),
+    }
+}
+
+/// 以表格形式输出
+///
+/// 对象的每个字段单独处理：能组成表格的字段（记录数组、键值对象）输出为表格，
+/// 其余字段输出为 `key: value`。无法组成表格时退回 JSON。
+fn render_table(data: &Value) -> String {
+    if let Some(table) = to_table(data) {
+        return format_table(&table);
+    }
+
+    let Value::Object(map) = data else {
+        return render_json(data);
+    };
+
+    let mut sections = Vec::new();
+    for (key, value) in map {
+        match to_table(value) {
+            Some(table) => sections.push(format!("{}:\n{}", key, format_table(&table))),
+            _ => sections.push(format!("{}: {}", key, render_scalar(value))),
+        }
+    }
+    sections.join("\n")
+}
+
+/// 把数据转换为表格
+///
+/// 支持数组组成的数组、字段一致的对象数组，以及值全为标量的非空对象（两列键值表）。
+fn to_table(value: &Value) -> Option<Table> {
+    match value {
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_array) => {
+            let rows = items
+                .iter()
+                .filter_map(Value::as_array)
+                .map(|cells| cells.iter().map(render_scalar).collect())
+                .collect();
+            Some(Table { header: None, rows })
+        }
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            let header: Vec<String> = items[0].as_object()?.keys().cloned().collect();
+            let rows = items
+                .iter()
+                .map(|item| {
+                    header
+                        .iter()
+                        .map(|key| item.get(key).map(render_scalar).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            Some(Table {
+                header: Some(header),
+                rows,
+            })
+        }
+        Value::Object(map)
+            if !map.is_empty() && map.values().all(|v| !v.is_array() && !v.is_object()) =>
+        {
+            let rows = map
+                .iter()
+                .map(|(key, value)| vec![key.clone(), render_scalar(value)])
+                .collect();
+            Some(Table { header: None, rows })
+        }
+        _ => None,
+    }
+}
+
+/// 按列宽对齐输出表格，有表头时在表头下加分隔线
+fn format_table(table: &Table) -> String {
+    let header = table.header.as_deref();
+    let rows = &table.rows;
+    let column_count = header
+        .map(<[String]>::len)
+        .into_iter()
+        .chain(rows.iter().map(Vec::len))
+        .max()
+        .unwrap_or(0);
+
+    let mut widths = vec![0; column_count];
+    for row in header.into_iter().chain(rows.iter().map(Vec::as_slice)) {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let format_row = |row: &[String]| {
+        row.iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let padding = widths[i] - cell.chars().count();
+                format!("{}{}", cell, " ".repeat(padding))
+            })
+            .collect::<Vec<_>>()
+            .join(COLUMN_GAP)
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = Vec::new();
+    if let Some(header) = header {
+        lines.push(format_row(header));
+        lines.push(
+            widths
+                .iter()
+                .map(|w| "-".repeat(*w))
+                .collect::<Vec<_>>()
+                .join(COLUMN_GAP),
+        );
+    }
+    lines.extend(rows.iter().map(|row| format_row(row)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn table_hint_renders_aligned_columns() {
+        let data = json!({
+            "total_files": 3,
+            "largest_files": [
+                {"path": "src/main.rs", "size": 1024},
+                {"path": "a.txt", "size": 7},
+            ]
+        });
+
+        let rendered = render_data(&data, DisplayHint::Table);
+        assert_eq!(
+            rendered,
+            "largest_files:\n\
+             path         size\n\
+             -----------  ----\n\
+             src/main.rs  1024\n\
+             a.txt        7\n\
+             total_files: 3"
+        );
+    }
+
+    #[test]
+    fn table_hint_falls_back_to_json_for_untabular_data() {
+        let data = json!(["a", 1]);
+        assert_eq!(
+            render_data(&data, DisplayHint::Table),
+            render_data(&data, DisplayHint::Json)
+        );
+    }
+}
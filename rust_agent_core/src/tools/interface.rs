@@ -9,11 +9,106 @@ pub struct ToolParameters {
 }
 
 /// 工具调用的结果
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     pub success: bool,
     pub data: serde_json::Value,
     pub error: Option<String>,
+    /// 失败原因的类别，成功时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ToolErrorKind>,
+    /// 结果数据的展示方式，默认按 JSON 输出
+    #[serde(default, skip_serializing_if = "DisplayHint::is_json")]
+    pub display_hint: DisplayHint,
+    /// 服务端开启调试日志时附带的工具执行日志
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<String>,
+    /// 结果超过服务端的大小上限，`data` 已被截断为文本
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+/// 结果数据的展示方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayHint {
+    /// 格式化的 JSON
+    #[default]
+    Json,
+    /// 表格，适合由多行记录组成的数据
+    Table,
+    /// 列表，每个元素一行
+    List,
+    /// 纯文本
+    Text,
+}
+
+impl DisplayHint {
+    pub fn is_json(&self) -> bool {
+        *self == Self::Json
+    }
+}
+
+/// 工具执行失败的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorKind {
+    /// 参数校验失败，修正参数后重试可能成功
+    InvalidArgs,
+    /// 执行过程出错
+    Execution,
+}
+
+impl ToolResult {
+    /// 执行成功
+    pub fn success(data: serde_json::Value) -> Self {
+        Self {
+            success: true,
+            data,
+            error: None,
+            error_kind: None,
+            display_hint: DisplayHint::Json,
+            logs: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// 执行失败
+    pub fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(error.into()),
+            error_kind: Some(ToolErrorKind::Execution),
+            display_hint: DisplayHint::Json,
+            logs: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// 参数校验失败
+    pub fn invalid_args(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(error.into()),
+            error_kind: Some(ToolErrorKind::InvalidArgs),
+            display_hint: DisplayHint::Json,
+            logs: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// 设置结果数据的展示方式
+    pub fn with_display_hint(mut self, hint: DisplayHint) -> Self {
+        self.display_hint = hint;
+        self
+    }
+
+    /// 是否因参数校验失败
+    pub fn is_invalid_args(&self) -> bool {
+        self.error_kind == Some(ToolErrorKind::InvalidArgs)
+    }
 }
 
 /// 工具特征定义
@@ -25,6 +120,16 @@ pub trait Tool: Send + Sync {
     /// 获取工具描述
     fn description(&self) -> &str;
 
+    /// 参数的 JSON Schema，用于生成工具说明
+    fn parameters(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// 调用示例，每个元素为一次调用的 `args`
+    fn examples(&self) -> Vec<serde_json::Value> {
+        Vec::new()
+    }
+
     /// 执行工具
     async fn execute(&self, params: ToolParameters) -> Result<ToolResult>;
 }
@@ -1,7 +1,10 @@
+mod display;
 pub mod interface;
 pub mod parser;
+mod prompt;
 pub mod rpc;
 
-pub use interface::{Tool, ToolParameters, ToolResult};
-pub use parser::{format_tool_result, parse_tool_calls};
-pub use rpc::client::ToolsClient;
+pub use interface::{DisplayHint, Tool, ToolErrorKind, ToolParameters, ToolResult};
+pub use parser::{format_tool_result, parse_tool_calls, strip_tool_calls, ToolCallFormat};
+pub use prompt::{build_system_prompt, build_system_prompt_with_format};
+pub use rpc::client::{ConnectOptions, ToolInfo, ToolServiceError, ToolsClient};
@@ -1,29 +1,141 @@
 use anyhow::{anyhow, Result};
-use regex::Regex;
+use regex::{Captures, Regex};
 use serde_json::Value;
+use std::sync::OnceLock;
 
+use crate::tools::display::render_data;
 use crate::tools::interface::{ToolParameters, ToolResult};
 
-/// 工具调用标记
+/// 默认的工具调用标记
 const TOOL_CALL_START: &str = "```tool";
 const TOOL_CALL_END: &str = "```";
 
-/// 解析 AI 回复中的工具调用
-pub fn parse_tool_calls(ai_message: &str) -> Vec<ToolParameters> {
-    let mut tool_calls = Vec::new();
+/// XML 风格的工具调用标记
+const XML_TOOL_CALL_START: &str = "<tool>";
+const XML_TOOL_CALL_END: &str = "</tool>";
+
+/// 工具调用块的格式
+///
+/// 由开始和结束标记包围的内容被视为一次工具调用。默认使用 ```` ```tool ```` 代码块；
+/// 有些模型会转义或改写三个反引号，此时可以改用 `<tool>...</tool>` 或自定义标记。
+#[derive(Debug, Clone)]
+pub struct ToolCallFormat {
+    start: String,
+    end: String,
+    regex: Regex,
+}
+
+impl Default for ToolCallFormat {
+    fn default() -> Self {
+        Self::new(TOOL_CALL_START, TOOL_CALL_END)
+    }
+}
+
+impl ToolCallFormat {
+    /// 使用自定义的开始和结束标记
+    pub fn new(start: impl Into<String>, end: impl Into<String>) -> Self {
+        let start = start.into();
+        let end = end.into();
+
+        // 以字母结尾的标记（如 ```tool）后面不能紧跟其他字母，避免匹配 ```toolbox
+        let boundary = if start.ends_with(|c: char| c.is_alphanumeric()) {
+            r"\b"
+        } else {
+            ""
+        };
+        let pattern = format!(
+            r"{}{}\s*([\s\S]*?)\s*{}",
+            regex::escape(&start),
+            boundary,
+            regex::escape(&end)
+        );
+        let regex = Regex::new(&pattern).expect("转义后的标记总是合法的正则表达式");
+
+        Self { start, end, regex }
+    }
+
+    /// ```` ```tool ```` 代码块（默认格式）
+    pub fn backtick() -> Self {
+        Self::default()
+    }
+
+    /// `<tool>...</tool>` 标签
+    pub fn xml() -> Self {
+        Self::new(XML_TOOL_CALL_START, XML_TOOL_CALL_END)
+    }
 
-    // 使用正则表达式匹配工具调用块
-    let re = Regex::new(r"```tool\s*\n([\s\S]*?)\n```").unwrap();
+    /// 开始标记
+    pub fn start(&self) -> &str {
+        &self.start
+    }
 
-    for cap in re.captures_iter(ai_message) {
-        if let Some(tool_content) = cap.get(1) {
-            if let Ok(params) = parse_tool_content(tool_content.as_str()) {
-                tool_calls.push(params);
+    /// 结束标记
+    pub fn end(&self) -> &str {
+        &self.end
+    }
+
+    /// 用标记包围工具调用内容，生成一个工具调用块
+    pub fn wrap(&self, content: &str) -> String {
+        format!("{}\n{}\n{}", self.start, content, self.end)
+    }
+
+    /// 解析 AI 回复中的工具调用
+    pub fn parse(&self, ai_message: &str) -> Vec<ToolParameters> {
+        let mut tool_calls = Vec::new();
+
+        // 使用正则表达式匹配工具调用块
+        for cap in self.regex.captures_iter(ai_message) {
+            if let Some(tool_content) = cap.get(1) {
+                if let Ok(params) = parse_tool_content(tool_content.as_str()) {
+                    tool_calls.push(params);
+                }
             }
         }
+
+        tool_calls
     }
 
-    tool_calls
+    /// 去掉回复中能被解析的工具调用块，只保留文字内容
+    ///
+    /// 无法解析的块不会被执行，原样保留。
+    pub fn strip(&self, ai_message: &str) -> String {
+        let stripped =
+            self.regex.replace_all(ai_message, |cap: &Captures| {
+                match parse_tool_content(&cap[1]) {
+                    Ok(_) => String::new(),
+                    Err(_) => cap[0].to_string(),
+                }
+            });
+
+        // 合并去掉代码块后留下的多余空行
+        let mut output = String::new();
+        for line in stripped.trim().lines() {
+            if line.trim().is_empty() && output.ends_with("\n\n") {
+                continue;
+            }
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        output.trim_end().to_string()
+    }
+}
+
+/// 默认格式，避免每次调用都重新编译正则表达式
+fn default_format() -> &'static ToolCallFormat {
+    static FORMAT: OnceLock<ToolCallFormat> = OnceLock::new();
+    FORMAT.get_or_init(ToolCallFormat::default)
+}
+
+/// 按默认格式解析 AI 回复中的工具调用
+pub fn parse_tool_calls(ai_message: &str) -> Vec<ToolParameters> {
+    default_format().parse(ai_message)
+}
+
+/// 按默认格式去掉回复中能被解析的工具调用块，只保留文字内容
+///
+/// 无法解析的块不会被执行，原样保留。
+pub fn strip_tool_calls(ai_message: &str) -> String {
+    default_format().strip(ai_message)
 }
 
 /// 解析工具调用内容
@@ -68,13 +180,16 @@ pub fn format_tool_result(tool_name: &str, result: &ToolResult) -> String {
     if result.success {
         output.push_str("成功：\n\n");
 
-        // 格式化结果数据
-        let formatted_data = match &result.data {
-            Value::String(s) => s.clone(),
-            _ => serde_json::to_string_pretty(&result.data).unwrap_or_default(),
-        };
+        // 按工具给出的展示方式格式化结果数据
+        output.push_str(&render_data(&result.data, result.display_hint));
 
-        output.push_str(&formatted_data);
+        // 服务端截断结果时在 error 中说明原因
+        if result.truncated {
+            if let Some(note) = &result.error {
+                output.push_str("\n\n");
+                output.push_str(note);
+            }
+        }
     } else {
         output.push_str("失败：\n\n");
         if let Some(error) = &result.error {
@@ -84,5 +199,59 @@ pub fn format_tool_result(tool_name: &str, result: &ToolResult) -> String {
         }
     }
 
+    if !result.logs.is_empty() {
+        output.push_str("\n\n执行日志：\n");
+        output.push_str(&result.logs.join("\n"));
+    }
+
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn calls(format: &ToolCallFormat, message: &str) -> Vec<(String, Value)> {
+        format
+            .parse(message)
+            .into_iter()
+            .map(|call| (call.name, call.args))
+            .collect()
+    }
+
+    #[test]
+    fn backtick_and_xml_formats_produce_identical_parameters() {
+        let content = r#"{"name": "file_hash", "args": {"path": "a.txt", "algorithm": "md5"}}"#;
+        let backtick = format!("先计算摘要：\n```tool\n{}\n```\n稍等。", content);
+        let xml = format!("先计算摘要：\n<tool>{}</tool>\n稍等。", content);
+
+        let expected = vec![(
+            "file_hash".to_string(),
+            json!({"path": "a.txt", "algorithm": "md5"}),
+        )];
+        assert_eq!(calls(&ToolCallFormat::backtick(), &backtick), expected);
+        assert_eq!(calls(&ToolCallFormat::xml(), &xml), expected);
+
+        // 各格式只识别自己的标记
+        assert!(ToolCallFormat::xml().parse(&backtick).is_empty());
+        assert!(ToolCallFormat::backtick().parse(&xml).is_empty());
+        assert_eq!(
+            ToolCallFormat::backtick().strip(&backtick),
+            ToolCallFormat::xml().strip(&xml)
+        );
+    }
+
+    #[test]
+    fn marker_ending_in_a_letter_does_not_match_longer_tags() {
+        let message = "```toolbox\n{\"name\": \"x\"}\n```";
+        assert!(ToolCallFormat::backtick().parse(message).is_empty());
+
+        let custom = ToolCallFormat::new("[[call]]", "[[/call]]");
+        let message = "[[call]] web_search: {\"query\": \"rust\"} [[/call]]";
+        assert_eq!(
+            calls(&custom, message),
+            [("web_search".to_string(), json!({"query": "rust"}))]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use serde_json::json;
+
+use crate::tools::parser::ToolCallFormat;
+use crate::tools::rpc::client::ToolInfo;
+
+/// 调用格式说明中的示例内容
+const CALL_TEMPLATE: &str = "{\"name\": \"工具名称\", \"args\": {\"参数名\": \"参数值\"}}";
+
+/// 使用工具的注意事项
+const NOTES: &str = "注意事项：
+1. 工具调用必须使用上述 JSON 格式
+2. 参数名称和类型必须严格匹配
+3. 每个工具都有特定的用途，请根据实际需求选择合适的工具
+4. 如果工具执行失败，会返回错误信息";
+
+/// 根据工具信息生成告知模型如何使用工具的系统提示
+///
+/// 每个工具依次列出名称、描述、参数的 JSON Schema 和调用示例，
+/// 最后附上工具调用的格式说明和注意事项。调用格式为默认的 ```` ```tool ```` 代码块。
+pub fn build_system_prompt(tools: &[ToolInfo]) -> String {
+    build_system_prompt_with_format(tools, &ToolCallFormat::default())
+}
+
+/// 按指定的工具调用格式生成系统提示，格式应与解析回复时使用的一致
+pub fn build_system_prompt_with_format(tools: &[ToolInfo], format: &ToolCallFormat) -> String {
+    let mut prompt = String::from("你可以使用以下工具来辅助完成任务：\n");
+
+    for (index, tool) in tools.iter().enumerate() {
+        prompt.push_str(&format!("\n{}. {}：\n", index + 1, tool.name));
+        prompt.push_str(&format!("   - 功能：{}\n", tool.description));
+
+        if let Some(parameters) = &tool.parameters {
+            let schema = serde_json::to_string_pretty(parameters).unwrap_or_default();
+            prompt.push_str("   - 参数（JSON Schema）：\n");
+            prompt.push_str(&indent(&format!("```json\n{}\n```", schema)));
+        }
+
+        for args in &tool.examples {
+            // 手动拼接以保证 name 在 args 之前，与调用格式说明一致
+            let call = format!("{{\"name\": {}, \"args\": {}}}", json!(tool.name), args);
+            prompt.push_str("   - 示例：\n");
+            prompt.push_str(&indent(&format.wrap(&call)));
+        }
+    }
+
+    prompt.push_str(&format!(
+        "\n调用工具时，请单独输出一个以 {} 开始、以 {} 结束的工具调用块，内容为包含工具名称和参数的 JSON：\n{}\n\
+需要调用多个工具时，每个调用使用一个单独的工具调用块。",
+        format.start(),
+        format.end(),
+        format.wrap(CALL_TEMPLATE)
+    ));
+    prompt.push_str("\n\n");
+    prompt.push_str(NOTES);
+    prompt
+}
+
+/// 每行缩进三个空格，与列表项对齐
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("   {}\n", line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools() -> Vec<ToolInfo> {
+        vec![
+            ToolInfo::new("web_search", "搜索网页")
+                .with_parameters(
+                    json!({"type": "object", "properties": {"query": {"type": "string"}}}),
+                )
+                .with_example(json!({"query": "rust"})),
+            ToolInfo::new("file_hash", "计算文件摘要"),
+        ]
+    }
+
+    #[test]
+    fn prompt_mentions_each_tool_and_the_fenced_format() {
+        let prompt = build_system_prompt(&tools());
+
+        assert!(
+            prompt.contains("1. web_search：\n   - 功能：搜索网页"),
+            "{}",
+            prompt
+        );
+        assert!(
+            prompt.contains("2. file_hash：\n   - 功能：计算文件摘要"),
+            "{}",
+            prompt
+        );
+        assert!(prompt.contains("\"query\""), "{}", prompt);
+        assert!(
+            prompt.contains("以 ```tool 开始、以 ``` 结束"),
+            "{}",
+            prompt
+        );
+        assert!(prompt.ends_with(NOTES));
+
+        // 示例和格式说明都是可以按默认格式解析的工具调用块
+        let calls = ToolCallFormat::default().parse(&prompt);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].name, "web_search");
+        assert_eq!(calls[0].args, json!({"query": "rust"}));
+        assert_eq!(calls[1].name, "工具名称");
+    }
+
+    #[test]
+    fn prompt_uses_the_given_format() {
+        let prompt = build_system_prompt_with_format(&tools(), &ToolCallFormat::xml());
+
+        assert!(!prompt.contains("```tool"), "{}", prompt);
+        let calls = ToolCallFormat::xml().parse(&prompt);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].name, "web_search");
+    }
+}
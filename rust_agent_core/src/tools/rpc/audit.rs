@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tracing::warn;
+
+/// 脱敏后的参数值
+const REDACTED: &str = "***";
+
+/// 一次工具调用的审计记录
+#[derive(Debug, Serialize)]
+pub struct AuditRecord<'a> {
+    pub timestamp: String,
+    /// 调用方标识，目前为客户端地址，无法获取时为 None
+    pub caller: Option<String>,
+    /// 客户端随调用传来的请求 ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub tool: &'a str,
+    /// 已按配置脱敏的参数
+    pub args: Value,
+    pub success: bool,
+    /// 失败时的错误信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u128,
+}
+
+/// 工具调用审计日志
+///
+/// 每次工具调用以 NDJSON 格式追加一行到单独的文件，与普通日志相互独立。
+/// 参数中名称匹配脱敏字段的值（任意嵌套层级）会被替换为 `***`。
+pub struct AuditLog {
+    file: Mutex<File>,
+    redacted_keys: HashSet<String>,
+}
+
+impl AuditLog {
+    /// 打开（或创建）审计日志文件，已有内容会被保留
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("无法打开审计日志文件: {:?}", path))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            redacted_keys: HashSet::new(),
+        })
+    }
+
+    /// 设置需要脱敏的参数名称
+    pub fn with_redacted_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.redacted_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 对参数脱敏
+    pub fn redact(&self, args: &Value) -> Value {
+        let mut args = args.clone();
+        redact_value(&mut args, &self.redacted_keys);
+        args
+    }
+
+    /// 记录一次工具调用，写入失败只记录警告，不影响工具执行
+    pub fn record(
+        &self,
+        caller: Option<String>,
+        request_id: Option<String>,
+        tool: &str,
+        args: &Value,
+        outcome: Result<(), String>,
+        duration_ms: u128,
+    ) {
+        let record = AuditRecord {
+            timestamp: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            caller,
+            request_id,
+            tool,
+            args: self.redact(args),
+            success: outcome.is_ok(),
+            error: outcome.err(),
+            duration_ms,
+        };
+
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("序列化审计记录失败: {}", e);
+                return;
+            }
+        };
+
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            warn!("写入审计日志失败: {}", e);
+        }
+    }
+}
+
+/// 递归替换名称匹配的字段值
+fn redact_value(value: &mut Value, keys: &HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if keys.contains(key) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(field, keys);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_value(item, keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::interface::{ToolParameters, ToolResult};
+    use crate::tools::rpc::server::ToolsFlightService;
+    use crate::tools::rpc::testing::{spawn_in_process, FnTool};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn executing_a_tool_writes_a_redacted_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit/tools.ndjson");
+        let audit = AuditLog::open(&path)
+            .unwrap()
+            .with_redacted_keys(["token", "path"]);
+        let fetch = FnTool::new("fetch", |_| async { Ok(ToolResult::success(json!("ok"))) });
+        let service = ToolsFlightService::with_tools(vec![fetch.boxed()]).with_audit_log(audit);
+        let (_server, client) = spawn_in_process(service).await.unwrap();
+
+        let params = ToolParameters {
+            name: "fetch".to_string(),
+            args: json!({
+                "url": "https://example.com",
+                "headers": [{"token": "secret-1"}],
+                "path": "/home/user/secret.txt"
+            }),
+        };
+        client
+            .execute_tool_with_request_id(params, Some("req-7"))
+            .await
+            .unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("secret"), "{}", contents);
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+
+        let record = &lines[0];
+        assert_eq!(record["tool"], "fetch");
+        assert_eq!(record["success"], true);
+        assert_eq!(
+            record["args"],
+            json!({
+                "url": "https://example.com",
+                "headers": [{"token": "***"}],
+                "path": "***"
+            })
+        );
+        assert!(record["caller"].as_str().unwrap().starts_with("127.0.0.1:"));
+        assert_eq!(record["request_id"], "req-7");
+        assert!(record["timestamp"].is_string());
+        assert!(record["duration_ms"].is_u64());
+    }
+}
@@ -1,22 +1,284 @@
-use anyhow::Result;
-use arrow_flight::{flight_service_client::FlightServiceClient, Action, Criteria};
-use tonic::transport::Channel;
+use anyhow::{anyhow, Result};
+use arrow_flight::{
+    flight_service_client::FlightServiceClient, Action, ActionType, Criteria, Empty, Ticket,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+use tracing::warn;
 
-use crate::tools::interface::{ToolParameters, ToolResult};
+use crate::correlation::{new_request_id, REQUEST_ID_METADATA};
+use crate::tools::interface::{Tool, ToolParameters, ToolResult};
+use crate::tools::rpc::idempotency::IDEMPOTENCY_KEY_METADATA;
+use crate::tools::rpc::server::HEALTH_ACTION;
 
+/// 工具服务连接选项
+///
+/// 默认开启 HTTP/2 keep-alive，避免空闲的长连接被中间代理或负载均衡断开。
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// keep-alive 探测间隔，None 表示不发送探测
+    pub keep_alive_interval: Option<Duration>,
+    /// 等待 keep-alive 响应的超时时间
+    pub keep_alive_timeout: Duration,
+    /// 没有进行中的请求时是否也发送探测
+    pub keep_alive_while_idle: bool,
+    /// 建立的连接数，请求按轮询分配到各个连接上
+    pub pool_size: usize,
+    /// 服务繁忙时工具调用的最大重试次数，0 表示不重试
+    pub busy_retries: usize,
+    /// 与工具服务通信失败时工具调用的最大重试次数，0 表示不重试
+    ///
+    /// 重试沿用同一个幂等键，服务端已经执行过的调用不会再次执行。
+    pub transport_retries: usize,
+    /// 第一次重试前的等待时间，之后每次加倍
+    pub busy_backoff: Duration,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            keep_alive_interval: Some(Duration::from_secs(30)),
+            keep_alive_timeout: Duration::from_secs(10),
+            keep_alive_while_idle: true,
+            pool_size: 1,
+            busy_retries: 0,
+            transport_retries: 1,
+            busy_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl ConnectOptions {
+    /// 设置 keep-alive 探测间隔
+    pub fn with_keep_alive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive_interval = interval;
+        self
+    }
+
+    /// 设置 keep-alive 超时时间
+    pub fn with_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// 设置空闲时是否发送探测
+    pub fn with_keep_alive_while_idle(mut self, enable: bool) -> Self {
+        self.keep_alive_while_idle = enable;
+        self
+    }
+
+    /// 设置连接数，至少为 1
+    ///
+    /// 大量并发的工具调用共用一个 HTTP/2 连接时可能互相阻塞，
+    /// 建立多个连接可以把请求分散开。
+    pub fn with_pool_size(mut self, size: usize) -> Self {
+        self.pool_size = size.max(1);
+        self
+    }
+
+    /// 设置服务繁忙（`resource_exhausted`）时工具调用的重试次数
+    pub fn with_busy_retries(mut self, retries: usize) -> Self {
+        self.busy_retries = retries;
+        self
+    }
+
+    /// 设置连接断开、调用超时等通信失败时工具调用的重试次数（默认 1 次）
+    pub fn with_transport_retries(mut self, retries: usize) -> Self {
+        self.transport_retries = retries;
+        self
+    }
+
+    /// 设置服务繁忙或通信失败时第一次重试前的等待时间
+    pub fn with_busy_backoff(mut self, backoff: Duration) -> Self {
+        self.busy_backoff = backoff;
+        self
+    }
+
+    /// 根据选项构建连接端点
+    pub fn endpoint(&self, addr: &str) -> Result<Endpoint> {
+        let mut endpoint = Endpoint::from_shared(addr.to_string())?
+            .keep_alive_timeout(self.keep_alive_timeout)
+            .keep_alive_while_idle(self.keep_alive_while_idle);
+
+        if let Some(interval) = self.keep_alive_interval {
+            endpoint = endpoint.http2_keep_alive_interval(interval);
+        }
+
+        Ok(endpoint)
+    }
+}
+
+/// 工具服务返回的 gRPC 错误
+///
+/// 按状态码转换为便于用户理解的提示，原始状态码和信息仍可通过字段获取。
+#[derive(Debug, Clone)]
+pub struct ToolServiceError {
+    pub code: Code,
+    pub message: String,
+}
+
+impl ToolServiceError {
+    /// 服务端因负载过高拒绝了请求，稍后重试可能成功
+    pub fn is_busy(&self) -> bool {
+        self.code == Code::ResourceExhausted
+    }
+
+    /// 与工具服务通信失败：连接不上、连接断开或调用超时
+    ///
+    /// 这类错误与调用的内容无关，调用方修改工具名称或参数也无法解决；
+    /// 其余错误（如工具不存在、工具执行出错、重试后仍然繁忙）属于这次调用本身的失败。
+    pub fn is_transport(&self) -> bool {
+        matches!(self.code, Code::Unavailable | Code::DeadlineExceeded)
+    }
+}
+
+impl From<Status> for ToolServiceError {
+    fn from(status: Status) -> Self {
+        // 连接层的错误可能以 Unknown 状态返回，统一按服务不可用处理
+        let code = if is_connection_error(&status) {
+            Code::Unavailable
+        } else {
+            status.code()
+        };
+        Self {
+            code,
+            message: status.message().to_string(),
+        }
+    }
+}
+
+/// 状态是否由连接层的错误（建立连接失败、连接断开）产生
+fn is_connection_error(status: &Status) -> bool {
+    let mut source = std::error::Error::source(status);
+    while let Some(error) = source {
+        if error.is::<tonic::transport::Error>() || error.is::<std::io::Error>() {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+impl fmt::Display for ToolServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Code::ResourceExhausted => write!(f, "工具服务器繁忙，请稍后重试"),
+            Code::Unavailable => write!(f, "无法连接到工具服务，请确认服务已启动"),
+            Code::DeadlineExceeded => write!(f, "工具调用超时"),
+            Code::Cancelled => write!(f, "工具调用已取消"),
+            Code::NotFound => write!(f, "工具不存在: {}", self.message),
+            Code::InvalidArgument => write!(f, "工具调用请求无效: {}", self.message),
+            Code::Internal => write!(f, "工具执行出错: {}", self.message),
+            code => write!(f, "工具服务返回错误（{:?}）: {}", code, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ToolServiceError {}
+
+/// 工具的基本信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub description: String,
+    /// 参数的 JSON Schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+    /// 调用示例，每个元素为一次调用的 `args`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<serde_json::Value>,
+}
+
+impl ToolInfo {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters: None,
+            examples: Vec::new(),
+        }
+    }
+
+    /// 从工具实现中读取信息
+    pub fn from_tool(tool: &dyn Tool) -> Self {
+        Self {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            parameters: tool.parameters(),
+            examples: tool.examples(),
+        }
+    }
+
+    /// 设置参数的 JSON Schema
+    pub fn with_parameters(mut self, parameters: serde_json::Value) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// 添加一个调用示例
+    pub fn with_example(mut self, args: serde_json::Value) -> Self {
+        self.examples.push(args);
+        self
+    }
+}
+
+/// 工具服务客户端
+///
+/// 所有方法都只需要共享引用，可以在多个任务中同时调用；
+/// 每个请求使用连接池中的下一个连接。
+#[derive(Clone)]
 pub struct ToolsClient {
-    client: FlightServiceClient<Channel>,
+    clients: Vec<FlightServiceClient<Channel>>,
+    next: Arc<AtomicUsize>,
+    busy_retries: usize,
+    transport_retries: usize,
+    busy_backoff: Duration,
 }
 
 impl ToolsClient {
+    /// 使用默认选项连接工具服务
     pub async fn connect(addr: &str) -> Result<Self> {
-        let client = FlightServiceClient::connect(addr.to_string()).await?;
-        Ok(Self { client })
+        Self::connect_with_options(addr, ConnectOptions::default()).await
+    }
+
+    /// 使用指定选项连接工具服务
+    pub async fn connect_with_options(addr: &str, options: ConnectOptions) -> Result<Self> {
+        let endpoint = options.endpoint(addr)?;
+        let mut clients = Vec::with_capacity(options.pool_size.max(1));
+        for _ in 0..options.pool_size.max(1) {
+            let channel = endpoint.connect().await?;
+            clients.push(FlightServiceClient::new(channel));
+        }
+
+        Ok(Self {
+            clients,
+            next: Arc::new(AtomicUsize::new(0)),
+            busy_retries: options.busy_retries,
+            transport_retries: options.transport_retries,
+            busy_backoff: options.busy_backoff,
+        })
     }
 
-    pub async fn list_tools(&mut self) -> Result<Vec<String>> {
+    /// 连接池中的连接数
+    pub fn pool_size(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// 按轮询取出下一个连接
+    fn client(&self) -> FlightServiceClient<Channel> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        self.clients[index].clone()
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<String>> {
         let request = tonic::Request::new(Criteria::default());
-        let response = self.client.list_flights(request).await?;
+        let response = self.client().list_flights(request).await?;
         let mut stream = response.into_inner();
 
         let mut tools = Vec::new();
@@ -30,21 +292,412 @@ impl ToolsClient {
         Ok(tools)
     }
 
-    pub async fn execute_tool(&mut self, params: ToolParameters) -> Result<ToolResult> {
+    /// 获取工具的名称和描述
+    pub async fn describe_tool(&self, name: &str) -> Result<ToolInfo> {
+        let request = tonic::Request::new(Ticket {
+            ticket: name.as_bytes().to_vec().into(),
+        });
+        let response = self.client().do_get(request).await?;
+        let mut stream = response.into_inner();
+
+        match stream.message().await? {
+            Some(data) => Ok(serde_json::from_slice(&data.data_body)?),
+            None => anyhow::bail!("No description received for tool: {}", name),
+        }
+    }
+
+    /// 列出服务端支持的动作类型
+    ///
+    /// 可以在调用新增的动作前先确认服务端是否支持。
+    pub async fn list_actions(&self) -> Result<Vec<ActionType>> {
+        let request = tonic::Request::new(Empty {});
+        let response = self.client().list_actions(request).await?;
+        let mut stream = response.into_inner();
+
+        let mut actions = Vec::new();
+        while let Some(action) = stream.message().await? {
+            actions.push(action);
+        }
+
+        Ok(actions)
+    }
+
+    /// 服务端是否支持指定的动作类型
+    pub async fn supports_action(&self, action_type: &str) -> Result<bool> {
+        let actions = self.list_actions().await?;
+        Ok(actions.iter().any(|a| a.r#type == action_type))
+    }
+
+    /// 检查工具服务是否存活
+    ///
+    /// 发送一个不执行任何工具的动作，只有通信失败（[`ToolServiceError::is_transport`]）
+    /// 才说明服务不可用；不支持该动作的旧版服务会返回参数错误，同样视为存活。
+    pub async fn health_check(&self) -> Result<(), ToolServiceError> {
+        let action = Action {
+            r#type: HEALTH_ACTION.into(),
+            body: vec![].into(),
+        };
+
+        match self.client().do_action(tonic::Request::new(action)).await {
+            Ok(_) => Ok(()),
+            Err(status) => {
+                let error = ToolServiceError::from(status);
+                if error.is_transport() {
+                    Err(error)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// 执行工具
+    ///
+    /// gRPC 错误会转换为 [`ToolServiceError`]；服务繁忙或通信失败时按连接选项等待后重试，
+    /// 每次重试的等待时间加倍。
+    pub async fn execute_tool(&self, params: ToolParameters) -> Result<ToolResult> {
+        self.execute_tool_with_request_id(params, None).await
+    }
+
+    /// 与 [`execute_tool`](Self::execute_tool) 相同，并把请求 ID 放入 Flight 元数据
+    /// （[`REQUEST_ID_METADATA`]），工具服务会在日志和审计记录中带上它
+    ///
+    /// 每次调用生成一个幂等键（[`IDEMPOTENCY_KEY_METADATA`]），重试时沿用同一个键，
+    /// 服务端已经执行过的调用不会再次执行，而是直接返回第一次的结果。因此结果在返回途中
+    /// 因连接断开而丢失时，重试是安全的。
+    pub async fn execute_tool_with_request_id(
+        &self,
+        params: ToolParameters,
+        request_id: Option<&str>,
+    ) -> Result<ToolResult> {
+        let mut metadata = MetadataMap::new();
+        if let Some(id) = request_id {
+            let value = MetadataValue::try_from(id)
+                .map_err(|_| anyhow!("请求 ID 不是合法的元数据值: {}", id))?;
+            metadata.insert(REQUEST_ID_METADATA, value);
+        }
+        let idempotency_key = MetadataValue::try_from(new_request_id())?;
+        metadata.insert(IDEMPOTENCY_KEY_METADATA, idempotency_key);
+        let body = serde_json::to_vec(&params)?;
+        let mut backoff = self.busy_backoff;
+        let mut busy_attempts = 0;
+        let mut transport_attempts = 0;
+
+        loop {
+            match self.try_execute_tool(body.clone(), metadata.clone()).await {
+                Err(e) if e.is_busy() && busy_attempts < self.busy_retries => {
+                    busy_attempts += 1;
+                    warn!(
+                        "工具服务繁忙，{:?} 后第 {} 次重试: {}",
+                        backoff, busy_attempts, params.name
+                    );
+                }
+                Err(e) if e.is_transport() && transport_attempts < self.transport_retries => {
+                    transport_attempts += 1;
+                    warn!(
+                        "与工具服务通信失败（{}），{:?} 后第 {} 次重试: {}",
+                        e, backoff, transport_attempts, params.name
+                    );
+                }
+                result => return Ok(result?),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    async fn try_execute_tool(
+        &self,
+        body: Vec<u8>,
+        metadata: MetadataMap,
+    ) -> Result<ToolResult, ToolServiceError> {
         let action = Action {
             r#type: "execute".into(),
-            body: serde_json::to_vec(&params)?.into(),
+            body: body.into(),
         };
 
-        let request = tonic::Request::new(action);
-        let response = self.client.do_action(request).await?;
+        let mut request = tonic::Request::new(action);
+        *request.metadata_mut() = metadata;
+        let response = self.client().do_action(request).await?;
         let mut stream = response.into_inner();
 
-        if let Some(result) = stream.message().await? {
-            let tool_result: ToolResult = serde_json::from_slice(&result.body.to_vec())?;
-            Ok(tool_result)
-        } else {
-            anyhow::bail!("No result received from tool execution")
+        match stream.message().await? {
+            Some(result) => serde_json::from_slice(&result.body).map_err(|e| ToolServiceError {
+                code: Code::Internal,
+                message: format!("无法解析工具执行结果: {}", e),
+            }),
+            None => Err(ToolServiceError {
+                code: Code::Internal,
+                message: "No result received from tool execution".to_string(),
+            }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::interface::ToolResult;
+    use crate::tools::rpc::audit::AuditLog;
+    use crate::tools::rpc::server::ToolsFlightService;
+    use crate::tools::rpc::testing::{spawn_in_process, FnTool, InProcessServer};
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicBool;
+    use tokio::sync::Barrier;
+
+    #[test]
+    fn connect_options_builders_apply_keep_alive_settings() {
+        let options = ConnectOptions::default()
+            .with_keep_alive_interval(Some(Duration::from_secs(5)))
+            .with_keep_alive_timeout(Duration::from_secs(2))
+            .with_keep_alive_while_idle(false)
+            .with_pool_size(0);
+
+        assert_eq!(options.pool_size, 1);
+        assert_eq!(options.keep_alive_interval, Some(Duration::from_secs(5)));
+        assert_eq!(options.keep_alive_timeout, Duration::from_secs(2));
+        assert!(!options.keep_alive_while_idle);
+        assert!(options.endpoint("http://[::1]:50051").is_ok());
+
+        let defaults = ConnectOptions::default();
+        assert_eq!(defaults.keep_alive_interval, Some(Duration::from_secs(30)));
+        assert!(defaults.keep_alive_while_idle);
+        assert!(ConnectOptions::default()
+            .with_keep_alive_interval(None)
+            .endpoint("http://127.0.0.1:50051")
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_address_is_rejected() {
+        assert!(ConnectOptions::default().endpoint("not a uri").is_err());
+    }
+
+    #[test]
+    fn resource_exhausted_status_has_a_friendly_message() {
+        let error = ToolServiceError::from(Status::resource_exhausted("too many concurrent calls"));
+        assert!(error.is_busy());
+        assert_eq!(error.to_string(), "工具服务器繁忙，请稍后重试");
+        // 原始信息仍然保留，便于排查
+        assert_eq!(error.message, "too many concurrent calls");
+
+        // 经过 anyhow 传递给调用方时显示的也是友好提示
+        let error = anyhow::Error::from(error);
+        assert_eq!(error.to_string(), "工具服务器繁忙，请稍后重试");
+        assert!(error.downcast_ref::<ToolServiceError>().unwrap().is_busy());
+
+        let error = ToolServiceError::from(Status::internal("磁盘已满"));
+        assert!(!error.is_busy());
+        assert_eq!(error.to_string(), "工具执行出错: 磁盘已满");
+    }
+
+    #[test]
+    fn only_unreachable_and_timed_out_calls_are_transport_errors() {
+        let transport = [
+            Status::unavailable("连接被拒绝"),
+            Status::deadline_exceeded("超时"),
+        ];
+        for status in transport {
+            assert!(ToolServiceError::from(status).is_transport());
+        }
+
+        let call_failures = [
+            Status::not_found("工具不存在"),
+            Status::invalid_argument("参数错误"),
+            Status::internal("磁盘已满"),
+            Status::unknown("工具崩溃"),
+            Status::aborted("冲突"),
+            Status::data_loss("结果损坏"),
+            Status::cancelled("已取消"),
+            Status::resource_exhausted("繁忙"),
+        ];
+        for status in call_failures {
+            let code = status.code();
+            assert!(!ToolServiceError::from(status).is_transport(), "{:?}", code);
+        }
+
+        // 连接层的错误即使以 Unknown 返回也属于通信失败
+        let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset");
+        let status = Status::from_error(Box::new(io_error));
+        assert!(ToolServiceError::from(status).is_transport());
+    }
+
+    #[tokio::test]
+    async fn stopped_server_is_a_transport_error_and_failed_tools_are_not() {
+        let service = ToolsFlightService::with_tools(vec![
+            FnTool::new("broken", |_| async {
+                Ok(ToolResult::failure("参数超出范围"))
+            })
+            .boxed(),
+            FnTool::new("crash", |_| async { Err(anyhow!("工具内部错误")) }).boxed(),
+        ]);
+        let (server, client) = spawn_in_process(service).await.unwrap();
+        let params = |name: &str| ToolParameters {
+            name: name.to_string(),
+            args: serde_json::json!({}),
+        };
+
+        // 工具返回的失败是正常的结果
+        let result = client.execute_tool(params("broken")).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("参数超出范围"));
+
+        // 工具执行出错或不存在是这次调用的失败
+        for name in ["crash", "missing"] {
+            let error = client.execute_tool(params(name)).await.unwrap_err();
+            let error = error.downcast_ref::<ToolServiceError>().unwrap();
+            assert!(!error.is_transport(), "{}: {:?}", name, error);
+        }
+
+        server.stop().await;
+        let error = client.execute_tool(params("broken")).await.unwrap_err();
+        let error = error.downcast_ref::<ToolServiceError>().unwrap();
+        assert!(error.is_transport(), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn list_actions_includes_execute() {
+        let (_server, client) = spawn_in_process(ToolsFlightService::new()).await.unwrap();
+
+        let actions = client.list_actions().await.unwrap();
+        assert!(actions.iter().any(|action| action.r#type == "execute"));
+        assert!(client.supports_action("execute").await.unwrap());
+        assert!(!client.supports_action("upload").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn describe_tool_returns_name_and_description() {
+        let echo = FnTool::new("echo", |args| async move { Ok(ToolResult::success(args)) });
+        let (_server, client) =
+            spawn_in_process(ToolsFlightService::with_tools(vec![echo.boxed()]))
+                .await
+                .unwrap();
+
+        let info = client.describe_tool("echo").await.unwrap();
+        assert_eq!(info.name, "echo");
+        assert_eq!(info.description, "测试工具");
+        assert!(client.describe_tool("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_run_in_parallel_across_pooled_connections() {
+        const CALLS: usize = 4;
+        let dir = tempfile::tempdir().unwrap();
+        let audit_path = dir.path().join("audit.ndjson");
+
+        // 每次调用都要等所有调用同时到达才能返回，调用被串行执行时会超时
+        let barrier = Arc::new(Barrier::new(CALLS));
+        let wait = FnTool::new("wait", move |_| {
+            let barrier = barrier.clone();
+            async move {
+                barrier.wait().await;
+                Ok(ToolResult::success(serde_json::json!("done")))
+            }
+        });
+        let service = ToolsFlightService::with_tools(vec![wait.boxed()])
+            .with_audit_log(AuditLog::open(&audit_path).unwrap());
+        let server = InProcessServer::start(service).await.unwrap();
+        let options = ConnectOptions::default().with_pool_size(CALLS);
+        let client = ToolsClient::connect_with_options(server.addr(), options)
+            .await
+            .unwrap();
+        assert_eq!(client.pool_size(), CALLS);
+
+        let calls = (0..CALLS).map(|_| {
+            client.execute_tool(ToolParameters {
+                name: "wait".to_string(),
+                args: serde_json::json!({}),
+            })
+        });
+        let results =
+            tokio::time::timeout(Duration::from_secs(5), futures::future::join_all(calls))
+                .await
+                .expect("并发的工具调用被串行执行");
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref().unwrap().success));
+
+        // 请求按轮询分配，每个调用来自不同的连接
+        let callers: HashSet<String> = std::fs::read_to_string(&audit_path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                record["caller"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(callers.len(), CALLS);
+    }
+
+    /// 在客户端和工具服务之间转发数据的代理；`cut` 被设置后，服务端发来的下一段数据
+    /// 不再转发，而是直接断开这条连接，模拟结果在返回途中因网络故障丢失
+    async fn spawn_cutting_proxy(upstream: &str, cut: Arc<AtomicBool>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::{TcpListener, TcpStream};
+
+        let upstream = upstream.trim_start_matches("http://").to_string();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let server = TcpStream::connect(&upstream).await.unwrap();
+                let (mut client_read, mut client_write) = client.into_split();
+                let (mut server_read, mut server_write) = server.into_split();
+                let cut = cut.clone();
+                let forward = tokio::spawn(async move {
+                    tokio::io::copy(&mut client_read, &mut server_write).await
+                });
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 16 * 1024];
+                    while let Ok(n) = server_read.read(&mut buf).await {
+                        if n == 0 || cut.swap(false, Ordering::SeqCst) {
+                            break;
+                        }
+                        if client_write.write_all(&buf[..n]).await.is_err() {
+                            break;
+                        }
+                    }
+                    forward.abort();
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn call_retried_after_a_lost_response_executes_the_tool_once() {
+        let executions = Arc::new(AtomicUsize::new(0));
+        let cut = Arc::new(AtomicBool::new(false));
+        let tool = {
+            let executions = executions.clone();
+            let cut = cut.clone();
+            FnTool::new("write", move |_| {
+                executions.fetch_add(1, Ordering::SeqCst);
+                // 结果写回时连接被断开
+                cut.store(true, Ordering::SeqCst);
+                async { Ok(ToolResult::success(serde_json::json!("written"))) }
+            })
+        };
+        let server = InProcessServer::start(ToolsFlightService::with_tools(vec![tool.boxed()]))
+            .await
+            .unwrap();
+        let proxy = spawn_cutting_proxy(server.addr(), cut).await;
+        let client = ToolsClient::connect_with_options(
+            &proxy,
+            ConnectOptions::default().with_busy_backoff(Duration::from_millis(10)),
+        )
+        .await
+        .unwrap();
+
+        let result = client
+            .execute_tool(ToolParameters {
+                name: "write".to_string(),
+                args: serde_json::json!({}),
+            })
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.data, serde_json::json!("written"));
+        assert_eq!(executions.load(Ordering::SeqCst), 1);
+    }
+}
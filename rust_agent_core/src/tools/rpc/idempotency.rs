@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+use tonic::{Code, Status};
+use tracing::info;
+
+/// 工具调用的 Flight 元数据中携带幂等键的键
+pub const IDEMPOTENCY_KEY_METADATA: &str = "x-idempotency-key";
+
+/// 默认记住执行结果的时长
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// 默认最多记住的执行结果数量
+pub const DEFAULT_IDEMPOTENCY_MAX_ENTRIES: usize = 1024;
+
+/// 定时清理过期记录的最长间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 编码后返回给客户端的结果
+type Outcome = Result<arrow_flight::Result, Status>;
+
+struct Entry {
+    created: Instant,
+    outcome: Arc<OnceCell<Outcome>>,
+}
+
+struct Entries {
+    ttl: Duration,
+    max_entries: usize,
+    map: Mutex<HashMap<String, Entry>>,
+}
+
+impl Entries {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.map
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 清理过期的记录
+    fn prune(&self) {
+        self.lock()
+            .retain(|_, entry| entry.created.elapsed() < self.ttl);
+    }
+}
+
+/// 按幂等键记住工具调用的结果
+///
+/// 客户端重试同一个调用时携带相同的键，服务端直接返回第一次执行的结果，
+/// 避免写文件、格式转换等有副作用的工具执行两次。第一次执行尚未结束时，
+/// 重复的调用会等待它完成；执行被取消（客户端断开）或服务繁忙时不会留下结果，下一次调用会重新执行。
+///
+/// 记住的是编码（截断）后的响应，重试拿到的内容与第一次完全相同。记录数量超过上限时
+/// 丢弃最早的记录；在 Tokio 运行时中创建时还会定时清理过期的记录。
+pub struct IdempotencyCache {
+    entries: Arc<Entries>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_max_entries(ttl, DEFAULT_IDEMPOTENCY_MAX_ENTRIES)
+    }
+
+    /// 指定有效期和最多记住的结果数量（至少为 1）
+    pub fn with_max_entries(ttl: Duration, max_entries: usize) -> Self {
+        let entries = Arc::new(Entries {
+            ttl,
+            max_entries: max_entries.max(1),
+            map: Mutex::new(HashMap::new()),
+        });
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(prune_periodically(Arc::downgrade(&entries)));
+        }
+        Self { entries }
+    }
+
+    /// 执行调用，同一个键在有效期内只执行一次，之后返回缓存的结果
+    pub async fn run<F>(&self, key: &str, execute: F) -> Outcome
+    where
+        F: Future<Output = Outcome>,
+    {
+        let cell = self.cell(key);
+        let mut executed = false;
+        let outcome = cell
+            .get_or_init(|| {
+                executed = true;
+                execute
+            })
+            .await
+            .clone();
+
+        if !executed {
+            info!("重复的工具调用，返回已有的执行结果: {}", key);
+        }
+        // 服务繁忙时调用没有被执行，不记住结果，让重试真正执行
+        if matches!(&outcome, Err(status) if status.code() == Code::ResourceExhausted) {
+            self.forget(key);
+        }
+        outcome
+    }
+
+    fn forget(&self, key: &str) {
+        self.entries.lock().remove(key);
+    }
+
+    /// 获取键对应的结果槽位，顺便清理过期的记录；记录已满时丢弃最早的一条
+    fn cell(&self, key: &str) -> Arc<OnceCell<Outcome>> {
+        let ttl = self.entries.ttl;
+        let mut entries = self.entries.lock();
+        entries.retain(|_, entry| entry.created.elapsed() < ttl);
+
+        if !entries.contains_key(key) && entries.len() >= self.entries.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.created)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                created: Instant::now(),
+                outcome: Arc::new(OnceCell::new()),
+            })
+            .outcome
+            .clone()
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDEMPOTENCY_TTL)
+    }
+}
+
+/// 定时清理过期的记录，缓存被丢弃后结束
+async fn prune_periodically(entries: Weak<Entries>) {
+    let period = match entries.upgrade() {
+        Some(entries) => entries.ttl.min(PRUNE_INTERVAL),
+        None => return,
+    };
+    let mut interval = tokio::time::interval(period.max(Duration::from_millis(1)));
+    // 第一次 tick 立即完成
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match entries.upgrade() {
+            Some(entries) => entries.prune(),
+            None => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn response(body: &str) -> arrow_flight::Result {
+        arrow_flight::Result {
+            body: body.as_bytes().to_vec().into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn same_key_runs_once_and_returns_the_first_response() {
+        let cache = IdempotencyCache::default();
+        let runs = AtomicUsize::new(0);
+        let execute = |body: &'static str| {
+            let runs = &runs;
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(response(body))
+            }
+        };
+
+        let first = cache.run("key", execute("first")).await.unwrap();
+        let second = cache.run("key", execute("second")).await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(first.body, second.body);
+
+        cache.run("other", execute("other")).await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn busy_responses_are_not_remembered() {
+        let cache = IdempotencyCache::default();
+        let busy = cache
+            .run("key", async { Err(Status::resource_exhausted("busy")) })
+            .await;
+        assert!(busy.is_err());
+
+        let retried = cache
+            .run("key", async { Ok(response("done")) })
+            .await
+            .unwrap();
+        assert_eq!(&retried.body[..], b"done");
+    }
+
+    #[tokio::test]
+    async fn oldest_entries_are_evicted_when_full() {
+        let cache = IdempotencyCache::with_max_entries(DEFAULT_IDEMPOTENCY_TTL, 2);
+        for key in ["a", "b", "c"] {
+            cache
+                .run(key, async move { Ok(response(key)) })
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(cache.len(), 2);
+
+        // 最早的 a 已被丢弃，再次调用会重新执行
+        let again = cache.run("a", async { Ok(response("a2")) }).await.unwrap();
+        assert_eq!(&again.body[..], b"a2");
+        let kept = cache.run("c", async { Ok(response("c2")) }).await.unwrap();
+        assert_eq!(&kept.body[..], b"c");
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_pruned_on_a_timer() {
+        let cache = IdempotencyCache::new(Duration::from_millis(50));
+        cache
+            .run("key", async { Ok(response("done")) })
+            .await
+            .unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // 没有新的调用，过期的记录也会被定时任务清理
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod audit;
 pub mod client;
+pub mod idempotency;
 pub mod server;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
@@ -8,29 +8,104 @@ use async_trait::async_trait;
 use futures::Stream;
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 use tonic::{Request, Response, Status, Streaming};
+use tracing::{info_span, warn, Instrument};
 
-use crate::tools::interface::{Tool, ToolParameters};
+use crate::correlation::REQUEST_ID_METADATA;
+use crate::logging::LogCapture;
+use crate::text::truncate_bytes_safe;
+use crate::tools::interface::{Tool, ToolParameters, ToolResult};
+use crate::tools::rpc::audit::AuditLog;
+use crate::tools::rpc::client::ToolInfo;
+use crate::tools::rpc::idempotency::{IdempotencyCache, IDEMPOTENCY_KEY_METADATA};
+
+/// 存活检查的动作类型，服务端收到后立即返回，不执行任何工具
+pub const HEALTH_ACTION: &str = "health";
+
+/// 单个工具结果序列化后的默认大小上限（字节）
+pub const DEFAULT_MAX_RESULT_BYTES: usize = 1024 * 1024;
 
 /// 工具服务实现
 pub struct ToolsFlightService {
-    tools: Arc<Mutex<Vec<Box<dyn Tool>>>>,
+    tools: Arc<RwLock<Vec<Box<dyn Tool>>>>,
+    /// 随结果返回的日志行数上限，None 表示不返回日志
+    log_capture_lines: Option<usize>,
+    /// 工具调用审计日志
+    audit: Option<Arc<AuditLog>>,
+    /// 按幂等键记住的执行结果
+    idempotency: IdempotencyCache,
+    /// 单个工具结果序列化后的大小上限（字节）
+    max_result_bytes: usize,
 }
 
 impl ToolsFlightService {
     pub fn new() -> Self {
         Self {
-            tools: Arc::new(Mutex::new(Vec::new())),
+            tools: Arc::new(RwLock::new(Vec::new())),
+            log_capture_lines: None,
+            audit: None,
+            idempotency: IdempotencyCache::default(),
+            max_result_bytes: DEFAULT_MAX_RESULT_BYTES,
+        }
+    }
+
+    /// 使用一组工具直接创建服务，构造时无需加锁
+    pub fn with_tools(tools: Vec<Box<dyn Tool>>) -> Self {
+        Self {
+            tools: Arc::new(RwLock::new(tools)),
+            log_capture_lines: None,
+            audit: None,
+            idempotency: IdempotencyCache::default(),
+            max_result_bytes: DEFAULT_MAX_RESULT_BYTES,
         }
     }
 
+    /// 把工具执行期间的日志随结果返回给客户端，最多保留最新的 `max_lines` 行
+    ///
+    /// 日志可能包含路径、参数等敏感信息，只应在调试时开启。
+    pub fn with_log_capture(mut self, max_lines: usize) -> Self {
+        self.log_capture_lines = Some(max_lines);
+        self
+    }
+
+    /// 把每次工具调用记录到审计日志
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(Arc::new(audit));
+        self
+    }
+
+    /// 设置幂等键对应的执行结果保留多久（默认 10 分钟）
+    pub fn with_idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency = IdempotencyCache::new(ttl);
+        self
+    }
+
+    /// 设置单个工具结果序列化后的大小上限（默认 1 MiB）
+    ///
+    /// 超过上限时 `data` 被截断为文本，并设置 `truncated` 标记、在 `error` 中说明原因，
+    /// 避免一次返回过大的结果占满服务端和客户端的内存。
+    pub fn with_max_result_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_result_bytes = max_bytes;
+        self
+    }
+
+    /// 注册工具
+    ///
+    /// 使用异步读写锁，可以在 Tokio 任务中安全调用；执行工具只需读锁，多个调用可以同时进行。
     pub async fn register_tool(&self, tool: Box<dyn Tool>) {
-        let mut tools = self.tools.lock().await;
+        let mut tools = self.tools.write().await;
         tools.push(tool);
     }
 }
 
+impl Default for ToolsFlightService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 type BoxStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
 
 #[async_trait]
@@ -88,13 +163,13 @@ impl FlightService for ToolsFlightService {
         &self,
         _request: Request<Criteria>,
     ) -> Result<Response<Self::ListFlightsStream>, Status> {
-        let tools = self.tools.lock().await;
+        let tools = self.tools.read().await;
 
-        let flights: Vec<Result<FlightInfo, Status>> = tools
+        let flights: Vec<FlightInfo> = tools
             .iter()
             .map(|tool| {
                 let name = tool.name().to_string();
-                Ok(FlightInfo {
+                FlightInfo {
                     flight_descriptor: Some(FlightDescriptor {
                         r#type: 0,
                         cmd: name.as_bytes().to_vec().into(),
@@ -106,11 +181,11 @@ impl FlightService for ToolsFlightService {
                     endpoint: vec![],
                     app_metadata: vec![].into(),
                     ordered: false,
-                })
+                }
             })
             .collect();
 
-        let output = futures::stream::iter(flights);
+        let output = futures::stream::iter(flights.into_iter().map(Ok));
         Ok(Response::new(Box::pin(output)))
     }
 
@@ -122,19 +197,13 @@ impl FlightService for ToolsFlightService {
         let tool_name = String::from_utf8(ticket.ticket.to_vec())
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let tools = self.tools.lock().await;
+        let tools = self.tools.read().await;
         let tool = tools
             .iter()
             .find(|t| t.name() == tool_name)
             .ok_or_else(|| Status::not_found("Tool not found"))?;
 
-        let name = tool.name().to_string();
-        let description = tool.description().to_string();
-
-        let info = serde_json::json!({
-            "name": name,
-            "description": description,
-        });
+        let info = ToolInfo::from_tool(tool.as_ref());
 
         let data = FlightData {
             flight_descriptor: Some(FlightDescriptor {
@@ -162,34 +231,106 @@ impl FlightService for ToolsFlightService {
         &self,
         request: Request<Action>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
+        let caller = request.remote_addr().map(|addr| addr.to_string());
+        let request_id = request
+            .metadata()
+            .get(REQUEST_ID_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let idempotency_key = request
+            .metadata()
+            .get(IDEMPOTENCY_KEY_METADATA)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
         let action = request.into_inner();
 
+        if action.r#type == HEALTH_ACTION {
+            let output = futures::stream::once(async move {
+                Ok(arrow_flight::Result {
+                    body: vec![].into(),
+                })
+            });
+            return Ok(Response::new(Box::pin(output)));
+        }
+
         if action.r#type != "execute" {
             return Err(Status::invalid_argument("Unsupported action type"));
         }
 
-        let params: ToolParameters = serde_json::from_slice(&action.body.to_vec())
+        let params: ToolParameters = serde_json::from_slice(&action.body)
             .map_err(|e| Status::invalid_argument(e.to_string()))?;
 
         // 克隆参数以避免借用问题
         let params_name = params.name.clone();
 
-        let tools = self.tools.lock().await;
+        let tools = self.tools.read().await;
         let tool = tools
             .iter()
             .find(|t| t.name() == params_name)
             .ok_or_else(|| Status::not_found("Tool not found"))?;
 
-        // 执行工具并获取结果
-        let result = tool
-            .execute(params)
-            .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let execute = async move {
+            // 审计日志需要原始参数，只在开启时复制
+            let audit_args = self.audit.as_ref().map(|_| params.args.clone());
+            let started = Instant::now();
 
-        let flight_result = arrow_flight::Result {
-            body: serde_json::to_vec(&result).unwrap().into(),
+            // 执行期间的日志都带上客户端传来的请求 ID，便于与客户端日志关联
+            let span = info_span!(
+                "tool_call",
+                tool = %params_name,
+                request_id = request_id.as_deref().unwrap_or("-")
+            );
+
+            // 执行工具并获取结果，开启调试日志时一并捕获执行期间的日志
+            let outcome = async {
+                match self.log_capture_lines {
+                    Some(max_lines) => {
+                        let capture = LogCapture::start(max_lines);
+                        let outcome = tool.execute(params).instrument(capture.span()).await;
+                        let logs = capture.finish();
+                        match outcome {
+                            Ok(mut result) => {
+                                result.logs = logs;
+                                Ok(result)
+                            }
+                            Err(e) => {
+                                let mut message = e.to_string();
+                                if !logs.is_empty() {
+                                    message = format!("{}\n{}", message, logs.join("\n"));
+                                }
+                                Err(Status::internal(message))
+                            }
+                        }
+                    }
+                    None => tool
+                        .execute(params)
+                        .await
+                        .map_err(|e| Status::internal(e.to_string())),
+                }
+            }
+            .instrument(span)
+            .await;
+
+            if let (Some(audit), Some(args)) = (&self.audit, audit_args) {
+                let status = match &outcome {
+                    Ok(result) if result.success => Ok(()),
+                    Ok(result) => Err(result.error.clone().unwrap_or_default()),
+                    Err(status) => Err(status.message().to_string()),
+                };
+                let duration_ms = started.elapsed().as_millis();
+                audit.record(caller, request_id, &params_name, &args, status, duration_ms);
+            }
+            outcome.map(|result| arrow_flight::Result {
+                body: encode_result(result, self.max_result_bytes).into(),
+            })
         };
 
+        // 携带幂等键的调用在有效期内只执行一次，客户端重试时直接返回第一次编码后的结果
+        let flight_result = match &idempotency_key {
+            Some(key) => self.idempotency.run(key, execute).await,
+            None => execute.await,
+        }?;
+
         let output = futures::stream::once(async move { Ok(flight_result) });
         Ok(Response::new(Box::pin(output)))
     }
@@ -198,12 +339,280 @@ impl FlightService for ToolsFlightService {
         &self,
         _request: Request<Empty>,
     ) -> Result<Response<Self::ListActionsStream>, Status> {
-        let actions = vec![Ok(ActionType {
-            r#type: "execute".to_string(),
-            description: "Execute a tool".to_string(),
-        })];
+        let actions = vec![
+            Ok(ActionType {
+                r#type: "execute".to_string(),
+                description: "Execute a tool".to_string(),
+            }),
+            Ok(ActionType {
+                r#type: HEALTH_ACTION.to_string(),
+                description: "Check that the server is alive".to_string(),
+            }),
+        ];
 
         let output = futures::stream::iter(actions);
         Ok(Response::new(Box::pin(output)))
     }
 }
+
+/// 序列化工具结果，超过 `max_bytes` 时把 `data` 截断为文本
+///
+/// 转义可能让截断后的文本变长，因此按实际序列化的大小逐步缩短，直到不超过上限。
+fn encode_result(mut result: ToolResult, max_bytes: usize) -> Vec<u8> {
+    let body = serde_json::to_vec(&result).unwrap();
+    if body.len() <= max_bytes {
+        return body;
+    }
+
+    let original_bytes = body.len();
+    warn!(
+        "工具结果大小 {} 字节超过上限 {} 字节，截断 data",
+        original_bytes, max_bytes
+    );
+    let text = match std::mem::take(&mut result.data) {
+        serde_json::Value::String(text) => text,
+        other => other.to_string(),
+    };
+    let note = format!(
+        "结果大小 {} 字节超过上限 {} 字节，data 已截断",
+        original_bytes, max_bytes
+    );
+    result.error = Some(match result.error.take() {
+        Some(error) => format!("{}；{}", error, note),
+        None => note,
+    });
+    result.truncated = true;
+
+    let overhead = serde_json::to_vec(&result).unwrap().len();
+    let mut kept = truncate_bytes_safe(&text, max_bytes.saturating_sub(overhead));
+    loop {
+        result.data = serde_json::Value::String(kept.to_string());
+        let body = serde_json::to_vec(&result).unwrap();
+        if body.len() <= max_bytes || kept.is_empty() {
+            return body;
+        }
+        let excess = body.len() - max_bytes;
+        kept = truncate_bytes_safe(kept, kept.len().saturating_sub(excess));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LogCaptureLayer;
+    use crate::tools::interface::ToolResult;
+    use crate::tools::rpc::client::ToolServiceError;
+    use crate::tools::rpc::testing::spawn_in_process;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// 只返回自身名称的工具
+    struct NamedTool(&'static str);
+
+    #[async_trait]
+    impl Tool for NamedTool {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            "测试工具"
+        }
+
+        async fn execute(&self, _params: ToolParameters) -> Result<ToolResult> {
+            Ok(ToolResult::success(serde_json::json!(self.0)))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn register_tool_from_tokio_tasks() {
+        let service = Arc::new(ToolsFlightService::new());
+        let handles: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| {
+                let service = service.clone();
+                tokio::spawn(async move { service.register_tool(Box::new(NamedTool(name))).await })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let tools = service.tools.read().await;
+        let mut names: Vec<_> = tools.iter().map(|tool| tool.name()).collect();
+        names.sort();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn with_tools_registers_at_construction() {
+        let service = ToolsFlightService::with_tools(vec![Box::new(NamedTool("a"))]);
+        let tools = service.tools.read().await;
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name(), "a");
+    }
+
+    /// 记录日志后失败的工具，参数 `error` 为 true 时返回错误而不是失败的结果
+    struct FailingTool;
+
+    #[async_trait]
+    impl Tool for FailingTool {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn description(&self) -> &str {
+            "测试工具"
+        }
+
+        async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+            tracing::info!("正在写入 {}", "/data/out.bin");
+            tracing::warn!(free_bytes = 0, "磁盘空间不足");
+            if params.args["error"] == true {
+                return Err(anyhow::anyhow!("写入失败"));
+            }
+            Ok(ToolResult::failure("写入失败"))
+        }
+    }
+
+    // 单线程运行时中服务端的任务与测试在同一线程，能看到这里设置的日志订阅者
+    #[tokio::test]
+    async fn failing_tool_logs_reach_the_client() {
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let service =
+            ToolsFlightService::with_tools(vec![Box::new(FailingTool)]).with_log_capture(10);
+        let (_server, client) = spawn_in_process(service).await.unwrap();
+
+        let params = ToolParameters {
+            name: "failing".to_string(),
+            args: serde_json::json!({}),
+        };
+        let result = client.execute_tool(params).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.logs.len(), 2);
+        assert!(result.logs[0].contains("正在写入 /data/out.bin"));
+        assert!(result.logs[1].starts_with("WARN"));
+        assert!(result.logs[1].contains("磁盘空间不足 free_bytes=0"));
+
+        // 工具返回错误时日志附在错误信息后面
+        let params = ToolParameters {
+            name: "failing".to_string(),
+            args: serde_json::json!({"error": true}),
+        };
+        let error = client.execute_tool(params).await.unwrap_err();
+        let error = error.downcast_ref::<ToolServiceError>().unwrap();
+        assert!(error.message.contains("写入失败"), "{}", error.message);
+        assert!(error.message.contains("磁盘空间不足"), "{}", error.message);
+    }
+
+    #[tokio::test]
+    async fn logs_are_not_captured_by_default() {
+        let subscriber = tracing_subscriber::registry().with(LogCaptureLayer::new());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let service = ToolsFlightService::with_tools(vec![Box::new(FailingTool)]);
+        let (_server, client) = spawn_in_process(service).await.unwrap();
+        let params = ToolParameters {
+            name: "failing".to_string(),
+            args: serde_json::json!({}),
+        };
+        assert!(client.execute_tool(params).await.unwrap().logs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn oversized_results_are_truncated_with_a_marker() {
+        use crate::tools::rpc::testing::FnTool;
+
+        let big = FnTool::new("big", |args: serde_json::Value| async move {
+            let size = args["size"].as_u64().unwrap() as usize;
+            Ok(ToolResult::success(serde_json::json!(
+                "匹配行\n".repeat(size)
+            )))
+        });
+        let service = ToolsFlightService::with_tools(vec![big.boxed()]).with_max_result_bytes(300);
+        let (_server, client) = spawn_in_process(service).await.unwrap();
+        let call = |size: u64| ToolParameters {
+            name: "big".to_string(),
+            args: serde_json::json!({ "size": size }),
+        };
+
+        // 未超过上限的结果原样返回
+        let small = client.execute_tool(call(3)).await.unwrap();
+        assert!(!small.truncated);
+        assert_eq!(small.error, None);
+        assert_eq!(small.data, serde_json::json!("匹配行\n".repeat(3)));
+
+        let result = client.execute_tool(call(10_000)).await.unwrap();
+        assert!(result.success);
+        assert!(result.truncated);
+        let note = result.error.as_deref().unwrap();
+        assert!(note.contains("data 已截断"), "{}", note);
+        assert!(note.contains("上限 300 字节"), "{}", note);
+        // 截断在字符边界上，保留开头的内容，序列化后不超过上限
+        let data = result.data.as_str().unwrap();
+        assert!(!data.is_empty());
+        assert!("匹配行\n".repeat(10_000).starts_with(data));
+        assert!(serde_json::to_vec(&result).unwrap().len() <= 300);
+    }
+
+    #[test]
+    fn truncation_keeps_the_tool_error_and_fits_escaped_text() {
+        let mut result = ToolResult::failure("部分失败");
+        result.data = serde_json::json!({ "lines": vec!["\"quoted\"\t"; 200] });
+
+        let body = encode_result(result, 256);
+        assert!(body.len() <= 256, "{}", body.len());
+        let result: ToolResult = serde_json::from_slice(&body).unwrap();
+        assert!(result.truncated);
+        let error = result.error.unwrap();
+        assert!(error.starts_with("部分失败；"), "{}", error);
+        // 非字符串的 data 被转换为 JSON 文本后截断
+        assert!(result.data.as_str().unwrap().starts_with("{\"lines\":["));
+    }
+
+    #[tokio::test]
+    async fn repeated_key_returns_the_same_truncated_body_without_executing_again() {
+        use crate::tools::rpc::testing::FnTool;
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let executions = Arc::new(AtomicUsize::new(0));
+        let tool = {
+            let executions = executions.clone();
+            FnTool::new("big", move |_| {
+                let run = executions.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Ok(ToolResult::success(serde_json::json!(format!(
+                        "{}{}",
+                        run,
+                        "x".repeat(1000)
+                    ))))
+                }
+            })
+        };
+        let service = ToolsFlightService::with_tools(vec![tool.boxed()]).with_max_result_bytes(200);
+
+        let call = || async {
+            let mut request = Request::new(Action {
+                r#type: "execute".into(),
+                body: serde_json::to_vec(&serde_json::json!({"name": "big", "args": {}}))
+                    .unwrap()
+                    .into(),
+            });
+            request
+                .metadata_mut()
+                .insert(IDEMPOTENCY_KEY_METADATA, "retry-key".parse().unwrap());
+            let mut stream = service.do_action(request).await.unwrap().into_inner();
+            stream.next().await.unwrap().unwrap().body
+        };
+
+        let first = call().await;
+        let second = call().await;
+        assert_eq!(executions.load(Ordering::SeqCst), 1);
+        assert_eq!(first, second);
+        assert!(first.len() <= 200);
+        let result: ToolResult = serde_json::from_slice(&first).unwrap();
+        assert!(result.truncated);
+    }
+}
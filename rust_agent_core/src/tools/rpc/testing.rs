@@ -0,0 +1,184 @@
+//! 进程内的 Flight 服务，便于测试工具调用的完整 RPC 路径
+//!
+//! 本 crate 的测试中直接可用，其他 crate 需要启用 `test-util` 特性。
+
+use anyhow::Result;
+use arrow_flight::flight_service_server::FlightServiceServer;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+
+use super::client::ToolsClient;
+use super::server::ToolsFlightService;
+use crate::tools::interface::{Tool, ToolParameters, ToolResult};
+
+/// 运行在当前进程中的工具服务
+///
+/// 丢弃时服务随之停止。
+pub struct InProcessServer {
+    addr: String,
+    handle: JoinHandle<()>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl InProcessServer {
+    /// 在本机的随机端口上启动服务
+    pub async fn start(service: ToolsFlightService) -> Result<Self> {
+        Self::start_shared(Arc::new(service)).await
+    }
+
+    /// 启动共享的服务，调用方可以在服务运行期间继续注册工具
+    pub async fn start_shared(service: Arc<ToolsFlightService>) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = format!("http://{}", listener.local_addr()?);
+
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            let incoming = TcpListenerStream::new(listener);
+            let stopped = async {
+                stopped.await.ok();
+            };
+            if let Err(e) = Server::builder()
+                .add_service(FlightServiceServer::from_arc(service))
+                .serve_with_incoming_shutdown(incoming, stopped)
+                .await
+            {
+                tracing::error!("进程内工具服务运行出错: {}", e);
+            }
+        });
+
+        Ok(Self {
+            addr,
+            handle,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// 停止服务并关闭已建立的连接，之后客户端的调用会因无法连接而失败
+    pub async fn stop(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        (&mut self.handle).await.ok();
+    }
+
+    /// 服务地址，形如 `http://127.0.0.1:port`
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// 创建连接到该服务的客户端
+    pub async fn connect(&self) -> Result<ToolsClient> {
+        ToolsClient::connect(&self.addr).await
+    }
+}
+
+impl Drop for InProcessServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// 启动进程内服务并返回已连接的客户端
+pub async fn spawn_in_process(
+    service: ToolsFlightService,
+) -> Result<(InProcessServer, ToolsClient)> {
+    let server = InProcessServer::start(service).await?;
+    let client = server.connect().await?;
+    Ok((server, client))
+}
+
+/// 由闭包实现的工具，闭包收到调用的 `args` 并返回工具结果
+pub struct FnTool<F> {
+    name: String,
+    description: String,
+    parameters: Option<serde_json::Value>,
+    handler: F,
+}
+
+impl<F, Fut> FnTool<F>
+where
+    F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ToolResult>> + Send + 'static,
+{
+    pub fn new(name: impl Into<String>, handler: F) -> Self {
+        Self {
+            name: name.into(),
+            description: "测试工具".to_string(),
+            parameters: None,
+            handler,
+        }
+    }
+
+    /// 设置工具描述
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// 设置参数的 JSON Schema
+    pub fn with_parameters(mut self, parameters: serde_json::Value) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// 转换为可以注册到服务的工具
+    pub fn boxed(self) -> Box<dyn Tool> {
+        Box::new(self)
+    }
+}
+
+#[async_trait]
+impl<F, Fut> Tool for FnTool<F>
+where
+    F: Fn(serde_json::Value) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ToolResult>> + Send + 'static,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        self.parameters.clone()
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        (self.handler)(params.args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn in_process_server_executes_fn_tools() {
+        let echo = FnTool::new("echo", |args| async move { Ok(ToolResult::success(args)) });
+        let (server, client) = spawn_in_process(ToolsFlightService::with_tools(vec![echo.boxed()]))
+            .await
+            .unwrap();
+        assert!(server.addr().starts_with("http://127.0.0.1:"));
+        assert_eq!(client.list_tools().await.unwrap(), ["echo"]);
+
+        let params = ToolParameters {
+            name: "echo".to_string(),
+            args: json!({"text": "你好"}),
+        };
+        let result = client.execute_tool(params).await.unwrap();
+        assert_eq!(result.data, json!({"text": "你好"}));
+
+        // 同一个服务可以有多个客户端
+        let other = server.connect().await.unwrap();
+        assert_eq!(other.list_tools().await.unwrap(), ["echo"]);
+    }
+}
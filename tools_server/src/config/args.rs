@@ -0,0 +1,118 @@
+use clap::Parser;
+use rust_agent_core::tools::rpc::server::DEFAULT_MAX_RESULT_BYTES;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// 工具清单文件（TOML 或 JSON），只注册清单中启用的工具
+    #[arg(long)]
+    pub manifest: Option<PathBuf>,
+
+    /// 只启用指定的工具（逗号分隔），未设置时启用全部工具
+    #[arg(long, value_delimiter = ',')]
+    pub enable_tools: Option<Vec<String>>,
+
+    /// 禁用指定的工具（逗号分隔），优先级高于 --enable-tools
+    #[arg(long, value_delimiter = ',')]
+    pub disable_tools: Vec<String>,
+
+    /// 文件类工具只允许访问的根目录（逗号分隔），未设置时不限制
+    #[arg(long, value_delimiter = ',')]
+    pub allowed_roots: Vec<PathBuf>,
+
+    /// 把工具执行日志随结果返回给客户端，便于排查失败原因；日志可能包含敏感信息
+    #[arg(long)]
+    pub debug_logs: bool,
+
+    /// 随结果返回的日志最大行数
+    #[arg(long, default_value_t = 50)]
+    pub debug_log_lines: usize,
+
+    /// 把每次工具调用（时间、调用方、工具、参数、是否成功、耗时）以 NDJSON 格式追加到该文件
+    #[arg(long)]
+    pub audit_log: Option<PathBuf>,
+
+    /// 审计日志中需要脱敏的参数名称（逗号分隔），如 path,token
+    #[arg(long, value_delimiter = ',')]
+    pub audit_redact: Vec<String>,
+
+    /// 单个工具结果的最大字节数，超过时截断结果数据并标记 truncated
+    #[arg(long, default_value_t = DEFAULT_MAX_RESULT_BYTES)]
+    pub max_result_bytes: usize,
+
+    /// 网络类工具的出站代理地址
+    #[arg(long)]
+    pub http_proxy: Option<String>,
+
+    /// 网络类工具的请求超时时间（秒），默认 15 秒
+    #[arg(long)]
+    pub http_timeout_secs: Option<u64>,
+
+    /// 网络类工具请求时使用的 User-Agent
+    #[arg(long)]
+    pub http_user_agent: Option<String>,
+
+    /// 跳过 TLS 证书校验，只应在调试时使用
+    #[arg(long)]
+    pub http_insecure: bool,
+
+    /// 服务自身及智能体的地址（逗号分隔，host 或 host:port），网络类工具请求这些地址时会被拒绝，避免循环调用
+    #[arg(long, value_delimiter = ',')]
+    pub self_addresses: Vec<String>,
+
+    /// 网页读取工具只允许访问的主机（逗号分隔），未设置时不限制
+    #[arg(long, value_delimiter = ',')]
+    pub fetch_allowed_hosts: Vec<String>,
+
+    /// 网页读取工具禁止访问的主机（逗号分隔）
+    #[arg(long, value_delimiter = ',')]
+    pub fetch_denied_hosts: Vec<String>,
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Self::parse()
+    }
+
+    /// 判断工具是否允许注册
+    pub fn is_tool_enabled(&self, name: &str) -> bool {
+        if self.disable_tools.iter().any(|t| t == name) {
+            return false;
+        }
+
+        match &self.enable_tools {
+            Some(enabled) => enabled.iter().any(|t| t == name),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(std::iter::once("tools_server").chain(flags.iter().copied()))
+    }
+
+    #[test]
+    fn all_tools_are_enabled_by_default() {
+        let config = args(&[]);
+        assert!(config.is_tool_enabled("file_tool"));
+        assert!(config.is_tool_enabled("web_search"));
+    }
+
+    #[test]
+    fn disable_takes_precedence_over_enable() {
+        let config = args(&[
+            "--enable-tools",
+            "file_tool,web_search",
+            "--disable-tools",
+            "file_tool",
+        ]);
+        assert!(!config.is_tool_enabled("file_tool"));
+        assert!(config.is_tool_enabled("web_search"));
+        assert!(!config.is_tool_enabled("file_analyzer"));
+    }
+}
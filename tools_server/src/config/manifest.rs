@@ -0,0 +1,173 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 所有内置工具的名称
+pub const BUILTIN_TOOLS: &[&str] = &[
+    "file_analyzer",
+    "file_tool",
+    "web_search",
+    "fetch_page",
+    "file_hash",
+    "pdf_text",
+    "diff",
+    "workspace",
+    "tree",
+    "grep",
+    "encoding",
+];
+
+/// 工具清单
+///
+/// 描述要注册哪些内置工具以及各自的选项，例如：
+///
+/// ```toml
+/// allowed_roots = ["/data"]
+///
+/// [[tools]]
+/// name = "file_analyzer"
+///
+/// [[tools]]
+/// name = "fetch_page"
+/// options = { allowed_hosts = ["docs.rs"] }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolManifest {
+    /// 文件类工具允许访问的根目录，与命令行参数合并
+    #[serde(default)]
+    pub allowed_roots: Vec<PathBuf>,
+    #[serde(default)]
+    pub tools: Vec<ToolEntry>,
+}
+
+/// 清单中的单个工具
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolEntry {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 工具选项，各工具自行解析
+    #[serde(default)]
+    pub options: Value,
+}
+
+/// 没有可配置选项的工具使用，清单中出现任何选项都会报错
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoOptions {}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl ToolManifest {
+    /// 读取清单文件，按扩展名选择格式（`.json` 为 JSON，其余按 TOML 解析）
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("无法读取工具清单: {:?}", path))?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let manifest: Self = if is_json {
+            serde_json::from_str(&content)
+                .with_context(|| format!("工具清单格式错误: {:?}", path))?
+        } else {
+            toml::from_str(&content).with_context(|| format!("工具清单格式错误: {:?}", path))?
+        };
+
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// 检查清单中的工具名称
+    fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        for entry in &self.tools {
+            if !BUILTIN_TOOLS.contains(&entry.name.as_str()) {
+                return Err(anyhow!(
+                    "工具清单中包含未知工具: {}（可用工具: {}）",
+                    entry.name,
+                    BUILTIN_TOOLS.join(", ")
+                ));
+            }
+            if !seen.insert(entry.name.as_str()) {
+                return Err(anyhow!("工具清单中重复配置了工具: {}", entry.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// 获取已启用的工具配置，未列出或已禁用时返回 None
+    pub fn enabled_entry(&self, name: &str) -> Option<&ToolEntry> {
+        self.tools.iter().find(|e| e.name == name && e.enabled)
+    }
+}
+
+impl ToolEntry {
+    /// 把选项解析为工具自己的选项类型，未设置时使用默认值
+    pub fn options<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        if self.options.is_null() {
+            return Ok(T::default());
+        }
+
+        serde_json::from_value(self.options.clone())
+            .with_context(|| format!("工具 {} 的选项无效", self.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Args;
+    use crate::tools::build_tools;
+    use clap::Parser;
+    use std::path::PathBuf;
+
+    fn write_manifest(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn manifest_enabling_only_file_analyzer_registers_only_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_manifest(
+            dir.path(),
+            "tools.toml",
+            r#"
+            [[tools]]
+            name = "file_analyzer"
+
+            [[tools]]
+            name = "web_search"
+            enabled = false
+            "#,
+        );
+
+        let manifest = ToolManifest::load(&path).unwrap();
+        let tools = build_tools(&Args::parse_from(["tools_server"]), Some(&manifest)).unwrap();
+        let names: Vec<_> = tools.iter().map(|tool| tool.name()).collect();
+        assert_eq!(names, ["file_analyzer"]);
+    }
+
+    #[test]
+    fn unknown_tool_name_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_manifest(
+            dir.path(),
+            "tools.json",
+            r#"{"tools": [{"name": "shell"}]}"#,
+        );
+
+        let err = ToolManifest::load(&path).unwrap_err().to_string();
+        assert!(err.contains("未知工具: shell"), "{}", err);
+    }
+}
@@ -0,0 +1,9 @@
+mod args;
+mod manifest;
+
+pub use args::Args;
+pub use manifest::{NoOptions, ToolEntry, ToolManifest, BUILTIN_TOOLS};
+
+pub fn get_config() -> Args {
+    Args::new()
+}
@@ -1,53 +1,66 @@
+mod config;
 mod tools;
 
 use anyhow::Result;
 use arrow_flight::flight_service_server::FlightServiceServer;
 use rust_agent_core::{
     logging::{init_logger, LoggerConfig},
-    tools::rpc::server::ToolsFlightService,
+    paths::AgentDirs,
+    tools::rpc::{audit::AuditLog, server::ToolsFlightService},
 };
 use tonic::transport::Server;
 use tracing::{error, info, Level};
 
-use crate::tools::{FileAnalyzerTool, FileTool, WebSearchTool};
+use crate::config::ToolManifest;
+use crate::tools::build_tools;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let config = config::get_config();
+
     // 创建日志目录
-    tokio::fs::create_dir_all("logs").await?;
+    let log_dir = AgentDirs::resolve().log_dir();
+    tokio::fs::create_dir_all(&log_dir).await?;
 
     // 初始化日志系统
-    let log_config =
-        LoggerConfig::new("logs", "tools_server", Level::DEBUG).with_console_output(true);
+    let log_config = LoggerConfig::new(log_dir.to_string_lossy(), "tools_server", Level::DEBUG)
+        .with_console_output(true);
 
     // 初始化日志系统
-    if let Err(e) = init_logger(log_config) {
-        eprintln!("日志系统初始化失败: {}", e);
-        return Err(anyhow::anyhow!("日志系统初始化失败: {}", e));
-    }
+    // 持有守卫直到程序退出，退出时写出缓冲中的日志
+    let _log_guard = match init_logger(log_config) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("日志系统初始化失败: {}", e);
+            return Err(anyhow::anyhow!("日志系统初始化失败: {}", e));
+        }
+    };
 
     info!("工具服务器正在启动...");
 
-    // 创建服务实例
-    let service = ToolsFlightService::new();
-
-    // 注册文件分析工具
-    service
-        .register_tool(Box::new(FileAnalyzerTool::new()))
-        .await;
-    info!("已注册文件分析工具");
-
-    // 注册文件处理工具
-    if let Ok(file_tool) = FileTool::new() {
-        service.register_tool(Box::new(file_tool)).await;
-        info!("已注册文件处理工具");
-    } else {
-        error!("文件处理工具初始化失败");
+    // 按清单和命令行参数创建工具
+    let manifest = config
+        .manifest
+        .as_deref()
+        .map(ToolManifest::load)
+        .transpose()?;
+    if let Some(path) = &config.manifest {
+        info!("使用工具清单: {:?}", path);
     }
+    let tools = build_tools(&config, manifest.as_ref())?;
 
-    // 注册网络搜索工具
-    service.register_tool(Box::new(WebSearchTool::new())).await;
-    info!("已注册网络搜索工具");
+    // 创建服务实例
+    let mut service =
+        ToolsFlightService::with_tools(tools).with_max_result_bytes(config.max_result_bytes);
+    if config.debug_logs {
+        info!("已开启调试日志，工具执行日志将返回给客户端");
+        service = service.with_log_capture(config.debug_log_lines);
+    }
+    if let Some(path) = &config.audit_log {
+        info!("工具调用审计日志: {:?}", path);
+        let audit = AuditLog::open(path)?.with_redacted_keys(config.audit_redact.iter().cloned());
+        service = service.with_audit_log(audit);
+    }
 
     // 启动服务器
     let addr = "[::1]:50051".parse()?;
@@ -55,7 +68,7 @@ async fn main() -> Result<()> {
 
     match Server::builder()
         .add_service(FlightServiceServer::new(service))
-        .serve(addr)
+        .serve_with_shutdown(addr, shutdown_signal())
         .await
     {
         Ok(_) => info!("服务器正常关闭"),
@@ -64,3 +77,34 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// 等待 Ctrl-C 或 SIGTERM，收到后停止接受新请求，让工具有机会清理临时文件
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("无法监听 Ctrl-C 信号: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("无法监听 SIGTERM 信号: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("收到退出信号，正在关闭服务器...");
+}
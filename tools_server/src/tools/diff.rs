@@ -0,0 +1,331 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use rust_agent_core::text::truncate_bytes_safe;
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use super::PathSandbox;
+
+/// 默认的上下文行数
+const DEFAULT_CONTEXT_LINES: usize = 3;
+/// 默认的差异输出上限
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 64 * 1024;
+/// 允许比较的单个文件大小上限
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// 检查是否包含空字节的前缀长度
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// 清单中 diff 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiffOptions {
+    /// 差异输出的最大字节数
+    pub max_output_bytes: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffParams {
+    old_path: String,
+    new_path: String,
+    /// 每处改动前后保留的上下文行数
+    #[serde(default)]
+    context_lines: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffResult {
+    old_path: String,
+    new_path: String,
+    /// 两个文件内容是否完全相同
+    identical: bool,
+    /// 任一文件是否为二进制文件，此时不比较具体内容
+    binary: bool,
+    /// 新增的行数
+    added: usize,
+    /// 删除的行数
+    removed: usize,
+    /// 统一格式的差异，二进制文件为简短说明
+    diff: String,
+    /// 差异输出是否因超过上限被截断
+    truncated: bool,
+}
+
+/// 文件对比工具
+///
+/// 比较两个文本文件并返回统一格式（unified diff）的差异，任一文件为二进制时只报告是否相同。
+pub struct DiffTool {
+    sandbox: PathSandbox,
+    max_output_bytes: usize,
+}
+
+impl DiffTool {
+    pub fn new() -> Self {
+        Self {
+            sandbox: PathSandbox::default(),
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+        }
+    }
+
+    pub fn with_options(mut self, options: DiffOptions) -> Self {
+        if let Some(max_output_bytes) = options.max_output_bytes {
+            self.max_output_bytes = max_output_bytes;
+        }
+        self
+    }
+
+    /// 限制可访问的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 检查路径是否允许访问，并确认是大小在上限内的普通文件
+    fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        let path = self.sandbox.check_existing(Path::new(path))?;
+        if !path.is_file() {
+            return Err(anyhow!("不是普通文件: {:?}", path));
+        }
+
+        let size = path.metadata()?.len();
+        if size > MAX_FILE_BYTES {
+            return Err(anyhow!(
+                "文件过大: {:?}（{} 字节，上限 {} 字节）",
+                path,
+                size,
+                MAX_FILE_BYTES
+            ));
+        }
+
+        Ok(path)
+    }
+}
+
+/// 包含空字节或不是有效 UTF-8 的内容视为二进制
+fn as_text(content: &[u8]) -> Option<&str> {
+    let sniff = &content[..content.len().min(BINARY_SNIFF_BYTES)];
+    if sniff.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// 读取两个文件并生成差异
+fn diff_files(
+    old: &Path,
+    new: &Path,
+    params: &DiffParams,
+    max_output_bytes: usize,
+) -> Result<DiffResult> {
+    let old_content = std::fs::read(old).with_context(|| format!("无法读取文件: {:?}", old))?;
+    let new_content = std::fs::read(new).with_context(|| format!("无法读取文件: {:?}", new))?;
+    let identical = old_content == new_content;
+
+    let mut result = DiffResult {
+        old_path: params.old_path.clone(),
+        new_path: params.new_path.clone(),
+        identical,
+        binary: false,
+        added: 0,
+        removed: 0,
+        diff: String::new(),
+        truncated: false,
+    };
+
+    let (Some(old_text), Some(new_text)) = (as_text(&old_content), as_text(&new_content)) else {
+        result.binary = true;
+        if !identical {
+            result.diff = format!(
+                "Binary files {} and {} differ",
+                params.old_path, params.new_path
+            );
+        }
+        return Ok(result);
+    };
+
+    let diff = TextDiff::from_lines(old_text, new_text);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => result.added += 1,
+            ChangeTag::Delete => result.removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    let unified = diff
+        .unified_diff()
+        .context_radius(params.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES))
+        .header(&params.old_path, &params.new_path)
+        .to_string();
+    let kept = truncate_bytes_safe(&unified, max_output_bytes);
+    result.truncated = kept.len() < unified.len();
+    result.diff = kept.to_string();
+
+    Ok(result)
+}
+
+#[async_trait]
+impl Tool for DiffTool {
+    fn name(&self) -> &str {
+        "diff"
+    }
+
+    fn description(&self) -> &str {
+        "比较两个文本文件，返回统一格式（unified diff）的差异。返回两个文件路径 old_path 和 new_path、是否相同 identical、\
+是否为二进制文件 binary、新增行数 added、删除行数 removed、差异内容 diff 以及差异是否被截断 truncated"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "old_path": {"type": "string", "description": "原文件路径"},
+                "new_path": {"type": "string", "description": "新文件路径"},
+                "context_lines": {"type": "integer", "minimum": 0, "description": "每处改动前后保留的上下文行数，默认 3"}
+            },
+            "required": ["old_path", "new_path"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"old_path": "/tmp/config.old.toml", "new_path": "/tmp/config.toml"}),
+        ]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行文件对比工具，参数: {:?}", params);
+
+        // 解析参数
+        let params: DiffParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let paths = self
+            .resolve_path(&params.old_path)
+            .and_then(|old| Ok((old, self.resolve_path(&params.new_path)?)));
+        let (old, new) = match paths {
+            Ok(paths) => paths,
+            Err(e) => {
+                error!("文件路径无效: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        // 读取和比较大文件耗时较长，放到阻塞线程中执行
+        let max_output_bytes = self.max_output_bytes;
+        let outcome =
+            tokio::task::spawn_blocking(move || diff_files(&old, &new, &params, max_output_bytes))
+                .await?;
+
+        match outcome {
+            Ok(result) => {
+                info!(
+                    "文件对比完成: +{} -{}{}",
+                    result.added,
+                    result.removed,
+                    if result.binary { "（二进制）" } else { "" }
+                );
+                Ok(ToolResult::success(serde_json::to_value(result)?))
+            }
+            Err(e) => {
+                error!("文件对比失败: {}", e);
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    async fn run(tool: &DiffTool, args: serde_json::Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "diff".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn text_files_produce_a_unified_diff_with_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.txt");
+        let new = dir.path().join("new.txt");
+        fs::write(&old, "一\n二\n三\n四\n五\n六\n七\n").unwrap();
+        fs::write(&new, "一\n二\n3\n四\n五\n六\n七\n八\n").unwrap();
+        let tool = DiffTool::new().with_sandbox(PathSandbox::new(vec![dir.path().into()]).unwrap());
+
+        let result = run(
+            &tool,
+            json!({"old_path": old, "new_path": new, "context_lines": 1}),
+        )
+        .await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data["added"], 2);
+        assert_eq!(result.data["removed"], 1);
+        assert_eq!(result.data["identical"], false);
+        assert_eq!(result.data["binary"], false);
+        assert_eq!(result.data["truncated"], false);
+
+        let diff = result.data["diff"].as_str().unwrap();
+        assert!(diff.starts_with(&format!("--- {}\n+++ {}\n", old.display(), new.display())));
+        assert!(diff.contains(" 二\n-三\n+3\n 四\n"), "{}", diff);
+        assert!(diff.contains(" 七\n+八\n"), "{}", diff);
+        // 上下文只有一行，相距较远的第一行不出现在差异中
+        assert!(!diff.contains(" 一\n"), "{}", diff);
+    }
+
+    #[tokio::test]
+    async fn binary_files_only_report_that_they_differ() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.bin");
+        let new = dir.path().join("new.bin");
+        fs::write(&old, [0u8, 1, 2, 3]).unwrap();
+        fs::write(&new, [0u8, 1, 2, 4]).unwrap();
+
+        let result = run(&DiffTool::new(), json!({"old_path": old, "new_path": new})).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data["binary"], true);
+        assert_eq!(
+            result.data["diff"],
+            format!(
+                "Binary files {} and {} differ",
+                old.display(),
+                new.display()
+            )
+        );
+        assert_eq!(result.data["added"], 0);
+    }
+
+    #[tokio::test]
+    async fn output_is_capped_and_paths_outside_the_sandbox_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.txt");
+        let new = dir.path().join("new.txt");
+        fs::write(&old, "旧\n".repeat(100)).unwrap();
+        fs::write(&new, "新\n".repeat(100)).unwrap();
+
+        let tool = DiffTool::new().with_options(DiffOptions {
+            max_output_bytes: Some(101),
+        });
+        let result = run(&tool, json!({"old_path": old, "new_path": new})).await;
+        assert_eq!(result.data["truncated"], true);
+        assert!(result.data["diff"].as_str().unwrap().len() <= 101);
+
+        let other = tempfile::tempdir().unwrap();
+        let tool =
+            DiffTool::new().with_sandbox(PathSandbox::new(vec![other.path().into()]).unwrap());
+        let result = run(&tool, json!({"old_path": old, "new_path": new})).await;
+        assert!(result.is_invalid_args(), "{:?}", result.error);
+    }
+}
@@ -0,0 +1,333 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, Engine, GeneralPurpose, GeneralPurposeConfig};
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{error, info};
+
+use super::PathSandbox;
+
+/// 读取的文件大小上限
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// 编码时补齐填充，解码时填充可有可无
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// 支持的编码方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    Base64,
+    Hex,
+    Url,
+}
+
+/// 编码或解码
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Encode,
+    Decode,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EncodingParams {
+    operation: Operation,
+    encoding: Encoding,
+    /// 直接提供的文本，与 path 二选一
+    #[serde(default)]
+    input: Option<String>,
+    /// 读取内容的文件，与 input 二选一
+    #[serde(default)]
+    path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncodingResult {
+    operation: Operation,
+    encoding: Encoding,
+    /// 转换后的文本
+    result: String,
+    /// 输入的字节数
+    input_bytes: usize,
+}
+
+pub struct EncodingTool {
+    sandbox: PathSandbox,
+}
+
+impl EncodingTool {
+    pub fn new() -> Self {
+        Self {
+            sandbox: PathSandbox::default(),
+        }
+    }
+
+    /// 限制可访问的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 取出要转换的内容，文件需要在允许访问的目录中且不超过大小上限
+    fn read_input(&self, params: &EncodingParams) -> Result<Vec<u8>> {
+        match (&params.input, &params.path) {
+            (Some(input), None) => Ok(input.as_bytes().to_vec()),
+            (None, Some(path)) => {
+                let path = self.sandbox.check_existing(Path::new(path))?;
+                if !path.is_file() {
+                    return Err(anyhow!("不是普通文件: {:?}", path));
+                }
+                let size = path.metadata()?.len();
+                if size > MAX_FILE_BYTES {
+                    return Err(anyhow!(
+                        "文件大小 {} 字节超过上限 {} 字节",
+                        size,
+                        MAX_FILE_BYTES
+                    ));
+                }
+                std::fs::read(&path).with_context(|| format!("无法读取文件: {:?}", path))
+            }
+            _ => Err(anyhow!("input 和 path 需要且只能提供一个")),
+        }
+    }
+}
+
+/// 把字节编码为文本
+fn encode(encoding: Encoding, data: &[u8]) -> String {
+    match encoding {
+        Encoding::Base64 => BASE64.encode(data),
+        Encoding::Hex => data.iter().map(|b| format!("{:02x}", b)).collect(),
+        Encoding::Url => urlencoding::encode_binary(data).into_owned(),
+    }
+}
+
+/// 把文本解码为字节，输入不合法时返回说明原因的错误
+fn decode(encoding: Encoding, data: &[u8]) -> Result<Vec<u8>> {
+    // base64 和 hex 常被换行分段，解码前去掉空白
+    let compact = || -> Vec<u8> {
+        data.iter()
+            .copied()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect()
+    };
+    match encoding {
+        Encoding::Base64 => BASE64
+            .decode(compact())
+            .map_err(|e| anyhow!("输入不是合法的 base64: {}", e)),
+        Encoding::Hex => decode_hex(&compact()),
+        Encoding::Url => Ok(urlencoding::decode_binary(data).into_owned()),
+    }
+}
+
+fn decode_hex(data: &[u8]) -> Result<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return Err(anyhow!("输入不是合法的 hex: 长度 {} 不是偶数", data.len()));
+    }
+
+    data.chunks(2)
+        .enumerate()
+        .map(|(index, pair)| {
+            let digits = std::str::from_utf8(pair).ok();
+            digits
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "输入不是合法的 hex: 第 {} 个字节 {:?} 不是十六进制数",
+                        index + 1,
+                        String::from_utf8_lossy(pair)
+                    )
+                })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Tool for EncodingTool {
+    fn name(&self) -> &str {
+        "encoding"
+    }
+
+    fn description(&self) -> &str {
+        "对文本或文件内容进行 base64、hex 或 URL 编码和解码。返回操作 operation、编码方式 encoding、转换结果 result 和输入的字节数 input_bytes；解码结果必须是 UTF-8 文本"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["encode", "decode"],
+                    "description": "编码还是解码"
+                },
+                "encoding": {
+                    "type": "string",
+                    "enum": ["base64", "hex", "url"],
+                    "description": "编码方式"
+                },
+                "input": {"type": "string", "description": "要转换的文本，与 path 二选一"},
+                "path": {"type": "string", "description": "要转换内容的文件路径，与 input 二选一"}
+            },
+            "required": ["operation", "encoding"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"operation": "decode", "encoding": "base64", "input": "aGVsbG8="}),
+            serde_json::json!({"operation": "encode", "encoding": "hex", "path": "/tmp/data.bin"}),
+        ]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行编码转换工具，参数: {:?}", params);
+
+        let params: EncodingParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let input = match self.read_input(&params) {
+            Ok(input) => input,
+            Err(e) => {
+                error!("无法读取输入: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let result = match params.operation {
+            Operation::Encode => encode(params.encoding, &input),
+            Operation::Decode => {
+                let decoded = match decode(params.encoding, &input) {
+                    Ok(decoded) => decoded,
+                    Err(e) => {
+                        error!("解码失败: {}", e);
+                        return Ok(ToolResult::invalid_args(e.to_string()));
+                    }
+                };
+                match String::from_utf8(decoded) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        return Ok(ToolResult::failure(format!(
+                            "解码结果不是 UTF-8 文本（共 {} 字节），无法作为文本返回",
+                            e.as_bytes().len()
+                        )));
+                    }
+                }
+            }
+        };
+
+        info!(
+            "编码转换完成: {:?} {:?}，输入 {} 字节",
+            params.operation,
+            params.encoding,
+            input.len()
+        );
+        let result = EncodingResult {
+            operation: params.operation,
+            encoding: params.encoding,
+            result,
+            input_bytes: input.len(),
+        };
+        Ok(ToolResult::success(serde_json::to_value(result)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    async fn convert(tool: &EncodingTool, args: Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "encoding".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn base64_hex_and_url_round_trip() {
+        let tool = EncodingTool::new();
+        let text = "你好, agent?&=/";
+
+        for (encoding, encoded) in [
+            ("base64", "5L2g5aW9LCBhZ2VudD8mPS8="),
+            ("hex", "e4bda0e5a5bd2c206167656e743f263d2f"),
+            ("url", "%E4%BD%A0%E5%A5%BD%2C%20agent%3F%26%3D%2F"),
+        ] {
+            let args = json!({"operation": "encode", "encoding": encoding, "input": text});
+            let result = convert(&tool, args).await;
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(result.data["result"], encoded, "{}", encoding);
+            assert_eq!(result.data["input_bytes"], text.len());
+
+            let args = json!({"operation": "decode", "encoding": encoding, "input": encoded});
+            let result = convert(&tool, args).await;
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(result.data["result"], text, "{}", encoding);
+        }
+
+        // 被换行分段、省略填充或使用大写的输入同样可以解码
+        for (encoding, input) in [("base64", "aGVs\nbG8"), ("hex", "68 65 6C\n6C 6F")] {
+            let args = json!({"operation": "decode", "encoding": encoding, "input": input});
+            assert_eq!(convert(&tool, args).await.data["result"], "hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_input_is_a_clear_error() {
+        let tool = EncodingTool::new();
+        for (encoding, input, message) in [
+            ("base64", "aGVsbG8*", "输入不是合法的 base64"),
+            ("hex", "abc", "长度 3 不是偶数"),
+            ("hex", "zz", "第 1 个字节 \"zz\" 不是十六进制数"),
+        ] {
+            let args = json!({"operation": "decode", "encoding": encoding, "input": input});
+            let result = convert(&tool, args).await;
+            assert!(result.is_invalid_args(), "{}", input);
+            let error = result.error.unwrap();
+            assert!(error.contains(message), "{}", error);
+        }
+
+        // 解码结果不是文本时说明原因
+        let args = json!({"operation": "decode", "encoding": "base64", "input": "/w=="});
+        let result = convert(&tool, args).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("不是 UTF-8 文本"));
+
+        // input 和 path 必须且只能提供一个
+        let args = json!({"operation": "encode", "encoding": "hex"});
+        assert!(convert(&tool, args).await.is_invalid_args());
+    }
+
+    #[tokio::test]
+    async fn files_are_read_only_inside_the_allowed_roots() {
+        let allowed = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let inside = allowed.path().join("data.bin");
+        std::fs::write(&inside, b"hi").unwrap();
+        let other = outside.path().join("data.bin");
+        std::fs::write(&other, b"hi").unwrap();
+        let sandbox = PathSandbox::new(vec![allowed.path().to_path_buf()]).unwrap();
+        let tool = EncodingTool::new().with_sandbox(sandbox);
+
+        let args = json!({"operation": "encode", "encoding": "hex", "path": inside});
+        let result = convert(&tool, args).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data["result"], "6869");
+
+        let args = json!({"operation": "encode", "encoding": "hex", "path": other});
+        let result = convert(&tool, args).await;
+        assert!(result.is_invalid_args());
+    }
+}
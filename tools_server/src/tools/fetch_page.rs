@@ -0,0 +1,425 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::Url;
+use rust_agent_core::text::truncate_chars;
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use super::{check_status, HttpClientFactory};
+
+/// 默认返回的最大文本长度（字符数）
+const DEFAULT_MAX_LENGTH: usize = 5000;
+/// 下载页面的最大字节数
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// 不参与正文提取的标签
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "nav", "header", "footer", "aside", "form", "iframe", "svg",
+    "button", "template",
+];
+
+/// class / id 中出现这些词的元素视为页面杂项（广告、导航等）
+const BOILERPLATE_HINTS: &[&str] = &[
+    "ad",
+    "ads",
+    "advert",
+    "advertisement",
+    "banner",
+    "breadcrumb",
+    "comment",
+    "comments",
+    "cookie",
+    "menu",
+    "nav",
+    "navbar",
+    "popup",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+];
+
+/// 块级标签，提取文本时在前后换行
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "li",
+    "ul",
+    "ol",
+    "tr",
+    "table",
+    "blockquote",
+    "pre",
+    "br",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FetchPageParams {
+    url: String,
+    max_length: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FetchPageResult {
+    url: String,
+    title: String,
+    text: String,
+    truncated: bool,
+}
+
+/// 清单中 fetch_page 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FetchPageOptions {
+    /// 只允许访问的主机，为空表示不限制
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// 追加禁止访问的主机
+    #[serde(default)]
+    pub denied_hosts: Vec<String>,
+    /// 请求超时时间（秒）
+    pub timeout_secs: Option<u64>,
+}
+
+pub struct FetchPageTool {
+    allowed_hosts: Vec<String>,
+    denied_hosts: Vec<String>,
+    http: HttpClientFactory,
+}
+
+impl FetchPageTool {
+    pub fn new() -> Self {
+        Self {
+            allowed_hosts: Vec::new(),
+            denied_hosts: vec!["localhost".to_string()],
+            http: HttpClientFactory::new(),
+        }
+    }
+
+    /// 使用服务配置的出站 HTTP 策略
+    pub fn with_http(mut self, http: HttpClientFactory) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// 应用清单中的选项
+    pub fn with_options(mut self, options: FetchPageOptions) -> Self {
+        self = self
+            .with_allowed_hosts(options.allowed_hosts)
+            .with_denied_hosts(options.denied_hosts);
+        if let Some(secs) = options.timeout_secs {
+            self.http = self.http.with_timeout(Duration::from_secs(secs));
+        }
+        self
+    }
+
+    /// 只允许访问指定主机（包括其子域名），为空表示不限制
+    pub fn with_allowed_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.allowed_hosts = hosts;
+        self
+    }
+
+    /// 追加禁止访问的主机（包括其子域名）
+    pub fn with_denied_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.denied_hosts.extend(hosts);
+        self
+    }
+
+    /// 检查 URL 是否允许访问
+    async fn check_url(&self, url: &Url) -> Result<()> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("只支持 http/https 协议: {}", url.scheme()));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("URL 缺少主机名"))?
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_lowercase();
+
+        // 禁止访问本机和内网地址
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            let is_private = match ip {
+                IpAddr::V4(v4) => {
+                    v4.is_loopback() || v4.is_private() || v4.is_link_local() || v4.is_unspecified()
+                }
+                IpAddr::V6(v6) => v6.is_loopback() || v6.is_unspecified(),
+            };
+            if is_private {
+                return Err(anyhow!("不允许访问本机或内网地址: {}", host));
+            }
+        }
+
+        let matches_host = |pattern: &String| {
+            let pattern = pattern.to_lowercase();
+            host == pattern || host.ends_with(&format!(".{}", pattern))
+        };
+
+        if self.denied_hosts.iter().any(matches_host) {
+            return Err(anyhow!("主机已被禁止访问: {}", host));
+        }
+
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.iter().any(matches_host) {
+            return Err(anyhow!("主机不在允许列表中: {}", host));
+        }
+
+        self.http.check_not_self(url).await
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<(String, bool)> {
+        let url = Url::parse(url).map_err(|e| anyhow!("无效的 URL: {}", e))?;
+        self.check_url(&url).await?;
+
+        let client = self.http.build()?;
+        let response = client.get(url).send().await?;
+
+        info!("页面响应状态: {}", response.status());
+        let mut response = check_status(self.name(), response).await?;
+
+        if let Some(content_type) = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        {
+            if !content_type.contains("html") && !content_type.starts_with("text/") {
+                return Err(anyhow!("不支持的页面类型: {}", content_type));
+            }
+        }
+
+        // 分块读取，超过上限后丢弃剩余内容
+        let mut body = Vec::new();
+        let mut oversized = false;
+        while let Some(chunk) = response.chunk().await? {
+            let remaining = MAX_BODY_BYTES - body.len();
+            if chunk.len() > remaining {
+                body.extend_from_slice(&chunk[..remaining]);
+                oversized = true;
+                warn!("页面超过 {} 字节，已截断", MAX_BODY_BYTES);
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok((String::from_utf8_lossy(&body).to_string(), oversized))
+    }
+}
+
+/// 从 HTML 中提取标题和正文
+///
+/// 优先选取 `<article>`、`<main>`，否则使用 `<body>`；跳过导航、脚本、广告等元素。
+fn extract_readable_text(html: &str) -> (String, String) {
+    let document = Html::parse_document(html);
+
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|s| document.select(&s).next())
+        .map(|t| normalize_whitespace(&t.text().collect::<String>()))
+        .unwrap_or_default();
+
+    let root = ["article", "main", "body"]
+        .iter()
+        .filter_map(|tag| Selector::parse(tag).ok())
+        .find_map(|s| document.select(&s).next())
+        .unwrap_or_else(|| document.root_element());
+
+    let mut text = String::new();
+    collect_text(root, &mut text);
+
+    let text = text
+        .lines()
+        .map(normalize_whitespace)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (title, text)
+}
+
+fn collect_text(element: ElementRef, output: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => output.push_str(text),
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                if is_boilerplate(&child) {
+                    continue;
+                }
+
+                let is_block = BLOCK_TAGS.contains(&child.value().name());
+                if is_block {
+                    output.push('\n');
+                }
+                collect_text(child, output);
+                if is_block {
+                    output.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_boilerplate(element: &ElementRef) -> bool {
+    let value = element.value();
+    if SKIPPED_TAGS.contains(&value.name()) {
+        return true;
+    }
+
+    [value.attr("class"), value.attr("id")]
+        .into_iter()
+        .flatten()
+        .flat_map(|attr| attr.split(|c: char| c.is_whitespace() || c == '-' || c == '_'))
+        .any(|word| BOILERPLATE_HINTS.contains(&word.to_lowercase().as_str()))
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[async_trait]
+impl Tool for FetchPageTool {
+    fn name(&self) -> &str {
+        "fetch_page"
+    }
+
+    fn description(&self) -> &str {
+        "下载网页并提取标题和正文内容，去除导航、广告等页面杂项。返回网页地址 url、标题 title、正文 text 以及正文是否被截断 truncated"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "description": "网页地址，仅支持 http/https"},
+                "max_length": {"type": "integer", "description": "返回正文的最大字符数，默认 5000"}
+            },
+            "required": ["url"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"url": "https://www.rust-lang.org", "max_length": 3000})]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行网页读取工具，参数: {:?}", params);
+
+        // 解析参数
+        let params: FetchPageParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let max_length = params.max_length.unwrap_or(DEFAULT_MAX_LENGTH);
+
+        match self.fetch_page(&params.url).await {
+            Ok((html, oversized)) => {
+                let (title, text) = extract_readable_text(&html);
+                let kept = truncate_chars(&text, max_length);
+                let truncated = oversized || kept.len() < text.len();
+                let text = kept.to_string();
+
+                info!(
+                    "网页读取完成: 标题 = {}, 正文 {} 字符",
+                    title,
+                    text.chars().count()
+                );
+                let page = FetchPageResult {
+                    url: params.url,
+                    title,
+                    text,
+                    truncated,
+                };
+                Ok(ToolResult::success(serde_json::to_value(page)?))
+            }
+            Err(e) => {
+                error!("网页读取失败: {}", e);
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <title>  Rust   发布说明 </title>
+  <style>body { color: red; }</style>
+  <script>trackVisitor();</script>
+</head>
+<body>
+  <nav><a href="/">首页</a> <a href="/blog">博客</a></nav>
+  <div class="cookie-banner">本站使用 Cookie</div>
+  <main>
+    <h1>Rust 1.80 发布</h1>
+    <div id="sidebar">热门文章</div>
+    <p>这个版本稳定了   <b>LazyCell</b> 和 LazyLock。</p>
+    <div class="ad-slot">购买我们的产品</div>
+    <ul><li>第一项</li><li>第二项</li></ul>
+    <div class="comments">评论区</div>
+  </main>
+  <footer>版权所有</footer>
+</body>
+</html>"#;
+
+    #[test]
+    fn extracts_title_and_main_text_without_boilerplate() {
+        let (title, text) = extract_readable_text(PAGE);
+        assert_eq!(title, "Rust 发布说明");
+        assert_eq!(
+            text,
+            "Rust 1.80 发布\n这个版本稳定了 LazyCell 和 LazyLock。\n第一项\n第二项"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_body_without_main() {
+        let html =
+            "<html><body><header>站点标题</header><p>正文</p><script>x()</script></body></html>";
+        let (title, text) = extract_readable_text(html);
+        assert_eq!(title, "");
+        assert_eq!(text, "正文");
+    }
+
+    #[tokio::test]
+    async fn fetching_a_self_address_is_rejected() {
+        let http = HttpClientFactory::new().with_self_addresses(vec!["127.0.0.1:8080".to_string()]);
+        // 去掉默认的 localhost 禁止项，确认拦截来自自身地址检查
+        let tool = FetchPageTool {
+            denied_hosts: Vec::new(),
+            ..FetchPageTool::new()
+        }
+        .with_http(http);
+
+        let params = ToolParameters {
+            name: "fetch_page".to_string(),
+            args: serde_json::json!({"url": "http://localhost:8080/chat"}),
+        };
+        let result = tool.execute(params).await.unwrap();
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("不允许请求服务自身的地址"), "{}", error);
+    }
+}
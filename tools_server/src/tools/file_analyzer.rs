@@ -1,11 +1,13 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use rust_agent_core::tools::interface::{DisplayHint, Tool, ToolParameters, ToolResult};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
 use std::path::Path;
 use tracing::{error, info};
+use walkdir::WalkDir;
+
+use super::PathSandbox;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileAnalyzerParams {
@@ -21,14 +23,56 @@ pub struct FileAnalysis {
     largest_files: Vec<(String, u64)>,
 }
 
-pub struct FileAnalyzerTool;
+/// 默认列出的最大文件数量
+const DEFAULT_LARGEST_FILES: usize = 5;
+
+/// 清单中 file_analyzer 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileAnalyzerOptions {
+    /// 结果中列出的最大文件数量
+    pub largest_files: Option<usize>,
+}
+
+pub struct FileAnalyzerTool {
+    sandbox: PathSandbox,
+    largest_files: usize,
+}
 
 impl FileAnalyzerTool {
     pub fn new() -> Self {
-        Self
+        Self {
+            sandbox: PathSandbox::default(),
+            largest_files: DEFAULT_LARGEST_FILES,
+        }
     }
 
-    async fn analyze_directory(&self, path: &Path, recursive: bool) -> Result<FileAnalysis> {
+    /// 应用清单中的选项
+    pub fn with_options(mut self, options: FileAnalyzerOptions) -> Self {
+        if let Some(largest_files) = options.largest_files {
+            self.largest_files = largest_files;
+        }
+        self
+    }
+
+    /// 限制可访问的目录
+    ///
+    /// 启用限制后遍历时不再跟随符号链接，避免通过链接访问根目录之外的文件。
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 遍历目录并统计文件信息
+    ///
+    /// 使用同步的文件系统调用，应在阻塞线程中执行。`follow_links` 为 true 时跟随符号链接，
+    /// 链接形成的循环由 walkdir 检测并跳过。
+    fn analyze_directory(
+        path: &Path,
+        recursive: bool,
+        follow_links: bool,
+        largest_files: usize,
+    ) -> Result<FileAnalysis> {
         let mut analysis = FileAnalysis {
             total_size: 0,
             file_count: 0,
@@ -40,35 +84,37 @@ impl FileAnalyzerTool {
             return Err(anyhow::anyhow!("路径不存在"));
         }
 
-        let mut files_to_process = vec![path.to_path_buf()];
-
-        while let Some(current_path) = files_to_process.pop() {
-            if current_path.is_file() {
-                if let Ok(metadata) = fs::metadata(&current_path) {
-                    let size = metadata.len();
-                    analysis.total_size += size;
-                    analysis.file_count += 1;
-
-                    // 统计文件扩展名
-                    if let Some(ext) = current_path.extension() {
-                        let ext_str = ext.to_string_lossy().to_string();
-                        *analysis.extension_stats.entry(ext_str).or_insert(0) += 1;
-                    }
-
-                    // 记录大文件
-                    analysis
-                        .largest_files
-                        .push((current_path.to_string_lossy().to_string(), size));
-                    analysis.largest_files.sort_by(|a, b| b.1.cmp(&a.1));
-                    analysis.largest_files.truncate(5);
-                }
-            } else if current_path.is_dir() && (recursive || current_path == path) {
-                if let Ok(entries) = fs::read_dir(&current_path) {
-                    for entry in entries.flatten() {
-                        files_to_process.push(entry.path());
-                    }
-                }
+        let mut walker = WalkDir::new(path).follow_links(follow_links);
+        if !recursive {
+            walker = walker.max_depth(1);
+        }
+
+        for entry in walker.into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+
+            let size = metadata.len();
+            analysis.total_size += size;
+            analysis.file_count += 1;
+
+            // 统计文件扩展名
+            if let Some(ext) = entry.path().extension() {
+                let ext_str = ext.to_string_lossy().to_string();
+                *analysis.extension_stats.entry(ext_str).or_insert(0) += 1;
             }
+
+            // 记录大文件
+            analysis
+                .largest_files
+                .push((entry.path().to_string_lossy().to_string(), size));
+            analysis
+                .largest_files
+                .sort_by_key(|f| std::cmp::Reverse(f.1));
+            analysis.largest_files.truncate(largest_files);
         }
 
         Ok(analysis)
@@ -82,7 +128,22 @@ impl Tool for FileAnalyzerTool {
     }
 
     fn description(&self) -> &str {
-        "分析指定目录下的文件信息，包括大小、类型统计等"
+        "分析指定目录下的文件信息，包括大小、类型统计等。返回总大小 total_size、文件数量 file_count、扩展名统计 extension_stats 和最大的若干个文件 largest_files"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "要分析的目录路径"},
+                "recursive": {"type": "boolean", "description": "是否递归分析子目录"}
+            },
+            "required": ["path", "recursive"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"path": "/tmp", "recursive": true})]
     }
 
     async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
@@ -93,39 +154,130 @@ impl Tool for FileAnalyzerTool {
             Ok(p) => p,
             Err(e) => {
                 error!("参数解析失败: {}", e);
-                return Ok(ToolResult {
-                    success: false,
-                    data: serde_json::Value::Null,
-                    error: Some(e.to_string()),
-                });
+                return Ok(ToolResult::invalid_args(e.to_string()));
             }
         };
 
         // 分析目录
-        let path = Path::new(&params.path);
+        let path = match self.sandbox.check_existing(Path::new(&params.path)) {
+            Ok(path) => path,
+            Err(e) => {
+                error!("路径检查失败: {}", e);
+                return Ok(ToolResult::failure(e.to_string()));
+            }
+        };
         info!(
             "开始分析路径: {}, 递归: {}",
             path.display(),
             params.recursive
         );
 
-        match self.analyze_directory(path, params.recursive).await {
+        // 遍历大目录耗时较长，放到阻塞线程中执行，避免阻塞服务的异步运行时
+        let recursive = params.recursive;
+        let follow_links = !self.sandbox.is_restricted();
+        let largest_files = self.largest_files;
+        let outcome = tokio::task::spawn_blocking(move || {
+            Self::analyze_directory(&path, recursive, follow_links, largest_files)
+        })
+        .await?;
+
+        match outcome {
             Ok(analysis) => {
                 info!("分析成功完成");
-                Ok(ToolResult {
-                    success: true,
-                    data: serde_json::to_value(analysis)?,
-                    error: None,
-                })
+                Ok(ToolResult::success(serde_json::to_value(analysis)?)
+                    .with_display_hint(DisplayHint::Table))
             }
             Err(e) => {
                 error!("分析失败: {}", e);
-                Ok(ToolResult {
-                    success: false,
-                    data: serde_json::Value::Null,
-                    error: Some(e.to_string()),
-                })
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// 用最直接的递归方式统计，作为对照
+    fn walk_sequential(dir: &Path, recursive: bool, files: &mut Vec<(PathBuf, u64)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            let file_type = entry.file_type().unwrap();
+            if file_type.is_dir() && recursive {
+                walk_sequential(&entry.path(), recursive, files);
+            } else if file_type.is_file() {
+                files.push((entry.path(), entry.metadata().unwrap().len()));
+            }
+        }
+    }
+
+    /// 生成 20 个子目录、每个目录 50 个大小各不相同的文件
+    fn large_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for d in 0..20 {
+            let sub = dir.path().join(format!("dir{}", d)).join("nested");
+            fs::create_dir_all(&sub).unwrap();
+            for f in 0..50 {
+                let ext = ["rs", "txt", "md"][f % 3];
+                let size = d * 50 + f;
+                fs::write(sub.join(format!("f{}.{}", f, ext)), vec![b'x'; size]).unwrap();
             }
         }
+        fs::write(dir.path().join("top.log"), b"top").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn large_tree_matches_sequential_walk() {
+        let dir = large_tree();
+        let mut expected = Vec::new();
+        walk_sequential(dir.path(), true, &mut expected);
+        assert_eq!(expected.len(), 1001);
+
+        let tool = FileAnalyzerTool::new();
+        let params = ToolParameters {
+            name: "file_analyzer".to_string(),
+            args: serde_json::json!({"path": dir.path(), "recursive": true}),
+        };
+        let result = tool.execute(params).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let data = result.data;
+
+        assert_eq!(data["file_count"], expected.len());
+        let total_size: u64 = expected.iter().map(|(_, size)| size).sum();
+        assert_eq!(data["total_size"], total_size);
+
+        let mut extensions: HashMap<String, usize> = HashMap::new();
+        for (path, _) in &expected {
+            let ext = path.extension().unwrap().to_string_lossy().into_owned();
+            *extensions.entry(ext).or_default() += 1;
+        }
+        assert_eq!(
+            data["extension_stats"],
+            serde_json::to_value(&extensions).unwrap()
+        );
+
+        expected.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        let largest: Vec<(String, u64)> =
+            serde_json::from_value(data["largest_files"].clone()).unwrap();
+        let expected_largest: Vec<_> = expected
+            .iter()
+            .take(DEFAULT_LARGEST_FILES)
+            .map(|(path, size)| (path.to_string_lossy().into_owned(), *size))
+            .collect();
+        assert_eq!(largest, expected_largest);
+    }
+
+    #[test]
+    fn non_recursive_walk_counts_only_top_level_files() {
+        let dir = large_tree();
+        let analysis =
+            FileAnalyzerTool::analyze_directory(dir.path(), false, true, DEFAULT_LARGEST_FILES)
+                .unwrap();
+        assert_eq!(analysis.file_count, 1);
+        assert_eq!(analysis.total_size, 3);
     }
 }
@@ -0,0 +1,251 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use md5::Md5;
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use super::PathSandbox;
+
+/// 每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// 支持的哈希算法
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+    Md5,
+}
+
+/// 流式计算摘要的统一接口
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Md5(Md5),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Md5 => Self::Md5(Md5::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(data),
+            Self::Blake3(h) => {
+                h.update(data);
+            }
+            Self::Md5(h) => h.update(data),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        let bytes = match self {
+            Self::Sha256(h) => h.finalize().to_vec(),
+            Self::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Self::Md5(h) => h.finalize().to_vec(),
+        };
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileHashParams {
+    path: String,
+    #[serde(default)]
+    algorithm: HashAlgorithm,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileHashResult {
+    path: String,
+    algorithm: HashAlgorithm,
+    digest: String,
+    size: u64,
+}
+
+pub struct FileHashTool {
+    sandbox: PathSandbox,
+}
+
+impl FileHashTool {
+    pub fn new() -> Self {
+        Self {
+            sandbox: PathSandbox::default(),
+        }
+    }
+
+    /// 限制可访问的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 检查路径是否允许访问，并确认是普通文件
+    fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        let path = self.sandbox.check_existing(Path::new(path))?;
+        if !path.is_file() {
+            return Err(anyhow!("不是普通文件: {:?}", path));
+        }
+
+        Ok(path)
+    }
+}
+
+/// 分块读取文件计算摘要，不会把整个文件读入内存
+fn hash_file(path: &Path, algorithm: HashAlgorithm) -> Result<(String, u64)> {
+    let mut file = File::open(path).with_context(|| format!("无法打开文件: {:?}", path))?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut size = 0;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((hasher.finalize_hex(), size))
+}
+
+#[async_trait]
+impl Tool for FileHashTool {
+    fn name(&self) -> &str {
+        "file_hash"
+    }
+
+    fn description(&self) -> &str {
+        "计算文件的哈希值，用于校验文件完整性，支持 sha256、blake3 和 md5。返回文件路径 path、使用的算法 algorithm、十六进制摘要 digest 和文件大小 size"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "文件路径"},
+                "algorithm": {
+                    "type": "string",
+                    "enum": ["sha256", "blake3", "md5"],
+                    "description": "哈希算法，默认 sha256"
+                }
+            },
+            "required": ["path"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"path": "/tmp/archive.zip", "algorithm": "sha256"})]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行文件哈希工具，参数: {:?}", params);
+
+        // 解析参数，未知算法在这里报错
+        let params: FileHashParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let path = match self.resolve_path(&params.path) {
+            Ok(path) => path,
+            Err(e) => {
+                error!("文件路径无效: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        // 大文件读取耗时较长，放到阻塞线程中执行
+        let algorithm = params.algorithm;
+        let outcome = tokio::task::spawn_blocking(move || hash_file(&path, algorithm)).await?;
+
+        match outcome {
+            Ok((digest, size)) => {
+                info!("哈希计算完成: {} ({:?})", digest, algorithm);
+                let result = FileHashResult {
+                    path: params.path,
+                    algorithm,
+                    digest,
+                    size,
+                };
+                Ok(ToolResult::success(serde_json::to_value(result)?))
+            }
+            Err(e) => {
+                error!("哈希计算失败: {}", e);
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    async fn hash(tool: &FileHashTool, args: Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "file_hash".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn computes_known_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let tool = FileHashTool::new();
+
+        for (algorithm, digest) in [
+            (
+                "sha256",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            ("md5", "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                "blake3",
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+        ] {
+            let result = hash(&tool, json!({"path": path, "algorithm": algorithm})).await;
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(result.data["digest"], digest, "{}", algorithm);
+            assert_eq!(result.data["size"], 3);
+        }
+
+        // 未指定算法时使用 sha256
+        let result = hash(&tool, json!({"path": path})).await;
+        assert_eq!(result.data["algorithm"], "sha256");
+    }
+
+    #[tokio::test]
+    async fn unknown_algorithm_is_invalid_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+
+        let args = json!({"path": path.to_string_lossy(), "algorithm": "crc32"});
+        let result = hash(&FileHashTool::new(), args).await;
+        assert!(result.is_invalid_args());
+        assert!(result.error.unwrap().contains("crc32"));
+    }
+}
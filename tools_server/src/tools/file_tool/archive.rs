@@ -0,0 +1,373 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, warn};
+
+/// 单次解压允许写入的最大总字节数，防止压缩炸弹
+pub const MAX_EXTRACT_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// 支持的压缩包格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// 根据文件名检测压缩包格式
+    pub fn detect(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow!("无效的压缩包路径"))?
+            .to_lowercase();
+
+        if name.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Ok(Self::Tar)
+        } else {
+            Err(anyhow!("不支持的压缩格式: {}", name))
+        }
+    }
+}
+
+/// 压缩包中的单个条目
+#[derive(Debug, Serialize)]
+pub struct ArchiveEntry {
+    /// 条目在压缩包内的路径
+    pub name: String,
+    /// 解压后的大小
+    pub size: u64,
+    /// 压缩后的大小（tar 格式不单独压缩条目，为 None）
+    pub compressed_size: Option<u64>,
+    /// 是否为目录
+    pub is_dir: bool,
+}
+
+/// 解压结果
+#[derive(Debug)]
+pub struct ExtractOutcome {
+    /// 压缩包中的条目
+    pub entries: Vec<ArchiveEntry>,
+    /// 实际写入的总字节数
+    pub extracted_size: u64,
+}
+
+/// 检查条目路径是否安全
+///
+/// 绝对路径、盘符前缀以及包含 `..` 的路径都视为不安全（zip-slip）。
+pub fn is_safe_entry_path(name: &str) -> bool {
+    if name.is_empty() || name.starts_with('/') || name.starts_with('\\') {
+        return false;
+    }
+
+    // 同时按两种分隔符检查，避免 `..\evil` 在类 Unix 系统上被当成普通文件名
+    if name.split(['/', '\\']).any(|segment| segment == "..") {
+        return false;
+    }
+
+    Path::new(name)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// 列出压缩包内容（不解压）
+///
+/// 只要有一个条目路径不安全，就拒绝整个压缩包。
+pub fn list_archive(path: &Path) -> Result<Vec<ArchiveEntry>> {
+    let format = ArchiveFormat::detect(path)?;
+    debug!("读取压缩包条目: {:?}, 格式 = {:?}", path, format);
+
+    let file = File::open(path).with_context(|| format!("无法打开压缩包: {:?}", path))?;
+    let entries = match format {
+        ArchiveFormat::Zip => list_zip(file)?,
+        ArchiveFormat::Tar => list_tar(tar::Archive::new(file))?,
+        ArchiveFormat::TarGz => list_tar(tar::Archive::new(GzDecoder::new(file)))?,
+    };
+
+    if let Some(entry) = entries.iter().find(|e| !is_safe_entry_path(&e.name)) {
+        warn!("压缩包包含可疑路径: {}", entry.name);
+        return Err(anyhow!("压缩包包含可疑路径，已拒绝处理: {}", entry.name));
+    }
+
+    Ok(entries)
+}
+
+fn list_zip(file: File) -> Result<Vec<ArchiveEntry>> {
+    let mut archive = zip::ZipArchive::new(file).context("无法读取 zip 压缩包")?;
+
+    (0..archive.len())
+        .map(|i| {
+            // 使用 raw 读取，只解析头信息，不解压数据
+            let entry = archive.by_index_raw(i)?;
+            Ok(ArchiveEntry {
+                name: entry.name().to_string(),
+                size: entry.size(),
+                compressed_size: Some(entry.compressed_size()),
+                is_dir: entry.is_dir(),
+            })
+        })
+        .collect()
+}
+
+fn list_tar<R: Read>(mut archive: tar::Archive<R>) -> Result<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
+
+    for entry in archive.entries().context("无法读取 tar 压缩包")? {
+        let entry = entry?;
+        let header = entry.header();
+        entries.push(ArchiveEntry {
+            name: String::from_utf8_lossy(&entry.path_bytes()).to_string(),
+            size: header.size()?,
+            compressed_size: None,
+            is_dir: header.entry_type().is_dir(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// 将压缩包解压到目标目录
+///
+/// 解压前会先检查所有条目，任何可疑路径都会拒绝整个操作；写入时再规范化每个
+/// 条目的目标路径，确认仍位于目标目录内。实际写入量超过 `max_total_size` 时中止。
+pub fn extract_archive(path: &Path, target: &Path, max_total_size: u64) -> Result<ExtractOutcome> {
+    let format = ArchiveFormat::detect(path)?;
+    let entries = list_archive(path)?;
+
+    let declared_size: u64 = entries.iter().map(|e| e.size).sum();
+    if declared_size > max_total_size {
+        return Err(anyhow!(
+            "解压后大小 {} 字节超过上限 {} 字节，已拒绝处理",
+            declared_size,
+            max_total_size
+        ));
+    }
+
+    fs::create_dir_all(target).with_context(|| format!("无法创建目标目录: {:?}", target))?;
+    let root = target.canonicalize()?;
+    debug!("解压压缩包: {:?} -> {:?}", path, root);
+
+    let file = File::open(path).with_context(|| format!("无法打开压缩包: {:?}", path))?;
+    let mut remaining = max_total_size;
+    match format {
+        ArchiveFormat::Zip => extract_zip(file, &root, &mut remaining)?,
+        ArchiveFormat::Tar => extract_tar(tar::Archive::new(file), &root, &mut remaining)?,
+        ArchiveFormat::TarGz => extract_tar(
+            tar::Archive::new(GzDecoder::new(file)),
+            &root,
+            &mut remaining,
+        )?,
+    }
+
+    Ok(ExtractOutcome {
+        entries,
+        extracted_size: max_total_size - remaining,
+    })
+}
+
+fn extract_zip(file: File, root: &Path, remaining: &mut u64) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(file).context("无法读取 zip 压缩包")?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+
+        if entry.is_dir() {
+            fs::create_dir_all(resolve_entry_path(root, &name)?)?;
+        } else if entry
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170000 == 0o120000)
+        {
+            warn!("跳过符号链接条目: {}", name);
+        } else {
+            let dest = resolve_entry_path(root, &name)?;
+            write_entry(&mut entry, &dest, remaining)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_tar<R: Read>(
+    mut archive: tar::Archive<R>,
+    root: &Path,
+    remaining: &mut u64,
+) -> Result<()> {
+    for entry in archive.entries().context("无法读取 tar 压缩包")? {
+        let mut entry = entry?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_dir() {
+            fs::create_dir_all(resolve_entry_path(root, &name)?)?;
+        } else if entry_type.is_file() {
+            let dest = resolve_entry_path(root, &name)?;
+            write_entry(&mut entry, &dest, remaining)?;
+        } else {
+            // 符号链接、硬链接、设备文件等都可能被用来逃逸目标目录，一律跳过
+            warn!("跳过非普通文件条目: {}", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// 计算条目在目标目录中的实际写入路径，并确认没有逃逸出目标目录
+fn resolve_entry_path(root: &Path, name: &str) -> Result<PathBuf> {
+    if !is_safe_entry_path(name) {
+        return Err(anyhow!("压缩包包含可疑路径，已拒绝处理: {}", name));
+    }
+
+    let dest = root.join(name);
+    let file_name = dest
+        .file_name()
+        .ok_or_else(|| anyhow!("无效的条目路径: {}", name))?
+        .to_owned();
+
+    // 先创建父目录再规范化，这样已存在的符号链接也会被解析出来
+    let parent = dest.parent().unwrap_or(root);
+    fs::create_dir_all(parent)?;
+    let parent = parent.canonicalize()?;
+    if !parent.starts_with(root) {
+        return Err(anyhow!("条目路径超出目标目录，已拒绝处理: {}", name));
+    }
+
+    let dest = parent.join(file_name);
+    if fs::symlink_metadata(&dest).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(anyhow!("目标位置已存在符号链接，已拒绝处理: {}", name));
+    }
+
+    Ok(dest)
+}
+
+/// 写入单个条目，超过剩余额度时删除已写入的部分并报错
+fn write_entry<R: Read>(reader: &mut R, dest: &Path, remaining: &mut u64) -> Result<()> {
+    let mut output = File::create(dest).with_context(|| format!("无法创建文件: {:?}", dest))?;
+    let written = io::copy(&mut reader.take(*remaining + 1), &mut output)?;
+
+    if written > *remaining {
+        drop(output);
+        let _ = fs::remove_file(dest);
+        return Err(anyhow!("解压内容超过大小上限，已中止: {:?}", dest));
+    }
+
+    *remaining -= written;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    /// 按给定的条目创建 zip 压缩包，名称以 `/` 结尾的条目为目录
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in entries {
+            if name.ends_with('/') {
+                writer
+                    .add_directory(*name, SimpleFileOptions::default())
+                    .unwrap();
+            } else {
+                writer
+                    .start_file(*name, SimpleFileOptions::default())
+                    .unwrap();
+                writer.write_all(data).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    fn names(entries: &[ArchiveEntry]) -> Vec<(&str, u64, bool)> {
+        entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.size, e.is_dir))
+            .collect()
+    }
+
+    #[test]
+    fn lists_zip_entries_without_extracting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.zip");
+        write_zip(
+            &path,
+            &[
+                ("docs/", b""),
+                ("docs/readme.txt", b"hello"),
+                ("a.bin", &[0; 10]),
+            ],
+        );
+
+        let entries = list_archive(&path).unwrap();
+        assert_eq!(
+            names(&entries),
+            vec![
+                ("docs/", 0, true),
+                ("docs/readme.txt", 5, false),
+                ("a.bin", 10, false)
+            ]
+        );
+        assert!(entries[1].compressed_size.is_some());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn lists_tar_gz_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.tar.gz");
+        let encoder =
+            flate2::write::GzEncoder::new(File::create(&path).unwrap(), Default::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "src/main.rs", &b"abc"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let entries = list_archive(&path).unwrap();
+        assert_eq!(names(&entries), vec![("src/main.rs", 3, false)]);
+        assert_eq!(entries[0].compressed_size, None);
+    }
+
+    #[test]
+    fn rejects_zip_slip_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("evil.zip");
+        write_zip(&path, &[("ok.txt", b"fine"), ("../evil", b"pwned")]);
+        let target = dir.path().join("out");
+
+        let error = extract_archive(&path, &target, MAX_EXTRACT_SIZE).unwrap_err();
+        assert!(error.to_string().contains("../evil"), "{}", error);
+        assert!(!dir.path().join("evil").exists());
+        assert!(!target.join("ok.txt").exists());
+        assert!(list_archive(&path).is_err());
+    }
+
+    #[test]
+    fn detects_unsafe_entry_paths() {
+        assert!(is_safe_entry_path("docs/readme.txt"));
+        assert!(is_safe_entry_path("./a.txt"));
+        for name in [
+            "../evil",
+            "a/../../evil",
+            "..\\evil",
+            "/etc/passwd",
+            "\\evil",
+            "",
+        ] {
+            assert!(!is_safe_entry_path(name), "{}", name);
+        }
+    }
+}
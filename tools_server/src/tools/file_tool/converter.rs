@@ -1,16 +1,29 @@
 use anyhow::{anyhow, Context, Result};
-use std::path::Path;
-use std::process::Command;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
 use tracing::{debug, info, warn};
 
 use super::types::ConvertOptions;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ConverterType {
     Document,
     Image,
     Media,
-    PDF,
+    Pdf,
+}
+
+/// 转换计划（试运行时返回，不会真正执行）
+#[derive(Debug, Serialize)]
+pub struct ConversionPlan {
+    /// 检测到的转换器类型
+    pub converter_type: ConverterType,
+    /// 将要执行的完整命令（程序名 + 参数）
+    pub command: Vec<String>,
+    /// 对应的外部工具是否已安装
+    pub tool_available: bool,
 }
 
 pub struct FileConverter {
@@ -54,15 +67,55 @@ impl FileConverter {
             ConverterType::Document => self.convert_document(input, output, options).await,
             ConverterType::Image => self.convert_image(input, output, options).await,
             ConverterType::Media => self.convert_media(input, output, options).await,
-            ConverterType::PDF => self.convert_pdf(input, output, options).await,
+            ConverterType::Pdf => self.convert_pdf(input, output, options).await,
         }
     }
 
+    /// 生成转换计划，只构建命令而不执行
+    pub fn plan(
+        &self,
+        input: &Path,
+        output: &Path,
+        options: &ConvertOptions,
+    ) -> Result<ConversionPlan> {
+        let converter_type = self.detect_converter_type(input, &options.format)?;
+
+        let (cmd, tool_available) = match converter_type {
+            ConverterType::Document => (
+                self.document_command(input, output, options)?,
+                self.libreoffice_available,
+            ),
+            ConverterType::Image => (
+                self.image_command(input, output, options),
+                self.imagemagick_available,
+            ),
+            ConverterType::Media => (
+                self.media_command(input, output, options)?,
+                self.ffmpeg_available,
+            ),
+            ConverterType::Pdf => (
+                self.pdf_command(input, output, options)?,
+                self.ghostscript_available,
+            ),
+        };
+
+        let command = std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+
+        Ok(ConversionPlan {
+            converter_type,
+            command,
+            tool_available,
+        })
+    }
+
     async fn convert_document(
         &self,
         input: &Path,
         output: &Path,
-        _options: &ConvertOptions,
+        options: &ConvertOptions,
     ) -> Result<()> {
         if !self.libreoffice_available {
             return Err(anyhow!("LibreOffice 未安装，无法进行文档转换"));
@@ -70,26 +123,26 @@ impl FileConverter {
 
         info!("开始转换文档: {:?} -> {:?}", input, output);
 
-        // 使用 LibreOffice 进行转换
-        let status = Command::new("soffice")
-            .args([
-                "--headless",
-                "--convert-to",
-                output
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .ok_or_else(|| anyhow!("无效的输出格式"))?,
-                input.to_str().unwrap(),
-                "--outdir",
-                output.parent().unwrap().to_str().unwrap(),
-            ])
-            .status()
+        let status = run_command(self.document_command(input, output, options)?)
+            .await
             .context("执行 LibreOffice 转换失败")?;
 
         if !status.success() {
             return Err(anyhow!("文档转换失败"));
         }
 
+        // LibreOffice 总是把结果写成 `<outdir>/<输入文件名>.<格式>`，与指定的输出文件名不同时需要改名
+        let produced = libreoffice_output_path(input, output, &options.format)?;
+        if produced != output {
+            debug!(
+                "重命名 LibreOffice 输出文件: {:?} -> {:?}",
+                produced, output
+            );
+            tokio::fs::rename(&produced, output)
+                .await
+                .with_context(|| format!("无法重命名转换结果: {:?}", produced))?;
+        }
+
         info!("文档转换完成");
         Ok(())
     }
@@ -106,18 +159,9 @@ impl FileConverter {
 
         info!("开始转换图片: {:?} -> {:?}", input, output);
 
-        let mut cmd = Command::new("convert");
-        cmd.arg(input);
-
-        // 添加质量设置
-        if let Some(quality) = &options.quality {
-            cmd.args(["-quality", quality]);
-            debug!("设置图片质量: {}", quality);
-        }
-
-        cmd.arg(output);
-
-        let status = cmd.status().context("执行 ImageMagick 转换失败")?;
+        let status = run_command(self.image_command(input, output, options))
+            .await
+            .context("执行 ImageMagick 转换失败")?;
 
         if !status.success() {
             return Err(anyhow!("图片转换失败"));
@@ -139,8 +183,92 @@ impl FileConverter {
 
         info!("开始转换媒体文件: {:?} -> {:?}", input, output);
 
+        let status = run_command(self.media_command(input, output, options)?)
+            .await
+            .context("执行 FFmpeg 转换失败")?;
+
+        if !status.success() {
+            return Err(anyhow!("媒体转换失败"));
+        }
+
+        info!("媒体文件转换完成");
+        Ok(())
+    }
+
+    async fn convert_pdf(
+        &self,
+        input: &Path,
+        output: &Path,
+        options: &ConvertOptions,
+    ) -> Result<()> {
+        if !self.ghostscript_available {
+            return Err(anyhow!("Ghostscript 未安装，无法进行 PDF 转换"));
+        }
+
+        info!("开始转换 PDF: {:?} -> {:?}", input, output);
+
+        let status = run_command(self.pdf_command(input, output, options)?)
+            .await
+            .context("执行 Ghostscript 转换失败")?;
+
+        if !status.success() {
+            return Err(anyhow!("PDF 转换失败"));
+        }
+
+        info!("PDF 转换完成");
+        Ok(())
+    }
+
+    /// 构建 LibreOffice 转换命令
+    ///
+    /// 目标格式取自 `options.format`，LibreOffice 只能指定输出目录，文件名由输入文件名决定。
+    fn document_command(
+        &self,
+        input: &Path,
+        output: &Path,
+        options: &ConvertOptions,
+    ) -> Result<Command> {
+        let mut cmd = Command::new("soffice");
+        cmd.args([
+            "--headless",
+            "--convert-to",
+            options.format.trim_start_matches('.'),
+            input.to_str().ok_or_else(|| anyhow!("无效的输入路径"))?,
+            "--outdir",
+            output_dir(output)?
+                .to_str()
+                .ok_or_else(|| anyhow!("无效的输出路径"))?,
+        ]);
+        Ok(cmd)
+    }
+
+    /// 构建 ImageMagick 转换命令
+    fn image_command(&self, input: &Path, output: &Path, options: &ConvertOptions) -> Command {
+        let mut cmd = Command::new("convert");
+        cmd.arg(input);
+
+        // 添加质量设置
+        if let Some(quality) = &options.quality {
+            cmd.args(["-quality", quality]);
+            debug!("设置图片质量: {}", quality);
+        }
+
+        cmd.arg(output);
+        cmd
+    }
+
+    /// 构建 FFmpeg 转换命令
+    fn media_command(
+        &self,
+        input: &Path,
+        output: &Path,
+        options: &ConvertOptions,
+    ) -> Result<Command> {
         let mut cmd = Command::new("ffmpeg");
-        cmd.args(["-i", input.to_str().unwrap()]);
+        cmd.args([
+            "-i",
+            input.to_str().ok_or_else(|| anyhow!("无效的输入路径"))?,
+        ]);
 
         // 添加质量设置
         if let Some(quality) = &options.quality {
@@ -171,47 +299,29 @@ impl FileConverter {
         }
 
         cmd.arg(output);
-
-        let status = cmd.status().context("执行 FFmpeg 转换失败")?;
-
-        if !status.success() {
-            return Err(anyhow!("媒体转换失败"));
-        }
-
-        info!("媒体文件转换完成");
-        Ok(())
+        Ok(cmd)
     }
 
-    async fn convert_pdf(
+    /// 构建 Ghostscript 转换命令
+    fn pdf_command(
         &self,
         input: &Path,
         output: &Path,
         _options: &ConvertOptions,
-    ) -> Result<()> {
-        if !self.ghostscript_available {
-            return Err(anyhow!("Ghostscript 未安装，无法进行 PDF 转换"));
-        }
-
-        info!("开始转换 PDF: {:?} -> {:?}", input, output);
-
-        let status = Command::new("gs")
-            .args([
-                "-sDEVICE=pdfwrite",
-                "-dNOPAUSE",
-                "-dBATCH",
-                "-dSAFER",
-                &format!("-sOutputFile={}", output.to_str().unwrap()),
-                input.to_str().unwrap(),
-            ])
-            .status()
-            .context("执行 Ghostscript 转换失败")?;
-
-        if !status.success() {
-            return Err(anyhow!("PDF 转换失败"));
-        }
-
-        info!("PDF 转换完成");
-        Ok(())
+    ) -> Result<Command> {
+        let mut cmd = Command::new("gs");
+        cmd.args([
+            "-sDEVICE=pdfwrite",
+            "-dNOPAUSE",
+            "-dBATCH",
+            "-dSAFER",
+            &format!(
+                "-sOutputFile={}",
+                output.to_str().ok_or_else(|| anyhow!("无效的输出路径"))?
+            ),
+            input.to_str().ok_or_else(|| anyhow!("无效的输入路径"))?,
+        ]);
+        Ok(cmd)
     }
 
     fn detect_converter_type(&self, input: &Path, target_format: &str) -> Result<ConverterType> {
@@ -236,8 +346,121 @@ impl FileConverter {
             // 媒体格式
             "mp4" | "avi" | "mkv" | "mov" | "mp3" | "wav" | "flac" => Ok(ConverterType::Media),
             // PDF 相关
-            "pdf" | "ps" | "eps" => Ok(ConverterType::PDF),
+            "pdf" | "ps" | "eps" => Ok(ConverterType::Pdf),
             _ => Err(anyhow!("不支持的文件格式: {}", ext)),
         }
     }
 }
+
+/// 目标格式对应的扩展名
+///
+/// LibreOffice 的格式可以带导出过滤器（如 `pdf:writer_pdf_Export`），扩展名是冒号前的部分。
+fn format_extension(format: &str) -> String {
+    format
+        .trim_start_matches('.')
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// 确保输出文件名以目标格式结尾
+///
+/// 输出路径是已存在的目录时，在该目录下使用输入文件名；扩展名与目标格式不一致时追加目标扩展名。
+pub fn output_with_format(input: &Path, output: &Path, format: &str) -> Result<PathBuf> {
+    let extension = format_extension(format);
+    if extension.is_empty() {
+        return Err(anyhow!("无效的输出格式: {}", format));
+    }
+
+    if output.is_dir() {
+        let stem = input
+            .file_stem()
+            .ok_or_else(|| anyhow!("无效的输入路径"))?
+            .to_string_lossy();
+        return Ok(output.join(format!("{}.{}", stem, extension)));
+    }
+
+    let matches = output
+        .extension()
+        .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case(&extension));
+    if matches {
+        return Ok(output.to_path_buf());
+    }
+
+    let file_name = output
+        .file_name()
+        .ok_or_else(|| anyhow!("无效的输出路径"))?
+        .to_string_lossy();
+    Ok(output.with_file_name(format!("{}.{}", file_name, extension)))
+}
+
+/// 输出文件所在的目录，相对路径没有父目录时使用当前目录
+fn output_dir(output: &Path) -> Result<&Path> {
+    match output.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => Ok(Path::new(".")),
+        Some(parent) => Ok(parent),
+        None => Err(anyhow!("无效的输出路径")),
+    }
+}
+
+/// LibreOffice 实际写出的文件路径：`<outdir>/<输入文件名>.<格式扩展名>`
+fn libreoffice_output_path(input: &Path, output: &Path, format: &str) -> Result<PathBuf> {
+    let stem = input
+        .file_stem()
+        .ok_or_else(|| anyhow!("无效的输入路径"))?
+        .to_string_lossy();
+    Ok(output_dir(output)?.join(format!("{}.{}", stem, format_extension(format))))
+}
+
+/// 异步执行外部命令并等待结束
+///
+/// 子进程设置了 `kill_on_drop`：客户端取消调用时 gRPC 请求被中断，执行中的 future
+/// 会被丢弃，子进程随之被终止，不会在后台继续运行。
+async fn run_command(cmd: Command) -> std::io::Result<ExitStatus> {
+    let mut cmd = tokio::process::Command::from(cmd);
+    cmd.kill_on_drop(true);
+    cmd.status().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_file_name_matches_the_requested_format() {
+        let input = Path::new("/in/report.docx");
+        let dir = tempfile::tempdir().unwrap();
+
+        let output = output_with_format(input, Path::new("/out/report.pdf"), "pdf").unwrap();
+        assert_eq!(output, Path::new("/out/report.pdf"));
+        let output = output_with_format(input, Path::new("/out/report.PDF"), "pdf").unwrap();
+        assert_eq!(output, Path::new("/out/report.PDF"));
+        let output = output_with_format(input, Path::new("/out/report"), ".pdf").unwrap();
+        assert_eq!(output, Path::new("/out/report.pdf"));
+        let output = output_with_format(input, Path::new("/out/report.txt"), "pdf").unwrap();
+        assert_eq!(output, Path::new("/out/report.txt.pdf"));
+
+        // 输出到目录时使用输入文件名，导出过滤器不属于扩展名
+        let output = output_with_format(input, dir.path(), "pdf:writer_pdf_Export").unwrap();
+        assert_eq!(output, dir.path().join("report.pdf"));
+
+        assert!(output_with_format(input, Path::new("/out/report"), ":filter").is_err());
+    }
+
+    #[test]
+    fn libreoffice_output_is_named_after_the_input() {
+        let produced = libreoffice_output_path(
+            Path::new("/in/report.docx"),
+            Path::new("/out/final.pdf"),
+            "pdf:writer_pdf_Export",
+        )
+        .unwrap();
+        assert_eq!(produced, Path::new("/out/report.pdf"));
+
+        let produced =
+            libreoffice_output_path(Path::new("report.docx"), Path::new("final.pdf"), "pdf")
+                .unwrap();
+        assert_eq!(produced, Path::new("./report.pdf"));
+    }
+}
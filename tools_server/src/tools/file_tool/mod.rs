@@ -1,5 +1,6 @@
+mod archive;
 mod converter;
 mod tool;
 mod types;
 
-pub use tool::FileTool;
+pub use tool::{FileTool, FileToolOptions};
@@ -1,41 +1,124 @@
 use anyhow::{anyhow, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tracing::{debug, error, info};
 
-use super::converter::FileConverter;
+use super::archive;
+use super::converter::{output_with_format, FileConverter};
 use super::types::{FileDetails, FileOperation, FileToolParams, FileToolResponse};
 use async_trait::async_trait;
 use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::Deserialize;
+
+use crate::tools::PathSandbox;
+
+/// 默认同时运行的外部转换进程数
+const DEFAULT_MAX_CONCURRENT_CONVERSIONS: usize = 2;
+
+/// 清单中 file_tool 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileToolOptions {
+    /// 解压后允许的最大总大小（字节）
+    pub max_extract_bytes: Option<u64>,
+    /// 同时运行的外部转换进程数，超出的转换排队等待
+    pub max_concurrent_conversions: Option<usize>,
+}
 
 pub struct FileTool {
     converter: FileConverter,
+    sandbox: PathSandbox,
+    max_extract_bytes: u64,
+    /// 限制同时运行的转换进程（LibreOffice、FFmpeg 等）
+    conversions: Semaphore,
 }
 
 impl FileTool {
     pub fn new() -> Result<Self> {
         Ok(Self {
             converter: FileConverter::new()?,
+            sandbox: PathSandbox::default(),
+            max_extract_bytes: archive::MAX_EXTRACT_SIZE,
+            conversions: Semaphore::new(DEFAULT_MAX_CONCURRENT_CONVERSIONS),
         })
     }
 
-    async fn convert_file(&self, params: &FileToolParams) -> Result<FileToolResponse> {
+    /// 应用清单中的选项
+    pub fn with_options(mut self, options: FileToolOptions) -> Self {
+        if let Some(max_extract_bytes) = options.max_extract_bytes {
+            self.max_extract_bytes = max_extract_bytes;
+        }
+        if let Some(max_conversions) = options.max_concurrent_conversions {
+            self.conversions = Semaphore::new(max_conversions.max(1));
+        }
+        self
+    }
+
+    /// 限制可读写的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 检查输入路径是否存在且允许访问
+    fn input_path(&self, params: &FileToolParams) -> Result<PathBuf> {
         let input = Path::new(&params.input);
+        if !input.exists() {
+            return Err(anyhow!("输入文件不存在"));
+        }
+        self.sandbox.check_existing(input)
+    }
+
+    /// 检查输出路径是否允许写入
+    fn output_path(&self, params: &FileToolParams, missing_message: &str) -> Result<PathBuf> {
         let output = params
             .output
             .as_ref()
-            .map(Path::new)
-            .ok_or_else(|| anyhow!("需要指定输出路径"))?;
+            .ok_or_else(|| anyhow!("{}", missing_message))?;
+        self.sandbox.check_new(Path::new(output))
+    }
+
+    /// 获取一个转换进程名额，持有期间计入并发上限
+    ///
+    /// 转换进程占用大量 CPU 和内存，超出并发上限时排队等待。
+    async fn conversion_permit(&self, input: &Path) -> Result<SemaphorePermit<'_>> {
+        match self.conversions.try_acquire() {
+            Ok(permit) => Ok(permit),
+            Err(_) => {
+                info!("转换进程数已达上限，等待其他转换完成: {:?}", input);
+                Ok(self.conversions.acquire().await?)
+            }
+        }
+    }
 
+    async fn convert_file(&self, params: &FileToolParams) -> Result<FileToolResponse> {
+        let output = self.output_path(params, "需要指定输出路径")?;
         let options = params
             .options
             .as_ref()
             .ok_or_else(|| anyhow!("需要指定转换选项"))?;
 
-        if !input.exists() {
-            return Err(anyhow!("输入文件不存在"));
+        let input = self.input_path(params)?;
+        // 输出文件名与目标格式不一致时调整，返回的 output_path 与实际写出的文件一致
+        let output = output_with_format(&input, &output, &options.format)?;
+        let (input, output) = (input.as_path(), output.as_path());
+
+        if options.dry_run {
+            let plan = self.converter.plan(input, output, options)?;
+            info!("试运行转换: {:?}", plan.command);
+            return Ok(FileToolResponse {
+                success: true,
+                message: "试运行，未执行转换".to_string(),
+                output_path: Some(output.to_string_lossy().to_string()),
+                details: None,
+                entries: None,
+                plan: Some(plan),
+            });
         }
 
+        let _permit = self.conversion_permit(input).await?;
+
         debug!("开始文件转换: {:?} -> {:?}", input, output);
         let start = Instant::now();
         let original_size = input.metadata()?.len();
@@ -59,6 +142,65 @@ impl FileTool {
                 processed_size,
                 processing_time,
             }),
+            entries: None,
+            plan: None,
+        })
+    }
+
+    async fn decompress_file(&self, params: &FileToolParams) -> Result<FileToolResponse> {
+        let output = self.output_path(params, "需要指定解压目标目录")?;
+        let input = self.input_path(params)?;
+        let (input, output) = (input.as_path(), output.as_path());
+
+        debug!("开始解压: {:?} -> {:?}", input, output);
+        let start = Instant::now();
+        let original_size = input.metadata()?.len();
+
+        // 解压是大量的同步读写，放到阻塞线程中执行，避免占住运行时的工作线程
+        let max_extract_bytes = self.max_extract_bytes;
+        let (archive_path, target) = (input.to_path_buf(), output.to_path_buf());
+        let outcome = tokio::task::spawn_blocking(move || {
+            archive::extract_archive(&archive_path, &target, max_extract_bytes)
+        })
+        .await??;
+        let processing_time = start.elapsed().as_secs_f64();
+
+        info!(
+            "解压完成: 条目数={}, 原始大小={}, 解压后大小={}, 耗时={:.2}s",
+            outcome.entries.len(),
+            original_size,
+            outcome.extracted_size,
+            processing_time
+        );
+
+        Ok(FileToolResponse {
+            success: true,
+            message: format!("解压成功，共 {} 个条目", outcome.entries.len()),
+            output_path: Some(output.to_string_lossy().to_string()),
+            details: Some(FileDetails {
+                original_size,
+                processed_size: outcome.extracted_size,
+                processing_time,
+            }),
+            entries: Some(outcome.entries),
+            plan: None,
+        })
+    }
+
+    async fn list_archive(&self, params: &FileToolParams) -> Result<FileToolResponse> {
+        let input = self.input_path(params)?;
+        let archive_path = input.clone();
+        let entries =
+            tokio::task::spawn_blocking(move || archive::list_archive(&archive_path)).await??;
+        info!("压缩包读取完成: {:?}, 共 {} 个条目", input, entries.len());
+
+        Ok(FileToolResponse {
+            success: true,
+            message: format!("共 {} 个条目", entries.len()),
+            output_path: None,
+            details: None,
+            entries: Some(entries),
+            plan: None,
         })
     }
 }
@@ -70,7 +212,47 @@ impl Tool for FileTool {
     }
 
     fn description(&self) -> &str {
-        "文件处理工具，支持文件转换、压缩、解压、重命名和整理等操作"
+        "文件处理工具，支持文件转换、压缩、解压、重命名、整理和压缩包内容预览等操作。\
+返回是否成功 success、结果消息 message、输出路径 output_path 和处理详情 details；\
+试运行时返回转换计划 plan，list 操作返回压缩包条目 entries。\
+格式转换需要系统安装相应的命令行工具：文档转换需要 LibreOffice (soffice)，图片处理需要 ImageMagick (convert)，\
+音视频处理需要 FFmpeg (ffmpeg)，PDF 处理需要 Ghostscript (gs)"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["convert", "compress", "decompress", "rename", "organize", "list"],
+                    "description": "操作类型：convert 格式转换；decompress 解压到 output 指定的目录（支持 zip/tar/tar.gz）；list 列出压缩包内容（不解压）；compress、rename、organize 尚未实现"
+                },
+                "input": {"type": "string", "description": "输入文件路径"},
+                "output": {"type": "string", "description": "输出路径，convert 和 decompress 必填"},
+                "options": {
+                    "type": "object",
+                    "properties": {
+                        "format": {"type": "string", "description": "目标格式"},
+                        "quality": {"type": "string", "enum": ["high", "medium", "low"], "description": "质量设置"},
+                        "page_range": {"type": "string", "description": "页面范围"},
+                        "extra_args": {"type": "array", "items": {"type": "string"}, "description": "额外参数"},
+                        "dry_run": {"type": "boolean", "description": "试运行，只返回将要执行的命令"}
+                    },
+                    "required": ["format"]
+                }
+            },
+            "required": ["operation", "input"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({
+            "operation": "convert",
+            "input": "document.docx",
+            "output": "document.pdf",
+            "options": {"format": "pdf", "quality": "high"}
+        })]
     }
 
     async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
@@ -80,36 +262,107 @@ impl Tool for FileTool {
             Ok(p) => p,
             Err(e) => {
                 error!("参数解析失败: {}", e);
-                return Ok(ToolResult {
-                    success: false,
-                    data: serde_json::Value::Null,
-                    error: Some(e.to_string()),
-                });
+                return Ok(ToolResult::invalid_args(e.to_string()));
             }
         };
 
         let result = match params.operation {
             FileOperation::Convert => self.convert_file(&params).await,
             FileOperation::Compress => Err(anyhow!("压缩功能尚未实现")),
-            FileOperation::Decompress => Err(anyhow!("解压功能尚未实现")),
+            FileOperation::Decompress => self.decompress_file(&params).await,
             FileOperation::Rename => Err(anyhow!("重命名功能尚未实现")),
             FileOperation::Organize => Err(anyhow!("整理功能尚未实现")),
+            FileOperation::List => self.list_archive(&params).await,
         };
 
         match result {
-            Ok(response) => Ok(ToolResult {
-                success: true,
-                data: serde_json::to_value(response)?,
-                error: None,
-            }),
+            Ok(response) => Ok(ToolResult::success(serde_json::to_value(response)?)),
             Err(e) => {
                 error!("文件处理失败: {}", e);
-                Ok(ToolResult {
-                    success: false,
-                    data: serde_json::Value::Null,
-                    error: Some(e.to_string()),
-                })
+                Ok(ToolResult::failure(e.to_string()))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn run(tool: &FileTool, args: serde_json::Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "file_tool".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn dry_run_returns_command_without_converting() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("photo.png");
+        std::fs::write(&input, b"not really a png").unwrap();
+        let output = dir.path().join("photo.jpg");
+        let tool = FileTool::new().unwrap();
+
+        let result = run(
+            &tool,
+            json!({
+                "operation": "convert",
+                "input": input,
+                "output": output,
+                "options": {"format": "jpg", "quality": "80", "dry_run": true}
+            }),
+        )
+        .await;
+
+        assert!(result.success, "{:?}", result.error);
+        let plan = &result.data["plan"];
+        assert_eq!(plan["converter_type"], "image");
+        assert_eq!(
+            plan["command"],
+            json!(["convert", input, "-quality", "80", output])
+        );
+        assert!(!output.exists());
+    }
+
+    /// 同时发起多个转换，返回同一时刻最多有几个转换持有名额
+    async fn peak_conversions(tool: &FileTool, count: usize) -> usize {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let conversions = (0..count).map(|i| {
+            let (active, peak) = (&active, &peak);
+            async move {
+                let input = PathBuf::from(format!("input-{}.mp4", i));
+                let _permit = tool.conversion_permit(&input).await.unwrap();
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        futures::future::join_all(conversions).await;
+        peak.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn conversion_limit_caps_overlapping_conversions() {
+        let options = |max| FileToolOptions {
+            max_concurrent_conversions: Some(max),
+            ..Default::default()
+        };
+
+        let serial = FileTool::new().unwrap().with_options(options(1));
+        assert_eq!(peak_conversions(&serial, 4).await, 1);
+
+        let parallel = FileTool::new().unwrap().with_options(options(2));
+        assert_eq!(peak_conversions(&parallel, 4).await, 2);
+
+        // 0 按 1 处理，避免所有转换永远等待
+        let zero = FileTool::new().unwrap().with_options(options(0));
+        assert_eq!(peak_conversions(&zero, 2).await, 1);
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+
+use super::archive::ArchiveEntry;
+use super::converter::ConversionPlan;
 
 #[derive(Debug, Deserialize)]
 pub struct FileToolParams {
@@ -19,6 +21,7 @@ pub enum FileOperation {
     Decompress,
     Rename,
     Organize,
+    List,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,9 +30,10 @@ pub struct ConvertOptions {
     #[serde(default)]
     pub quality: Option<String>,
     #[serde(default)]
-    pub page_range: Option<String>,
-    #[serde(default)]
     pub extra_args: Option<Vec<String>>,
+    /// 试运行：只返回将要执行的命令，不真正转换
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,6 +42,12 @@ pub struct FileToolResponse {
     pub message: String,
     pub output_path: Option<String>,
     pub details: Option<FileDetails>,
+    /// 压缩包条目列表（仅 list 操作）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<Vec<ArchiveEntry>>,
+    /// 转换计划（仅试运行）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<ConversionPlan>,
 }
 
 #[derive(Debug, Serialize)]
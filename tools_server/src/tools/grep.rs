@@ -0,0 +1,438 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use rust_agent_core::text::truncate_bytes_safe;
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+use super::PathSandbox;
+
+/// 默认最多返回的匹配行数
+const DEFAULT_MAX_MATCHES: usize = 200;
+/// 跳过超过这个大小的文件
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// 检查是否包含空字节的前缀长度
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+/// 单个匹配行返回的最大字节数，避免压缩后的单行文件撑满结果
+const MAX_LINE_BYTES: usize = 500;
+
+/// 清单中 grep 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrepOptions {
+    /// 最多返回的匹配行数，调用参数中的 max_matches 不能超过这个值
+    pub max_matches: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrepParams {
+    /// 正则表达式
+    pattern: String,
+    /// 搜索的目录或文件
+    path: String,
+    /// 是否忽略大小写
+    #[serde(default)]
+    case_insensitive: bool,
+    /// 只搜索匹配这些 glob 的文件，例如 `*.rs`
+    #[serde(default)]
+    include: Vec<String>,
+    /// 跳过匹配这些 glob 的文件或目录，例如 `target`
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// 最多返回的匹配行数
+    #[serde(default)]
+    max_matches: Option<usize>,
+}
+
+/// 一处匹配
+#[derive(Debug, Serialize)]
+pub struct GrepMatch {
+    path: String,
+    /// 行号，从 1 开始
+    line: usize,
+    /// 匹配的行，过长时被截断
+    text: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GrepResult {
+    matches: Vec<GrepMatch>,
+    /// 搜索过的文本文件数
+    files_searched: usize,
+    /// 因是二进制文件或过大而跳过的文件数
+    files_skipped: usize,
+    /// 匹配数是否达到上限，其余匹配已省略
+    truncated: bool,
+}
+
+/// 文件内容搜索工具
+///
+/// 在目录下按正则表达式搜索文件内容，返回匹配的文件路径、行号和行内容，便于在代码库中定位代码。
+/// 默认跳过隐藏文件和 .gitignore 中忽略的文件，二进制文件和过大的文件不会被搜索。
+pub struct GrepTool {
+    sandbox: PathSandbox,
+    max_matches: usize,
+}
+
+impl GrepTool {
+    pub fn new() -> Self {
+        Self {
+            sandbox: PathSandbox::default(),
+            max_matches: DEFAULT_MAX_MATCHES,
+        }
+    }
+
+    pub fn with_options(mut self, options: GrepOptions) -> Self {
+        if let Some(max_matches) = options.max_matches {
+            self.max_matches = max_matches.max(1);
+        }
+        self
+    }
+
+    /// 限制可访问的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+}
+
+/// 按包含和排除的 glob 构造过滤规则
+fn build_overrides(root: &Path, params: &GrepParams) -> Result<Override> {
+    let mut builder = OverrideBuilder::new(root);
+    for glob in &params.include {
+        builder.add(glob)?;
+    }
+    for glob in &params.exclude {
+        builder.add(&format!("!{}", glob))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// 包含空字节或不是有效 UTF-8 的内容视为二进制
+fn as_text(content: &[u8]) -> Option<&str> {
+    let sniff = &content[..content.len().min(BINARY_SNIFF_BYTES)];
+    if sniff.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// 在结果中显示的路径：以调用时传入的路径开头，便于模型直接用于后续调用
+fn display_path(root: &Path, label: &str, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => Path::new(label)
+            .join(relative)
+            .to_string_lossy()
+            .into_owned(),
+        _ => label.to_string(),
+    }
+}
+
+/// 遍历并搜索文件，匹配数达到上限时停止
+fn search(
+    root: &Path,
+    params: &GrepParams,
+    regex: &Regex,
+    overrides: Override,
+    max_matches: usize,
+) -> GrepResult {
+    let walker = WalkBuilder::new(root)
+        .standard_filters(true)
+        .require_git(false)
+        .overrides(overrides)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build();
+
+    let mut result = GrepResult::default();
+    for entry in walker {
+        // 无法读取的条目（如没有权限）只记录日志，不影响其余部分
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("跳过无法读取的条目: {}", e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+
+        let path = entry.path();
+        if entry.metadata().map(|m| m.len()).unwrap_or(0) > MAX_FILE_BYTES {
+            result.files_skipped += 1;
+            continue;
+        }
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("无法读取文件 {:?}: {}", path, e);
+                continue;
+            }
+        };
+        let Some(text) = as_text(&content) else {
+            result.files_skipped += 1;
+            continue;
+        };
+
+        result.files_searched += 1;
+        for (index, line) in text.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            if result.matches.len() >= max_matches {
+                result.truncated = true;
+                return result;
+            }
+            result.matches.push(GrepMatch {
+                path: display_path(root, &params.path, path),
+                line: index + 1,
+                text: truncate_bytes_safe(line, MAX_LINE_BYTES).to_string(),
+            });
+        }
+    }
+
+    result
+}
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn description(&self) -> &str {
+        "在目录下按正则表达式搜索文件内容，用于在代码库中查找函数、配置项或任意文本。返回匹配列表 matches\
+（每项包含文件路径 path、行号 line 和行内容 text）、搜索的文件数 files_searched、跳过的二进制或过大文件数 files_skipped，\
+以及匹配是否达到上限 truncated。可以用 include/exclude 按 glob 筛选文件，默认跳过隐藏文件和 .gitignore 中忽略的文件"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {"type": "string", "description": "正则表达式（Rust regex 语法）"},
+                "path": {"type": "string", "description": "搜索的目录或文件路径"},
+                "case_insensitive": {"type": "boolean", "description": "是否忽略大小写，默认 false"},
+                "include": {"type": "array", "items": {"type": "string"}, "description": "只搜索匹配这些 glob 的文件，例如 [\"*.rs\"]"},
+                "exclude": {"type": "array", "items": {"type": "string"}, "description": "跳过匹配这些 glob 的文件或目录，例如 [\"target\"]"},
+                "max_matches": {"type": "integer", "minimum": 1, "maximum": self.max_matches, "description": "最多返回的匹配行数"}
+            },
+            "required": ["pattern", "path"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![
+            serde_json::json!({"pattern": "fn main", "path": "/data/project", "include": ["*.rs"]}),
+        ]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行内容搜索工具，参数: {:?}", params);
+
+        // 解析参数
+        let params: GrepParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let regex = match RegexBuilder::new(&params.pattern)
+            .case_insensitive(params.case_insensitive)
+            .build()
+        {
+            Ok(regex) => regex,
+            Err(e) => {
+                error!("正则表达式无效: {}", e);
+                return Ok(ToolResult::invalid_args(format!("正则表达式无效: {}", e)));
+            }
+        };
+
+        let root: PathBuf = match self.sandbox.check_existing(Path::new(&params.path)) {
+            Ok(root) => root,
+            Err(e) => {
+                error!("搜索路径无效: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let overrides = match build_overrides(&root, &params) {
+            Ok(overrides) => overrides,
+            Err(e) => {
+                error!("glob 无效: {}", e);
+                return Ok(ToolResult::invalid_args(format!("glob 无效: {}", e)));
+            }
+        };
+
+        let max_matches = params
+            .max_matches
+            .unwrap_or(self.max_matches)
+            .clamp(1, self.max_matches);
+
+        // 遍历和读取大量文件耗时较长，放到阻塞线程中执行
+        let result = tokio::task::spawn_blocking(move || {
+            search(&root, &params, &regex, overrides, max_matches)
+        })
+        .await?;
+
+        info!(
+            "内容搜索完成: {} 处匹配，搜索 {} 个文件，跳过 {} 个文件",
+            result.matches.len(),
+            result.files_searched,
+            result.files_skipped
+        );
+        Ok(ToolResult::success(serde_json::to_value(result)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// 在临时目录中创建：
+    /// src/main.rs、src/lib.rs、notes.txt、app.bin（二进制）、target/（被 .gitignore 忽略）
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "use app::run;\n\nfn main() {\n    run();\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn run() {}\npub fn Run_twice() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("notes.txt"), "call run() first\n").unwrap();
+        fs::write(root.join("app.bin"), b"run\0\x01\x02run").unwrap();
+        fs::write(root.join("target/out.rs"), "fn run() {}\n").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        dir
+    }
+
+    async fn grep(tool: &GrepTool, args: serde_json::Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "grep".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    /// 结果中的 (路径, 行号, 行内容)
+    fn matches(result: &ToolResult) -> Vec<(String, u64, String)> {
+        result.data["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| {
+                (
+                    m["path"].as_str().unwrap().to_string(),
+                    m["line"].as_u64().unwrap(),
+                    m["text"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn returns_paths_line_numbers_and_lines_and_skips_binaries() {
+        let dir = project();
+        let path = dir.path().to_str().unwrap();
+        let tool = GrepTool::new();
+
+        let result = grep(
+            &tool,
+            serde_json::json!({"pattern": r"\brun\(", "path": path}),
+        )
+        .await;
+        assert!(result.success, "{:?}", result.error);
+        let file = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        assert_eq!(
+            matches(&result),
+            vec![
+                (file("notes.txt"), 1, "call run() first".to_string()),
+                (file("src/lib.rs"), 1, "pub fn run() {}".to_string()),
+                (file("src/main.rs"), 4, "    run();".to_string()),
+            ]
+        );
+        // 二进制文件被跳过，target/ 被 .gitignore 忽略，隐藏的 .gitignore 本身也不搜索
+        assert_eq!(result.data["files_searched"], 3);
+        assert_eq!(result.data["files_skipped"], 1);
+        assert_eq!(result.data["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn options_filter_files_ignore_case_and_cap_matches() {
+        let dir = project();
+        let path = dir.path().to_str().unwrap();
+        let tool = GrepTool::new();
+
+        let result = grep(
+            &tool,
+            serde_json::json!({
+                "pattern": "fn run",
+                "path": path,
+                "case_insensitive": true,
+                "include": ["*.rs"],
+                "exclude": ["main.rs"]
+            }),
+        )
+        .await;
+        let lines: Vec<_> = matches(&result).into_iter().map(|m| m.1).collect();
+        assert_eq!(lines, vec![1, 2]);
+
+        let capped = GrepTool::new().with_options(GrepOptions {
+            max_matches: Some(1),
+        });
+        // 调用参数不能超过清单中的上限
+        let result = grep(
+            &capped,
+            serde_json::json!({"pattern": "run", "path": path, "max_matches": 50}),
+        )
+        .await;
+        assert_eq!(matches(&result).len(), 1);
+        assert_eq!(result.data["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn invalid_patterns_and_paths_outside_the_sandbox_are_rejected() {
+        let dir = project();
+        let tool =
+            GrepTool::new().with_sandbox(PathSandbox::new(vec![dir.path().join("src")]).unwrap());
+
+        let result = grep(
+            &tool,
+            serde_json::json!({"pattern": "run", "path": dir.path().to_str().unwrap()}),
+        )
+        .await;
+        assert!(!result.success);
+
+        let src = dir.path().join("src");
+        let result = grep(
+            &tool,
+            serde_json::json!({"pattern": "(", "path": src.to_str().unwrap()}),
+        )
+        .await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("正则表达式无效"));
+
+        let result = grep(
+            &tool,
+            serde_json::json!({"pattern": "run", "path": src.to_str().unwrap()}),
+        )
+        .await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(matches(&result).len(), 3);
+    }
+}
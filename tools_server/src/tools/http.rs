@@ -0,0 +1,490 @@
+use anyhow::{Context, Result};
+use reqwest::Url;
+use rust_agent_core::text::truncate_chars;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::config::Args;
+
+/// 默认请求超时时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 默认 User-Agent
+const DEFAULT_USER_AGENT: &str = "RustAgent/1.0";
+
+/// 最多跟随的重定向次数，与 reqwest 的默认值一致
+const MAX_REDIRECTS: usize = 10;
+
+/// 错误信息中附带的响应内容摘录长度（字符数）
+const EXCERPT_CHARS: usize = 200;
+
+/// 网络类工具收到非成功状态码时的错误
+///
+/// 格式统一为 `<工具名>: HTTP <状态码>: <响应摘录>`，便于模型和用户判断是否值得重试。
+#[derive(Debug)]
+pub struct HttpError {
+    pub tool: String,
+    pub status: reqwest::StatusCode,
+    pub excerpt: String,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: HTTP {}", self.tool, self.status)?;
+        if !self.excerpt.is_empty() {
+            write!(f, ": {}", self.excerpt)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// 检查响应状态码，非成功时读取响应开头并返回 [`HttpError`]
+pub async fn check_status(tool: &str, response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.bytes().await.unwrap_or_default();
+    Err(HttpError {
+        tool: tool.to_string(),
+        status,
+        excerpt: body_excerpt(&body),
+    }
+    .into())
+}
+
+/// 响应内容的简短摘录：合并空白，只保留开头的若干字符
+pub fn body_excerpt(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    truncate_chars(&text, EXCERPT_CHARS).to_string()
+}
+
+/// 创建出站 HTTP 客户端
+///
+/// 所有网络类工具都通过它创建 `reqwest::Client`，代理、超时、User-Agent 和证书校验
+/// 等出站策略集中在服务配置中设置；工具可以在副本上按自身选项覆盖。
+#[derive(Debug, Clone)]
+pub struct HttpClientFactory {
+    proxy: Option<String>,
+    timeout: Duration,
+    user_agent: String,
+    accept_invalid_certs: bool,
+    /// 服务自身（及智能体）的地址，主机名在设置时已解析
+    self_addresses: Vec<SelfAddress>,
+}
+
+impl Default for HttpClientFactory {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            timeout: DEFAULT_TIMEOUT,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            accept_invalid_certs: false,
+            self_addresses: Vec::new(),
+        }
+    }
+}
+
+impl HttpClientFactory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按服务的命令行参数创建
+    pub fn from_args(config: &Args) -> Self {
+        let mut factory = Self::new()
+            .with_proxy(config.http_proxy.clone())
+            .with_accept_invalid_certs(config.http_insecure)
+            .with_self_addresses(config.self_addresses.clone());
+        if let Some(secs) = config.http_timeout_secs {
+            factory = factory.with_timeout(Duration::from_secs(secs));
+        }
+        if let Some(user_agent) = &config.http_user_agent {
+            factory = factory.with_user_agent(user_agent);
+        }
+        factory
+    }
+
+    /// 设置代理地址，None 或空字符串表示不使用代理
+    pub fn with_proxy(mut self, proxy: Option<String>) -> Self {
+        self.proxy = proxy.filter(|p| !p.is_empty());
+        self
+    }
+
+    /// 设置请求超时时间
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 设置 User-Agent
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// 是否跳过 TLS 证书校验，只应在调试时开启
+    pub fn with_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// 设置服务自身的地址，请求这些地址（包括经重定向到达）会被拒绝
+    ///
+    /// 用于避免工具回调智能体自身的接口造成循环调用。每项为 `host` 或 `host:port`，
+    /// 只写主机名时匹配该主机的所有端口。主机名在这里解析一次（阻塞的 DNS 查询），
+    /// 之后检查请求时直接使用解析结果。
+    pub fn with_self_addresses(mut self, addresses: Vec<String>) -> Self {
+        self.self_addresses = addresses
+            .iter()
+            .map(|address| SelfAddress::parse(address))
+            .collect();
+        self
+    }
+
+    /// 检查 URL 是否指向服务自身
+    ///
+    /// 请求的主机名会异步解析成 IP 后与自身地址比较，`127.1`、`0.0.0.0`
+    /// 和指向本机的域名都能识别。
+    pub async fn check_not_self(&self, url: &Url) -> Result<()> {
+        if self.self_addresses.is_empty() {
+            return Ok(());
+        }
+        let Some((host, port)) = host_and_port(url) else {
+            return Ok(());
+        };
+
+        let target_ips = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+                .unwrap_or_default(),
+        };
+        let is_self = self
+            .self_addresses
+            .iter()
+            .any(|address| address.matches(&host, port, &target_ips));
+        if is_self {
+            return Err(SelfRequest {
+                url: url.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// 当前配置的代理地址
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// 按当前配置创建客户端
+    pub fn build(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .user_agent(&self.user_agent)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if !self.self_addresses.is_empty() {
+            // 重定向策略是同步回调，不能查询 DNS，只比较主机名和 IP 字面量
+            let addresses = self.self_addresses.clone();
+            builder = builder.redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if is_self_literal(&addresses, attempt.url()) {
+                    let url = attempt.url().to_string();
+                    attempt.error(SelfRequest { url })
+                } else if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("重定向次数过多")
+                } else {
+                    attempt.follow()
+                }
+            }));
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy =
+                reqwest::Proxy::all(proxy).with_context(|| format!("无效的代理地址: {}", proxy))?;
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// 请求指向服务自身，可能造成循环调用
+#[derive(Debug)]
+pub struct SelfRequest {
+    pub url: String,
+}
+
+impl fmt::Display for SelfRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "不允许请求服务自身的地址（可能造成循环调用）: {}",
+            self.url
+        )
+    }
+}
+
+impl std::error::Error for SelfRequest {}
+
+/// 服务自身的一个地址
+#[derive(Debug, Clone)]
+struct SelfAddress {
+    /// 小写的主机名或 IP
+    host: String,
+    /// 未指定时匹配该主机的所有端口
+    port: Option<u16>,
+    /// 主机解析出的 IP，解析失败时为空
+    ips: Vec<IpAddr>,
+}
+
+impl SelfAddress {
+    /// 解析 `host` 或 `host:port`，主机名会同步解析成 IP
+    fn parse(address: &str) -> Self {
+        let (host, port) = split_host_port(address);
+        let ips = resolve_host(&host);
+        Self { host, port, ips }
+    }
+
+    /// 端口相符，并且主机名相同或解析出的 IP 与目标的某个 IP 指向同一台主机
+    fn matches(&self, host: &str, port: Option<u16>, target_ips: &[IpAddr]) -> bool {
+        if self.port.is_some_and(|p| Some(p) != port) {
+            return false;
+        }
+        self.host == host
+            || self
+                .ips
+                .iter()
+                .any(|&ip| target_ips.iter().any(|&target| same_host_ip(ip, target)))
+    }
+}
+
+/// URL 的主机（小写，去掉 IPv6 的方括号）和端口
+fn host_and_port(url: &Url) -> Option<(String, Option<u16>)> {
+    let host = url.host_str()?;
+    let host = host.trim_matches(|c| c == '[' || c == ']').to_lowercase();
+    Some((host, url.port_or_known_default()))
+}
+
+/// 不查询 DNS 的检查：只比较主机名，URL 的主机是 IP 时再与自身地址的 IP 比较
+fn is_self_literal(addresses: &[SelfAddress], url: &Url) -> bool {
+    let Some((host, port)) = host_and_port(url) else {
+        return false;
+    };
+    let target_ips: Vec<IpAddr> = host.parse().into_iter().collect();
+    addresses
+        .iter()
+        .any(|address| address.matches(&host, port, &target_ips))
+}
+
+/// 解析主机名得到的所有 IP，解析失败时为空
+fn resolve_host(host: &str) -> Vec<IpAddr> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return vec![ip];
+    }
+    (host, 0)
+        .to_socket_addrs()
+        .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+        .unwrap_or_default()
+}
+
+/// 两个 IP 是否指向同一台主机
+///
+/// 回环地址和未指定地址（`0.0.0.0`、`::`，连接时等同于本机）都视为本机。
+fn same_host_ip(a: IpAddr, b: IpAddr) -> bool {
+    let is_local = |ip: IpAddr| ip.is_loopback() || ip.is_unspecified();
+    let (a, b) = (a.to_canonical(), b.to_canonical());
+    a == b || (is_local(a) && is_local(b))
+}
+
+/// 拆分 `host:port`，IPv6 地址需要写成 `[::1]:8080` 才能带端口
+fn split_host_port(address: &str) -> (String, Option<u16>) {
+    let address = address.trim();
+    if let Ok(socket) = address.parse::<SocketAddr>() {
+        return (socket.ip().to_string(), Some(socket.port()));
+    }
+
+    let host_port = address
+        .rsplit_once(':')
+        .filter(|(host, _)| !host.contains(':'))
+        .and_then(|(host, port)| Some((host, port.parse().ok()?)));
+    let (host, port) = match host_port {
+        Some((host, port)) => (host, Some(port)),
+        None => (address, None),
+    };
+    (
+        host.trim_matches(|c| c == '[' || c == ']').to_lowercase(),
+        port,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_agent_core::api::testing::{MockChatServer, MockResponse};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn configured_timeout_applies_to_requests() {
+        // 接受连接但从不响应
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _server = tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let client = HttpClientFactory::new()
+            .with_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let error = client
+            .get(format!("http://{}/", addr))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(error.is_timeout(), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn configured_proxy_receives_requests() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let request_line = tokio::spawn(async move {
+            let (mut socket, _) = proxy.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            let head = String::from_utf8_lossy(&buf[..n]).into_owned();
+            head.lines().next().unwrap_or_default().to_string()
+        });
+
+        let factory = HttpClientFactory::new().with_proxy(Some(format!("http://{}", proxy_addr)));
+        assert_eq!(
+            factory.proxy(),
+            Some(format!("http://{}", proxy_addr).as_str())
+        );
+        let body = factory
+            .build()
+            .unwrap()
+            .get("http://example.invalid/search")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert_eq!(body, "ok");
+        // 经 HTTP 代理的请求行使用完整 URL
+        assert_eq!(
+            request_line.await.unwrap(),
+            "GET http://example.invalid/search HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn empty_proxy_means_no_proxy() {
+        assert_eq!(
+            HttpClientFactory::new()
+                .with_proxy(Some(String::new()))
+                .proxy(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn server_error_yields_the_standard_error_string() {
+        let body = format!("{{\n  \"error\":   \"backend down\" }}{}", "x".repeat(500));
+        let server = MockChatServer::start(vec![MockResponse::Status(500, body)])
+            .await
+            .unwrap();
+        let response = HttpClientFactory::new()
+            .build()
+            .unwrap()
+            .get(server.url())
+            .send()
+            .await
+            .unwrap();
+
+        let error = check_status("web_search", response).await.unwrap_err();
+        let message = error.to_string();
+        assert!(
+            message.starts_with(
+                "web_search: HTTP 500 Internal Server Error: { \"error\": \"backend down\" }"
+            ),
+            "{}",
+            message
+        );
+        // 摘录被截断
+        assert!(message.len() < 300, "{}", message);
+        let http_error = error.downcast_ref::<HttpError>().unwrap();
+        assert_eq!(
+            http_error.status,
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_resolving_to_a_self_address_are_rejected() {
+        let factory = HttpClientFactory::new().with_self_addresses(vec!["127.1:8080".to_string()]);
+        for url in [
+            "http://127.0.0.1:8080/api",
+            "http://127.1:8080/",
+            "http://0.0.0.0:8080/",
+            "http://[::1]:8080/",
+            "http://[::ffff:127.0.0.1]:8080/",
+            "http://localhost:8080/",
+        ] {
+            let error = factory
+                .check_not_self(&Url::parse(url).unwrap())
+                .await
+                .unwrap_err();
+            assert!(error.is::<SelfRequest>(), "{}: {}", url, error);
+        }
+
+        // 端口不同或者不是本机时放行
+        for url in ["http://127.0.0.1:9090/", "http://192.0.2.1:8080/"] {
+            assert!(factory
+                .check_not_self(&Url::parse(url).unwrap())
+                .await
+                .is_ok());
+        }
+
+        // 自身地址写成域名或未指定地址时同样按 IP 比较
+        for address in ["localhost", "0.0.0.0"] {
+            let factory = HttpClientFactory::new().with_self_addresses(vec![address.to_string()]);
+            let url = Url::parse("http://127.0.0.1:3000/").unwrap();
+            assert!(factory.check_not_self(&url).await.is_err(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn redirect_check_compares_literals_without_dns() {
+        let addresses = [
+            SelfAddress::parse("localhost"),
+            SelfAddress::parse("10.0.0.5:50051"),
+        ];
+        let is_self = |url: &str| is_self_literal(&addresses, &Url::parse(url).unwrap());
+
+        // 自身地址的主机名在配置时已解析，重定向到回环 IP 能被识别
+        assert!(is_self("http://127.0.0.1/"));
+        assert!(is_self("http://LOCALHOST:8080/"));
+        assert!(is_self("http://10.0.0.5:50051/"));
+        assert!(!is_self("http://10.0.0.5:80/"));
+        // 重定向目标的主机名不会被解析
+        assert!(!is_self("http://agent.invalid/"));
+    }
+}
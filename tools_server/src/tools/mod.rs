@@ -1,7 +1,29 @@
+mod diff;
+mod encoding;
+mod fetch_page;
 mod file_analyzer;
+mod file_hash;
 mod file_tool;
+mod grep;
+mod http;
+mod pdf_text;
+mod registry;
+mod sandbox;
+mod tree;
 mod web_search;
+mod workspace;
 
-pub use file_analyzer::FileAnalyzerTool;
-pub use file_tool::FileTool;
-pub use web_search::WebSearchTool;
+pub use diff::{DiffOptions, DiffTool};
+pub use encoding::EncodingTool;
+pub use fetch_page::{FetchPageOptions, FetchPageTool};
+pub use file_analyzer::{FileAnalyzerOptions, FileAnalyzerTool};
+pub use file_hash::FileHashTool;
+pub use file_tool::{FileTool, FileToolOptions};
+pub use grep::{GrepOptions, GrepTool};
+pub use http::{body_excerpt, check_status, HttpClientFactory};
+pub use pdf_text::PdfTextTool;
+pub use registry::build_tools;
+pub use sandbox::PathSandbox;
+pub use tree::{TreeOptions, TreeTool};
+pub use web_search::{WebSearchOptions, WebSearchTool};
+pub use workspace::WorkspaceTool;
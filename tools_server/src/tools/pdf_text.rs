@@ -0,0 +1,326 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use lopdf::Document;
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+use super::PathSandbox;
+
+#[derive(Debug, Deserialize)]
+pub struct PdfTextParams {
+    path: String,
+    /// 页面范围，如 "1-3,5"，未指定时提取全部页面
+    #[serde(default)]
+    pages: Option<String>,
+    /// 是否按页分别返回文本
+    #[serde(default)]
+    per_page: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageText {
+    page: u32,
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PdfTextResult {
+    path: String,
+    /// 文档总页数
+    page_count: u32,
+    /// 提取的页码
+    extracted_pages: Vec<u32>,
+    /// 合并后的文本，按页返回时为 None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    /// 每页的文本，仅在 per_page 为 true 时返回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pages: Option<Vec<PageText>>,
+}
+
+/// PDF 文本提取工具
+///
+/// 直接读取 PDF 中的文本，不需要先转换格式；扫描件等没有文本层的页面会得到空文本。
+pub struct PdfTextTool {
+    sandbox: PathSandbox,
+}
+
+impl PdfTextTool {
+    pub fn new() -> Self {
+        Self {
+            sandbox: PathSandbox::default(),
+        }
+    }
+
+    /// 限制可访问的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 检查路径是否允许访问，并确认是普通文件
+    fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        let path = self.sandbox.check_existing(Path::new(path))?;
+        if !path.is_file() {
+            return Err(anyhow!("不是普通文件: {:?}", path));
+        }
+
+        Ok(path)
+    }
+}
+
+/// 解析页面范围，如 "1-3,5"；页码从 1 开始且不能超过总页数
+fn parse_page_range(range: &str, page_count: u32) -> Result<Vec<u32>> {
+    let mut pages = Vec::new();
+
+    for part in range.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (part, part),
+        };
+        let start: u32 = start.parse().map_err(|_| anyhow!("无效的页码: {}", part))?;
+        let end: u32 = end.parse().map_err(|_| anyhow!("无效的页码: {}", part))?;
+
+        if start == 0 || start > end || end > page_count {
+            return Err(anyhow!(
+                "页面范围 {} 超出文档范围（共 {} 页）",
+                part,
+                page_count
+            ));
+        }
+        pages.extend(start..=end);
+    }
+
+    if pages.is_empty() {
+        return Err(anyhow!("页面范围为空: {:?}", range));
+    }
+
+    pages.sort_unstable();
+    pages.dedup();
+    Ok(pages)
+}
+
+/// 读取 PDF 并提取指定页面的文本
+fn extract_text(path: &Path, params: &PdfTextParams) -> Result<PdfTextResult> {
+    let document = Document::load(path).map_err(|e| anyhow!("无法读取 PDF 文件: {}", e))?;
+
+    // 使用空密码可以解密的文档会在加载时自动解密，否则内容仍是加密的
+    if document.is_encrypted() && document.encryption_state.is_none() {
+        return Err(anyhow!("PDF 文件已加密，需要密码才能读取: {:?}", path));
+    }
+
+    let page_count = document.get_pages().len() as u32;
+    let extracted_pages = match &params.pages {
+        Some(range) => parse_page_range(range, page_count)?,
+        None => (1..=page_count).collect(),
+    };
+
+    let (text, pages) = if params.per_page {
+        let pages = extracted_pages
+            .iter()
+            .map(|&page| {
+                let text = document
+                    .extract_text(&[page])
+                    .map_err(|e| anyhow!("提取第 {} 页文本失败: {}", page, e))?;
+                Ok(PageText { page, text })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        (None, Some(pages))
+    } else {
+        let text = document
+            .extract_text(&extracted_pages)
+            .map_err(|e| anyhow!("提取文本失败: {}", e))?;
+        (Some(text), None)
+    };
+
+    Ok(PdfTextResult {
+        path: params.path.clone(),
+        page_count,
+        extracted_pages,
+        text,
+        pages,
+    })
+}
+
+#[async_trait]
+impl Tool for PdfTextTool {
+    fn name(&self) -> &str {
+        "pdf_text"
+    }
+
+    fn description(&self) -> &str {
+        "直接提取 PDF 文件中的文本，可以指定页面范围或按页返回。返回文件路径 path、总页数 page_count、提取的页码 extracted_pages，\
+以及合并后的文本 text 或每页的文本 pages"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "PDF 文件路径"},
+                "pages": {"type": "string", "description": "页面范围，如 \"1-3,5\"，默认全部页面"},
+                "per_page": {"type": "boolean", "description": "是否按页分别返回文本，默认 false"}
+            },
+            "required": ["path"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"path": "/tmp/report.pdf", "pages": "1-2"})]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行 PDF 文本提取工具，参数: {:?}", params);
+
+        // 解析参数
+        let params: PdfTextParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let path = match self.resolve_path(&params.path) {
+            Ok(path) => path,
+            Err(e) => {
+                error!("文件路径无效: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        // 解析大文件耗时较长，放到阻塞线程中执行
+        let outcome = tokio::task::spawn_blocking(move || extract_text(&path, &params)).await?;
+
+        match outcome {
+            Ok(result) => {
+                info!(
+                    "PDF 文本提取完成: {} 页，共 {} 页",
+                    result.extracted_pages.len(),
+                    result.page_count
+                );
+                Ok(ToolResult::success(serde_json::to_value(result)?))
+            }
+            Err(e) => {
+                error!("PDF 文本提取失败: {}", e);
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+    use serde_json::json;
+
+    /// 生成每页一行文本的 PDF
+    fn write_pdf(path: &Path, pages: &[&str]) {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+
+        let kids: Vec<Object> = pages
+            .iter()
+            .map(|text| {
+                let content = Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                        Operation::new("Td", vec![100.into(), 600.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(*text)]),
+                        Operation::new("ET", vec![]),
+                    ],
+                };
+                let content_id =
+                    doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                })
+                .into()
+            })
+            .collect();
+
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => pages.len() as i64,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    async fn run(args: serde_json::Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "pdf_text".to_string(),
+            args,
+        };
+        PdfTextTool::new().execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn extracts_text_and_page_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        write_pdf(&path, &["First page", "Second page", "Third page"]);
+        let path = path.to_str().unwrap();
+
+        let result = run(json!({ "path": path })).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data["page_count"], 3);
+        assert_eq!(result.data["extracted_pages"], json!([1, 2, 3]));
+        let text = result.data["text"].as_str().unwrap();
+        assert!(
+            text.contains("First page") && text.contains("Third page"),
+            "{}",
+            text
+        );
+
+        let result = run(json!({ "path": path, "pages": "2-3", "per_page": true })).await;
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.data.get("text").is_none());
+        let pages = result.data["pages"].as_array().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0]["page"], 2);
+        assert!(pages[0]["text"].as_str().unwrap().contains("Second page"));
+        assert!(!pages[0]["text"].as_str().unwrap().contains("Third page"));
+    }
+
+    #[tokio::test]
+    async fn out_of_range_pages_and_non_pdf_files_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("one.pdf");
+        write_pdf(&pdf, &["Only page"]);
+        let result = run(json!({ "path": pdf.to_str().unwrap(), "pages": "1-2" })).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("共 1 页"));
+
+        let text = dir.path().join("notes.pdf");
+        std::fs::write(&text, "not a pdf").unwrap();
+        let result = run(json!({ "path": text.to_str().unwrap() })).await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("无法读取 PDF 文件"));
+    }
+}
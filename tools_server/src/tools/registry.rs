@@ -0,0 +1,313 @@
+use anyhow::Result;
+use rust_agent_core::tools::Tool;
+use tracing::{error, info, warn};
+
+use super::{
+    DiffOptions, DiffTool, EncodingTool, FetchPageOptions, FetchPageTool, FileAnalyzerOptions,
+    FileAnalyzerTool, FileHashTool, FileTool, FileToolOptions, GrepOptions, GrepTool,
+    HttpClientFactory, PathSandbox, PdfTextTool, TreeOptions, TreeTool, WebSearchOptions,
+    WebSearchTool, WorkspaceTool,
+};
+use crate::config::{Args, NoOptions, ToolEntry, ToolManifest, BUILTIN_TOOLS};
+
+/// 按命令行参数和工具清单创建要注册的工具
+///
+/// 提供清单时只创建清单中启用的工具；命令行的启用/禁用参数在此基础上继续筛选。
+/// 命令行和清单中配置的根目录合并后组成所有文件类工具共用的沙箱。
+pub fn build_tools(config: &Args, manifest: Option<&ToolManifest>) -> Result<Vec<Box<dyn Tool>>> {
+    let mut enabled = Vec::new();
+    for &name in BUILTIN_TOOLS {
+        let entry = match manifest {
+            Some(manifest) => match manifest.enabled_entry(name) {
+                Some(entry) => Some(entry),
+                None => {
+                    info!("工具未在清单中启用，跳过注册: {}", name);
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        if !config.is_tool_enabled(name) {
+            warn!("工具已被配置禁用，跳过注册: {}", name);
+            continue;
+        }
+        enabled.push((name, entry));
+    }
+
+    let mut roots = config.allowed_roots.clone();
+    if let Some(manifest) = manifest {
+        roots.extend(manifest.allowed_roots.iter().cloned());
+    }
+
+    // 工作区根目录需要在创建沙箱前建立；限制了访问目录时加入沙箱，让文件类工具可以读写工作区
+    let mut workspace = None;
+    if let Some((_, entry)) = enabled.iter().find(|(name, _)| *name == "workspace") {
+        options::<NoOptions>(*entry)?;
+        let tool = WorkspaceTool::new()?;
+        if !roots.is_empty() {
+            roots.push(tool.root().to_path_buf());
+        }
+        workspace = Some(tool);
+    }
+
+    let sandbox = PathSandbox::new(roots)?;
+    if sandbox.is_restricted() {
+        info!("文件类工具只允许访问: {:?}", sandbox.roots());
+    }
+
+    let http = HttpClientFactory::from_args(config);
+    if let Some(proxy) = http.proxy() {
+        info!("网络类工具使用代理: {}", proxy);
+    }
+
+    let mut tools = Vec::new();
+
+    for (name, entry) in enabled {
+        let tool = match name {
+            "workspace" => workspace.take().map(|tool| Box::new(tool) as Box<dyn Tool>),
+            _ => create_tool(name, entry, config, &sandbox, &http)?,
+        };
+        if let Some(tool) = tool {
+            info!("已注册工具: {}", tool.name());
+            tools.push(tool);
+        }
+    }
+
+    Ok(tools)
+}
+
+/// 创建单个工具，选项无效时返回错误，工具初始化失败时只记录日志
+///
+/// 每个工具的选项来自清单中对应条目，部署相关的配置（沙箱、出站 HTTP 策略、主机列表）来自命令行参数。
+fn create_tool(
+    name: &str,
+    entry: Option<&ToolEntry>,
+    config: &Args,
+    sandbox: &PathSandbox,
+    http: &HttpClientFactory,
+) -> Result<Option<Box<dyn Tool>>> {
+    let tool: Box<dyn Tool> = match name {
+        "file_analyzer" => {
+            let options: FileAnalyzerOptions = options(entry)?;
+            Box::new(
+                FileAnalyzerTool::new()
+                    .with_options(options)
+                    .with_sandbox(sandbox.clone()),
+            )
+        }
+        "file_tool" => {
+            let options: FileToolOptions = options(entry)?;
+            match FileTool::new() {
+                Ok(file_tool) => Box::new(
+                    file_tool
+                        .with_options(options)
+                        .with_sandbox(sandbox.clone()),
+                ),
+                Err(e) => {
+                    error!("文件处理工具初始化失败: {}", e);
+                    return Ok(None);
+                }
+            }
+        }
+        "web_search" => {
+            let options: WebSearchOptions = options(entry)?;
+            Box::new(
+                WebSearchTool::new()
+                    .with_http(http.clone())
+                    .with_options(options),
+            )
+        }
+        "fetch_page" => {
+            let mut options: FetchPageOptions = options(entry)?;
+            options
+                .allowed_hosts
+                .extend(config.fetch_allowed_hosts.iter().cloned());
+            options
+                .denied_hosts
+                .extend(config.fetch_denied_hosts.iter().cloned());
+            Box::new(
+                FetchPageTool::new()
+                    .with_http(http.clone())
+                    .with_options(options),
+            )
+        }
+        "file_hash" => {
+            options::<NoOptions>(entry)?;
+            Box::new(FileHashTool::new().with_sandbox(sandbox.clone()))
+        }
+        "pdf_text" => {
+            options::<NoOptions>(entry)?;
+            Box::new(PdfTextTool::new().with_sandbox(sandbox.clone()))
+        }
+        "diff" => {
+            let options: DiffOptions = options(entry)?;
+            Box::new(
+                DiffTool::new()
+                    .with_options(options)
+                    .with_sandbox(sandbox.clone()),
+            )
+        }
+        "tree" => {
+            let options: TreeOptions = options(entry)?;
+            Box::new(
+                TreeTool::new()
+                    .with_options(options)
+                    .with_sandbox(sandbox.clone()),
+            )
+        }
+        "grep" => {
+            let options: GrepOptions = options(entry)?;
+            Box::new(
+                GrepTool::new()
+                    .with_options(options)
+                    .with_sandbox(sandbox.clone()),
+            )
+        }
+        "encoding" => {
+            options::<NoOptions>(entry)?;
+            Box::new(EncodingTool::new().with_sandbox(sandbox.clone()))
+        }
+        _ => unreachable!("未知的内置工具: {}", name),
+    };
+
+    Ok(Some(tool))
+}
+
+fn options<T: serde::de::DeserializeOwned + Default>(entry: Option<&ToolEntry>) -> Result<T> {
+    entry.map_or_else(|| Ok(T::default()), ToolEntry::options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rust_agent_core::tools::rpc::server::ToolsFlightService;
+    use rust_agent_core::tools::rpc::testing::spawn_in_process;
+    use rust_agent_core::tools::{ToolParameters, ToolResult, ToolServiceError};
+    use serde_json::json;
+    use tonic::Code;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(std::iter::once("tools_server").chain(flags.iter().copied()))
+    }
+
+    async fn run(tools: &[Box<dyn Tool>], name: &str, args: serde_json::Value) -> ToolResult {
+        let tool = tools.iter().find(|tool| tool.name() == name).unwrap();
+        let params = ToolParameters {
+            name: name.to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn tools_built_from_config_apply_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        for (name, size) in [("a.txt", 1), ("b.txt", 2), ("c.txt", 3)] {
+            std::fs::write(root.join(name), "match\n".repeat(size)).unwrap();
+        }
+        std::fs::write(dir.path().join("outside.txt"), "secret").unwrap();
+
+        let manifest: ToolManifest = toml::from_str(
+            r#"
+            [[tools]]
+            name = "file_analyzer"
+            options = { largest_files = 1 }
+
+            [[tools]]
+            name = "tree"
+            options = { max_entries = 2 }
+
+            [[tools]]
+            name = "grep"
+            options = { max_matches = 1 }
+
+            [[tools]]
+            name = "fetch_page"
+            options = { allowed_hosts = ["docs.rs"] }
+
+            [[tools]]
+            name = "file_hash"
+            "#,
+        )
+        .unwrap();
+        let root_flag = root.to_string_lossy().into_owned();
+        let config = args(&[
+            "--allowed-roots",
+            &root_flag,
+            "--fetch-denied-hosts",
+            "private.docs.rs",
+        ]);
+        let tools = build_tools(&config, Some(&manifest)).unwrap();
+
+        let result = run(
+            &tools,
+            "file_analyzer",
+            json!({"path": root, "recursive": true}),
+        )
+        .await;
+        assert_eq!(result.data["file_count"], 3);
+        assert_eq!(result.data["largest_files"].as_array().unwrap().len(), 1);
+
+        let result = run(&tools, "tree", json!({"path": root})).await;
+        assert!(result.data.as_str().unwrap().contains("条目超过上限 2"));
+
+        let result = run(&tools, "grep", json!({"pattern": "match", "path": root})).await;
+        assert_eq!(result.data["matches"].as_array().unwrap().len(), 1);
+        assert_eq!(result.data["truncated"], true);
+
+        // 清单中的允许列表和命令行的禁止列表同时生效，检查在发出请求之前完成
+        let result = run(&tools, "fetch_page", json!({"url": "https://example.com"})).await;
+        assert!(result.error.unwrap().contains("不在允许列表中"));
+        let result = run(
+            &tools,
+            "fetch_page",
+            json!({"url": "https://private.docs.rs"}),
+        )
+        .await;
+        assert!(result.error.unwrap().contains("已被禁止访问"));
+
+        // 命令行配置的根目录作用于所有文件类工具
+        let outside = dir.path().join("outside.txt");
+        assert!(
+            !run(&tools, "file_hash", json!({"path": outside}))
+                .await
+                .success
+        );
+        let inside = root.join("a.txt");
+        assert!(
+            run(&tools, "file_hash", json!({"path": inside}))
+                .await
+                .success
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_tool_is_not_listed_or_executable() {
+        let config = args(&[
+            "--enable-tools",
+            "file_tool,file_hash,fetch_page",
+            "--disable-tools",
+            "file_tool",
+        ]);
+        let tools = build_tools(&config, None).unwrap();
+        let (_server, client) = spawn_in_process(ToolsFlightService::with_tools(tools))
+            .await
+            .unwrap();
+
+        let mut names = client.list_tools().await.unwrap();
+        names.sort();
+        assert_eq!(names, ["fetch_page", "file_hash"]);
+
+        let params = ToolParameters {
+            name: "file_tool".to_string(),
+            args: json!({"operation": "list", "input": "a.zip"}),
+        };
+        let error = client.execute_tool(params).await.unwrap_err();
+        let error = error.downcast_ref::<ToolServiceError>().unwrap();
+        assert_eq!(error.code, Code::NotFound);
+    }
+}
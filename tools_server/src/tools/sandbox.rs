@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// 文件系统沙箱
+///
+/// 所有文件类工具在读写前通过它检查路径：路径会先被规范化（解析 `..` 和符号链接），
+/// 再确认位于某个允许的根目录内。未配置根目录时不做限制，路径原样返回。
+#[derive(Debug, Clone, Default)]
+pub struct PathSandbox {
+    /// 规范化后的根目录
+    roots: Vec<PathBuf>,
+}
+
+impl PathSandbox {
+    /// 创建沙箱，根目录必须存在
+    pub fn new(roots: Vec<PathBuf>) -> Result<Self> {
+        let roots = roots
+            .iter()
+            .map(|root| {
+                root.canonicalize()
+                    .with_context(|| format!("允许的根目录不存在: {:?}", root))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { roots })
+    }
+
+    /// 是否限制了可访问的目录
+    pub fn is_restricted(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// 允许访问的根目录
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// 检查已存在的路径，返回规范化后的路径
+    pub fn check_existing(&self, path: &Path) -> Result<PathBuf> {
+        if !self.is_restricted() {
+            return Ok(path.to_path_buf());
+        }
+
+        let resolved = path
+            .canonicalize()
+            .with_context(|| format!("无法访问路径: {:?}", path))?;
+        self.ensure_within(path, resolved)
+    }
+
+    /// 检查将要创建的路径（如输出文件或解压目录）
+    ///
+    /// 规范化最近的已存在上级目录，其余部分不能包含 `..`。
+    /// 悬空的符号链接也算作已存在：写入时会沿链接在目标位置创建文件，
+    /// 目标无法解析就无法确认它在根目录内，因此直接拒绝。
+    pub fn check_new(&self, path: &Path) -> Result<PathBuf> {
+        if !self.is_restricted() {
+            return Ok(path.to_path_buf());
+        }
+
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(path)
+        };
+
+        // 用 `symlink_metadata` 而不是 `exists`，后者会跟随链接，把悬空链接当成不存在
+        let mut existing = absolute.as_path();
+        let mut missing = Vec::new();
+        let is_symlink = loop {
+            match fs::symlink_metadata(existing) {
+                Ok(metadata) => break metadata.file_type().is_symlink(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("无法访问路径: {:?}", path)),
+            }
+            let name = existing
+                .file_name()
+                .ok_or_else(|| anyhow!("无效的路径: {:?}", path))?;
+            missing.push(name.to_owned());
+            existing = existing
+                .parent()
+                .ok_or_else(|| anyhow!("无效的路径: {:?}", path))?;
+        };
+
+        let mut resolved = match existing.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) if is_symlink => {
+                return Err(anyhow!("路径包含无法解析的符号链接: {:?}", path));
+            }
+            Err(e) => return Err(e).with_context(|| format!("无法访问路径: {:?}", path)),
+        };
+        for name in missing.iter().rev() {
+            if !matches!(
+                Path::new(name).components().next(),
+                Some(Component::Normal(_))
+            ) {
+                return Err(anyhow!("路径包含非法的组成部分: {:?}", path));
+            }
+            resolved.push(name);
+        }
+
+        self.ensure_within(path, resolved)
+    }
+
+    fn ensure_within(&self, original: &Path, resolved: PathBuf) -> Result<PathBuf> {
+        if self.roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            Err(anyhow!("路径不在允许访问的目录内: {:?}", original))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 创建允许访问的根目录 `root` 和与之并列的 `outside`，各含一个文件
+    fn layout() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("sub/inside.txt"), "inside").unwrap();
+        fs::write(outside.join("secret.txt"), "secret").unwrap();
+        (dir, root, outside)
+    }
+
+    #[test]
+    fn paths_inside_a_root_are_allowed() {
+        let (_dir, root, _) = layout();
+        let sandbox = PathSandbox::new(vec![root.clone()]).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let path = sandbox
+            .check_existing(&root.join("sub/inside.txt"))
+            .unwrap();
+        assert_eq!(path, root.join("sub/inside.txt"));
+        // `..` 没有离开根目录时允许
+        let path = sandbox
+            .check_existing(&root.join("sub/../sub/inside.txt"))
+            .unwrap();
+        assert_eq!(path, root.join("sub/inside.txt"));
+        let path = sandbox.check_new(&root.join("sub/new/out.txt")).unwrap();
+        assert_eq!(path, root.join("sub/new/out.txt"));
+    }
+
+    #[test]
+    fn parent_dir_escapes_are_denied() {
+        let (_dir, root, _) = layout();
+        let sandbox = PathSandbox::new(vec![root.clone()]).unwrap();
+
+        let escape = root.join("sub/../../outside/secret.txt");
+        assert!(sandbox.check_existing(&escape).is_err());
+        assert!(sandbox.check_new(&root.join("../outside/new.txt")).is_err());
+        assert!(sandbox
+            .check_new(&root.join("missing/../../new.txt"))
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_escapes_are_denied() {
+        let (_dir, root, outside) = layout();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        let sandbox = PathSandbox::new(vec![root.clone()]).unwrap();
+
+        assert!(sandbox
+            .check_existing(&root.join("link/secret.txt"))
+            .is_err());
+        assert!(sandbox.check_new(&root.join("link/new.txt")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_symlinks_out_of_the_root_are_denied() {
+        let (_dir, root, outside) = layout();
+        let target = outside.join("missing");
+        std::os::unix::fs::symlink(&target, root.join("dangling")).unwrap();
+        let sandbox = PathSandbox::new(vec![root.clone()]).unwrap();
+
+        // 写入悬空链接本身会在根目录外创建目标文件
+        assert!(sandbox.check_new(&root.join("dangling")).is_err());
+        assert!(sandbox.check_new(&root.join("dangling/new.txt")).is_err());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn unrestricted_sandbox_returns_paths_unchanged() {
+        let sandbox = PathSandbox::default();
+        assert!(!sandbox.is_restricted());
+        let path = Path::new("/anywhere/../file.txt");
+        assert_eq!(sandbox.check_new(path).unwrap(), path);
+        assert!(PathSandbox::new(vec![PathBuf::from("/no/such/root")]).is_err());
+    }
+}
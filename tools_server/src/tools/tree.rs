@@ -0,0 +1,398 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ignore::WalkBuilder;
+use rust_agent_core::tools::interface::{DisplayHint, Tool, ToolParameters, ToolResult};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+use super::PathSandbox;
+
+/// 默认展开的目录层数
+const DEFAULT_DEPTH: usize = 3;
+/// 允许展开的最大层数
+const MAX_DEPTH: usize = 10;
+/// 默认最多列出的条目数
+const DEFAULT_MAX_ENTRIES: usize = 500;
+
+/// 清单中 tree 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TreeOptions {
+    /// 最多列出的条目数，超出部分会被省略
+    pub max_entries: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeParams {
+    path: String,
+    /// 展开的目录层数，1 表示只列出直接子项
+    #[serde(default)]
+    depth: Option<usize>,
+    /// 是否跳过 .gitignore 中忽略的文件
+    #[serde(default = "default_true")]
+    gitignore: bool,
+    /// 是否显示隐藏文件（以 . 开头）
+    #[serde(default)]
+    show_hidden: bool,
+    /// 是否在文件后标注大小
+    #[serde(default)]
+    show_sizes: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 遍历得到的一个条目
+struct TreeEntry {
+    /// 相对于根目录的层数，直接子项为 1
+    depth: usize,
+    name: String,
+    is_dir: bool,
+    size: u64,
+}
+
+/// 目录树工具
+///
+/// 以类似 `tree` 命令的文本形式列出目录结构，便于模型了解代码库的组织方式。
+/// 默认跳过隐藏文件和 .gitignore 中忽略的文件，不会跟随符号链接。
+pub struct TreeTool {
+    sandbox: PathSandbox,
+    max_entries: usize,
+}
+
+impl TreeTool {
+    pub fn new() -> Self {
+        Self {
+            sandbox: PathSandbox::default(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    pub fn with_options(mut self, options: TreeOptions) -> Self {
+        if let Some(max_entries) = options.max_entries {
+            self.max_entries = max_entries.max(1);
+        }
+        self
+    }
+
+    /// 限制可访问的目录
+    pub fn with_sandbox(mut self, sandbox: PathSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    /// 检查路径是否允许访问，并确认是目录
+    fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        let path = self.sandbox.check_existing(Path::new(path))?;
+        if !path.is_dir() {
+            return Err(anyhow!("不是目录: {:?}", path));
+        }
+
+        Ok(path)
+    }
+}
+
+/// 按深度优先顺序收集条目，同一目录下目录在前、再按名称排序；返回条目和是否因数量上限被截断
+fn collect_entries(
+    root: &Path,
+    params: &TreeParams,
+    depth: usize,
+    max_entries: usize,
+) -> Result<(Vec<TreeEntry>, bool)> {
+    let walker = WalkBuilder::new(root)
+        .standard_filters(false)
+        .hidden(!params.show_hidden)
+        .git_ignore(params.gitignore)
+        .git_exclude(params.gitignore)
+        .parents(params.gitignore)
+        .ignore(params.gitignore)
+        .require_git(false)
+        .max_depth(Some(depth))
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .sort_by_file_path(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| a.cmp(b)))
+        .build();
+
+    let mut entries = Vec::new();
+    for entry in walker {
+        // 无法读取的条目（如没有权限）只记录日志，不影响其余部分
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("跳过无法读取的条目: {}", e);
+                continue;
+            }
+        };
+        if entry.depth() == 0 {
+            continue;
+        }
+        if entries.len() >= max_entries {
+            return Ok((entries, true));
+        }
+
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        let size = if is_dir {
+            0
+        } else {
+            entry.metadata().map(|m| m.len()).unwrap_or(0)
+        };
+        entries.push(TreeEntry {
+            depth: entry.depth(),
+            name: entry.file_name().to_string_lossy().into_owned(),
+            is_dir,
+            size,
+        });
+    }
+
+    Ok((entries, false))
+}
+
+/// 把条目渲染为树形文本
+fn render_tree(root_label: &str, entries: &[TreeEntry], show_sizes: bool) -> String {
+    // 倒序扫描，判断每个条目是否是同级的最后一项
+    let mut is_last = vec![false; entries.len()];
+    let mut sibling_below: Vec<bool> = Vec::new();
+    for (index, entry) in entries.iter().enumerate().rev() {
+        if sibling_below.len() <= entry.depth {
+            sibling_below.resize(entry.depth + 1, false);
+        }
+        is_last[index] = !sibling_below[entry.depth];
+        sibling_below[entry.depth] = true;
+        sibling_below.truncate(entry.depth + 1);
+    }
+
+    let mut output = format!("{}\n", root_label);
+    // 每一层祖先是否是同级的最后一项，决定前缀中是否画竖线
+    let mut ancestors_last: Vec<bool> = Vec::new();
+    for (entry, &last) in entries.iter().zip(&is_last) {
+        ancestors_last.truncate(entry.depth - 1);
+        for &ancestor_last in &ancestors_last {
+            output.push_str(if ancestor_last { "    " } else { "│   " });
+        }
+        output.push_str(if last { "└── " } else { "├── " });
+        output.push_str(&entry.name);
+        if entry.is_dir {
+            output.push('/');
+        } else if show_sizes {
+            output.push_str(&format!(" ({})", format_size(entry.size)));
+        }
+        output.push('\n');
+        ancestors_last.push(last);
+    }
+
+    output
+}
+
+/// 以 B、KB、MB、GB 为单位显示大小
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[async_trait]
+impl Tool for TreeTool {
+    fn name(&self) -> &str {
+        "tree"
+    }
+
+    fn description(&self) -> &str {
+        "以树形文本列出目录结构，用于了解代码库或目录的组织方式。可以指定展开层数 depth（默认 3），\
+默认跳过隐藏文件和 .gitignore 中忽略的文件，可选在文件后标注大小。条目过多时会省略其余部分"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "目录路径"},
+                "depth": {"type": "integer", "minimum": 1, "maximum": MAX_DEPTH, "description": "展开的目录层数，默认 3"},
+                "gitignore": {"type": "boolean", "description": "是否跳过 .gitignore 中忽略的文件，默认 true"},
+                "show_hidden": {"type": "boolean", "description": "是否显示隐藏文件，默认 false"},
+                "show_sizes": {"type": "boolean", "description": "是否在文件后标注大小，默认 false"}
+            },
+            "required": ["path"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"path": "/data/project", "depth": 2})]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行目录树工具，参数: {:?}", params);
+
+        // 解析参数
+        let params: TreeParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let depth = params.depth.unwrap_or(DEFAULT_DEPTH);
+        if depth == 0 || depth > MAX_DEPTH {
+            return Ok(ToolResult::invalid_args(format!(
+                "depth 必须在 1 到 {} 之间",
+                MAX_DEPTH
+            )));
+        }
+
+        let root = match self.resolve_path(&params.path) {
+            Ok(root) => root,
+            Err(e) => {
+                error!("目录路径无效: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        // 遍历大目录耗时较长，放到阻塞线程中执行
+        let max_entries = self.max_entries;
+        let outcome = tokio::task::spawn_blocking(move || {
+            let (entries, truncated) = collect_entries(&root, &params, depth, max_entries)?;
+            let mut tree = render_tree(&params.path, &entries, params.show_sizes);
+
+            let directories = entries.iter().filter(|e| e.is_dir).count();
+            let files = entries.len() - directories;
+            if truncated {
+                tree.push_str(&format!("…（条目超过上限 {}，其余已省略）\n", max_entries));
+            }
+            tree.push_str(&format!("\n{} 个目录，{} 个文件", directories, files));
+            Ok::<_, anyhow::Error>((tree, entries.len()))
+        })
+        .await?;
+
+        match outcome {
+            Ok((tree, count)) => {
+                info!("目录树生成完成: {} 个条目", count);
+                Ok(ToolResult::success(serde_json::Value::String(tree))
+                    .with_display_hint(DisplayHint::Text))
+            }
+            Err(e) => {
+                error!("目录树生成失败: {}", e);
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// 在临时目录中创建：
+    /// src/main.rs、src/util/mod.rs、README.md、target/（被 .gitignore 忽略）
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/util")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/util/mod.rs"), "").unwrap();
+        fs::write(root.join("README.md"), "x".repeat(2048)).unwrap();
+        fs::write(root.join("target/debug/app"), "").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        dir
+    }
+
+    async fn tree(tool: &TreeTool, args: serde_json::Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "tree".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn renders_the_structure_up_to_the_requested_depth() {
+        let dir = project();
+        let path = dir.path().to_str().unwrap();
+        let tool = TreeTool::new();
+
+        let result = tree(&tool, serde_json::json!({"path": path})).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(
+            result.data.as_str().unwrap(),
+            format!(
+                "{}\n\
+                 ├── src/\n\
+                 │   ├── util/\n\
+                 │   │   └── mod.rs\n\
+                 │   └── main.rs\n\
+                 └── README.md\n\
+                 \n2 个目录，3 个文件",
+                path
+            )
+        );
+
+        let result = tree(
+            &tool,
+            serde_json::json!({"path": path, "depth": 1, "show_sizes": true}),
+        )
+        .await;
+        assert_eq!(
+            result.data.as_str().unwrap(),
+            format!(
+                "{}\n├── src/\n└── README.md (2.0 KB)\n\n1 个目录，1 个文件",
+                path
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn ignored_files_and_entry_cap_are_honoured() {
+        let dir = project();
+        let path = dir.path().to_str().unwrap();
+
+        let result = tree(
+            &TreeTool::new(),
+            serde_json::json!({"path": path, "depth": 1, "gitignore": false, "show_hidden": true}),
+        )
+        .await;
+        let text = result.data.as_str().unwrap();
+        assert!(
+            text.contains("target/") && text.contains(".gitignore"),
+            "{}",
+            text
+        );
+
+        let tool = TreeTool::new().with_options(TreeOptions {
+            max_entries: Some(2),
+        });
+        let result = tree(&tool, serde_json::json!({"path": path})).await;
+        let text = result.data.as_str().unwrap();
+        assert!(text.contains("条目超过上限 2"), "{}", text);
+        assert!(text.ends_with("2 个目录，0 个文件"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn invalid_depth_and_paths_outside_the_sandbox_are_rejected() {
+        let dir = project();
+        let tool =
+            TreeTool::new().with_sandbox(PathSandbox::new(vec![dir.path().join("src")]).unwrap());
+
+        for args in [
+            serde_json::json!({"path": dir.path().join("src"), "depth": 0}),
+            serde_json::json!({"path": dir.path().join("src"), "depth": MAX_DEPTH + 1}),
+            serde_json::json!({"path": dir.path()}),
+            serde_json::json!({"path": dir.path().join("README.md")}),
+        ] {
+            let result = tree(&tool, args.clone()).await;
+            assert!(result.is_invalid_args(), "{}: {:?}", args, result);
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+use super::{body_excerpt, check_status, HttpClientFactory};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WebSearchParams {
@@ -23,11 +27,104 @@ pub struct WebSearchResult {
     results: Vec<SearchResult>,
 }
 
-pub struct WebSearchTool;
+#[derive(Deserialize)]
+struct DuckDuckGoResult {
+    #[serde(rename = "AbstractText")]
+    abstract_text: String,
+    #[serde(rename = "AbstractURL")]
+    abstract_url: String,
+    #[serde(rename = "RelatedTopics")]
+    related_topics: Vec<Topic>,
+}
+
+#[derive(Deserialize)]
+struct Topic {
+    #[serde(rename = "Text")]
+    text: Option<String>,
+    #[serde(rename = "FirstURL")]
+    url: Option<String>,
+}
+
+/// 解析搜索后端的响应体
+///
+/// 代理或后端出错时常返回 HTML 错误页面，这里先确认内容是 JSON，
+/// 否则把状态码和内容开头附在错误里，便于排查配置问题。
+fn parse_search_response(status: reqwest::StatusCode, body: &[u8]) -> Result<DuckDuckGoResult> {
+    let text = String::from_utf8_lossy(body);
+    let trimmed = text.trim_start();
+
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return Err(anyhow!(
+            "搜索后端返回了非 JSON 响应（状态码 {}）: {}",
+            status,
+            body_excerpt(body)
+        ));
+    }
+
+    serde_json::from_str(trimmed)
+        .with_context(|| format!("搜索后端返回的 JSON 无法解析（状态码 {}）", status))
+}
+
+/// 默认使用的代理地址
+const DEFAULT_PROXY: &str = "http://127.0.0.1:7890";
+
+/// 搜索结果的相关性过滤方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelevanceFilter {
+    /// 去重，并丢弃标题和摘要都不包含任何查询词的结果
+    #[default]
+    Strict,
+    /// 只按链接去重
+    Loose,
+}
+
+/// 清单中 web_search 的选项
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSearchOptions {
+    /// 代理地址，设置为空字符串表示不使用代理
+    pub proxy: Option<String>,
+    /// 相关性过滤方式
+    pub relevance: Option<RelevanceFilter>,
+    /// 请求超时时间（秒）
+    pub timeout_secs: Option<u64>,
+}
+
+pub struct WebSearchTool {
+    http: HttpClientFactory,
+    relevance: RelevanceFilter,
+}
 
 impl WebSearchTool {
     pub fn new() -> Self {
-        Self
+        Self {
+            http: HttpClientFactory::new().with_proxy(Some(DEFAULT_PROXY.to_string())),
+            relevance: RelevanceFilter::default(),
+        }
+    }
+
+    /// 使用服务配置的出站 HTTP 策略，未配置代理时仍使用默认代理
+    pub fn with_http(mut self, http: HttpClientFactory) -> Self {
+        self.http = match http.proxy() {
+            Some(_) => http,
+            None => http.with_proxy(Some(DEFAULT_PROXY.to_string())),
+        };
+        self
+    }
+
+    /// 应用清单中的选项，优先于服务配置
+    pub fn with_options(mut self, options: WebSearchOptions) -> Self {
+        if let Some(proxy) = options.proxy {
+            self.http = self.http.with_proxy(Some(proxy));
+        }
+        if let Some(relevance) = options.relevance {
+            self.relevance = relevance;
+        }
+        if let Some(secs) = options.timeout_secs {
+            self.http = self.http.with_timeout(Duration::from_secs(secs));
+        }
+        self
     }
 
     async fn perform_search(&self, query: &str, max_results: usize) -> Result<Vec<SearchResult>> {
@@ -38,72 +135,83 @@ impl WebSearchTool {
             encoded_query
         );
 
-        // 创建支持代理的客户端
-        let client = reqwest::Client::builder()
-            .proxy(reqwest::Proxy::all("http://127.0.0.1:7890")?) // 设置代理
-            .build()?;
-
         // 发送请求
-        let response = client
-            .get(&url)
-            .header("User-Agent", "RustAgent/1.0")
-            .send()
-            .await?;
+        let client = self.http.build()?;
+        let response = client.get(&url).send().await?;
+
+        // 打印响应状态和内容以便调试
+        let status = response.status();
+        info!("搜索响应状态: {}", status);
+        let response = check_status(self.name(), response).await?;
+        let body = response.bytes().await?;
+        info!("搜索响应内容: {}", String::from_utf8_lossy(&body));
 
         // 解析响应
-        #[derive(Deserialize)]
-        struct DuckDuckGoResult {
-            #[serde(rename = "AbstractText")]
-            abstract_text: String,
-            #[serde(rename = "AbstractURL")]
-            abstract_url: String,
-            #[serde(rename = "RelatedTopics")]
-            related_topics: Vec<Topic>,
-        }
+        let ddg_result = parse_search_response(status, &body)?;
+        Ok(collect_results(
+            ddg_result,
+            query,
+            max_results,
+            self.relevance,
+        ))
+    }
+}
 
-        #[derive(Deserialize)]
-        struct Topic {
-            #[serde(rename = "Text")]
-            text: Option<String>,
-            #[serde(rename = "FirstURL")]
-            url: Option<String>,
-        }
+/// 整理搜索结果：按链接去重、按相关性过滤，再截取前 `max_results` 个
+///
+/// 主要结果（摘要）总是排在最前且不参与相关性过滤。
+fn collect_results(
+    ddg_result: DuckDuckGoResult,
+    query: &str,
+    max_results: usize,
+    relevance: RelevanceFilter,
+) -> Vec<SearchResult> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| term.to_lowercase())
+        .collect();
+    let is_relevant = |result: &SearchResult| {
+        let text = format!("{} {}", result.title, result.snippet).to_lowercase();
+        terms.is_empty() || terms.iter().any(|term| text.contains(term.as_str()))
+    };
 
-        // 打印响应状态和内容以便调试
-        info!("搜索响应状态: {}", response.status());
-        let text = response.text().await?;
-        info!("搜索响应内容: {}", text);
+    let mut results = Vec::new();
+    let mut seen_links = HashSet::new();
 
-        // 解析响应
-        let ddg_result: DuckDuckGoResult = serde_json::from_str(&text)?;
-        let mut results = Vec::new();
-
-        // 添加主要结果
-        if !ddg_result.abstract_text.is_empty() {
-            results.push(SearchResult {
-                title: "主要结果".to_string(),
-                link: ddg_result.abstract_url,
-                snippet: ddg_result.abstract_text,
-            });
-        }
+    // 添加主要结果
+    if !ddg_result.abstract_text.is_empty() {
+        seen_links.insert(ddg_result.abstract_url.clone());
+        results.push(SearchResult {
+            title: "主要结果".to_string(),
+            link: ddg_result.abstract_url,
+            snippet: ddg_result.abstract_text,
+        });
+    }
 
-        // 添加相关主题
-        for topic in ddg_result
-            .related_topics
-            .iter()
-            .take(max_results.saturating_sub(1))
-        {
-            if let (Some(text), Some(url)) = (&topic.text, &topic.url) {
-                results.push(SearchResult {
-                    title: text.clone(),
-                    link: url.clone(),
-                    snippet: text.clone(),
-                });
-            }
+    // 添加相关主题
+    for topic in ddg_result.related_topics {
+        let (Some(text), Some(url)) = (topic.text, topic.url) else {
+            continue;
+        };
+        if !seen_links.insert(url.clone()) {
+            debug!("跳过重复的搜索结果: {}", url);
+            continue;
         }
 
-        Ok(results)
+        let result = SearchResult {
+            title: text.clone(),
+            link: url,
+            snippet: text,
+        };
+        if relevance == RelevanceFilter::Strict && !is_relevant(&result) {
+            debug!("跳过不相关的搜索结果: {}", result.link);
+            continue;
+        }
+        results.push(result);
     }
+
+    results.truncate(max_results);
+    results
 }
 
 #[async_trait]
@@ -113,7 +221,22 @@ impl Tool for WebSearchTool {
     }
 
     fn description(&self) -> &str {
-        "在互联网上搜索信息，返回相关结果"
+        "在互联网上搜索信息，返回相关结果。返回查询词 query 和结果列表 results，每个结果包含标题 title、链接 link 和摘要 snippet"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "搜索查询词"},
+                "max_results": {"type": "integer", "description": "最大结果数量，默认 5"}
+            },
+            "required": ["query"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"query": "Rust 编程语言", "max_results": 5})]
     }
 
     async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
@@ -124,11 +247,7 @@ impl Tool for WebSearchTool {
             Ok(p) => p,
             Err(e) => {
                 error!("参数解析失败: {}", e);
-                return Ok(ToolResult {
-                    success: false,
-                    data: serde_json::Value::Null,
-                    error: Some(e.to_string()),
-                });
+                return Ok(ToolResult::invalid_args(e.to_string()));
             }
         };
 
@@ -143,20 +262,93 @@ impl Tool for WebSearchTool {
                 };
 
                 info!("搜索成功完成，找到 {} 个结果", search_result.results.len());
-                Ok(ToolResult {
-                    success: true,
-                    data: serde_json::to_value(search_result)?,
-                    error: None,
-                })
+                Ok(ToolResult::success(serde_json::to_value(search_result)?))
             }
             Err(e) => {
                 error!("搜索失败: {}", e);
-                Ok(ToolResult {
-                    success: false,
-                    data: serde_json::Value::Null,
-                    error: Some(e.to_string()),
-                })
+                Ok(ToolResult::failure(e.to_string()))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn html_body_reports_non_json_response() {
+        let body = b"<!DOCTYPE html><html><body>502 Bad Gateway</body></html>";
+        let error = parse_search_response(StatusCode::BAD_GATEWAY, body)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("非 JSON 响应"), "{}", error);
+        assert!(error.contains("502"), "{}", error);
+        assert!(error.contains("Bad Gateway"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_utf8_body_does_not_panic() {
+        let error = parse_search_response(StatusCode::OK, &[0xff, 0xfe, b'<', 0x80])
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("非 JSON 响应"));
+    }
+
+    #[test]
+    fn json_body_parses() {
+        let body = br#" {"AbstractText": "Rust", "AbstractURL": "https://rust-lang.org", "RelatedTopics": []}"#;
+        let result = parse_search_response(StatusCode::OK, body).unwrap();
+        assert_eq!(result.abstract_text, "Rust");
+    }
+
+    /// 包含无关条目和重复链接的搜索结果
+    const RESULTS: &[u8] = br#"{
+        "AbstractText": "Rust is a systems programming language.",
+        "AbstractURL": "https://www.rust-lang.org",
+        "RelatedTopics": [
+            {"Text": "Rust (fungus) - a plant disease", "FirstURL": "https://example.com/fungus"},
+            {"Text": "Rust Book - learn the Rust language", "FirstURL": "https://doc.rust-lang.org/book"},
+            {"Text": "Rust Book (mirror)", "FirstURL": "https://doc.rust-lang.org/book"},
+            {"Text": "Corrosion - oxidation of iron", "FirstURL": "https://example.com/corrosion"},
+            {"Text": "The Rust language reference", "FirstURL": "https://www.rust-lang.org"},
+            {"Name": "Category without a link"}
+        ]
+    }"#;
+
+    fn links(relevance: RelevanceFilter, max_results: usize) -> Vec<String> {
+        let response = parse_search_response(StatusCode::OK, RESULTS).unwrap();
+        collect_results(response, "Rust language", max_results, relevance)
+            .into_iter()
+            .map(|result| result.link)
+            .collect()
+    }
+
+    #[test]
+    fn strict_filter_drops_irrelevant_and_duplicate_results() {
+        assert_eq!(
+            links(RelevanceFilter::Strict, 10),
+            [
+                "https://www.rust-lang.org",
+                "https://example.com/fungus",
+                "https://doc.rust-lang.org/book",
+            ]
+        );
+    }
+
+    #[test]
+    fn loose_filter_only_deduplicates_and_truncates_last() {
+        assert_eq!(
+            links(RelevanceFilter::Loose, 10),
+            [
+                "https://www.rust-lang.org",
+                "https://example.com/fungus",
+                "https://doc.rust-lang.org/book",
+                "https://example.com/corrosion",
+            ]
+        );
+        assert_eq!(links(RelevanceFilter::Loose, 2).len(), 2);
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use rust_agent_core::tools::interface::{Tool, ToolParameters, ToolResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/// 未指定名称时使用的工作区
+const DEFAULT_WORKSPACE: &str = "default";
+
+/// 工作区操作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceOperation {
+    /// 创建工作区，已存在时直接返回路径
+    Create,
+    /// 删除工作区中的所有内容，保留目录本身
+    Clear,
+    /// 删除整个工作区
+    Remove,
+    /// 列出已创建的工作区
+    List,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceParams {
+    operation: WorkspaceOperation,
+    /// 工作区名称，只能包含字母、数字、`-` 和 `_`
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceResult {
+    operation: WorkspaceOperation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// 工作区的绝对路径
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// 清空时删除的条目数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    removed_entries: Option<usize>,
+    /// 已创建的工作区名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workspaces: Option<Vec<String>>,
+}
+
+/// 临时工作区工具
+///
+/// 为多步骤的文件处理提供临时目录。所有工作区都位于系统临时目录下本进程专用的根目录中，
+/// 工具被释放（服务关闭）时整个根目录会被删除。配置了允许的根目录时，
+/// 该根目录会自动加入文件类工具的沙箱，其他工具可以直接读写工作区中的文件。
+pub struct WorkspaceTool {
+    root: PathBuf,
+}
+
+impl WorkspaceTool {
+    /// 在系统临时目录下创建本进程专用的工作区根目录
+    pub fn new() -> Result<Self> {
+        Self::with_root(
+            std::env::temp_dir().join(format!("rust_agent_workspaces_{}", std::process::id())),
+        )
+    }
+
+    /// 使用指定目录作为工作区根目录，不存在时创建
+    ///
+    /// 工具被释放时该目录会被整个删除，不应指向已有内容的目录。
+    pub fn with_root(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root).with_context(|| format!("无法创建工作区根目录: {:?}", root))?;
+        let root = root.canonicalize()?;
+        info!("临时工作区根目录: {:?}", root);
+
+        Ok(Self { root })
+    }
+
+    /// 工作区根目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 工作区名称对应的目录，名称不合法时返回错误
+    fn workspace_path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(anyhow!(
+                "无效的工作区名称: {:?}（只能包含字母、数字、- 和 _）",
+                name
+            ));
+        }
+
+        Ok(self.root.join(name))
+    }
+}
+
+impl Drop for WorkspaceTool {
+    fn drop(&mut self) {
+        match fs::remove_dir_all(&self.root) {
+            Ok(()) => info!("已删除临时工作区: {:?}", self.root),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("删除临时工作区失败: {:?}: {}", self.root, e),
+        }
+    }
+}
+
+/// 执行工作区操作
+fn run_operation(
+    root: &Path,
+    path: &Path,
+    name: &str,
+    operation: WorkspaceOperation,
+) -> Result<WorkspaceResult> {
+    let mut result = WorkspaceResult {
+        operation,
+        name: Some(name.to_string()),
+        path: Some(path.to_string_lossy().into_owned()),
+        removed_entries: None,
+        workspaces: None,
+    };
+
+    match operation {
+        WorkspaceOperation::Create => {
+            fs::create_dir_all(path).with_context(|| format!("无法创建工作区: {:?}", path))?;
+        }
+        WorkspaceOperation::Clear => {
+            let entries = fs::read_dir(path).with_context(|| format!("工作区不存在: {}", name))?;
+            let mut removed = 0;
+            for entry in entries {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    fs::remove_dir_all(entry.path())?;
+                } else {
+                    fs::remove_file(entry.path())?;
+                }
+                removed += 1;
+            }
+            result.removed_entries = Some(removed);
+        }
+        WorkspaceOperation::Remove => {
+            if !path.is_dir() {
+                return Err(anyhow!("工作区不存在: {}", name));
+            }
+            fs::remove_dir_all(path).with_context(|| format!("无法删除工作区: {:?}", path))?;
+        }
+        WorkspaceOperation::List => {
+            let mut names = Vec::new();
+            for entry in fs::read_dir(root)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    names.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+            names.sort();
+            result.name = None;
+            result.path = Some(root.to_string_lossy().into_owned());
+            result.workspaces = Some(names);
+        }
+    }
+
+    Ok(result)
+}
+
+#[async_trait]
+impl Tool for WorkspaceTool {
+    fn name(&self) -> &str {
+        "workspace"
+    }
+
+    fn description(&self) -> &str {
+        "创建和管理临时工作区目录，用于存放多步骤文件处理的中间文件，服务关闭时自动删除。\
+支持 create（创建并返回路径）、clear（清空内容）、remove（删除工作区）和 list（列出工作区）。\
+返回操作 operation、工作区名称 name、路径 path，清空时还返回删除的条目数 removed_entries，列出时返回 workspaces"
+    }
+
+    fn parameters(&self) -> Option<serde_json::Value> {
+        Some(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "enum": ["create", "clear", "remove", "list"],
+                    "description": "要执行的操作"
+                },
+                "name": {"type": "string", "description": "工作区名称，只能包含字母、数字、- 和 _，默认 default"}
+            },
+            "required": ["operation"]
+        }))
+    }
+
+    fn examples(&self) -> Vec<serde_json::Value> {
+        vec![serde_json::json!({"operation": "create", "name": "report"})]
+    }
+
+    async fn execute(&self, params: ToolParameters) -> Result<ToolResult> {
+        info!("执行工作区工具，参数: {:?}", params);
+
+        // 解析参数
+        let params: WorkspaceParams = match serde_json::from_value(params.args.clone()) {
+            Ok(p) => p,
+            Err(e) => {
+                error!("参数解析失败: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        let name = params.name.unwrap_or_else(|| DEFAULT_WORKSPACE.to_string());
+        let path = match self.workspace_path(&name) {
+            Ok(path) => path,
+            Err(e) => {
+                error!("工作区名称无效: {}", e);
+                return Ok(ToolResult::invalid_args(e.to_string()));
+            }
+        };
+
+        // 清空大目录耗时较长，放到阻塞线程中执行
+        let root = self.root.clone();
+        let operation = params.operation;
+        let outcome =
+            tokio::task::spawn_blocking(move || run_operation(&root, &path, &name, operation))
+                .await?;
+
+        match outcome {
+            Ok(result) => {
+                info!("工作区操作完成: {:?} {:?}", operation, result.path);
+                Ok(ToolResult::success(serde_json::to_value(result)?))
+            }
+            Err(e) => {
+                error!("工作区操作失败: {}", e);
+                Ok(ToolResult::failure(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn run(tool: &WorkspaceTool, args: serde_json::Value) -> ToolResult {
+        let params = ToolParameters {
+            name: "workspace".to_string(),
+            args,
+        };
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_makes_a_real_dir_and_clear_removes_its_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = WorkspaceTool::with_root(dir.path().join("workspaces")).unwrap();
+
+        let result = run(&tool, json!({"operation": "create", "name": "report"})).await;
+        assert!(result.success, "{:?}", result.error);
+        let path = PathBuf::from(result.data["path"].as_str().unwrap());
+        assert!(path.is_dir());
+        assert_eq!(path, tool.root().join("report"));
+
+        fs::write(path.join("draft.txt"), "草稿").unwrap();
+        fs::create_dir_all(path.join("images/raw")).unwrap();
+        fs::write(path.join("images/raw/a.png"), [0u8; 4]).unwrap();
+
+        let result = run(&tool, json!({"operation": "clear", "name": "report"})).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.data["removed_entries"], 2);
+        assert!(path.is_dir());
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 0);
+
+        let result = run(&tool, json!({"operation": "list"})).await;
+        assert_eq!(result.data["workspaces"], json!(["report"]));
+    }
+
+    #[tokio::test]
+    async fn invalid_names_are_rejected_and_drop_removes_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = WorkspaceTool::with_root(dir.path().join("workspaces")).unwrap();
+        let root = tool.root().to_path_buf();
+
+        let result = run(&tool, json!({"operation": "create", "name": "../escape"})).await;
+        assert!(result.is_invalid_args(), "{:?}", result.error);
+        assert!(!dir.path().join("escape").exists());
+
+        run(&tool, json!({"operation": "create"})).await;
+        assert!(root.join(DEFAULT_WORKSPACE).is_dir());
+        drop(tool);
+        assert!(!root.exists());
+    }
+}